@@ -0,0 +1,101 @@
+//! Readers for article summary dumps
+//!
+//! Two sources are supported, both read line by line:
+//! the Wikimedia `abstract.xml` dumps, and the DBpedia short abstracts in Turtle.
+
+use std::io::{self, BufRead};
+
+/// One article summary, with the title in the URL-safe form used by the index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Abstract {
+    pub title: String,
+    pub text: String,
+}
+
+/// The text between an opening and a closing tag on a single line
+fn element<'a>(line: &'a str, tag: &str) -> Option<&'a str> {
+    let rest = line.trim().strip_prefix(&format!("<{}>", tag))?;
+    rest.strip_suffix(&format!("</{}>", tag))
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Read a Wikimedia abstract dump, made of `<doc>` elements holding a
+/// `<title>Wikipedia: ...</title>` and an `<abstract>` line.
+pub fn read_xml<R: BufRead>(source: R) -> impl Iterator<Item = io::Result<Abstract>> {
+    let mut title = None;
+    source.lines().filter_map(move |line| {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => return Some(Err(e)),
+        };
+        if let Some(t) = element(&line, "title") {
+            let t = unescape_xml(t);
+            let t = t.split_once(": ").map_or(t.as_str(), |(_, t)| t);
+            title = Some(t.replace(' ', "_"));
+        } else if let Some(text) = element(&line, "abstract") {
+            let title = title.take()?;
+            let text = unescape_xml(text);
+            if !text.is_empty() { return Some(Ok(Abstract { title, text })) }
+        }
+        None
+    })
+}
+
+/// Read DBpedia short abstracts in Turtle, one
+/// `<http://dbpedia.org/resource/TITLE> <predicate> "TEXT"@lang .` triple per line.
+pub fn read_turtle<R: BufRead>(source: R) -> impl Iterator<Item = io::Result<Abstract>> {
+    source.lines().filter_map(|line| match line {
+        Ok(line) => parse_triple(&line).map(Ok),
+        Err(e) => Some(Err(e)),
+    })
+}
+
+fn parse_triple(line: &str) -> Option<Abstract> {
+    let rest = line.strip_prefix('<')?;
+    let (subject, rest) = rest.split_once('>')?;
+    let title = subject.rsplit('/').next()?;
+
+    let literal = rest.trim_start().split_once('>')?.1.trim_start().strip_prefix('"')?;
+    let mut text = String::new();
+    let mut chars = literal.chars();
+    loop {
+        match chars.next()? {
+            '"' => break,
+            '\\' => match chars.next()? {
+                'n' => text.push(' '),
+                't' => text.push(' '),
+                c => text.push(c),
+            },
+            c => text.push(c),
+        }
+    }
+
+    Some(Abstract { title: title.to_owned(), text })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sample_xml() {
+        let dump = "<feed>\n<doc>\n<title>Wikipedia: Tom &amp; Jerry</title>\n<url>https://en.wikipedia.org/wiki/Tom_and_Jerry</url>\n<abstract>A cat &lt;and&gt; a mouse.</abstract>\n<links></links>\n</doc>\n<doc>\n<title>Wikipedia: Empty</title>\n<abstract></abstract>\n</doc>\n</feed>\n";
+        let abstracts: Vec<_> = read_xml(dump.as_bytes()).collect::<io::Result<_>>().unwrap();
+        assert_eq!(abstracts, [Abstract { title: "Tom_&_Jerry".into(), text: "A cat <and> a mouse.".into() }]);
+    }
+
+    #[test]
+    fn sample_turtle() {
+        let dump = "# comment\n<http://dbpedia.org/resource/Paris> <http://www.w3.org/2000/01/rdf-schema#comment> \"Paris is the \\\"capital\\\" of France.\"@en .\n";
+        let abstracts: Vec<_> = read_turtle(dump.as_bytes()).collect::<io::Result<_>>().unwrap();
+        assert_eq!(abstracts, [Abstract { title: "Paris".into(), text: "Paris is the \"capital\" of France.".into() }]);
+    }
+}
@@ -0,0 +1,27 @@
+//! User aliases of article titles, such as `NYC` for `New_York_City`
+//!
+//! Alias files hold one `"NAME" = "Title"` line per alias, in TOML syntax.
+
+use std::collections::BTreeMap;
+
+/// Read the `(name, title)` pairs of an alias file
+pub fn read(text: &str) -> Result<Vec<(String, String)>, toml::de::Error> {
+    let aliases: BTreeMap<String, String> = toml::from_str(text)?;
+    Ok(aliases.into_iter().collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sample_aliases() {
+        let aliases = read("# Shorthands\n\"USA\" = \"United States\"\nNYC = \"New York City\"\n").unwrap();
+        assert_eq!(aliases, [
+            ("NYC".to_owned(), "New York City".to_owned()),
+            ("USA".to_owned(), "United States".to_owned()),
+        ]);
+        assert!(read("USA = 1").is_err());
+        assert!(read("USA = \"United States\"\nUSA = \"Usa\"").is_err());
+    }
+}
@@ -0,0 +1,159 @@
+//! Arrow IPC streams, for tools such as pyarrow or R's `arrow` package to read
+//! query results and parsed dumps without parsing text
+//!
+//! The column types are those declared by the fields, so that the schema holds
+//! whatever the records hold. Fields without a type are written as strings.
+
+use std::{io::{self, Write}, sync::Arc};
+
+use arrow_array::{ArrayRef, BooleanArray, Float64Array, Int64Array, RecordBatch, StringArray};
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::{ArrowError, DataType, Field, Schema};
+use serde_json::Value;
+
+use crate::output::{display, Kind};
+
+/// Number of records per batch
+const BATCH: usize = 8192;
+
+/// Encodes records into an Arrow IPC stream, one batch at a time
+pub struct Encoder {
+    schema: Arc<Schema>,
+    records: Vec<Vec<Value>>,
+    /// Writes to an in-memory buffer, drained after every batch
+    stream: Option<StreamWriter<Vec<u8>>>,
+}
+
+impl Encoder {
+
+    /// Encode records with the given fields, typed as in `id:int`
+    pub fn new(fields: &[&str]) -> Self {
+        Encoder { schema: Arc::new(schema(fields)), records: vec![], stream: None }
+    }
+
+    /// Buffer a record, and write out a batch once enough are buffered
+    pub fn write<W: Write>(&mut self, out: &mut W, record: &[Value]) -> io::Result<()> {
+        self.records.push(record.to_vec());
+        if self.records.len() >= BATCH { self.flush(out)? }
+        Ok(())
+    }
+
+    /// Write out the remaining records and the end of the stream
+    pub fn finish<W: Write>(mut self, out: &mut W) -> io::Result<()> {
+        self.flush(out)?;
+        if let Some(stream) = &mut self.stream {
+            stream.finish().map_err(io::Error::other)?;
+            out.write_all(stream.get_mut())?;
+        }
+        Ok(())
+    }
+
+    fn flush<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        if self.stream.is_none() {
+            self.stream = Some(StreamWriter::try_new(vec![], &self.schema).map_err(io::Error::other)?);
+        }
+        let Some(stream) = &mut self.stream else { unreachable!() };
+
+        if !self.records.is_empty() {
+            let batch = batch(&self.schema, &self.records).map_err(io::Error::other)?;
+            stream.write(&batch).map_err(io::Error::other)?;
+            self.records.clear();
+        }
+        out.write_all(stream.get_mut())?;
+        stream.get_mut().clear();
+        Ok(())
+    }
+}
+
+/// The schema of the declared fields
+fn schema(fields: &[&str]) -> Schema {
+    let fields: Vec<Field> = fields.iter().map(|field| {
+        let (name, kind) = Kind::parse(field);
+        let data_type = match kind {
+            Kind::Int => DataType::Int64,
+            Kind::Float => DataType::Float64,
+            Kind::Bool => DataType::Boolean,
+            Kind::Text => DataType::Utf8,
+        };
+        Field::new(name, data_type, true)
+    }).collect();
+    Schema::new(fields)
+}
+
+fn batch(schema: &Arc<Schema>, records: &[Vec<Value>]) -> Result<RecordBatch, ArrowError> {
+    let columns = schema.fields().iter().enumerate().map(|(i, field)| {
+        let values = records.iter().map(|r| &r[i]);
+        let mismatch = |v: &Value| ArrowError::InvalidArgumentError(
+            format!("{} is not of type {} in field {}", v, field.data_type(), field.name()));
+        let column: ArrayRef = match field.data_type() {
+            DataType::Int64 => Arc::new(values
+                .map(|v| if v.is_null() { Ok(None) } else { v.as_i64().map(Some).ok_or_else(|| mismatch(v)) })
+                .collect::<Result<Int64Array, _>>()?),
+            DataType::Float64 => Arc::new(values
+                .map(|v| if v.is_null() { Ok(None) } else { v.as_f64().map(Some).ok_or_else(|| mismatch(v)) })
+                .collect::<Result<Float64Array, _>>()?),
+            DataType::Boolean => Arc::new(values
+                .map(|v| if v.is_null() { Ok(None) } else { v.as_bool().map(Some).ok_or_else(|| mismatch(v)) })
+                .collect::<Result<BooleanArray, _>>()?),
+            _ => Arc::new(values
+                .map(|v| (!v.is_null()).then(|| display(v)))
+                .collect::<StringArray>()),
+        };
+        Ok(column)
+    }).collect::<Result<Vec<_>, ArrowError>>()?;
+    RecordBatch::try_new(schema.clone(), columns)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::Int64Type;
+    use arrow_ipc::reader::StreamReader;
+    use serde_json::json;
+
+    #[test]
+    fn roundtrip() {
+        let mut buffer = vec![];
+        let mut encoder = Encoder::new(&["id:int", "title", "score:float", "empty:bool"]);
+        encoder.write(&mut buffer, &[json!(1), json!("Paris"), json!(0.5), Value::Null]).unwrap();
+        encoder.write(&mut buffer, &[json!(2), json!(3), json!(3), Value::Null]).unwrap();
+        encoder.finish(&mut buffer).unwrap();
+
+        let reader = StreamReader::try_new(&buffer[..], None).unwrap();
+        let types: Vec<_> = reader.schema().fields().iter().map(|f| f.data_type().clone()).collect();
+        assert_eq!(types, [DataType::Int64, DataType::Utf8, DataType::Float64, DataType::Boolean]);
+
+        let batches: Vec<_> = reader.collect::<Result<_, _>>().unwrap();
+        assert_eq!(batches.len(), 1);
+        let ids: Vec<_> = batches[0].column(0).as_primitive::<Int64Type>().iter().collect();
+        assert_eq!(ids, [Some(1), Some(2)]);
+        let titles: Vec<_> = batches[0].column(1).as_string::<i32>().iter().collect();
+        assert_eq!(titles, [Some("Paris"), Some("3")]);
+    }
+
+    #[test]
+    fn late_values() {
+        // Null through the first batch, the field keeps its declared type
+        let mut buffer = vec![];
+        let mut encoder = Encoder::new(&["id:int"]);
+        for _ in 0..BATCH { encoder.write(&mut buffer, &[Value::Null]).unwrap() }
+        encoder.write(&mut buffer, &[json!(7)]).unwrap();
+        encoder.finish(&mut buffer).unwrap();
+
+        let reader = StreamReader::try_new(&buffer[..], None).unwrap();
+        let batches: Vec<_> = reader.collect::<Result<_, _>>().unwrap();
+        assert_eq!(batches.len(), 2);
+        let ids: Vec<_> = batches[1].column(0).as_primitive::<Int64Type>().iter().collect();
+        assert_eq!(ids, [Some(7)]);
+    }
+
+    #[test]
+    fn empty_stream() {
+        let mut buffer = vec![];
+        Encoder::new(&["id:int"]).finish(&mut buffer).unwrap();
+        let reader = StreamReader::try_new(&buffer[..], None).unwrap();
+        assert_eq!(reader.schema().fields().len(), 1);
+        assert_eq!(reader.count(), 0);
+    }
+}
@@ -0,0 +1,142 @@
+//! Title filters excluding articles from path searches
+
+use std::{collections::{HashMap, HashSet}, fs, io};
+use regex::RegexSet;
+use thiserror::Error;
+
+use crate::{Id, classify, cli::AvoidArgs, filter::{Article, Filter, FilterError, Scanned}, sqlite::Db};
+
+/// "List of ...", "Index of ...", "Outline of ..."
+pub const LISTS: &str = r"^(Lists?|Index|Outline)_of_";
+/// "1999", "1990s", "44_BC", "19th_century"
+pub const YEARS: &str = r"^(\d{1,4}s?(_BCE?|_AD)?|\d{1,2}(st|nd|rd|th)_century(_BCE?)?)$";
+/// "Mercury_(disambiguation)"
+pub const DISAMBIGUATION: &str = r"_\(disambiguation\)$";
+
+#[derive(Error, Debug)]
+pub enum AvoidError {
+    #[error(transparent)]
+    Filter(#[from] FilterError),
+    #[error("Invalid pattern: {0}")]
+    Regex(#[from] regex::Error),
+    #[error("Cannot read the titles to avoid from {0}: {1}")]
+    File(String, io::Error),
+}
+
+/// A compiled set of title patterns, and titles
+pub struct Avoid {
+    /// Title flags of the selected presets
+    flags: u32,
+    /// The selected presets, for databases indexed without title flags
+    presets: RegexSet,
+    patterns: RegexSet,
+    filter: Option<Filter>,
+    /// Articles avoided by title, with underscores
+    titles: Vec<String>,
+}
+
+impl Avoid {
+    /// Compile the presets and patterns selected on the command line
+    pub fn new(args: &AvoidArgs) -> Result<Self, AvoidError> {
+        let presets: Vec<_> = [
+            (args.skip_lists, classify::LIST, LISTS),
+            (args.skip_years, classify::DATE, YEARS),
+            (args.skip_disambiguation, classify::DISAMBIGUATION, DISAMBIGUATION),
+        ].into_iter().filter(|(enabled, _, _)| *enabled).collect();
+
+        let mut titles: Vec<String> = args.titles.iter().map(|title| title.trim().replace(' ', "_")).collect();
+        if let Some(path) = &args.avoid_file {
+            let text = fs::read_to_string(path).map_err(|e| AvoidError::File(path.clone(), e))?;
+            titles.extend(read_titles(&text));
+        }
+
+        Ok(Self {
+            flags: presets.iter().fold(0, |flags, (_, flag, _)| flags | flag),
+            presets: RegexSet::new(presets.iter().map(|(_, _, re)| re))?,
+            patterns: RegexSet::new(&args.patterns)?,
+            filter: args.filter.as_deref().map(Filter::new).transpose()?,
+            titles,
+        })
+    }
+
+    /// Whether a title must be avoided
+    pub fn matches(&self, title: &str) -> bool {
+        self.presets.is_match(title) || self.patterns.is_match(title)
+    }
+
+    /// Whether an article must be avoided because of the filter expression
+    fn filtered(&self, article: &dyn Article) -> bool {
+        self.filter.as_ref().is_some_and(|f| f.matches(article))
+    }
+
+    /// Collect the IDs of all the articles to avoid. Unless the presets can be
+    /// looked up in the title flags, this scans the whole title index.
+    pub fn ids(&self, db: &Db) -> Result<HashSet<Id>, rusqlite::Error> {
+        let mut ids = HashSet::new();
+        for title in &self.titles {
+            match db.resolve(title) {
+                Some(id) => { ids.insert(id); }
+                None => eprintln!("Warning: Unknown article to avoid: {}", title),
+            }
+        }
+        if self.presets.is_empty() && self.patterns.is_empty() && self.filter.is_none() { return Ok(ids) }
+
+        let flagged = self.flags != 0 && db.has_title_flags();
+        if flagged {
+            ids.extend(db.ids_with_flags(self.flags)?);
+            if self.patterns.is_empty() && self.filter.is_none() { return Ok(ids) }
+        }
+        // Counted in one pass over the links, rather than with two queries per article
+        let degrees = match &self.filter {
+            Some(filter) if filter.uses_degrees() => db.degrees()?,
+            _ => HashMap::new(),
+        };
+        db.scan_articles(|id, title, flags, redirect| {
+            let matched = match flagged {
+                true => self.patterns.is_match(title),
+                false => self.matches(title),
+            };
+            let degree = degrees.get(&id).copied().unwrap_or_default();
+            if matched || self.filtered(&Scanned { id, title, degree, redirect, flags }) { ids.insert(id); }
+        })?;
+
+        Ok(ids)
+    }
+}
+
+/// The titles of a file listing one title per line, with underscores
+fn read_titles(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|title| title.replace(' ', "_"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn presets() {
+        let args = AvoidArgs { skip_lists: true, skip_years: true, skip_disambiguation: true, patterns: vec!["^Foo$".into()], ..Default::default() };
+        let avoid = Avoid::new(&args).unwrap();
+
+        for title in ["List_of_lists", "1999", "1990s", "44_BC", "19th_century", "Mercury_(disambiguation)", "Foo"] {
+            assert!(avoid.matches(title), "{}", title);
+        }
+        for title in ["Listing", "1999_in_film", "Mercury_(planet)", "Foobar", "Century"] {
+            assert!(!avoid.matches(title), "{}", title);
+        }
+
+        assert!(!Avoid::new(&AvoidArgs::default()).unwrap().matches("List_of_lists"));
+    }
+
+    #[test]
+    fn title_file() {
+        let text = "# Hubs\nUnited States\n\n  World_War_II \n";
+        assert_eq!(read_titles(text).collect::<Vec<_>>(), ["United_States", "World_War_II"]);
+
+        let args = AvoidArgs { titles: vec!["France".into()], ..Default::default() };
+        assert_eq!(Avoid::new(&args).unwrap().titles, ["France"]);
+    }
+}
@@ -0,0 +1,74 @@
+//! Storage of the link graph, behind a trait so that other databases can hold it
+//!
+//! The indexers write through [`Backend`], and plain path queries can run on any
+//! backend. The commands reading the whole graph run through [`Analytics`]. The
+//! other commands need the SQLite database.
+
+use wikistra::path::{bidi_dijkstra_with_stats, Stats};
+
+use crate::{Id, sqlite::{PathError, Remap}, stats::GraphStats};
+
+pub trait Backend {
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    /// Insert an article, with its title flags
+    fn add(&mut self, id: Id, title: String, flags: u32) -> Result<(), Self::Error>;
+    /// Record that an article redirects to a title
+    fn add_redirect(&mut self, from: Id, title: &str) -> Result<(), Self::Error>;
+    /// Record a link from an article to another
+    fn add_link(&mut self, link: (Id, Id)) -> Result<(), Self::Error>;
+    /// Record the title of a category page, without its prefix. Backends without
+    /// categories ignore them.
+    fn add_category_page(&mut self, _id: Id, _title: &str) -> Result<(), Self::Error> { Ok(()) }
+    /// Write out any buffered insertions
+    fn flush(&mut self) -> Result<(), Self::Error> { Ok(()) }
+
+    /// The ID of a title, without following redirects
+    fn index(&self, title: &str) -> Option<Id>;
+    /// The IDs of several titles, without following redirects. Remote backends
+    /// look them up in one query.
+    fn index_all(&self, titles: &[&str]) -> Vec<Option<Id>> {
+        titles.iter().map(|title| self.index(title)).collect()
+    }
+    /// The ID of a title, following redirects
+    fn resolve(&self, title: &str) -> Option<Id>;
+    fn lookup(&self, id: Id) -> Option<String>;
+    fn links_from(&self, from: Id) -> Vec<Id>;
+    fn links_to(&self, to: Id) -> Vec<Id>;
+
+    /// Search for a shortest path between two articles, reporting the search statistics
+    fn path_ids(&self, from: Id, to: Id) -> (Option<Vec<Id>>, Stats) {
+        bidi_dijkstra_with_stats(from, to, |n: &Id| self.links_from(*n), |n: &Id| self.links_to(*n))
+    }
+
+    /// Find a shortest path between two titles
+    fn path(&self, from: &str, to: &str) -> Result<Vec<String>, PathError> {
+        let start = self.resolve(from)
+            .ok_or_else(|| PathError::UnknownTitle(from.to_owned()))?;
+        let goal = self.resolve(to)
+            .ok_or_else(|| PathError::UnknownTitle(to.to_owned()))?;
+
+        let (path, stats) = self.path_ids(start, goal);
+        let path = path.ok_or(PathError::NoPathFound(stats))?;
+        Ok(path.iter().map(|&id| self.lookup(id).unwrap_or("???".to_owned())).collect())
+    }
+}
+
+/// The commands reading the whole graph, which columnar backends answer faster
+pub trait Analytics {
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    /// The statistics of the `stats` command
+    fn graph_stats(&self) -> Result<GraphStats, Self::Error>;
+    /// The date of the indexed dumps, if known
+    fn dump_date(&self) -> Option<String>;
+
+    /// Number the articles densely, and prepare the scans of their links
+    fn stage_links(&self) -> Result<Remap, Self::Error>;
+    /// Pass all the links between the articles of `remap` to `f`, as dense indices
+    fn scan_dense_links<F: FnMut(u32, u32)>(&self, remap: &Remap, f: F) -> Result<(), Self::Error>;
+    /// Store the PageRank of the articles, replacing any previous one
+    fn save_ranks(&mut self, scores: impl Iterator<Item = (Id, f64)>) -> Result<(), Self::Error>;
+    /// The articles with the highest PageRank, with their title and score
+    fn top_ranks(&self, n: usize) -> Result<Vec<(Id, String, f64)>, Self::Error>;
+}
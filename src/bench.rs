@@ -0,0 +1,215 @@
+//! Built-in benchmarks
+
+use std::{cell::Cell, collections::{BTreeMap, HashSet}, fs::File, io::{BufRead, BufReader, Read}, rc::Rc, time::{Duration, Instant}};
+use color_eyre::Result;
+use flate2::bufread::GzDecoder;
+
+use wikistra::path::{DenseMap, Visited};
+
+use crate::{querylog::{Query, QueryLog}, sql::{self, Charsets}, sqlite::{self, Db}};
+
+/// Wraps a reader and counts the bytes consumed from it
+struct Counting<R> {
+    inner: R,
+    count: Rc<Cell<u64>>,
+}
+
+impl <R: Read> Read for Counting<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.set(self.count.get() + n as u64);
+        Ok(n)
+    }
+}
+
+/// Result of running one parser engine over a dump
+pub struct Measurement {
+    pub engine: &'static str,
+    pub tuples: u64,
+    /// Decompressed bytes read
+    pub bytes: u64,
+    pub elapsed: Duration,
+}
+
+impl Measurement {
+    pub fn tuples_per_sec(&self) -> f64 {
+        self.tuples as f64 / self.elapsed.as_secs_f64()
+    }
+
+    pub fn mb_per_sec(&self) -> f64 {
+        self.bytes as f64 / 1e6 / self.elapsed.as_secs_f64()
+    }
+}
+
+/// The available parser engines
+const ENGINES: [&str; 2] = ["tokenizer", "regex"];
+
+fn run_engine(engine: &'static str, path: &str, charset: &Charsets) -> Result<Measurement> {
+    let count = Rc::new(Cell::new(0));
+    let compressed = BufReader::new(File::open(path)?);
+    let source = BufReader::new(Counting { inner: GzDecoder::new(compressed), count: count.clone() });
+
+    let started = Instant::now();
+    let tuples = count_rows(engine, source, charset)?;
+    let elapsed = started.elapsed();
+
+    Ok(Measurement { engine, tuples, bytes: count.get(), elapsed })
+}
+
+fn count_rows<R: BufRead>(engine: &str, source: R, charset: &Charsets) -> Result<u64> {
+    let mut tuples = 0;
+    match engine {
+        "tokenizer" => for row in sql::Loader::load_with_charset(source, charset)? {
+            row?;
+            tuples += 1;
+        },
+        "regex" => for row in sql::regex::Loader::load_with_charset(source, charset) {
+            row?;
+            tuples += 1;
+        },
+        _ => unreachable!("unknown engine {}", engine),
+    }
+    Ok(tuples)
+}
+
+/// Measure the throughput of every parser engine on a gzipped dump, and print a comparison
+pub fn parse(path: &str, charset: &Charsets) -> Result<()> {
+    println!("{:<10} {:>12} {:>10} {:>9} {:>12} {:>8}", "engine", "tuples", "MB", "secs", "tuples/s", "MB/s");
+
+    for engine in ENGINES {
+        eprintln!("Running {} engine...", engine);
+        let m = run_engine(engine, path, charset)?;
+        println!("{:<10} {:>12} {:>10.1} {:>9.2} {:>12.0} {:>8.2}",
+            m.engine, m.tuples, m.bytes as f64 / 1e6, m.elapsed.as_secs_f64(), m.tuples_per_sec(), m.mb_per_sec());
+    }
+
+    Ok(())
+}
+
+/// Run the path queries listed in a TSV file of `start<TAB>end` pairs, and print
+/// the latency and the number of expanded nodes of each one.
+pub fn path(db: &Db, pairs: &str, log: &mut QueryLog) -> Result<()> {
+    let (mut total, mut queries) = (Duration::ZERO, 0);
+
+    println!("start\tend\tlength\texpanded\tms");
+
+    for (start, end) in read_pairs(pairs)? {
+        let (start, end) = (start.as_str(), end.as_str());
+
+        let (Some(from), Some(to)) = (db.resolve(start), db.resolve(end)) else {
+            eprintln!("Skipping {} -> {}: unknown article", start, end);
+            continue;
+        };
+
+        let started = Instant::now();
+        let (path, stats) = db.path_ids(from, to, &HashSet::new());
+        let elapsed = started.elapsed();
+        log.record(Query::Path { start: start.to_owned(), end: end.to_owned() }, elapsed, path.as_ref().map(|p| p.len() - 1))?;
+
+        let length = path.map(|p| (p.len() - 1).to_string()).unwrap_or("-".to_owned());
+        println!("{}\t{}\t{}\t{}\t{:.1}", start, end, length, stats.expanded, elapsed.as_secs_f64() * 1000.0);
+
+        total += elapsed;
+        queries += 1;
+    }
+
+    if queries > 0 {
+        eprintln!("{} queries, {:.1}ms total, {:.1}ms mean", queries,
+            total.as_secs_f64() * 1000.0, total.as_secs_f64() * 1000.0 / queries as f64);
+    }
+
+    Ok(())
+}
+
+/// Time the visited maps of path searches on a graph of `n` nodes, reaching more and
+/// more of them: the default tree, and the dense map of compiled databases, which
+/// moves to a vector once a search reaches `n / 16` nodes.
+pub fn visited(n: u32) -> Result<()> {
+    println!("{:>10} {:>10} {:>10} {:>6}", "reached", "tree ms", "dense ms", "vector");
+
+    let mut rng = fastrand::Rng::with_seed(0);
+    let mut reached = 1000;
+    while reached <= n {
+        let nodes: Vec<u32> = (0..reached).map(|_| rng.u32(0..n)).collect();
+        let time = |map: &mut dyn Visited<u32>| {
+            let started = Instant::now();
+            for (i, &node) in nodes.iter().enumerate() {
+                map.visit(node, &nodes[i / 2]);
+            }
+            let found = nodes.iter().filter(|node| map.contains(node)).count();
+            assert_eq!(found, nodes.len());
+            started.elapsed()
+        };
+        let tree = time(&mut BTreeMap::new());
+        let mut dense = DenseMap::new(n);
+        let dense_time = time(&mut dense);
+        println!("{:>10} {:>10.1} {:>10.1} {:>6}", reached, tree.as_secs_f64() * 1000.0, dense_time.as_secs_f64() * 1000.0,
+            if dense.is_dense() { "yes" } else { "no" });
+        reached = match reached.checked_mul(4) { Some(next) if next > n && reached < n => n, Some(next) => next, None => break };
+    }
+
+    Ok(())
+}
+
+/// Load a synthetic graph into new databases with different page sizes, with and
+/// without memory-mapping, and time the load and a round of link lookups on each
+pub fn storage(pages: u32, links: u64) -> Result<()> {
+    println!("{:>9} {:>12} {:>9} {:>9} {:>12}", "page size", "mmap", "load secs", "MB", "lookups ms");
+
+    for page_size in [4096, sqlite::PAGE_SIZE] {
+        for mmap_size in [0, 1 << 28] {
+            let path = std::env::temp_dir().join(format!("wikistra-bench-{}-{}.sq3", std::process::id(), page_size));
+            let path = path.to_str().unwrap();
+            let mut db = Db::open_with_page_size(path, page_size)?;
+            db.set_mmap_size(mmap_size)?;
+            let mut rng = fastrand::Rng::with_seed(0);
+
+            let started = Instant::now();
+            for id in 0..pages {
+                db.add(id, format!("Article_{}", id), 0)?;
+            }
+            for _ in 0..links {
+                db.add_link((rng.u32(0..pages), rng.u32(0..pages)))?;
+            }
+            let load = started.elapsed();
+            let size = db.file_size()?;
+
+            let started = Instant::now();
+            for _ in 0..10_000 {
+                db.links_from(rng.u32(0..pages));
+            }
+            let lookups = started.elapsed();
+
+            println!("{:>9} {:>12} {:>9.1} {:>9.1} {:>12.1}", page_size, mmap_size, load.as_secs_f64(), size as f64 / 1e6,
+                lookups.as_secs_f64() * 1000.0);
+            drop(db);
+            std::fs::remove_file(path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Read a TSV file of `start<TAB>end` title pairs, skipping malformed lines
+pub fn read_pairs(path: &str) -> std::io::Result<Vec<(String, String)>> {
+    let mut pairs = vec![];
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        let Some((start, end)) = line.split_once('\t') else { continue };
+        pairs.push((start.to_owned(), end.to_owned()));
+    }
+    Ok(pairs)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn engines_agree() {
+        let dump = b"/*!40000 ALTER TABLE `t` DISABLE KEYS */;\nINSERT INTO `t` VALUES (1,'a'),(2,'b');\nINSERT INTO `t` VALUES (3,'c');\n";
+        for engine in ENGINES {
+            assert_eq!(count_rows(engine, &dump[..], &sql::Charset::Utf8.into()).unwrap(), 3, "{}", engine);
+        }
+    }
+}
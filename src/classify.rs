@@ -0,0 +1,104 @@
+//! Classification of titles into flags, computed once at index time, along with
+//! the flags read from the page dump
+
+use regex::Regex;
+
+use crate::{avoid, cli::ClassifyArgs, wikiname::WikiName};
+
+/// "List of ..." and similar pages
+pub const LIST: u32 = 1;
+/// Year, decade and century pages
+pub const DATE: u32 = 2;
+/// Disambiguation pages
+pub const DISAMBIGUATION: u32 = 4;
+/// Pages marked as redirects in the page dump (`page_is_redirect`)
+pub const REDIRECT: u32 = 8;
+/// Pages with a single revision (`page_is_new`)
+pub const NEW: u32 = 16;
+
+/// Title patterns of a language, for the list, date and disambiguation flags
+struct Patterns {
+    list: &'static str,
+    date: &'static str,
+    disambiguation: &'static str,
+}
+
+const ENGLISH: Patterns = Patterns {
+    list: avoid::LISTS,
+    date: avoid::YEARS,
+    disambiguation: avoid::DISAMBIGUATION,
+};
+
+const FRENCH: Patterns = Patterns {
+    list: r"^Liste_(des?|d'|du)_?",
+    date: r"^(\d{1,4}(_av\._J\.-C\.)?|Années_\d+|[IVXLC]+e_siècle(_av\._J\.-C\.)?)$",
+    disambiguation: r"_\(homonymie\)$",
+};
+
+const GERMAN: Patterns = Patterns {
+    list: r"^Liste_(der|von|des)_",
+    date: r"^(\d{1,4}(_v\._Chr\.)?|\d{3,4}er|\d{1,2}\._Jahrhundert(_v\._Chr\.)?)$",
+    disambiguation: r"_\(Begriffsklärung\)$",
+};
+
+/// Computes the flags of titles
+pub struct Classifier {
+    rules: Vec<(u32, Regex)>,
+}
+
+impl Classifier {
+
+    /// The patterns for the language of a wiki, with the overrides given on
+    /// the command line. Unknown languages get the English patterns.
+    pub fn new(wikiname: &WikiName, args: &ClassifyArgs) -> Result<Self, regex::Error> {
+        let defaults = match wikiname.language().as_deref() {
+            Some("fr") => FRENCH,
+            Some("de") => GERMAN,
+            _ => ENGLISH,
+        };
+
+        let rules = [
+            (LIST, args.list_pattern.as_deref().unwrap_or(defaults.list)),
+            (DATE, args.date_pattern.as_deref().unwrap_or(defaults.date)),
+            (DISAMBIGUATION, args.disambiguation_pattern.as_deref().unwrap_or(defaults.disambiguation)),
+        ];
+
+        let rules = rules.into_iter()
+            .map(|(flag, re)| Ok((flag, Regex::new(re)?)))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Classifier { rules })
+    }
+
+    /// The flags of a title
+    pub fn classify(&self, title: &str) -> u32 {
+        self.rules.iter()
+            .filter(|(_, re)| re.is_match(title))
+            .fold(0, |flags, (flag, _)| flags | flag)
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sample_flags() {
+        let en = Classifier::new(&"enwiki".parse().unwrap(), &ClassifyArgs::default()).unwrap();
+        assert_eq!(en.classify("List_of_lists"), LIST);
+        assert_eq!(en.classify("1999"), DATE);
+        assert_eq!(en.classify("Mercury_(disambiguation)"), DISAMBIGUATION);
+        assert_eq!(en.classify("Paris"), 0);
+
+        let fr = Classifier::new(&"frwiki".parse().unwrap(), &ClassifyArgs::default()).unwrap();
+        assert_eq!(fr.classify("Liste_des_communes_de_France"), LIST);
+        assert_eq!(fr.classify("XIXe_siècle"), DATE);
+        assert_eq!(fr.classify("Mercure_(homonymie)"), DISAMBIGUATION);
+
+        let custom = ClassifyArgs { list_pattern: Some("^Lijst_van_".into()), ..Default::default() };
+        let nl = Classifier::new(&"nlwiki".parse().unwrap(), &custom).unwrap();
+        assert_eq!(nl.classify("Lijst_van_steden"), LIST);
+        assert_eq!(nl.classify("List_of_lists"), 0);
+    }
+}
@@ -0,0 +1,60 @@
+//! Ordering titles for display
+//!
+//! Titles compare by their bytes, which puts accented and non-Latin letters after
+//! `z`. With the `icu` feature, they compare with the collation rules of the language
+//! of the wiki, so that `Élan` sorts next to `Elan` on frwiki.
+
+use std::cmp::Ordering;
+
+use crate::wikiname::WikiName;
+
+pub struct Collation {
+    #[cfg(feature = "icu")]
+    collator: Option<icu_collator::Collator>,
+}
+
+impl Collation {
+
+    /// The collation of the language of a wiki, such as `de` for `dewiki`. Without the
+    /// `icu` feature, or for unknown languages, titles compare by their bytes.
+    #[cfg_attr(not(feature = "icu"), allow(unused_variables))]
+    pub fn new(wikiname: &WikiName) -> Self {
+        #[cfg(feature = "icu")]
+        {
+            use icu_collator::{Collator, CollatorOptions};
+            let collator = wikiname.language()
+                .and_then(|lang| Collator::try_new(&lang.parse().ok()?, CollatorOptions::new()).ok());
+            if collator.is_none() {
+                eprintln!("Warning: No collation for {}, sorting titles by bytes", wikiname);
+            }
+            Collation { collator }
+        }
+        #[cfg(not(feature = "icu"))]
+        Collation {}
+    }
+
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        #[cfg(feature = "icu")]
+        if let Some(collator) = &self.collator {
+            // Underscores stand for spaces, which the collation mostly ignores
+            return collator.compare(&a.replace('_', " "), &b.replace('_', " ")).then_with(|| a.cmp(b))
+        }
+        a.cmp(b)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sample_order() {
+        let collation = Collation::new(&"frwiki".parse().unwrap());
+        let mut titles = ["Zèbre", "Élan", "Eau", "Ferme"];
+        titles.sort_by(|a, b| collation.compare(a, b));
+        #[cfg(feature = "icu")]
+        assert_eq!(titles, ["Eau", "Élan", "Ferme", "Zèbre"]);
+        #[cfg(not(feature = "icu"))]
+        assert_eq!(titles, ["Eau", "Ferme", "Zèbre", "Élan"]);
+    }
+}
@@ -0,0 +1,114 @@
+//! Comparing the paths found in two databases, such as two dump dates
+//!
+//! Articles are matched across databases by title and, when the title is unknown to
+//! one of them, by page ID, which is kept when an article is renamed. An article
+//! that became a redirect was merged into the target of the redirect.
+
+use std::{collections::HashSet, fmt};
+
+use crate::{Id, sqlite::Db};
+
+/// How the path between two articles changed from one database to another
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Change {
+    /// Same path in both databases
+    Same,
+    /// A different path of the same length
+    Rerouted,
+    Shorter,
+    Longer,
+    /// The path existed in the old database only
+    Disappeared,
+    /// The path exists in the new database only
+    Appeared,
+    /// No path in either database
+    Unreachable,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Change::Same => "same",
+            Change::Rerouted => "rerouted",
+            Change::Shorter => "shorter",
+            Change::Longer => "longer",
+            Change::Disappeared => "disappeared",
+            Change::Appeared => "appeared",
+            Change::Unreachable => "unreachable",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Classify the change between two paths, given as lists of titles
+pub fn change(old: Option<&[String]>, new: Option<&[String]>) -> Change {
+    match (old, new) {
+        (None, None) => Change::Unreachable,
+        (Some(_), None) => Change::Disappeared,
+        (None, Some(_)) => Change::Appeared,
+        (Some(old), Some(new)) if old.len() > new.len() => Change::Shorter,
+        (Some(old), Some(new)) if old.len() < new.len() => Change::Longer,
+        (Some(old), Some(new)) if old == new => Change::Same,
+        _ => Change::Rerouted,
+    }
+}
+
+/// The path between two articles as a list of titles, or `None` if there is none
+pub fn path(db: &Db, start: Id, end: Id) -> Option<Vec<String>> {
+    let path = db.path_ids(start, end, &HashSet::new()).0?;
+    Some(path.iter().map(|&id| db.lookup(id).unwrap_or("???".to_owned())).collect())
+}
+
+/// The article that the article of ID `id` in another database of the same wiki
+/// is in `db`: the same page, possibly renamed, or the article it was merged into
+pub fn follow(db: &Db, id: Id) -> Option<Id> {
+    db.lookup(id)?;
+    Some(db.redirect_target(id).unwrap_or(id))
+}
+
+/// Complete the IDs of an article in a sequence of databases, missing where its
+/// title is unknown, from the nearest database where it is known.
+/// `follow(i, id)` finds in the database `i` the article of ID `id` in a neighbor.
+pub fn track<F: FnMut(usize, Id) -> Option<Id>>(ids: &mut [Option<Id>], mut follow: F) {
+    for i in 1..ids.len() {
+        if let (None, Some(previous)) = (ids[i], ids[i - 1]) { ids[i] = follow(i, previous) }
+    }
+    for i in (0..ids.len().saturating_sub(1)).rev() {
+        if let (None, Some(next)) = (ids[i], ids[i + 1]) { ids[i] = follow(i, next) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sample_changes() {
+        let path = |titles: &[&str]| titles.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        let (abc, adc, ac, abdc) = (path(&["A", "B", "C"]), path(&["A", "D", "C"]), path(&["A", "C"]), path(&["A", "B", "D", "C"]));
+
+        assert_eq!(change(Some(&abc), Some(&abc)), Change::Same);
+        assert_eq!(change(Some(&abc), Some(&adc)), Change::Rerouted);
+        assert_eq!(change(Some(&abc), Some(&ac)), Change::Shorter);
+        assert_eq!(change(Some(&abc), Some(&abdc)), Change::Longer);
+        assert_eq!(change(Some(&abc), None), Change::Disappeared);
+        assert_eq!(change(None, Some(&abc)), Change::Appeared);
+        assert_eq!(change(None, None), Change::Unreachable);
+    }
+
+    #[test]
+    fn sample_tracking() {
+        // Page 1 was renamed in the third database, and merged into page 2 in the fourth.
+        // Its title is only known to the second one.
+        let pages: [&[(Id, Id)]; 5] = [&[(1, 1)], &[(1, 1)], &[(1, 1), (2, 2)], &[(1, 2), (2, 2)], &[(2, 2)]];
+        let follow = |i: usize, id| pages[i].iter().find(|(page, _)| *page == id).map(|(_, target)| *target);
+
+        let mut ids = [None, Some(1), None, None, None];
+        track(&mut ids, follow);
+        assert_eq!(ids, [Some(1), Some(1), Some(1), Some(2), Some(2)]);
+
+        let mut ids = [None, None];
+        track(&mut ids, follow);
+        assert_eq!(ids, [None, None]);
+    }
+}
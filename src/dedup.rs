@@ -0,0 +1,81 @@
+//! Removing duplicate links before they reach the database
+//!
+//! The links of the pagelinks dump come sorted by source, so that the links of an
+//! article form a run. Each run is deduplicated in memory, which is cheaper than
+//! letting the database probe its index for every duplicate.
+
+use crate::Id;
+
+/// The current run of links from a single article
+pub struct Runs {
+    from: Option<Id>,
+    targets: Vec<Id>,
+    drop_self_links: bool,
+    /// Links dropped as duplicates of a link of the same run
+    pub duplicates: u64,
+    /// Links from an article to itself, dropped if asked for
+    pub self_links: u64,
+}
+
+impl Runs {
+
+    pub fn new(drop_self_links: bool) -> Self {
+        Runs { from: None, targets: vec![], drop_self_links, duplicates: 0, self_links: 0 }
+    }
+
+    /// Add a link. When it starts a new run, the links of the previous one are
+    /// passed to `emit`, once each and sorted by target.
+    pub fn push<E, F: FnMut((Id, Id)) -> Result<(), E>>(&mut self, (from, to): (Id, Id), emit: F) -> Result<(), E> {
+        if self.from != Some(from) {
+            self.finish(emit)?;
+            self.from = Some(from);
+        }
+        if from == to && self.drop_self_links {
+            self.self_links += 1;
+        } else {
+            self.targets.push(to);
+        }
+        Ok(())
+    }
+
+    /// Pass the links of the current run to `emit`
+    pub fn finish<E, F: FnMut((Id, Id)) -> Result<(), E>>(&mut self, mut emit: F) -> Result<(), E> {
+        let Some(from) = self.from else { return Ok(()) };
+        self.targets.sort_unstable();
+        let before = self.targets.len();
+        self.targets.dedup();
+        self.duplicates += (before - self.targets.len()) as u64;
+        for &to in &self.targets {
+            emit((from, to))?;
+        }
+        self.targets.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sample_runs() {
+        let links = [(1, 3), (1, 2), (1, 3), (1, 1), (2, 1), (2, 1), (3, 3)];
+        let mut kept: Vec<(Id, Id)> = vec![];
+        let mut runs = Runs::new(true);
+        for link in links {
+            runs.push(link, |link| { kept.push(link); Ok::<_, ()>(()) }).unwrap();
+        }
+        runs.finish(|link| { kept.push(link); Ok::<_, ()>(()) }).unwrap();
+        assert_eq!(kept, [(1, 2), (1, 3), (2, 1)]);
+        assert_eq!((runs.duplicates, runs.self_links), (2, 2));
+
+        let mut kept = vec![];
+        let mut runs = Runs::new(false);
+        for link in links {
+            runs.push(link, |link| { kept.push(link); Ok::<_, ()>(()) }).unwrap();
+        }
+        runs.finish(|link| { kept.push(link); Ok::<_, ()>(()) }).unwrap();
+        assert_eq!(kept, [(1, 1), (1, 2), (1, 3), (2, 1), (3, 3)]);
+        assert_eq!((runs.duplicates, runs.self_links), (2, 0));
+    }
+}
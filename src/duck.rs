@@ -0,0 +1,263 @@
+//! DuckDB storage of the link graph, for the commands scanning all the links
+//!
+//! The tables are columnar and without keys, so that counting the degrees of all
+//! the articles or staging the links for PageRank reads whole columns at once.
+//! Path queries keep using the SQLite database.
+
+use std::collections::HashMap;
+
+use duckdb::{Connection, OptionalExt, params};
+use once_cell::unsync::OnceCell;
+
+use crate::{Id, backend::{Analytics, Backend}, sqlite::Remap, stats::{Distribution, GraphStats}};
+
+/// Number of rows buffered before being appended at once
+const BATCH: usize = 100_000;
+
+/// The articles, and the number of links between indexed pages they have
+const DEGREES: &str = "
+    CREATE OR REPLACE TEMP VIEW indexed_link AS
+        SELECT \"from\", \"to\" FROM link
+        WHERE \"from\" IN (SELECT id FROM page) AND \"to\" IN (SELECT id FROM page);
+    CREATE OR REPLACE TEMP VIEW degree AS
+        SELECT coalesce(incoming.n, 0) AS links_in, coalesce(outgoing.n, 0) AS links_out
+        FROM page
+        LEFT JOIN (SELECT \"to\" AS id, count(*) AS n FROM indexed_link GROUP BY \"to\") incoming USING (id)
+        LEFT JOIN (SELECT \"from\" AS id, count(*) AS n FROM indexed_link GROUP BY \"from\") outgoing USING (id)
+        WHERE id NOT IN (SELECT id FROM redirect);
+";
+
+pub struct DuckDb {
+    conn: Connection,
+    path: String,
+    pages: Vec<(Id, String, u32)>,
+    redirects: Vec<(Id, String)>,
+    links: Vec<(Id, Id)>,
+    /// The IDs of all the titles, read in one scan on the first lookup
+    titles: OnceCell<HashMap<String, Id>>,
+}
+
+impl DuckDb {
+
+    /// Open or create a database file, and create the tables if needed
+    pub fn open(path: &str) -> Result<Self, duckdb::Error> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch("
+            CREATE TABLE IF NOT EXISTS page (id UINTEGER NOT NULL, title VARCHAR NOT NULL, flags UINTEGER NOT NULL);
+            CREATE TABLE IF NOT EXISTS redirect (id UINTEGER NOT NULL, title VARCHAR NOT NULL);
+            CREATE TABLE IF NOT EXISTS link (\"from\" UINTEGER NOT NULL, \"to\" UINTEGER NOT NULL);
+        ")?;
+        Ok(DuckDb { conn, path: path.to_owned(), pages: vec![], redirects: vec![], links: vec![], titles: OnceCell::new() })
+    }
+
+    fn titles(&self) -> Result<&HashMap<String, Id>, duckdb::Error> {
+        self.titles.get_or_try_init(|| {
+            self.conn.prepare("SELECT title, id FROM page")?
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect()
+        })
+    }
+
+    fn query_ids(&self, query: &str, id: Id) -> Vec<Id> {
+        let Ok(mut stmt) = self.conn.prepare(query) else { return vec![] };
+        let ids = stmt.query_map(params![id], |row| row.get(0)).and_then(|rows| rows.collect());
+        ids.unwrap_or_default()
+    }
+}
+
+impl Backend for DuckDb {
+    type Error = duckdb::Error;
+
+    fn add(&mut self, id: Id, title: String, flags: u32) -> Result<(), Self::Error> {
+        self.pages.push((id, title, flags));
+        if self.pages.len() >= BATCH { self.flush()? }
+        Ok(())
+    }
+
+    fn add_redirect(&mut self, from: Id, title: &str) -> Result<(), Self::Error> {
+        self.redirects.push((from, title.to_owned()));
+        if self.redirects.len() >= BATCH { self.flush()? }
+        Ok(())
+    }
+
+    fn add_link(&mut self, link: (Id, Id)) -> Result<(), Self::Error> {
+        self.links.push(link);
+        if self.links.len() >= BATCH { self.flush()? }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        if !self.pages.is_empty() {
+            let mut appender = self.conn.appender("page")?;
+            for (id, title, flags) in self.pages.drain(..) {
+                appender.append_row(params![id, title, flags])?;
+            }
+            appender.flush()?;
+            self.titles = OnceCell::new();
+        }
+        if !self.redirects.is_empty() {
+            let mut appender = self.conn.appender("redirect")?;
+            for (id, title) in self.redirects.drain(..) {
+                appender.append_row(params![id, title])?;
+            }
+            appender.flush()?;
+        }
+        if !self.links.is_empty() {
+            let mut appender = self.conn.appender("link")?;
+            for (from, to) in self.links.drain(..) {
+                appender.append_row(params![from, to])?;
+            }
+            appender.flush()?;
+        }
+        Ok(())
+    }
+
+    fn index(&self, title: &str) -> Option<Id> {
+        self.titles().ok()?.get(title).copied()
+    }
+
+    fn index_all(&self, titles: &[&str]) -> Vec<Option<Id>> {
+        match self.titles() {
+            Ok(ids) => titles.iter().map(|title| ids.get(*title).copied()).collect(),
+            Err(_) => vec![None; titles.len()],
+        }
+    }
+
+    fn resolve(&self, title: &str) -> Option<Id> {
+        let id = self.index(title)?;
+        let target = self.conn.query_row("SELECT title FROM redirect WHERE id = ?", params![id], |row| row.get::<_, String>(0))
+            .optional().ok()?;
+        Some(target.and_then(|target| self.index(&target)).unwrap_or(id))
+    }
+
+    fn lookup(&self, id: Id) -> Option<String> {
+        self.conn.query_row("SELECT title FROM page WHERE id = ?", params![id], |row| row.get(0))
+            .optional().ok()?
+    }
+
+    fn links_from(&self, from: Id) -> Vec<Id> {
+        self.query_ids("SELECT \"to\" FROM link WHERE \"from\" = ?", from)
+    }
+
+    fn links_to(&self, to: Id) -> Vec<Id> {
+        self.query_ids("SELECT \"from\" FROM link WHERE \"to\" = ?", to)
+    }
+}
+
+impl Analytics for DuckDb {
+    type Error = duckdb::Error;
+
+    fn graph_stats(&self) -> Result<GraphStats, Self::Error> {
+        self.conn.execute_batch(DEGREES)?;
+        let count = |query: &str| self.conn.query_row(query, [], |row| row.get::<_, i64>(0)).map(|n| n as u64);
+        // The distributions are measured on the number of articles of each degree
+        let distribution = |column: &str| -> Result<Distribution, duckdb::Error> {
+            let mut degrees = vec![];
+            let query = format!("SELECT {0}, count(*) FROM degree GROUP BY {0}", column);
+            for row in self.conn.prepare(&query)?.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))? {
+                let (degree, articles) = row?;
+                degrees.extend(std::iter::repeat(degree as u32).take(articles as usize));
+            }
+            Ok(Distribution::new(&mut degrees))
+        };
+        Ok(GraphStats {
+            pages: count("SELECT count(*) FROM page")?,
+            redirects: count("SELECT count(*) FROM redirect WHERE id IN (SELECT id FROM page)")?,
+            links: count("SELECT count(*) FROM indexed_link")?,
+            orphans: count("SELECT count(*) FROM degree WHERE links_in = 0")?,
+            dead_ends: count("SELECT count(*) FROM degree WHERE links_out = 0")?,
+            links_in: distribution("links_in")?,
+            links_out: distribution("links_out")?,
+            size: std::fs::metadata(&self.path).map(|m| m.len()).unwrap_or_default(),
+        })
+    }
+
+    fn dump_date(&self) -> Option<String> {
+        None
+    }
+
+    /// Stage the links as dense indices in a table, which every scan reads again
+    fn stage_links(&self) -> Result<Remap, Self::Error> {
+        self.conn.execute_batch("
+            CREATE OR REPLACE TEMP TABLE dense AS
+                SELECT id, (row_number() OVER (ORDER BY id) - 1)::UINTEGER AS i FROM page;
+            CREATE OR REPLACE TEMP TABLE dense_link AS
+                SELECT f.i AS \"from\", t.i AS \"to\" FROM link
+                JOIN dense f ON f.id = link.\"from\"
+                JOIN dense t ON t.id = link.\"to\";
+        ")?;
+        let ids: Vec<Id> = self.conn.prepare("SELECT id FROM dense ORDER BY i")?
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        Ok(ids.into())
+    }
+
+    fn scan_dense_links<F: FnMut(u32, u32)>(&self, _remap: &Remap, mut f: F) -> Result<(), Self::Error> {
+        let mut stmt = self.conn.prepare("SELECT \"from\", \"to\" FROM dense_link")?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            f(row.get(0)?, row.get(1)?);
+        }
+        Ok(())
+    }
+
+    fn save_ranks(&mut self, scores: impl Iterator<Item = (Id, f64)>) -> Result<(), Self::Error> {
+        self.conn.execute_batch("CREATE OR REPLACE TABLE rank (id UINTEGER NOT NULL, score DOUBLE NOT NULL);")?;
+        let mut appender = self.conn.appender("rank")?;
+        for (id, score) in scores {
+            appender.append_row(params![id, score])?;
+        }
+        appender.flush()
+    }
+
+    fn top_ranks(&self, n: usize) -> Result<Vec<(Id, String, f64)>, Self::Error> {
+        self.conn.prepare("SELECT id, title, score FROM rank JOIN page USING (id) ORDER BY score DESC LIMIT ?")?
+            .query_map(params![n as i64], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rank::PageRank;
+
+    fn sample_db() -> DuckDb {
+        let mut db = DuckDb::open(":memory:").unwrap();
+        for (id, title) in [(1, "Paris"), (2, "France"), (3, "Europe"), (4, "Ville_Lumière")] {
+            db.add(id, title.into(), 0).unwrap();
+        }
+        db.add_redirect(4, "Paris").unwrap();
+        // The link to an unknown article is left out of the scans
+        for link in [(1, 2), (1, 3), (2, 3), (3, 1), (4, 9)] {
+            db.add_link(link).unwrap();
+        }
+        db.flush().unwrap();
+        db
+    }
+
+    #[test]
+    fn sample_stats() {
+        let db = sample_db();
+        assert_eq!(db.resolve("Ville_Lumière"), Some(1));
+        assert_eq!(db.index_all(&["France", "Rome"]), [Some(2), None]);
+
+        let stats = db.graph_stats().unwrap();
+        assert_eq!((stats.pages, stats.redirects, stats.links), (4, 1, 4));
+        assert_eq!((stats.orphans, stats.dead_ends), (0, 0));
+        assert_eq!((stats.links_in.max, stats.links_out.max), (2, 2));
+    }
+
+    #[test]
+    fn sample_ranks() {
+        let mut db = sample_db();
+        let remap = db.stage_links().unwrap();
+        assert_eq!(remap.ids(), [1, 2, 3, 4]);
+        let pagerank = PageRank { damping: 0.85, iterations: 100, tolerance: 1e-9 };
+        let (scores, _) = pagerank.run(remap.len() as usize, |f| db.scan_dense_links(&remap, f)).unwrap();
+        db.save_ranks(remap.ids().iter().copied().zip(scores)).unwrap();
+
+        let top: Vec<String> = db.top_ranks(2).unwrap().into_iter().map(|(_, title, _)| title).collect();
+        assert_eq!(top, ["Europe", "Paris"]);
+    }
+}
@@ -0,0 +1,55 @@
+//! Errors of the downloading, loading, indexing, splitting and saving code, for
+//! callers that need to tell them apart. The commands report them with `color_eyre`.
+
+use std::io;
+
+use thiserror::Error;
+
+use crate::sql::{LoaderError, TypeError};
+
+#[derive(Error, Debug)]
+pub enum WikistraError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+    /// Boxed, as HTTP errors hold the whole response
+    #[error(transparent)]
+    Http(Box<ureq::Error>),
+    #[error("Invalid dump: {0}")]
+    Dump(#[from] LoaderError),
+    #[error("Invalid dump: unexpected {0}")]
+    Type(#[from] TypeError),
+    #[error("Invalid dump: a row has too few columns")]
+    MissingColumn,
+    #[error("Invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid index spec: {0}")]
+    Spec(String),
+    #[cfg(feature = "mysql")]
+    #[error(transparent)]
+    Mysql(#[from] mysql::Error),
+    #[cfg(feature = "postgres")]
+    #[error(transparent)]
+    Postgres(#[from] postgres::Error),
+    #[cfg(feature = "duckdb")]
+    #[error(transparent)]
+    DuckDb(#[from] duckdb::Error),
+    #[error("Invalid {0} header: {1}")]
+    Header(&'static str, String),
+    #[error("{0} does not support range requests")]
+    NoRanges(String),
+    #[error("Empty checksum file for {0}")]
+    EmptyChecksum(String),
+    /// The file that does not match is moved to `path`, out of the way of the next download
+    #[error("Checksum mismatch: expected {expected}, got {actual}. The file was moved to {path}, retry the download.")]
+    Checksum { path: String, expected: String, actual: String },
+}
+
+impl From<ureq::Error> for WikistraError {
+    fn from(e: ureq::Error) -> Self {
+        WikistraError::Http(Box::new(e))
+    }
+}
+
+pub type Result<T, E = WikistraError> = std::result::Result<T, E>;
@@ -0,0 +1,302 @@
+//! A small expression language selecting articles, such as
+//! `ns=0 && !regex('^List of') && indegree>10`
+//!
+//! Expressions combine comparisons of article attributes with `&&`, `||`, `!`
+//! and parentheses. The attributes are `id`, `ns`, `title`, `indegree` and
+//! `outdegree`, and the boolean `redirect`, `list`, `date`, `disambiguation` and `new`.
+//! `regex('...')` matches the title, written with spaces instead of underscores.
+//! Titles are ordered by code point, so that `title >= 'M'` selects the second half
+//! of the alphabet.
+
+use regex::Regex;
+use thiserror::Error;
+
+use crate::{Id, classify, sqlite::Db};
+
+#[derive(Error, Debug)]
+pub enum FilterError {
+    #[error("Invalid filter at position {0}: {1}")]
+    Syntax(usize, String),
+    #[error("Invalid filter regex: {0}")]
+    Regex(#[from] regex::Error),
+}
+
+/// The attributes of an article that filters look at
+pub trait Article {
+    fn id(&self) -> Id;
+    fn title(&self) -> &str;
+    /// Number of links pointing to, respectively from, the article
+    fn degree(&self) -> (usize, usize);
+    fn is_redirect(&self) -> bool;
+    /// Title flags computed at index time, see [`classify`]
+    fn flags(&self) -> u32;
+}
+
+/// An article of the database, whose attributes are looked up on demand
+pub struct Page<'a> {
+    pub db: &'a Db,
+    pub id: Id,
+    pub title: &'a str,
+}
+
+impl Article for Page<'_> {
+    fn id(&self) -> Id { self.id }
+    fn title(&self) -> &str { self.title }
+    fn degree(&self) -> (usize, usize) { self.db.degree(self.id) }
+    fn is_redirect(&self) -> bool { self.db.is_redirect(self.id) }
+    fn flags(&self) -> u32 { self.db.title_flags(self.id) }
+}
+
+/// An article read by a scan of the whole database, along with its attributes
+pub struct Scanned<'a> {
+    pub id: Id,
+    pub title: &'a str,
+    pub degree: (usize, usize),
+    pub redirect: bool,
+    pub flags: u32,
+}
+
+impl Article for Scanned<'_> {
+    fn id(&self) -> Id { self.id }
+    fn title(&self) -> &str { self.title }
+    fn degree(&self) -> (usize, usize) { self.degree }
+    fn is_redirect(&self) -> bool { self.redirect }
+    fn flags(&self) -> u32 { self.flags }
+}
+
+type Predicate = Box<dyn Fn(&dyn Article) -> bool>;
+
+/// A compiled filter expression
+pub struct Filter {
+    predicate: Predicate,
+    degrees: bool,
+}
+
+impl Filter {
+    pub fn new(expr: &str) -> Result<Self, FilterError> {
+        let mut parser = Parser { tokens: tokenize(expr)?, pos: 0, degrees: false };
+        let predicate = parser.or()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(Filter { predicate, degrees: parser.degrees }),
+            Some((at, token)) => Err(FilterError::Syntax(*at, format!("unexpected {:?}", token))),
+        }
+    }
+
+    /// Whether an article is selected by the filter
+    pub fn matches(&self, article: &dyn Article) -> bool {
+        (self.predicate)(article)
+    }
+
+    /// Whether the filter looks at the number of links of the articles
+    pub fn uses_degrees(&self) -> bool {
+        self.degrees
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(i64),
+    Text(String),
+    Op(&'static str),
+}
+
+const OPERATORS: [&str; 13] = ["&&", "||", "==", "!=", "<=", ">=", "!", "=", "<", ">", "(", ")", ","];
+
+fn tokenize(expr: &str) -> Result<Vec<(usize, Token)>, FilterError> {
+    let mut tokens = vec![];
+    let mut rest = expr;
+    loop {
+        rest = rest.trim_start();
+        let at = expr.len() - rest.len();
+        let Some(c) = rest.chars().next() else { return Ok(tokens) };
+
+        let (token, len) = if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            (Token::Op(op), op.len())
+        } else if c == '\'' || c == '"' {
+            let end = rest[1..].find(c)
+                .ok_or_else(|| FilterError::Syntax(at, "unterminated string".to_owned()))?;
+            (Token::Text(rest[1..=end].to_owned()), end + 2)
+        } else if c.is_ascii_digit() || c == '-' {
+            let len = 1 + rest[1..].find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len() - 1);
+            let n = rest[..len].parse()
+                .map_err(|_| FilterError::Syntax(at, format!("invalid number {}", &rest[..len])))?;
+            (Token::Number(n), len)
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let len = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(rest.len());
+            (Token::Ident(rest[..len].to_owned()), len)
+        } else {
+            return Err(FilterError::Syntax(at, format!("unexpected character {:?}", c)));
+        };
+
+        tokens.push((at, token));
+        rest = &rest[len..];
+    }
+}
+
+/// Recursive descent parser, compiling expressions as it goes
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    /// Whether `indegree` or `outdegree` were seen
+    degrees: bool,
+}
+
+impl Parser {
+    fn next(&mut self) -> Result<(usize, Token), FilterError> {
+        let end = self.tokens.last().map_or(0, |(at, _)| at + 1);
+        let token = self.tokens.get(self.pos).cloned()
+            .ok_or_else(|| FilterError::Syntax(end, "unexpected end of filter".to_owned()))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        let found = matches!(self.tokens.get(self.pos), Some((_, Token::Op(o))) if *o == op);
+        if found { self.pos += 1 }
+        found
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), FilterError> {
+        match self.next()? {
+            (_, Token::Op(o)) if o == op => Ok(()),
+            (at, token) => Err(FilterError::Syntax(at, format!("expected {}, found {:?}", op, token))),
+        }
+    }
+
+    fn or(&mut self) -> Result<Predicate, FilterError> {
+        let mut left = self.and()?;
+        while self.eat("||") {
+            let right = self.and()?;
+            left = Box::new(move |a| left(a) || right(a));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Predicate, FilterError> {
+        let mut left = self.unary()?;
+        while self.eat("&&") {
+            let right = self.unary()?;
+            left = Box::new(move |a| left(a) && right(a));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Predicate, FilterError> {
+        if self.eat("!") {
+            let inner = self.unary()?;
+            return Ok(Box::new(move |a| !inner(a)));
+        }
+        if self.eat("(") {
+            let inner = self.or()?;
+            self.expect(")")?;
+            return Ok(inner);
+        }
+
+        let (at, name) = match self.next()? {
+            (at, Token::Ident(name)) => (at, name),
+            (at, token) => return Err(FilterError::Syntax(at, format!("expected an attribute, found {:?}", token))),
+        };
+
+        match name.as_str() {
+            "regex" => {
+                self.expect("(")?;
+                let re = match self.next()? {
+                    (_, Token::Text(re)) => Regex::new(&re)?,
+                    (at, token) => return Err(FilterError::Syntax(at, format!("expected a string, found {:?}", token))),
+                };
+                self.expect(")")?;
+                Ok(Box::new(move |a| re.is_match(&a.title().replace('_', " "))))
+            }
+            "redirect" => Ok(Box::new(|a| a.is_redirect())),
+            "list" => Ok(Box::new(|a| a.flags() & classify::LIST != 0)),
+            "date" => Ok(Box::new(|a| a.flags() & classify::DATE != 0)),
+            "disambiguation" => Ok(Box::new(|a| a.flags() & classify::DISAMBIGUATION != 0)),
+            "new" => Ok(Box::new(|a| a.flags() & classify::NEW != 0)),
+            "title" => {
+                let op = self.comparison()?;
+                let title = match self.next()? {
+                    (_, Token::Text(title)) => title.replace(' ', "_"),
+                    (at, token) => return Err(FilterError::Syntax(at, format!("expected a string, found {:?}", token))),
+                };
+                Ok(Box::new(move |a| compare(op, a.title(), title.as_str())))
+            }
+            "id" | "ns" | "indegree" | "outdegree" => {
+                let op = self.comparison()?;
+                let value = match self.next()? {
+                    (_, Token::Number(n)) => n,
+                    (at, token) => return Err(FilterError::Syntax(at, format!("expected a number, found {:?}", token))),
+                };
+                self.degrees |= name.ends_with("degree");
+                let attribute: fn(&dyn Article) -> i64 = match name.as_str() {
+                    "id" => |a| a.id() as i64,
+                    // Only the main namespace is indexed
+                    "ns" => |_| 0,
+                    "indegree" => |a| a.degree().0 as i64,
+                    _ => |a| a.degree().1 as i64,
+                };
+                Ok(Box::new(move |a| compare(op, &attribute(a), &value)))
+            }
+            _ => Err(FilterError::Syntax(at, format!("unknown attribute {}", name))),
+        }
+    }
+
+    fn comparison(&mut self) -> Result<&'static str, FilterError> {
+        match self.next()? {
+            (_, Token::Op(op)) if ["=", "==", "!=", "<", "<=", ">", ">="].contains(&op) => Ok(op),
+            (at, token) => Err(FilterError::Syntax(at, format!("expected a comparison, found {:?}", token))),
+        }
+    }
+}
+
+/// Whether `left op right` holds, for an operator accepted by `Parser::comparison`
+fn compare<T: Ord + ?Sized>(op: &str, left: &T, right: &T) -> bool {
+    match op {
+        "=" | "==" => left == right,
+        "!=" => left != right,
+        "<" => left < right,
+        "<=" => left <= right,
+        ">" => left > right,
+        _ => left >= right,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Sample { id: Id, title: &'static str, degree: (usize, usize), flags: u32 }
+
+    impl Article for Sample {
+        fn id(&self) -> Id { self.id }
+        fn title(&self) -> &str { self.title }
+        fn degree(&self) -> (usize, usize) { self.degree }
+        fn is_redirect(&self) -> bool { false }
+        fn flags(&self) -> u32 { self.flags }
+    }
+
+    #[test]
+    fn sample_filters() {
+        let paris = Sample { id: 1, title: "Paris", degree: (50, 20), flags: 0 };
+        let list = Sample { id: 2, title: "List_of_cities", degree: (15, 300), flags: classify::LIST };
+
+        let filter = Filter::new("ns=0 && !regex('^List of') && indegree>10").unwrap();
+        assert!(filter.matches(&paris));
+        assert!(!filter.matches(&list));
+
+        let filter = Filter::new("list || (outdegree <= 20 && title != 'Lyon')").unwrap();
+        assert!(filter.matches(&paris));
+        assert!(filter.matches(&list));
+
+        assert!(Filter::new("title = \"List of cities\"").unwrap().matches(&list));
+        assert!(Filter::new("!(id == 1)").unwrap().matches(&list));
+        assert!(Filter::new("title < 'M' && title >= 'List'").unwrap().matches(&list));
+        assert!(!Filter::new("title > 'Paris' || title <= 'Lyon'").unwrap().matches(&paris));
+        assert!(!Filter::new("list || title = 'Paris'").unwrap().uses_degrees());
+        assert!(Filter::new("!(outdegree = 0)").unwrap().uses_degrees());
+
+        for invalid in ["", "indegree >", "size > 3", "regex('[')", "(list", "list list", "title = 'x"] {
+            assert!(Filter::new(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! Geographic coordinates of articles
+
+/// Latitude and longitude, in degrees
+pub type Point = (f64, f64);
+
+/// Mean radius of the Earth, in kilometers
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// Great-circle distance between two points given in degrees, in kilometers
+pub fn distance_km((lat1, lon1): Point, (lat2, lon2): Point) -> f64 {
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let dphi = (lat2 - lat1).to_radians();
+    let dlambda = (lon2 - lon1).to_radians();
+
+    let a = (dphi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (dlambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+/// A latitude and longitude range containing every point within `radius_km`
+/// of a center, as `(min_lat, max_lat, min_lon, max_lon)`
+pub fn bounding_box((lat, lon): Point, radius_km: f64) -> (f64, f64, f64, f64) {
+    let dlat = (radius_km / EARTH_RADIUS_KM).to_degrees();
+    let (min_lat, max_lat) = ((lat - dlat).max(-90.0), (lat + dlat).min(90.0));
+
+    // Near the poles or the antimeridian, the box spans all longitudes
+    let cos = min_lat.to_radians().cos().min(max_lat.to_radians().cos());
+    let dlon = if cos <= 1e-9 { 180.0 } else { dlat / cos };
+    if lon - dlon < -180.0 || lon + dlon > 180.0 {
+        (min_lat, max_lat, -180.0, 180.0)
+    } else {
+        (min_lat, max_lat, lon - dlon, lon + dlon)
+    }
+}
+
+/// Parse a distance such as `10km`, `500m` or `3` (kilometers), into kilometers
+pub fn parse_distance(text: &str) -> Result<f64, String> {
+    let text = text.trim();
+    let (number, scale) = if let Some(km) = text.strip_suffix("km") {
+        (km, 1.0)
+    } else if let Some(m) = text.strip_suffix('m') {
+        (m, 0.001)
+    } else {
+        (text, 1.0)
+    };
+    let value: f64 = number.trim().parse().map_err(|_| format!("invalid distance: {}", text))?;
+    if value < 0.0 { return Err(format!("negative distance: {}", text)) }
+    Ok(value * scale)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sample_distances() {
+        let paris = (48.8566, 2.3522);
+        let london = (51.5074, -0.1278);
+        let d = distance_km(paris, london);
+        assert!((d - 343.5).abs() < 1.0, "{}", d);
+        assert_eq!(distance_km(paris, paris), 0.0);
+
+        let (min_lat, max_lat, min_lon, max_lon) = bounding_box(paris, 400.0);
+        assert!(min_lat < london.0 && london.0 < max_lat);
+        assert!(min_lon < london.1 && london.1 < max_lon);
+        assert_eq!(bounding_box((89.9, 0.0), 100.0).2, -180.0);
+        assert_eq!(bounding_box((0.0, 179.9), 100.0).3, 180.0);
+
+        assert_eq!(parse_distance("10km"), Ok(10.0));
+        assert_eq!(parse_distance("500m"), Ok(0.5));
+        assert_eq!(parse_distance("3"), Ok(3.0));
+        assert!(parse_distance("far").is_err());
+    }
+}
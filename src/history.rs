@@ -0,0 +1,113 @@
+//! Databases built from the dumps of different dates, to follow articles over time
+//!
+//! The databases of a wiki are registered in `./<wikiname>-history.toml`:
+//!
+//! ```toml
+//! [[dump]]
+//! date = "20240601"
+//! db = "./enwiki-20240601-db.sq3"
+//! ```
+
+use std::{fs, io};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum HistoryError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("Invalid history file: {0}")]
+    Read(#[from] toml::de::Error),
+    #[error(transparent)]
+    Write(#[from] toml::ser::Error),
+    #[error("Invalid dump date {0}, expected YYYYMMDD")]
+    Date(String),
+    #[error("No database was registered for a dump of {0} or before")]
+    NoDump(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct History {
+    /// Sorted by date
+    #[serde(rename = "dump", default)]
+    pub dumps: Vec<Dump>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Dump {
+    /// Date of the dump, as `YYYYMMDD`
+    pub date: String,
+    /// Path of the database built from it
+    pub db: String,
+}
+
+/// Path of the history file of a wiki
+pub fn path(wikiname: &str) -> String {
+    format!("./{}-history.toml", wikiname)
+}
+
+fn check_date(date: &str) -> Result<(), HistoryError> {
+    match date.len() == 8 && date.bytes().all(|b| b.is_ascii_digit()) {
+        true => Ok(()),
+        false => Err(HistoryError::Date(date.to_owned())),
+    }
+}
+
+impl History {
+
+    /// Read a history file. A missing file is an empty history.
+    pub fn read(path: &str) -> Result<Self, HistoryError> {
+        match fs::read_to_string(path) {
+            Ok(text) => Ok(toml::from_str(&text)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(History::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn write(&self, path: &str) -> Result<(), HistoryError> {
+        fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Register the database of a dump, replacing any other one of the same date
+    pub fn add(&mut self, date: &str, db: &str) -> Result<(), HistoryError> {
+        check_date(date)?;
+        self.dumps.retain(|dump| dump.date != date);
+        self.dumps.push(Dump { date: date.to_owned(), db: db.to_owned() });
+        self.dumps.sort_by(|a, b| a.date.cmp(&b.date));
+        Ok(())
+    }
+
+    /// The latest dump made on `date` or before
+    pub fn as_of(&self, date: &str) -> Result<&Dump, HistoryError> {
+        check_date(date)?;
+        self.dumps.iter().rev()
+            .find(|dump| dump.date.as_str() <= date)
+            .ok_or_else(|| HistoryError::NoDump(date.to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sample_history() {
+        let mut history = History::default();
+        history.add("20240601", "./june.sq3").unwrap();
+        history.add("20230101", "./old.sq3").unwrap();
+        history.add("20240601", "./june-rebuilt.sq3").unwrap();
+        assert!(history.add("2024-06-01", "./x.sq3").is_err());
+
+        assert_eq!(history.dumps.len(), 2);
+        assert_eq!(history.as_of("20240615").unwrap().db, "./june-rebuilt.sq3");
+        assert_eq!(history.as_of("20240531").unwrap().db, "./old.sq3");
+        assert!(matches!(history.as_of("20221231"), Err(HistoryError::NoDump(_))));
+
+        let text = toml::to_string(&history).unwrap();
+        assert_eq!(toml::from_str::<History>(&text).unwrap(), history);
+    }
+}
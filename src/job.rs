@@ -0,0 +1,106 @@
+//! Long-running work in the background, reporting its progress and stopping early
+//! when cancelled, for applications such as GUIs that must stay responsive.
+//!
+//! Cancellation is cooperative: the work checks its [`Control`] regularly, and
+//! returns [`Cancelled`] once it notices. Path searches do so with
+//! [`crate::path::bidi_dijkstra_cancellable`], and the dump parser with
+//! [`crate::sql::Loader::with_control`]. The indexers are part of the command line
+//! tool, not of the library, and cannot be cancelled.
+//!
+//! ```
+//! use wikistra::{job::Job, path::bidi_dijkstra_cancellable};
+//!
+//! let job = Job::spawn(|control, progress| {
+//!     bidi_dijkstra_cancellable(0u32, 100, |n: &u32| [n + 1], |n: &u32| n.checked_sub(1), control, |stats| progress(stats.expanded))
+//! });
+//! let (path, _stats) = job.wait().unwrap();
+//! assert_eq!(path.map(|p| p.len()), Some(101));
+//! ```
+
+use std::{sync::{Arc, atomic::{AtomicBool, Ordering}, mpsc::{self, Receiver}}, thread::{self, JoinHandle}};
+
+use thiserror::Error;
+
+/// Returned by work that stopped because it was cancelled
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("cancelled")]
+pub struct Cancelled;
+
+/// Cancellation flag shared between some work and its owner
+#[derive(Debug, Clone, Default)]
+pub struct Control {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Control {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask the work to stop as soon as possible
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// `Err(Cancelled)` once cancelled, to bail out with `?`
+    pub fn check(&self) -> Result<(), Cancelled> {
+        if self.is_cancelled() { Err(Cancelled) } else { Ok(()) }
+    }
+}
+
+/// Work running on its own thread, sending progress updates of type `P` and
+/// producing a result of type `R`
+pub struct Job<P, R> {
+    control: Control,
+    progress: Receiver<P>,
+    handle: JoinHandle<Result<R, Cancelled>>,
+}
+
+impl <P: Send + 'static, R: Send + 'static> Job<P, R> {
+
+    /// Run `work` on a new thread. It gets the job's [`Control`], and a function
+    /// to report its progress with.
+    pub fn spawn<F>(work: F) -> Self
+    where F: FnOnce(&Control, &dyn Fn(P)) -> Result<R, Cancelled> + Send + 'static
+    {
+        let control = Control::new();
+        let (sender, progress) = mpsc::channel();
+        let shared = control.clone();
+        let handle = thread::spawn(move || {
+            // Updates sent after the owner dropped the job are lost
+            let report = |update| { let _ = sender.send(update); };
+            work(&shared, &report)
+        });
+        Job { control, progress, handle }
+    }
+
+    /// Ask the job to stop as soon as possible. `wait` then returns `Err(Cancelled)`,
+    /// unless the job was already done.
+    pub fn cancel(&self) {
+        self.control.cancel()
+    }
+
+    /// The progress updates sent since the last call, without blocking
+    pub fn progress(&self) -> impl Iterator<Item = P> + '_ {
+        self.progress.try_iter()
+    }
+
+    /// Whether the job is over, so that `wait` would not block
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Wait for the job to be over, and return its result.
+    /// Panics if the job panicked.
+    pub fn wait(self) -> Result<R, Cancelled> {
+        match self.handle.join() {
+            Ok(result) => result,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
@@ -0,0 +1,12 @@
+//! Wikistra - Finding shortest paths through wikipedia links.
+//!
+//! The graph algorithms are usable on their own, on any graph: see [`path`].
+//! Searches can also run as cancellable background jobs: see [`job`].
+//! The MediaWiki SQL dumps are read with [`sql`], and the indexing and loading
+//! code reports [`error::WikistraError`].
+
+pub mod error;
+pub mod job;
+pub mod path;
+pub mod snapshot;
+pub mod sql;
@@ -0,0 +1,103 @@
+//! Thread counts shared by every subsystem, set once from the command line
+
+use std::{collections::BTreeMap, num::NonZeroUsize, sync::{mpsc, Mutex, PoisonError}, thread};
+
+use once_cell::sync::OnceCell;
+
+struct Limits {
+    threads: usize,
+    io_threads: usize,
+}
+
+static LIMITS: OnceCell<Limits> = OnceCell::new();
+
+/// Set the limits. `threads` defaults to the number of CPUs. Only the first call has an effect.
+pub fn configure(threads: Option<NonZeroUsize>, io_threads: NonZeroUsize) {
+    let threads = threads.map_or_else(default_threads, NonZeroUsize::get);
+    let _ = LIMITS.set(Limits { threads, io_threads: io_threads.get() });
+}
+
+fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, NonZeroUsize::get)
+}
+
+/// Number of threads for CPU-bound work
+pub fn threads() -> usize {
+    LIMITS.get().map_or_else(default_threads, |l| l.threads)
+}
+
+/// Number of concurrent network transfers
+pub fn io_threads() -> usize {
+    LIMITS.get().map_or(1, |l| l.io_threads)
+}
+
+/// Run `work` on every item, on `threads()` threads that each get their own state
+/// from `init`, and pass the results to `done` in the order of the items. With a
+/// single thread, everything runs on the calling one.
+pub fn pool<I, S, R, E>(
+    items: impl Iterator<Item = I> + Send,
+    init: impl Fn() -> Result<S, E> + Sync,
+    work: impl Fn(&mut S, I) -> R + Sync,
+    mut done: impl FnMut(R) -> Result<(), E>,
+) -> Result<(), E>
+where
+    I: Send,
+    R: Send,
+    E: Send,
+{
+    let threads = threads();
+    if threads == 1 {
+        let mut state = init()?;
+        for item in items {
+            done(work(&mut state, item))?;
+        }
+        return Ok(())
+    }
+
+    let items = Mutex::new(items.enumerate());
+    thread::scope(|scope| {
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..threads {
+            let (tx, items, init, work) = (tx.clone(), &items, &init, &work);
+            scope.spawn(move || {
+                let mut state = match init() {
+                    Ok(state) => state,
+                    Err(e) => { let _ = tx.send(Err(e)); return }
+                };
+                loop {
+                    let next = items.lock().unwrap_or_else(PoisonError::into_inner).next();
+                    let Some((i, item)) = next else { break };
+                    if tx.send(Ok((i, work(&mut state, item)))).is_err() { break }
+                }
+            });
+        }
+        drop(tx);
+
+        // Results arrive in any order, hold them until their turn
+        let mut pending = BTreeMap::new();
+        let mut next = 0;
+        for result in rx {
+            let (i, r) = result?;
+            pending.insert(i, r);
+            while let Some(r) = pending.remove(&next) {
+                done(r)?;
+                next += 1;
+            }
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ordered_pool() {
+        configure(NonZeroUsize::new(4), NonZeroUsize::MIN);
+        let mut squares = vec![];
+        pool(0..100u64, || Ok::<_, ()>(()), |_, n| n * n, |r| { squares.push(r); Ok(()) }).unwrap();
+        assert_eq!(squares, (0..100).map(|n| n * n).collect::<Vec<_>>());
+        assert_eq!(pool(0..100, || Err("no state"), |_: &mut (), n| n, |_| Ok(())), Err("no state"));
+    }
+}
@@ -1,20 +1,77 @@
-use std::{fs::File, io::{BufReader, BufRead, SeekFrom, stdin}};
+use std::{collections::{BTreeSet, HashMap, HashSet}, fs::File, io::{BufReader, BufRead, SeekFrom, Write, stdin, stdout}, sync::{Arc, Mutex, PoisonError}, time::Instant};
 
 use flate2::bufread::GzDecoder;
 use indicatif::{self, ProgressBar, ProgressStyle, ProgressState};
+use clap::ValueEnum;
 use color_eyre::{Result, eyre::eyre};
 
 
+mod abstracts;
+mod alias;
+#[cfg(feature = "arrow")]
+mod arrow;
+mod avoid;
+mod backend;
+mod bench;
+mod classify;
+mod collate;
+mod compare;
+mod dedup;
 mod cli;
-mod sql;
+#[cfg(feature = "duckdb")]
+mod duck;
+mod filter;
+mod geo;
+mod history;
+mod limits;
+#[cfg(feature = "mysql")]
+mod mysql;
+mod namespace;
+mod output;
+mod pack;
+mod prefetch;
+mod profile;
+#[cfg(feature = "postgres")]
+mod pg;
+mod querylog;
+mod rank;
+mod remote;
+mod spec;
 mod source;
+mod translit;
 mod sqlite;
-mod path;
+mod timings;
+mod repl;
+mod report;
+mod rng;
+mod save;
+mod schema;
+mod selftest;
+mod similarity;
+mod split;
+mod stats;
+mod staging;
+mod typos;
+mod variant;
+mod walks;
+mod web;
+mod wikiname;
 
 pub type Id = u32;
 
+use wikistra::{error, sql};
+
+use backend::{Analytics, Backend};
+use error::WikistraError;
 use sqlite::Db;
+use sql::Charsets;
 use cli::*;
+use report::{IndexReport, TableReport};
+use output::{Output, Style, display, paint};
+use querylog::{Query, QueryLog};
+use serde_json::{Value, json};
+use rusqlite::types::ValueRef;
+use timings::{Phase, Timed};
 
 fn db_path(wikiname: &str, path: &Option<String>) -> String {
     path.as_ref()
@@ -26,63 +83,1304 @@ fn main() -> Result<()> {
 
     color_eyre::install()?;
     let args = cli::parse();
+    limits::configure(args.threads, args.io_threads);
+    source::set_date(&args.dump_date);
+    output::set_color(args.color);
+    if args.timings { timings::enable() }
+    let _timings = timings::Report;
 
-    let db_path = db_path(&args.wikiname, &args.db_path);
+    let live_path = db_path(&args.wikiname, &args.db_path);
+    let db_path = match args.staging {
+        true => staging::path(&live_path),
+        false => live_path.clone(),
+    };
+    let mmap_size = args.mmap_size;
+    let charsets = args.charsets();
+    let spill_after = args.spill_after;
+    let open_db_at = |path: &str| -> Result<Db> {
+        let mut db = Db::new(path)?;
+        db.set_mmap_size(mmap_size)?;
+        db.set_spill_after(spill_after);
+        Ok(db)
+    };
+    let open_db = || match &args.db_url {
+        Some(url) => {
+            let mut db = Db::open_remote(url)?;
+            db.set_spill_after(spill_after);
+            Ok(db)
+        }
+        None => open_db_at(&db_path),
+    };
+    // For the commands that only read, and must not change or upgrade the database
+    let open_db_read_only_at = |path: &str| -> Result<Db> {
+        let mut db = Db::open_read_only(path)?;
+        db.set_mmap_size(mmap_size)?;
+        db.set_spill_after(spill_after);
+        Ok(db)
+    };
+    let open_db_read_only = || match &args.db_url {
+        Some(_) => open_db(),
+        None => open_db_read_only_at(&db_path),
+    };
+    // For path searches, on the database of a past dump given by `past_dump`
+    let open_search_db = |past: Option<&str>, cache: bool| -> Result<Db> {
+        let mut db = match past {
+            Some(path) => open_db_read_only_at(path)?,
+            None => open_db()?,
+        };
+        db.set_path_cache(cache);
+        Ok(db)
+    };
+    let mut log = QueryLog::open(args.query_log.as_deref())?;
+    let provenance = save::Provenance { wikiname: &args.wikiname, db_path: &db_path };
 
     match args.cmd {
-        Download => source::download(&args.wikiname)?,
-        Index { mode } => {
-            let mut db = Db::new(&db_path)?;
-            if let Some(Table::Page) | None = mode { build_page_index(&mut db, &args.wikiname)?; }
-            if let Some(Table::Redirect) | None = mode { build_redirect_index(&mut db, &args.wikiname)?; }
-            if let Some(Table::Link) | None = mode { build_link_index(&mut db, &args.wikiname)?; }
+        Download { with, prebuilt: false, no_verify, .. } => {
+            let tables: Vec<usize> = (0..source::REQUIRED).chain(with.into_iter().map(Into::into)).collect();
+            source::download(&args.wikiname, &tables, !no_verify)?
+        }
+        Download { release_url, force, .. } => {
+            if pack::exists(&db_path) && !force {
+                return Err(eyre!("{} already exists, pass --force to replace it", db_path));
+            }
+            let release_url = release_url.ok_or(eyre!("--prebuilt requires --release-url"))?;
+            let archive = source::download_prebuilt(&release_url, &args.wikiname, &args.dump_date)?;
+            let metadata = pack::unpack(&archive, &db_path)?;
+            println!("Installed the {} database built by wikistra {} to {}", metadata.wikiname, metadata.version, db_path);
+        }
+        Aliases { file: Some(file) } => {
+            let aliases = alias::read(&std::fs::read_to_string(&file)?)?;
+            let mut db = open_db()?;
+            for (name, title) in &aliases {
+                if db.index(&title.replace(' ', "_")).is_none() {
+                    eprintln!("Warning: {} stands for an unknown article, {}", name, title);
+                }
+            }
+            db.set_aliases(aliases.iter().map(|(name, title)| (name.as_str(), title.as_str())))?;
+            println!("Loaded {} aliases", aliases.len());
+        }
+        Aliases { file: None } => {
+            let db = open_db()?;
+            let mut out = Output::stdout(args.format, &["name", "title"], |r| format!("{} = {}", display(&r[0]), display(&r[1])));
+            for (name, title) in db.aliases()? {
+                out.write(&[json!(name), json!(title)])?;
+            }
+            out.finish()?;
+        }
+        Build { report, keep_dumps, classify, links, drop_self_links } => {
+            // The namespace names are small, and downloaded as usual
+            source::download(&args.wikiname, &[], true)?;
+            let input = Input::Stream { wikiname: &args.wikiname, charset: &charsets, keep: keep_dumps };
+            let classifier = classify::Classifier::new(&args.wikiname, &classify)?;
+            let mut db = open_db()?;
+            db.clear_compiled()?;
+            db.set_dump_date(source::resolved_date(&args.wikiname).as_deref())?;
+            let mut index_report = IndexReport::new(&args.wikiname);
+            index_report.tables.push(build_page_index(&mut db, &input, &classifier)?);
+            db.save_title_flags()?;
+            index_report.tables.extend(build_namespace_index(&mut db, &args.wikiname)?);
+            let mut redirects = build_redirect_index(&mut db, &input)?;
+            check_redirects(&mut db, &mut redirects)?;
+            index_report.tables.push(redirects);
+            db.set_link_direction(links)?;
+            index_report.tables.extend(build_link_index(&mut db, &input, drop_self_links)?);
+            if let Some(path) = report { index_report.write(&path)?; }
+        }
+        Index { spec: Some(spec), report, .. } => {
+            let spec = spec::Spec::read(&spec)?;
+            let mut db = open_db()?;
+            let specs = db.spec_tables();
+            let taken: Vec<String> = db.tables()?.into_iter()
+                .filter(|table| !specs.iter().any(|spec| spec.eq_ignore_ascii_case(table)))
+                .collect();
+            spec.check_tables(&taken)?;
+            let mut index_report = IndexReport::new(&args.wikiname);
+            for table in &spec.tables {
+                index_report.tables.push(build_spec_index(&mut db, table, &args.wikiname, &charsets)?);
+            }
+            if let Some(path) = report { index_report.write(&path)?; }
+        }
+        Index { mode, report, classify, links, from_mysql, drop_self_links, .. } => {
+            let input = match from_mysql {
+                None => Input::Dumps { wikiname: &args.wikiname, charset: &charsets },
+                #[cfg(feature = "mysql")]
+                Some(uri) => Input::Mysql(uri),
+                #[cfg(not(feature = "mysql"))]
+                Some(_) => return Err(eyre!("--from-mysql requires building wikistra with the `mysql` feature")),
+            };
+            if args.postgres.is_some() || args.duckdb.is_some() {
+                let mut index_report = IndexReport::new(&args.wikiname);
+                let classifier = classify::Classifier::new(&args.wikiname, &classify)?;
+                index_report.tables = match &args.postgres {
+                    Some(uri) => build_graph_index(&mut open_postgres(uri)?, "PostgreSQL", mode, &input, &classifier, drop_self_links)?,
+                    None => build_graph_index(&mut open_duckdb(args.duckdb.as_deref().unwrap_or_default())?, "DuckDB", mode, &input, &classifier, drop_self_links)?,
+                };
+                if let Some(path) = report { index_report.write(&path)?; }
+                return Ok(())
+            }
+
+            let mut db = open_db()?;
+            // The compiled data only depends on the pages, redirects and links
+            if let Some(Table::Page | Table::Redirect | Table::Link | Table::LinkTarget) | None = mode { db.clear_compiled()?; }
+            if let Input::Dumps { .. } = input { db.set_dump_date(source::resolved_date(&args.wikiname).as_deref())? }
+            let mut index_report = IndexReport::new(&args.wikiname);
+            if let Some(Table::Page) | None = mode {
+                let classifier = classify::Classifier::new(&args.wikiname, &classify)?;
+                index_report.tables.push(build_page_index(&mut db, &input, &classifier)?);
+                db.save_title_flags()?;
+                index_report.tables.extend(build_namespace_index(&mut db, &args.wikiname)?);
+            }
+            if let Some(Table::Redirect) | None = mode {
+                let mut report = build_redirect_index(&mut db, &input)?;
+                check_redirects(&mut db, &mut report)?;
+                index_report.tables.push(report);
+            }
+            if let Some(Table::Link | Table::LinkTarget) | None = mode {
+                db.set_link_direction(links)?;
+                index_report.tables.extend(build_link_index(&mut db, &input, drop_self_links)?);
+            }
+            if let Some(Table::Category) = mode { index_report.tables.push(build_category_index(&mut db, &args.wikiname, &charsets)?); }
+            if let Some(Table::Template) = mode { index_report.tables.push(build_template_index(&mut db, &args.wikiname, &charsets)?); }
+            if let Some(Table::Image) = mode { index_report.tables.push(build_image_index(&mut db, &args.wikiname, &charsets)?); }
+            if let Some(Table::External) = mode { index_report.tables.push(build_external_index(&mut db, &args.wikiname, &charsets)?); }
+            if let Some(Table::Geo) = mode { index_report.tables.push(build_geo_index(&mut db, &args.wikiname, &charsets)?); }
+            if let Some(path) = report { index_report.write(&path)?; }
         },    
 
-        Search { query } => {
-                
-            let mut db = Db::new(&db_path)?;
+        Promote { .. } if args.staging => return Err(eyre!("promote moves the staging database in place, without --staging")),
+        Promote { keep_previous } => {
+            let staged = staging::path(&live_path);
+            if std::path::Path::new(&staged).is_file() && open_db_at(&staged)?.article_count()? == 0 {
+                return Err(eyre!("The staging database {} has no articles", staged));
+            }
+            staging::promote(&live_path, keep_previous)?;
+            println!("Moved {} to {}", staged, live_path);
+        }
+        Compile { components, snapshot } => {
+            let mut db = open_db()?;
+            let n = db.compile()?;
+            println!("Remapped {} articles", n);
+            if components {
+                let n = db.compile_components()?;
+                println!("Found {} strongly connected components", n);
+            }
+            if let Some(path) = snapshot {
+                let remap = db.remap().ok_or(eyre!("The articles could not be remapped"))?;
+                let (offsets, targets) = db.link_lists(remap)?;
+                let titles = db.dense_titles(remap)?;
+                let snapshot = wikistra::snapshot::Snapshot::new(remap.ids().to_vec(), titles, offsets, targets)?;
+                snapshot.write(std::io::BufWriter::new(File::create(&path)?))?;
+                println!("Wrote {} articles and {} links to {}", snapshot.len(), snapshot.link_count(), path);
+            }
+        }
+        Transliterate => {
+            let mut db = open_db()?;
+            let n = db.transliterate()?;
+            println!("Transliterated {} titles", n);
+        }
+        Variants { table } => {
+            let mut converter = variant::Converter::builtin(&args.wikiname).unwrap_or_default();
+            for path in &table {
+                let n = converter.read_opencc(path)?;
+                eprintln!("Read {} rules from {}", n, path);
+            }
+            if converter.is_empty() {
+                return Err(eyre!("{} has no built-in variants, pass conversion tables with --table", args.wikiname));
+            }
+            let mut db = open_db()?;
+            let n = db.index_variants(&converter)?;
+            println!("Indexed the variants of {} titles", n);
+        }
 
-            if let Some(query) = query {
-                for (id, title, redirect) in &db.search(&query) {
-                    if let Some(target) = redirect {
-                        println!("[{id}] {title} -> {target}")
+        Pack { out } => {
+            let archive = out.unwrap_or_else(|| format!("./{}-db.tar.gz", args.wikiname));
+            let metadata = pack::pack(&db_path, &args.wikiname, &archive)?;
+            for file in metadata.files {
+                println!("{}: {} bytes, sha256 {}", file.name, file.size, file.sha256);
+            }
+            println!("Packed into {}", archive);
+        }
+        Unpack { archive, force } => {
+            if pack::exists(&db_path) && !force {
+                return Err(eyre!("{} already exists, pass --force to replace it", db_path));
+            }
+            let metadata = pack::unpack(&archive, &db_path)?;
+            println!("Unpacked the {} database built by wikistra {} to {}", metadata.wikiname, metadata.version, db_path);
+        }
+        Abstracts { file } => {
+            let mut db = open_db()?;
+            import_abstracts(&mut db, &file)?;
+        }
+
+        Search { query, describe, filter, limit, namespaces, sort } => {
+                
+            let mut db = open_db()?;
+            let filter = filter.as_deref().map(filter::Filter::new).transpose()?;
+            let tagged = !namespaces.is_empty();
+            let namespaces = match tagged {
+                false => vec![namespace::MAIN],
+                true => namespaces.iter().map(|name| match namespace::parse(&db, name) {
+                    Some(ns @ (namespace::MAIN | namespace::CATEGORY)) => Ok(ns),
+                    Some(_) => Err(eyre!("The {} namespace is not indexed, only articles and category pages are", name)),
+                    None => Err(eyre!("Unknown namespace: {}", name)),
+                }).collect::<Result<_>>()?,
+            };
+            let mut fields = vec!["id:int", "title", "redirect"];
+            if describe { fields.push("description") }
+            if tagged { fields.push("namespace:int") }
+            let mut out = Output::stdout(args.format, &fields, move |r| {
+                let line = match &r[2] {
+                    Value::Null => format!("{} {}", paint(format!("[{}]", r[0]), Style::Muted), display(&r[1])),
+                    target => format!("{} {} {} {}", paint(format!("[{}]", r[0]), Style::Muted), display(&r[1]),
+                        paint("->", Style::Muted), display(target)),
+                };
+                match r.get(3).and_then(Value::as_str) {
+                    Some(description) if describe => format!("{}: {}", line, description),
+                    _ => line,
+                }
+            });
+            // Built only to sort by title, as it warns about wikis without a known collation
+            let collation = (sort == Some(cli::SortKey::Title)).then(|| collate::Collation::new(&args.wikiname));
+            // Pass the records of a query to `emit` as they are read, unless they are sorted
+            let mut search = |query: &str, emit: &mut dyn FnMut(Vec<Value>) -> Result<()>| -> Result<()> {
+                let started = Instant::now();
+                for &ns in &namespaces {
+                    let mut sorted = vec![];
+                    let mut push = |record: Vec<Value>| match sort {
+                        Some(_) => { sorted.push(record); Ok(()) }
+                        None => emit(record),
+                    };
+                    if ns == namespace::CATEGORY {
+                        let prefix = namespace::prefix(&db, ns);
+                        for (id, title) in db.search_category_pages(&namespace::strip(&db, query, ns)) {
+                            let mut record = vec![json!(id), json!(format!("{}:{}", prefix, title)), Value::Null];
+                            if describe { record.push(Value::Null) }
+                            record.push(json!(ns));
+                            push(record)?;
+                        }
                     } else {
-                        println!("[{id}] {title}")
+                        let results = db.search_each(query, |id, title, redirect| {
+                            if filter.as_ref().is_some_and(|f| !f.matches(&filter::Page { db: &db, id, title: &title })) { return Ok(()) }
+                            let mut record = vec![json!(id), json!(title), json!(redirect)];
+                            if describe { record.push(json!(db.abstract_of(id)?)) }
+                            if tagged { record.push(json!(ns)) }
+                            push(record)
+                        })?;
+                        // Searches of other namespaces cannot be replayed, and are not logged
+                        if !tagged { log.record(Query::Search { query: query.to_owned() }, started.elapsed(), Some(results))? }
+                    }
+                    match sort {
+                        Some(cli::SortKey::Id) => sorted.sort_by_key(|r| r[0].as_u64()),
+                        Some(cli::SortKey::Title) => if let Some(collation) = &collation {
+                            sorted.sort_by(|a, b| collation.compare(a[1].as_str().unwrap_or_default(), b[1].as_str().unwrap_or_default()))
+                        },
+                        None => (),
                     }
+                    for record in sorted { emit(record)? }
                 }
+                Ok(())
+            };
+            let footer = |remaining: usize| if remaining > 0 { eprintln!("{}", paint(repl::footer(remaining), Style::Muted)) };
+
+            if let Some(query) = query {
+                // Results past the limit are only counted
+                let limit = limit.map_or(usize::MAX, |n| n as usize);
+                let (mut shown, mut remaining) = (0, 0);
+                search(&query, &mut |record| {
+                    match shown < limit {
+                        true => { out.write(&record)?; shown += 1 }
+                        false => remaining += 1,
+                    }
+                    Ok(())
+                })?;
+                footer(remaining);
             } else {
-                eprintln!("Enter one query per line.");
+                let mut show = |pages: &mut repl::Pages<Vec<Value>>, limit: usize| -> Result<()> {
+                    for record in pages.next(limit) {
+                        out.write(record)?;
+                    }
+                    footer(pages.remaining());
+                    Ok(())
+                };
+                eprintln!("Enter one query per line, or !more to see more results.");
+                let mut limit = limit.map_or(repl::DEFAULT_LIMIT, |n| n as usize);
+                let mut pages = repl::Pages::default();
                 for line in stdin().lines() {
                     let line = line?;
-                    if line == "" { continue };
-                    
-                    for (id, title, redirect) in &db.search(&line) {
-                        if let Some(target) = redirect {
-                            println!("[{id}] {title} -> {target}")
-                        } else {
-                            println!("[{id}] {title}")
+                    match repl::parse(&line) {
+                        Ok(repl::Line::Empty) => continue,
+                        Ok(repl::Line::Query(query)) => {
+                            let mut records = vec![];
+                            search(query, &mut |record| { records.push(record); Ok(()) })?;
+                            pages = repl::Pages::new(records);
+                            show(&mut pages, limit)?
+                        }
+                        Ok(repl::Line::More) if pages.remaining() == 0 => eprintln!("No more results."),
+                        Ok(repl::Line::More) => show(&mut pages, limit)?,
+                        Ok(repl::Line::Limit(n)) => limit = n,
+                        Err(e) => eprintln!("{}", e),
+                    }
+                }
+            }
+            out.finish()?;
+
+        }
+
+        Schema => {
+            let db = open_db()?;
+            let mut tables = vec![("database".to_owned(), db.schema()?)];
+            for file in source::files(&args.wikiname) {
+                if let Some(table) = schema::read_dump(&file).ok().flatten() {
+                    tables.push((file, vec![table]));
+                }
+            }
+            let mut out = Output::stdout(args.format, &["source", "table", "kind", "name", "definition"], |r| match r[2].as_str() {
+                Some("table") => format!("\n{} {}", paint(display(&r[1]), Style::Heading), paint(format!("({}, {})", display(&r[0]), display(&r[4])), Style::Muted)),
+                Some("index") => format!("  {} {}", paint(format!("index {}", display(&r[3])), Style::Muted), display(&r[4])),
+                _ => format!("  {} {}", display(&r[3]), display(&r[4])),
+            });
+            for (source, tables) in tables {
+                for table in tables {
+                    let rows = table.rows.map(|n| format!("{} rows", n)).unwrap_or("dump".to_owned());
+                    out.write(&[json!(source), json!(table.name), json!("table"), json!(table.name), json!(rows)])?;
+                    for (kind, items) in [("column", table.columns), ("index", table.indexes)] {
+                        for (name, definition) in items {
+                            out.write(&[json!(source), json!(table.name), json!(kind), json!(name), json!(definition)])?;
+                        }
+                    }
+                }
+            }
+            out.finish()?;
+        }
+        Cache { cmd: CacheCommand::Stats } => {
+            let (paths, hits) = open_db()?.path_cache_stats()?;
+            let mut out = Output::stdout(args.format, &["paths:int", "hits:int"], |r| format!("{} cached paths, {} queries answered from the cache", r[0], r[1]));
+            out.write(&[json!(paths), json!(hits)])?;
+            out.finish()?;
+        }
+        Cache { cmd: CacheCommand::Clear } => {
+            open_db()?.clear_path_cache()?;
+        }
+        Sql { query } => {
+            let db = open_db_read_only()?;
+            let mut stmt = db.prepare_read_only(&query)?;
+            if !stmt.readonly() { return Err(eyre!("Only queries that do not modify the database can be run")) }
+            // Typed by the declared type of the columns, expressions are written as text
+            let fields: Vec<String> = stmt.columns().iter()
+                .map(|column| schema::field(column.name(), column.decl_type().unwrap_or_default()))
+                .collect();
+            let mut out = Output::stdout_table(args.format, &fields.iter().map(String::as_str).collect::<Vec<_>>());
+            let mut rows = stmt.query(())?;
+            while let Some(row) = rows.next()? {
+                let record = (0..fields.len()).map(|i| Ok(match row.get_ref(i)? {
+                    ValueRef::Null => Value::Null,
+                    ValueRef::Integer(n) => json!(n),
+                    ValueRef::Real(x) => json!(x),
+                    ValueRef::Text(text) => json!(String::from_utf8_lossy(text)),
+                    ValueRef::Blob(blob) => json!(blob.iter().map(|b| format!("{:02x}", b)).collect::<String>()),
+                })).collect::<Result<Vec<_>, rusqlite::Error>>()?;
+                out.write(&record)?;
+            }
+            out.finish()?;
+        }
+
+        Parse { table, profile: true, .. } => {
+            profile_table(&args.wikiname, table.into(), &charsets, args.format)?
+        }
+        Parse { table, out, .. } => {
+            parse_table(&args.wikiname, table.into(), &charsets, args.format, out.as_deref())?
+        }
+        Random { count } => {
+            let db = open_db()?;
+            let mut rng = rng::seeded(args.seed);
+            let articles = rng::Articles::new(&db)?;
+            let mut out = Output::stdout(args.format, &["id:int", "title"], |r| display(&r[1]));
+            for _ in 0..count {
+                let id = articles.draw(&mut rng).ok_or(eyre!("No article found"))?;
+                out.write(&[json!(id), json!(db.lookup(id))])?;
+            }
+            out.finish()?;
+        }
+        RandomPair { count } => {
+            let db = open_db()?;
+            let mut rng = rng::seeded(args.seed);
+            let articles = rng::Articles::new(&db)?;
+            let mut out = Output::stdout(args.format, &["start", "end"], |r| format!("{}\t{}", display(&r[0]), display(&r[1])));
+            for _ in 0..count {
+                let mut title = || -> Result<Option<String>> {
+                    let id = articles.draw(&mut rng).ok_or(eyre!("No article found"))?;
+                    Ok(db.lookup(id))
+                };
+                out.write(&[json!(title()?), json!(title()?)])?;
+            }
+            out.finish()?;
+        }
+        SampleEdges { count, negative_ratio, out } => {
+            let db = open_db()?;
+            let mut rng = rng::seeded(args.seed);
+            let negatives = (count as f64 * negative_ratio).round() as usize;
+            let edges = rng::edges(&db, &mut rng, count, negatives)?;
+            let mut file = std::io::BufWriter::new(File::create(&out)?);
+            writeln!(file, "from\tto\tlabel")?;
+            for (from, to, linked) in &edges {
+                writeln!(file, "{}\t{}\t{}", from, to, *linked as u8)?;
+            }
+            file.flush()?;
+            let links = edges.iter().filter(|e| e.2).count();
+            eprintln!("Wrote {} links and {} unlinked pairs to {}", links, edges.len() - links, out);
+            if links < count || edges.len() - links < negatives {
+                eprintln!("Note: the graph is too small or too dense for the requested number of samples.");
+            }
+        }
+        Export { format: ExportFormat::Node2vecWalks, out, walk_length, walks_per_node, p, q } => {
+            let db = open_db()?;
+            let remap = db.remap().ok_or(eyre!("Run `wikistra compile` first"))?;
+            let (offsets, mut targets) = db.link_lists(remap)?;
+            for i in 0..remap.len() as usize {
+                let links = &mut targets[offsets[i]..offsets[i + 1]];
+                links.sort_unstable();
+            }
+            let graph = walks::Graph { offsets: &offsets, targets: &targets };
+            let walks = walks::Walks { length: walk_length, per_node: walks_per_node, p, q };
+            let file = std::io::BufWriter::new(File::create(&out)?);
+            let written = walks.write(&graph, remap.ids(), &mut rng::seeded(args.seed), file)?;
+            eprintln!("Wrote {} walks to {}", written, out);
+        }
+        Reachable { from, max_depth, count_only, out, target } => {
+            let db = open_db()?;
+            let root = article(&db, &from)?;
+            let links = |id: &Id| db.links_from(*id);
+
+            if let Some(target) = target {
+                let goal = article(&db, &target)?;
+                let mut distance = None;
+                wikistra::path::bfs(root, max_depth, links, |&id, d| {
+                    if id == goal { distance = Some(d) }
+                    distance.is_none()
+                });
+                let mut out = Output::stdout(args.format, &["from", "to", "distance:int"], |r| match &r[2] {
+                    Value::Null => format!("{} is not reachable from {}", display(&r[1]), display(&r[0])),
+                    d => format!("{} is reachable from {} in {} hops", display(&r[1]), display(&r[0]), d),
+                });
+                out.write(&[json!(from), json!(target), json!(distance)])?;
+                out.finish()?;
+            } else {
+                let mut out = match (&out, count_only) {
+                    (_, true) => None,
+                    (Some(path), _) => Some(Box::new(std::io::BufWriter::new(File::create(path)?)) as Box<dyn Write>),
+                    (None, _) => Some(Box::new(std::io::stdout().lock()) as Box<dyn Write>),
+                }.map(|w| Output::new(args.format, w, &["title", "distance:int"], |r| format!("{}\t{}", display(&r[0]), r[1])));
+
+                let mut count = 0;
+                let mut result = Ok(());
+                wikistra::path::bfs(root, max_depth, links, |&id, d| {
+                    count += 1;
+                    if let Some(out) = &mut out {
+                        let title = db.lookup(id).unwrap_or("???".to_owned());
+                        result = out.write(&[json!(title), json!(d)]);
+                    }
+                    result.is_ok()
+                });
+                result?;
+                if let Some(out) = out { out.finish()?; }
+
+                eprintln!("{} articles reachable from {}", count, from);
+            }
+        }
+        Routes { targets, max_depth, out, save } => {
+            let db = open_db()?;
+            let ids = targets.iter()
+                .map(|title| article(&db, title))
+                .collect::<Result<Vec<_>>>()?;
+            let columns = [("title", "text"), ("next", "text"), ("target", "text"), ("distance", "integer")];
+            let mut results = save::Results::open(out.as_deref(), save.as_deref(), "routes", &columns, &provenance)?;
+
+            let hops = wikistra::path::next_hops(&ids, max_depth, |id: &Id| db.links_to(*id));
+            let title = |id| sql::Value::String(db.lookup(id).unwrap_or("???".to_owned()));
+            for (&id, hop) in &hops {
+                results.write(&[title(id), title(hop.next), title(hop.target), sql::Value::Integer(hop.distance as i64)])?;
+            }
+            results.finish()?;
+
+            eprintln!("{} articles lead to one of {}", hops.len(), targets.join(", "));
+        }
+        Distances { from, out, save } => {
+            let db = open_db()?;
+            let root = article(&db, &from)?;
+            let columns = [("title", "text"), ("distance", "integer")];
+            let mut results = (out.is_some() || save.is_some())
+                .then(|| save::Results::open(out.as_deref(), save.as_deref(), "distances", &columns, &provenance))
+                .transpose()?;
+            let mut printed = results.is_none()
+                .then(|| Output::stdout(args.format, &["title", "distance:int"], |r| format!("{}\t{}", display(&r[0]), r[1])));
+
+            let (count, max) = distances(&db, root, |title, d| match (&mut results, &mut printed) {
+                (Some(results), _) => results.write(&[sql::Value::String(title), sql::Value::Integer(d as i64)]).map_err(Into::into),
+                (_, Some(printed)) => printed.write(&[json!(title), json!(d)]).map_err(Into::into),
+                _ => Ok(()),
+            })?;
+            if let Some(results) = results { results.finish()? }
+            if let Some(printed) = printed { printed.finish()? }
+
+            eprintln!("{} articles reachable from {}, at most {} hops away", count, from, max);
+        }
+        Connect { articles, dot } => {
+            let db = open_db()?;
+            let ids = articles.iter()
+                .map(|title| article(&db, title))
+                .collect::<Result<Vec<_>>>()?;
+
+            let none = HashSet::new();
+            let network = wikistra::path::connect(&ids, |&a, &b| db.path_ids(a, b, &none).0)
+                .ok_or(eyre!("Could not connect all the articles"))?;
+            let title = |id| db.lookup(id).unwrap_or("???".to_owned());
+
+            if dot {
+                let links: BTreeSet<(Id, Id)> = network.iter()
+                    .flat_map(|path| path.windows(2).map(|w| (w[0], w[1])))
+                    .collect();
+                println!("digraph wikistra {{");
+                for &id in &ids {
+                    println!("  {:?} [shape=box];", title(id));
+                }
+                for (a, b) in links {
+                    println!("  {:?} -> {:?};", title(a), title(b));
+                }
+                println!("}}");
+            } else {
+                let mut out = Output::stdout(args.format, &["path"], |r| join_path(&r[0]));
+                for path in network {
+                    out.write(&[json!(path.into_iter().map(title).collect::<Vec<_>>())])?;
+                }
+                out.finish()?;
+            }
+        }
+        InCategory { title, category } => {
+            let db = open_db()?;
+            let id = article(&db, &title)?;
+            let category = namespace::strip(&db, &category, namespace::CATEGORY);
+            let member = db.in_category(id, &category);
+
+            let prefix = namespace::prefix(&db, namespace::CATEGORY);
+            let mut out = Output::stdout(args.format, &["title", "category", "member:bool"], move |r| {
+                let verb = if r[2] == Value::Bool(true) { paint("is", Style::Yes) } else { paint("is not", Style::No) };
+                format!("{} {} in {}:{}", display(&r[0]), verb, prefix, display(&r[1]))
+            });
+            out.write(&[json!(title), json!(category), json!(member)])?;
+            out.finish()?;
+
+            if !member { std::process::exit(1) }
+        }
+        CategoriesOf { title } => {
+            let db = open_db()?;
+            let id = article(&db, &title)?;
+            let mut out = Output::stdout(args.format, &["category"], |r| display(&r[0]));
+            for category in db.categories_of(id)? {
+                out.write(&[json!(category)])?;
+            }
+            out.finish()?;
+        }
+        UsesTemplate { title } => {
+            let db = open_db()?;
+            let id = article(&db, &title)?;
+            let prefix = namespace::prefix(&db, namespace::TEMPLATE);
+            let mut out = Output::stdout(args.format, &["template"], move |r| format!("{}:{}", prefix, display(&r[0])));
+            for template in db.templates_of(id)? {
+                out.write(&[json!(template)])?;
+            }
+            out.finish()?;
+        }
+        UsedBy { name } => {
+            let db = open_db()?;
+            let users = match namespace::split(&db, &name) {
+                Some((namespace::TEMPLATE, template)) => db.template_users(&template.replace(' ', "_"))?,
+                Some((namespace::FILE, file)) => db.image_users(&file.replace(' ', "_"))?,
+                _ => return Err(eyre!("Expected Template:NAME or File:NAME, got {}", name)),
+            };
+
+            let mut out = Output::stdout(args.format, &["id:int", "title"], |r| display(&r[1]));
+            for id in users {
+                // Users outside the main namespace are not in the page index
+                let Some(title) = db.lookup(id) else { continue };
+                out.write(&[json!(id), json!(title)])?;
+            }
+            out.finish()?;
+        }
+        Near { lat, lon, radius } => {
+            let db = open_db()?;
+            let mut out = Output::stdout(args.format, &["id:int", "title", "lat:float", "lon:float", "distance_km:float"],
+                |r| format!("{:>8.2}km {}", r[4].as_f64().unwrap_or(0.0), display(&r[1])));
+            for (id, (lat, lon), distance) in db.near((lat, lon), radius)? {
+                let Some(title) = db.lookup(id) else { continue };
+                out.write(&[json!(id), json!(title), json!(lat), json!(lon), json!(distance)])?;
+            }
+            out.finish()?;
+        }
+        Externals { title } => {
+            let db = open_db()?;
+            let id = article(&db, &title)?;
+            let mut out = Output::stdout(args.format, &["url"], |r| display(&r[0]));
+            for url in db.external_links(id)? {
+                out.write(&[json!(url)])?;
+            }
+            out.finish()?;
+        }
+        Register { date, db } => {
+            let path = history::path(&args.wikiname);
+            let mut history = history::History::read(&path)?;
+            history.add(&date, &db)?;
+            history.write(&path)?;
+            eprintln!("Registered {} dumps in {}", history.dumps.len(), path);
+        }
+        History { title, landmark } => {
+            let history = history::History::read(&history::path(&args.wikiname))?;
+            if history.dumps.is_empty() {
+                return Err(eyre!("No dump was registered, see `wikistra register`"));
+            }
+            let mut fields = vec!["date".to_owned(), "title".to_owned(), "links_in:int".to_owned(), "links_out:int".to_owned()];
+            fields.extend(landmark.iter().map(|l| format!("to {}:int", l)));
+            let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+            let mut out = Output::stdout_table(args.format, &fields);
+
+            // Follow the article through renames and merges, where its title is unknown
+            let dbs = history.dumps.iter().map(|dump| open_db_read_only_at(&dump.db)).collect::<Result<Vec<_>>>()?;
+            let mut ids: Vec<_> = dbs.iter().map(|db| db.resolve(&title)).collect();
+            compare::track(&mut ids, |i, id| compare::follow(&dbs[i], id));
+
+            for ((dump, db), id) in history.dumps.iter().zip(&dbs).zip(ids) {
+                let mut record = vec![json!(dump.date)];
+                match id {
+                    Some(id) => {
+                        let (links_in, links_out) = db.degree(id);
+                        record.extend([json!(db.lookup(id)), json!(links_in), json!(links_out)]);
+                        for target in &landmark {
+                            let distance = db.resolve(target)
+                                .and_then(|to| db.path_ids(id, to, &HashSet::new()).0)
+                                .map(|path| path.len() - 1);
+                            record.push(json!(distance));
                         }
                     }
+                    None => record.extend((0..fields.len() - 1).map(|_| Value::Null)),
+                }
+                out.write(&record)?;
+            }
+            out.finish()?;
+        }
+        Degrees { title } => {
+            let db = open_db()?;
+            let id = article(&db, &title)?;
+            let degree = db.degree(id);
+            let percentiles = db.degree_percentiles(degree);
+            if percentiles.is_none() {
+                eprintln!("Note: run `wikistra compile` to compare with the other articles.");
+            }
+            let mut out = Output::stdout_table(args.format, &["title", "links_in:int", "in_percentile:float", "links_out:int", "out_percentile:float"]);
+            // Percentiles of the articles with fewer links, to a tenth of a percent
+            let (in_percentile, out_percentile) = percentiles
+                .map(|(i, o): (f64, f64)| ((i * 10.0).round() / 10.0, (o * 10.0).round() / 10.0))
+                .unzip();
+            out.write(&[json!(title), json!(degree.0), json!(in_percentile), json!(degree.1), json!(out_percentile)])?;
+            out.finish()?;
+        }
+        Resolve { inputs } => {
+            let db = open_db()?;
+            let mut out = Output::stdout(args.format, &["input", "id:int", "title", "redirect", "namespace:int"], |r| match &r[1] {
+                Value::Null => format!("{}: {}", display(&r[0]), paint("unknown", Style::No)),
+                id => {
+                    let redirect = match &r[3] {
+                        Value::Null => String::new(),
+                        target => format!(" {} {}", paint("->", Style::Muted), display(target)),
+                    };
+                    format!("{}: {} {}{}", display(&r[0]), display(&r[2]), paint(format!("#{}", id), Style::Muted), redirect)
+                }
+            });
+            for input in inputs {
+                let target = match input.parse() {
+                    Ok(id) => web::Target::Id(id),
+                    Err(_) => web::url_target(&input).unwrap_or_else(|| web::Target::Title(input.replace(' ', "_"))),
+                };
+                let id = match target {
+                    web::Target::Id(id) => db.lookup(id).map(|_| id),
+                    web::Target::Title(title) => db.index(&title).or_else(|| db.resolve(&title)),
+                };
+                let title = id.and_then(|id| db.lookup(id));
+                let redirect = id.and_then(|id| db.redirect_title(id));
+                let namespace = title.as_deref().map(|title| namespace::of(&db, title));
+                out.write(&[json!(input), json!(id), json!(title), json!(redirect), json!(namespace)])?;
+            }
+            out.finish()?;
+        }
+        Stats => match &args.duckdb {
+            Some(path) => print_stats(&open_duckdb(path)?, args.format)?,
+            None => print_stats(&open_db()?, args.format)?,
+        },
+        Rank { top, damping, iterations, tolerance, show } => {
+            let pagerank = (!show).then_some(rank::PageRank { damping, iterations, tolerance });
+            match &args.duckdb {
+                Some(path) => rank_articles(&mut open_duckdb(path)?, pagerank, top, args.format)?,
+                None => rank_articles(&mut open_db()?, pagerank, top, args.format)?,
+            }
+        }
+        Analyze { cmd: AnalyzeCommand::Domains { top, save } } => {
+            let db = open_db()?;
+            let mut out = Output::stdout_table(args.format, &["domain", "articles:int"]);
+            let columns = [("domain", "text"), ("articles", "integer")];
+            let mut results = save::Results::open(None, save.as_deref(), "domains", &columns, &provenance)?;
+            for (domain, articles) in db.top_domains(top)? {
+                out.write(&[json!(domain), json!(articles)])?;
+                results.write(&[sql::Value::String(domain), sql::Value::Integer(articles as i64)])?;
+            }
+            out.finish()?;
+            results.finish()?;
+        }
+        Similar { a, b, metric } => {
+            let db = open_db()?;
+            let id = |title: &str| article(&db, title);
+            let (a, b) = (id(&a)?, id(&b)?);
+            let neighbors = |id| -> BTreeSet<Id> {
+                db.links_from(id).into_iter().chain(db.links_to(id)).filter(|&n| n != id).collect()
+            };
+            let metrics = if metric.is_empty() { similarity::Metric::value_variants().to_vec() } else { metric };
+            let mut out = Output::stdout_table(args.format, &["metric", "score:float"]);
+            for metric in metrics {
+                let name = metric.to_possible_value().map(|v| v.get_name().to_owned());
+                out.write(&[json!(name), json!(metric.score(a, b, neighbors))])?;
+            }
+            out.finish()?;
+        }
+        Why { from, to } => {
+            let db = open_db()?;
+            let mut out = Output::stdout(args.format, &["from", "to", "link"], explain_link);
+            for record in why(&db, &from, &to)? {
+                out.write(&record)?;
+            }
+            out.finish()?;
+            eprintln!("Note: the pagelinks dumps do not record anchor text, so the link context is not available.");
+        }
+        Bench { cmd: BenchCommand::Parse { file } } => bench::parse(&file, &charsets)?,
+        Bench { cmd: BenchCommand::Path { pairs } } => bench::path(&open_db()?, &pairs, &mut log)?,
+        Bench { cmd: BenchCommand::Visited { nodes } } => bench::visited(nodes)?,
+        Bench { cmd: BenchCommand::Storage { pages, links } } => bench::storage(pages, links)?,
+        Mangen { dir } => {
+            std::fs::create_dir_all(&dir)?;
+            clap_mangen::generate_to(cli::command(), &dir)?;
+            eprintln!("Wrote the man pages to {}", dir);
+        }
+        Selftest { cmd: SelftestCommand::Paths { samples } } => selftest::paths(&open_db()?, samples, args.seed, args.format)?,
+        Compare { old, new, pairs } => {
+            let (old_db, new_db) = (open_db_read_only_at(&old)?, open_db_read_only_at(&new)?);
+            let mut out = Output::stdout(args.format, &["start", "end", "change", "old_length:int", "new_length:int"], |r| {
+                format!("{} -> {}: {} ({} -> {})", display(&r[0]), display(&r[1]), display(&r[2]),
+                    if r[3].is_null() { "-".to_owned() } else { r[3].to_string() },
+                    if r[4].is_null() { "-".to_owned() } else { r[4].to_string() })
+            });
 
+            // Unknown titles are skipped, as they make the comparison meaningless
+            let dbs = [&old_db, &new_db];
+            let identify = |title: &str| -> Option<(Id, Id)> {
+                let mut ids = dbs.map(|db| db.resolve(title));
+                compare::track(&mut ids, |i, id| compare::follow(dbs[i], id));
+                let [Some(old), Some(new)] = ids else {
+                    eprintln!("Skipping {}: unknown in the {} database", title, if ids[0].is_none() { "old" } else { "new" });
+                    return None
+                };
+                if let (Some(before), Some(after)) = (old_db.lookup(old), new_db.lookup(new)) {
+                    if before != after { eprintln!("Following {} as {}", before, after) }
                 }
+                Some((old, new))
+            };
+
+            let mut changes = std::collections::BTreeMap::new();
+            for (start, end) in bench::read_pairs(&pairs)? {
+                let (Some((old_start, new_start)), Some((old_end, new_end))) = (identify(&start), identify(&end)) else { continue };
+                let old_path = compare::path(&old_db, old_start, old_end);
+                let new_path = compare::path(&new_db, new_start, new_end);
+
+                let change = compare::change(old_path.as_deref(), new_path.as_deref());
+                *changes.entry(change).or_insert(0) += 1;
+                let length = |path: &Option<Vec<String>>| path.as_ref().map(|p| p.len() - 1);
+                out.write(&[json!(start), json!(end), json!(change.to_string()), json!(length(&old_path)), json!(length(&new_path))])?;
             }
+            out.finish()?;
 
+            let summary: Vec<_> = changes.iter().map(|(change, n)| format!("{} {}", n, change)).collect();
+            eprintln!("{}", summary.join(", "));
         }
+        Replay { log: path } => {
+            let mut db = open_db()?;
+            let mut out = Output::stdout(args.format, &["query", "results:int", "replayed_results:int", "ms:float", "replayed_ms:float"], |r| {
+                let changed = if r[1] != r[2] { paint(" CHANGED", Style::No) } else { String::new() };
+                format!("{}: {} -> {}{} ({:.1}ms -> {:.1}ms)", display(&r[0]), r[1], r[2], changed,
+                    r[3].as_f64().unwrap_or(0.0), r[4].as_f64().unwrap_or(0.0))
+            });
+
+            let (mut changed, mut before, mut after) = (0, 0.0, 0.0);
+            let entries = querylog::read(&path)?;
+            for entry in &entries {
+                let (results, elapsed) = entry.query.run_timed(&mut db);
+                let ms = elapsed.as_secs_f64() * 1000.0;
+                if results != entry.results { changed += 1 }
+                before += entry.ms;
+                after += ms;
+                out.write(&[json!(entry.query.to_string()), json!(entry.results), json!(results), json!(entry.ms), json!(ms)])?;
+            }
+            out.finish()?;
+
+            eprintln!("{} queries replayed, {} with different results, {:.1}ms total (was {:.1}ms)",
+                entries.len(), changed, after, before);
+        }
+        Split { file, out_dir } => {
+            let (source, progress, _) = open_gz_with_progress(&file)?;
+            progress.set_message("Splitting dump");
+
+            let tables = split::split(source, args.charset, |table| {
+                let path = std::path::Path::new(&out_dir).join(format!("{}.csv", table));
+                Ok(std::io::BufWriter::new(File::create(path)?))
+            })?;
+            progress.finish_with_message("Done.");
 
-        Parse { table } => {
-            parse_table(&args.wikiname, table.into())?
+            for (name, mut table) in tables {
+                table.writer.flush()?;
+                println!("{}: {} rows", name, table.rows);
+            }
+        }
+        PathToCategory { category, from, depth, avoid } => {
+            let db = open_db()?;
+            if db.link_direction() != sqlite::LinkDirection::Both {
+                return Err(eyre!("path-to-category needs the links indexed in both directions"))
+            }
+            let start = article(&db, &from)?;
+            let category = namespace::strip(&db, &category, namespace::CATEGORY);
+            let members = db.category_members(&category, depth)?;
+            if members.is_empty() { return Err(eyre!("No article in category {}", category)) }
+            let avoid = avoid::Avoid::new(&avoid)?.ids(&db)?;
+
+            let (path, stats) = db.path_to_any(start, &members, &avoid);
+            let path = path.ok_or(sqlite::PathError::NoPathFound(stats))?;
+            let titles: Vec<_> = path.iter().map(|&id| db.lookup(id).unwrap_or("???".to_owned())).collect();
+            let mut out = Output::stdout(args.format, &["length:int", "path"], |r| join_path(&r[1]));
+            out.write(&[json!(titles.len() - 1), json!(titles)])?;
+            out.finish()?;
+        }
+        Path { verbose, open, describe, coords, as_of, avoid, .. } if args.postgres.is_some()
+            && (verbose || open || describe || coords || as_of.is_some() || avoid != AvoidArgs::default()) => {
+            return Err(eyre!("--verbose, --open, --describe, --coords, --as-of and the avoid options are not supported with --postgres"))
+        }
+        Path { start: None, verbose, open, describe, coords, .. } if verbose || open || describe || coords => {
+            return Err(eyre!("--verbose, --open, --describe and --coords need a start and an end"))
         }
-        Path { start, end } => {
-            let db = sqlite::Db::new(&db_path)?;
-            let path = db.path(&start, &end)?;
+        Path { start: None, .. } if args.postgres.is_some() => {
+            let uri = args.postgres.as_deref().unwrap_or_default();
+            batch_paths(args.format, || open_postgres(uri), |db, start, end| Ok(Backend::path(db, start, end)?))?;
+        }
+        Path { start: None, avoid, fix_typos, as_of, cache, any_namespace, .. } => {
+            let past = past_dump(&args.wikiname, as_of.as_deref())?;
+            let open = || open_search_db(past.as_deref(), cache);
+            let avoid = avoid::Avoid::new(&avoid)?.ids(&open()?)?;
+            let log = Mutex::new(&mut log);
+            batch_paths(args.format, open, |db, start, end| {
+                let start = search_title(db, start.to_owned(), any_namespace, fix_typos)?;
+                let end = search_title(db, end.to_owned(), any_namespace, fix_typos)?;
+                let started = Instant::now();
+                let path = match avoid.is_empty() {
+                    true => Backend::path(db, &start, &end),
+                    false => db.path(&start, &end, &avoid),
+                };
+                // As for single queries, only the plain ones are logged
+                if avoid.is_empty() && as_of.is_none() {
+                    log.lock().unwrap_or_else(PoisonError::into_inner)
+                        .record(Query::Path { start, end }, started.elapsed(), path.as_ref().ok().map(|p| p.len() - 1))?;
+                }
+                Ok(path?)
+            })?;
+        }
+        Path { start: Some(_), end: None, .. } => unreachable!("the end is required with the start"),
+        Path { start: Some(_), .. } if args.postgres.is_some() && args.format == output::Format::GraphJson => {
+            return Err(eyre!("--format graph-json is not supported with --postgres"))
+        }
+        Path { start: Some(start), end: Some(end), via, .. } if args.postgres.is_some() => {
+            let db = open_postgres(args.postgres.as_deref().unwrap_or_default())?;
+            let stops: Vec<String> = [start].into_iter().chain(via).chain([end]).collect();
+            let path = chain(&stops, |start, end| Backend::path(&db, start, end))?;
+            let mut out = Output::stdout(args.format, &["length:int", "path"], |r| join_path(&r[1]));
+            out.write(&[json!(path.len() - 1), path_value(&db, args.format, &path)])?;
+            out.finish()?;
+        }
+        Path { start: Some(start), end: Some(end), via, verbose, avoid, fix_typos, open, coords, describe, as_of, cache, context, any_namespace } => {
+            let db = open_search_db(past_dump(&args.wikiname, as_of.as_deref())?.as_deref(), cache)?;
+            let stops = [start].into_iter().chain(via).chain([end])
+                .map(|title| search_title(&db, title, any_namespace, fix_typos))
+                .collect::<Result<Vec<_>>>()?;
+            let (start, end) = (stops[0].clone(), stops[stops.len() - 1].clone());
+            let avoid = avoid::Avoid::new(&avoid)?.ids(&db)?;
 
-            println!("{}", path.join(" -> "));
+            // Queries with avoided articles or waypoints, or on past dumps, cannot be
+            // replayed, and are not logged
+            let started = Instant::now();
+            let mut record = |length: Option<usize>| match avoid.is_empty() && stops.len() == 2 && as_of.is_none() {
+                true => log.record(Query::Path { start: start.clone(), end: end.clone() }, started.elapsed(), length),
+                false => Ok(()),
+            };
+
+            let path = if args.format == output::Format::GraphJson {
+                let hops = chain(&stops, |start, end| db.path_hops(start, end, &avoid));
+                record(hops.as_ref().ok().map(|h| h.len() - 1))?;
+                let hops = hops?;
+                path_graph(&db, &args.wikiname, &hops, context).write(stdout().lock())?;
+                hops.iter().map(|&id| db.lookup(id).unwrap_or("???".to_owned())).collect()
+            } else if verbose {
+                // The PageRank of the hops, once `rank` has stored it
+                let ranked = db.ranked()?;
+                let mut fields = vec!["id:int", "title", "links_in:int", "links_out:int", "redirect:bool"];
+                if ranked { fields.push("rank:float") }
+                if coords { fields.extend(["lat:float", "lon:float"]) }
+                if describe { fields.push("description") }
+                let mut out = Output::stdout(args.format, &fields, move |r| {
+                    let redirect = if r[4] == Value::Bool(true) { ", redirect" } else { "" };
+                    let rank = match r.get(5).and_then(Value::as_f64) {
+                        Some(score) if ranked => format!(", rank: {:.3e}", score),
+                        _ => String::new(),
+                    };
+                    let at = 5 + ranked as usize;
+                    let location = match r.get(at..at + 2) {
+                        Some([Value::Null, _]) if coords => " @ unknown".to_owned(),
+                        Some([lat, lon]) if coords => format!(" @ {}, {}", lat, lon),
+                        _ => String::new(),
+                    };
+                    let description = match r.last().and_then(Value::as_str) {
+                        Some(text) if describe => format!(": {}", text),
+                        _ => String::new(),
+                    };
+                    let details = paint(format!("(in: {}, out: {}{}{})", r[2], r[3], rank, redirect), Style::Muted);
+                    format!("{} {} {}{}{}", paint(format!("[{}]", r[0]), Style::Muted), display(&r[1]), details, location, description)
+                });
+                let hops = chain(&stops, |start, end| db.path_hops(start, end, &avoid));
+                record(hops.as_ref().ok().map(|h| h.len() - 1))?;
+                let mut titles = vec![];
+                for id in hops? {
+                    let title = db.lookup(id).unwrap_or("???".to_owned());
+                    let (links_in, links_out) = db.degree(id);
+                    let mut record = vec![json!(id), json!(title), json!(links_in), json!(links_out), json!(db.is_redirect(id))];
+                    if ranked { record.push(json!(db.rank(id)?)) }
+                    if coords {
+                        let (lat, lon) = db.coordinates(id).unzip();
+                        record.extend([json!(lat), json!(lon)]);
+                    }
+                    if describe { record.push(json!(db.abstract_of(id)?)) }
+                    out.write(&record)?;
+                    titles.push(title);
+                }
+                out.finish()?;
+                titles
+            } else {
+                let path = chain(&stops, |start, end| match avoid.is_empty() {
+                    true => Backend::path(&db, start, end),
+                    false => db.path(start, end, &avoid),
+                });
+                record(path.as_ref().ok().map(|p| p.len() - 1))?;
+                let path = path?;
+                let fields: &[&str] = match describe {
+                    false => &["length:int", "path"],
+                    true => &["length:int", "path", "descriptions"],
+                };
+                let mut out = Output::stdout(args.format, fields, |r| match r.get(2) {
+                    Some(descriptions) => describe_path(&r[1], descriptions),
+                    None => join_path(&r[1]),
+                });
+                let mut record = vec![json!(path.len() - 1), path_value(&db, args.format, &path)];
+                if describe {
+                    let descriptions: Vec<_> = path.iter()
+                        .map(|title| db.index(title).map_or(Ok(None), |id| db.abstract_of(id)))
+                        .collect::<Result<_, _>>()?;
+                    record.push(json!(descriptions));
+                }
+                out.write(&record)?;
+                out.finish()?;
+                path
+            };
+
+            if open {
+                let report = web::open_path(&args.wikiname, &path)?;
+                eprintln!("Opened {}", report.display());
+            }
 
         },
     }
     Ok(())
 }
 
+/// Open the PostgreSQL database given with `--postgres`
+#[cfg(feature = "postgres")]
+fn open_postgres(uri: &str) -> Result<pg::PgDb> {
+    Ok(pg::PgDb::connect(uri)?)
+}
+
+#[cfg(not(feature = "postgres"))]
+fn open_postgres(_uri: &str) -> Result<Db> {
+    Err(eyre!("--postgres requires building wikistra with the `postgres` feature"))
+}
+
+/// Open the DuckDB database given with `--duckdb`
+#[cfg(feature = "duckdb")]
+fn open_duckdb(path: &str) -> Result<duck::DuckDb> {
+    Ok(duck::DuckDb::open(path)?)
+}
+
+#[cfg(not(feature = "duckdb"))]
+fn open_duckdb(_path: &str) -> Result<Db> {
+    Err(eyre!("--duckdb requires building wikistra with the `duckdb` feature"))
+}
+
+/// Print the statistics of the graph, one per row in text
+fn print_stats<A: Analytics>(db: &A, format: output::Format) -> Result<()> {
+    let stats = db.graph_stats()?;
+    let record = [json!(stats.pages), json!(stats.redirects), json!(stats.links), json!(stats.orphans), json!(stats.dead_ends),
+        stats.links_in.to_json(), stats.links_out.to_json(), json!(stats.size), json!(db.dump_date())];
+    if format != output::Format::Text {
+        let fields = ["pages:int", "redirects:int", "links:int", "orphans:int", "dead_ends:int", "links_in", "links_out", "size:int", "dump_date"];
+        let mut out = Output::stdout_table(format, &fields);
+        out.write(&record)?;
+        out.finish()?;
+        return Ok(())
+    }
+    // In text, one statistic per row
+    let distribution = |d: &Value| format!("mean {:.1}, median {}, 90% at most {}, 99% at most {}, max {}",
+        d["mean"].as_f64().unwrap_or_default(), d["median"], d["p90"], d["p99"], d["max"]);
+    let size = format!("{:.1} MiB", stats.size as f64 / (1 << 20) as f64);
+    let mut out = Output::stdout_table(format, &["statistic", "value", "note"]);
+    for (name, value, note) in [
+        ("Pages", record[0].clone(), Value::Null),
+        ("Redirects", record[1].clone(), Value::Null),
+        ("Links", record[2].clone(), Value::Null),
+        ("Orphans", record[3].clone(), json!(paint("articles without links in", Style::Muted))),
+        ("Dead ends", record[4].clone(), json!(paint("articles without links out", Style::Muted))),
+        ("Links in", json!(distribution(&record[5])), Value::Null),
+        ("Links out", json!(distribution(&record[6])), Value::Null),
+        ("Size", json!(size), Value::Null),
+        ("Dumps", json!(display(&record[8])), if record[8].is_null() { json!(paint("unknown", Style::Muted)) } else { Value::Null }),
+    ] {
+        out.write(&[json!(name), value, note])?;
+    }
+    out.finish()?;
+    Ok(())
+}
+
+/// Compute and store the PageRank of the articles unless `pagerank` is `None`,
+/// and print the `top` highest-ranked ones
+fn rank_articles<A: Analytics>(db: &mut A, pagerank: Option<rank::PageRank>, top: usize, format: output::Format) -> Result<()> {
+    if let Some(pagerank) = pagerank {
+        let remap = db.stage_links()?;
+        let (scores, ran) = pagerank.run(remap.len() as usize, |f| db.scan_dense_links(&remap, f))?;
+        if ran == pagerank.iterations { eprintln!("Warning: PageRank did not converge in {} iterations", ran) }
+        db.save_ranks(remap.ids().iter().copied().zip(scores))?;
+        eprintln!("Ranked {} articles in {} iterations", remap.len(), ran);
+    }
+    let mut out = Output::stdout_table(format, &["id:int", "title", "score:float"]);
+    for (id, title, score) in db.top_ranks(top)? {
+        out.write(&[json!(id), json!(title), json!(score)])?;
+    }
+    out.finish()?;
+    Ok(())
+}
+
+/// Find the paths between the `start<TAB>end` pairs read from the standard input,
+/// `--threads` at a time, each with its own database from `open`. The paths are
+/// printed in the order of the input. Failed queries are reported in the `error`
+/// field, and do not stop the others.
+fn batch_paths<B: Backend>(
+    format: output::Format,
+    open: impl Fn() -> Result<B> + Sync,
+    find: impl Fn(&B, &str, &str) -> Result<Vec<String>> + Sync,
+) -> Result<()> {
+    let mut out = Output::stdout(format, &["start", "end", "length:int", "path", "error"], |r| match &r[4] {
+        Value::Null => join_path(&r[3]),
+        error => paint(format!("{} -> {}: {}", display(&r[0]), display(&r[1]), display(error)), Style::No),
+    });
+    let search = |db: &mut B, line: std::io::Result<String>| -> Result<Option<[Value; 5]>> {
+        let line = line?;
+        if line.trim().is_empty() { return Ok(None) }
+        let Some((start, end)) = line.split_once('\t') else {
+            eprintln!("Skipping malformed line: {}", line);
+            return Ok(None)
+        };
+        Ok(Some(match find(db, start, end) {
+            Ok(path) => [json!(start), json!(end), json!(path.len() - 1), path_value(db, format, &path), Value::Null],
+            Err(e) => [json!(start), json!(end), Value::Null, Value::Null, json!(e.to_string())],
+        }))
+    };
+    limits::pool(BufReader::new(stdin()).lines(), open, search, |record| {
+        if let Some(record) = record? { out.write(&record)? }
+        Ok(())
+    })?;
+    out.finish()?;
+    Ok(())
+}
+
+/// Chain the paths between consecutive stops, the start, waypoints and end of a
+/// route, into the whole route
+fn chain<T, E>(stops: &[String], mut find: impl FnMut(&str, &str) -> Result<Vec<T>, E>) -> Result<Vec<T>, E> {
+    let mut route = vec![];
+    for leg in stops.windows(2) {
+        let path = find(&leg[0], &leg[1])?;
+        // Every leg starts where the previous one ended
+        let skip = usize::from(!route.is_empty());
+        route.extend(path.into_iter().skip(skip));
+    }
+    Ok(route)
+}
+
+/// The graph of a path, with up to `context` articles linked from and to every hop
+fn path_graph(db: &Db, wikiname: &str, hops: &[Id], context: usize) -> output::Graph {
+    let mut graph = output::Graph::default();
+    let add = |graph: &mut output::Graph, id: Id| {
+        if graph.contains(id.into()) { return }
+        let title = db.lookup(id).unwrap_or("???".to_owned());
+        let fields = json!({ "title": title, "url": web::article_url(wikiname, &title), "hop": false });
+        graph.node(id.into(), fields);
+    };
+    // Only the hops get their link counts, which take a scan of all their links
+    for &id in hops {
+        if graph.contains(id.into()) { continue }
+        let title = db.lookup(id).unwrap_or("???".to_owned());
+        let (links_in, links_out) = db.degree(id);
+        let fields = json!({
+            "title": title, "url": web::article_url(wikiname, &title),
+            "links_in": links_in, "links_out": links_out, "hop": true,
+        });
+        graph.node(id.into(), fields);
+    }
+    for leg in hops.windows(2) {
+        graph.edge(leg[0].into(), leg[1].into(), json!({ "hop": true }));
+    }
+    for &id in hops {
+        for to in db.links_from_limit(id, context) {
+            add(&mut graph, to);
+            graph.edge(id.into(), to.into(), json!({ "hop": false }));
+        }
+        for from in db.links_to_limit(id, context) {
+            add(&mut graph, from);
+            graph.edge(from.into(), id.into(), json!({ "hop": false }));
+        }
+    }
+    graph
+}
+
+/// Pass the title and distance of every article reachable from `root` to `emit`,
+/// nearest first. Returns their number, and the largest distance.
+fn distances(db: &Db, root: Id, mut emit: impl FnMut(String, usize) -> Result<()>) -> Result<(usize, usize)> {
+    let mut count = 0;
+    let mut max = 0;
+    let mut result = Ok(());
+    wikistra::path::bfs(root, None, |id: &Id| db.links_from(*id), |&id, d| {
+        count += 1;
+        max = d;
+        result = emit(db.lookup(id).unwrap_or("???".to_owned()), d);
+        result.is_ok()
+    });
+    result.map(|()| (count, max))
+}
+
+/// The records of `why`: whether each of two articles links to the other
+fn why(db: &Db, from: &str, to: &str) -> Result<[Vec<Value>; 2]> {
+    let id = |title: &str| article(db, title);
+    let (a, b) = (id(from)?, id(to)?);
+    Ok([(a, b, from, to), (b, a, to, from)]
+        .map(|(x, y, source, target)| vec![json!(source), json!(target), json!(db.has_link(x, y))]))
+}
+
+/// Text rendering of a record of `why`
+fn explain_link(r: &[Value]) -> String {
+    let verb = if r[2] == Value::Bool(true) { paint("links", Style::Yes) } else { paint("does not link", Style::No) };
+    format!("{} {} to {}", display(&r[0]), verb, display(&r[1]))
+}
+
+/// A path as a record field. JSON records get the ID and title of every hop, and
+/// the other formats the titles, which they join or spread over columns.
+fn path_value(db: &impl Backend, format: output::Format, path: &[String]) -> Value {
+    match format {
+        output::Format::Json | output::Format::Ndjson =>
+            path.iter().map(|title| json!({ "id": db.index(title), "title": title })).collect(),
+        _ => json!(path),
+    }
+}
+
+/// Text rendering of a path given as an array of titles
+fn join_path(path: &Value) -> String {
+    path.as_array().into_iter().flatten()
+        .map(display)
+        .collect::<Vec<_>>()
+        .join(&format!(" {} ", paint("->", Style::Muted)))
+}
+
+/// Text rendering of a path with the summary of every hop, one hop per line
+fn describe_path(path: &Value, descriptions: &Value) -> String {
+    let descriptions = descriptions.as_array().map(Vec::as_slice).unwrap_or_default();
+    path.as_array().into_iter().flatten()
+        .enumerate()
+        .map(|(i, title)| match descriptions.get(i).and_then(Value::as_str) {
+            Some(text) => format!("{}: {}", display(title), text),
+            None => display(title),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The ID of an article given by title. Titles of other namespaces are refused
+/// as such, since only the articles and their links are indexed.
+fn article(db: &Db, title: &str) -> Result<Id> {
+    if let Some(id) = db.resolve(title) { return Ok(id) }
+    match namespace::of(db, title) {
+        namespace::MAIN => Err(eyre!("Unknown article: {}", title)),
+        ns => Err(eyre!("{} is in the {} namespace, but only articles are indexed", title, namespace::prefix(db, ns))),
+    }
+}
+
+/// Refuse path endpoints outside the main namespace, whose links are not indexed,
+/// unless `allow` is set
+fn check_namespace(db: &Db, title: &str, allow: bool) -> Result<()> {
+    let ns = namespace::of(db, title);
+    let hint = match ns {
+        namespace::MAIN => return Ok(()),
+        namespace::CATEGORY => ", path-to-category finds paths to the articles of a category",
+        _ => "",
+    };
+    match allow {
+        true => { eprintln!("Warning: {} is outside the main namespace, whose links are the only ones indexed", title); Ok(()) }
+        false => Err(eyre!("{} is in the {} namespace, but paths only go through articles (use --any-namespace to try anyway{})",
+            title, namespace::prefix(db, ns), hint)),
+    }
+}
+
+/// Check that a title is known. Otherwise, replace it with the closest known title
+/// if `fix` is set, or fail listing the closest ones.
+fn correct_title(db: &Db, title: String, fix: bool) -> Result<String> {
+    if db.resolve(&title).is_some() { return Ok(title) }
+
+    let candidates = typos::suggest(db, &title, if fix { 1 } else { 5 })?;
+    match candidates.first() {
+        None => Err(eyre!("Unknown article: {}", title)),
+        Some(best) if fix => {
+            eprintln!("Unknown article {}, using {}", title, best);
+            Ok(best.clone())
+        }
+        Some(_) => Err(eyre!("Unknown article: {}. Did you mean: {}? (use --fix-typos to pick the first one)",
+            title, candidates.join(", "))),
+    }
+}
+
+/// A start, end or waypoint of a path search, checked with `check_namespace` and
+/// `correct_title`
+fn search_title(db: &Db, title: String, any_namespace: bool, fix_typos: bool) -> Result<String> {
+    check_namespace(db, &title, any_namespace)?;
+    correct_title(db, title, fix_typos)
+}
+
+/// The database of the dump registered for the given `--as-of` date, if any
+fn past_dump(wikiname: &str, as_of: Option<&str>) -> Result<Option<String>> {
+    let Some(date) = as_of else { return Ok(None) };
+    Ok(Some(history::History::read(&history::path(wikiname))?.as_of(date)?.db.clone()))
+}
+
 trait SeekLength: std::io::Seek {
     fn stream_length(&mut self) -> Result<u64, std::io::Error> {
         let old = self.seek(SeekFrom::Current(0))?;
@@ -93,10 +1391,71 @@ trait SeekLength: std::io::Seek {
 }
 impl <T: std::io::Seek> SeekLength for T {}
 
-fn open_gz_with_progress(path: &str) -> Result<(impl BufRead, ProgressBar), std::io::Error> {
+/// Rows of a MediaWiki table, in the column order of the dumps
+type Rows = Box<dyn Iterator<Item = error::Result<Vec<sql::Value>>>>;
+
+/// Where the page, redirect and link indexers read the MediaWiki tables from
+enum Input<'a> {
+    /// The downloaded `.sql.gz` dumps
+    Dumps { wikiname: &'a str, charset: &'a Charsets },
+    /// The dumps streamed from the mirror, and also saved if `keep` is set
+    Stream { wikiname: &'a str, charset: &'a Charsets, keep: bool },
+    /// A MySQL or MariaDB database the dumps were loaded into
+    #[cfg(feature = "mysql")]
+    Mysql(String),
+    /// Rows given in memory, by table
+    #[cfg(test)]
+    Rows(HashMap<&'static str, Vec<Vec<sql::Value>>>),
+}
 
+impl Input<'_> {
+    /// The rows of a table, such as `page`, along with a progress bar
+    fn rows(&self, table: &'static str) -> error::Result<(Rows, ProgressBar, Pipeline)> {
+        let (rows, progress, pipeline): (Rows, ProgressBar, Pipeline) = match self {
+            Input::Dumps { wikiname, charset } => {
+                let path = source::file(wikiname, table);
+                let (source, progress, pipeline) = open_gz_with_progress(&path)?;
+                let rows = sql::Loader::load_with_charset(source, *charset)?
+                    .map(|row| row.map_err(Into::into));
+                (Box::new(rows), progress, pipeline)
+            }
+            Input::Stream { wikiname, charset, keep } => {
+                let (source, length) = source::stream(wikiname, table, *keep)?;
+                let (source, progress, pipeline) = gz_with_progress(source, length);
+                let rows = sql::Loader::load_with_charset(source, *charset)?
+                    .map(|row| row.map_err(Into::into));
+                (Box::new(rows), progress, pipeline)
+            }
+            #[cfg(feature = "mysql")]
+            Input::Mysql(uri) => {
+                let progress = ProgressBar::new_spinner();
+                let rows = mysql::rows(uri, table)?
+                    .map(|row| row.map_err(Into::into));
+                (Box::new(progress.wrap_iter(rows)), progress, None)
+            }
+            #[cfg(test)]
+            Input::Rows(tables) => {
+                let rows = tables.get(table).cloned().unwrap_or_default();
+                (Box::new(rows.into_iter().map(Ok)), ProgressBar::hidden(), None)
+            }
+        };
+        Ok((Box::new(Timed::new(rows, Phase::Parse)), progress, pipeline))
+    }
+}
+
+/// Open a gzipped file, decompressing it on a separate thread unless `--threads 1`
+fn open_gz_with_progress(path: &str) -> Result<(Box<dyn BufRead>, ProgressBar, Pipeline), std::io::Error> {
     let mut file = File::open(path)?;
     let length: Option<u64> = file.stream_length().ok();
+    Ok(gz_with_progress(file, length))
+}
+
+/// The metrics of the queue between the decompression and the parsing, when they
+/// run on separate threads
+type Pipeline = Option<Arc<prefetch::Metrics>>;
+
+/// Decompress a gzipped stream of `length` bytes, on a separate thread unless `--threads 1`
+fn gz_with_progress<R: std::io::Read + Send + 'static>(source: R, length: Option<u64>) -> (Box<dyn BufRead>, ProgressBar, Pipeline) {
 
     let style = ProgressStyle::with_template("[{elapsed_precise}] {msg} [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta})").unwrap()
     .with_key("eta", |state: &ProgressState, w: &mut dyn std::fmt::Write| { 
@@ -105,122 +1464,657 @@ fn open_gz_with_progress(path: &str) -> Result<(impl BufRead, ProgressBar), std:
     .progress_chars("=> ");
 
     let progress = length
-        .map(|l| ProgressBar::new(l))
+        .map(ProgressBar::new)
         .unwrap_or(ProgressBar::new_spinner())
-        .with_style(style);
+        .with_style(style.clone());
 
-    let compressed = BufReader::new(progress.wrap_read(file));
-    let reader = BufReader::new(GzDecoder::new(compressed));
+    let compressed = BufReader::new(progress.wrap_read(source));
+    let (reader, pipeline): (Box<dyn BufRead>, Pipeline) = match limits::threads() {
+        1 => (Box::new(BufReader::new(Timed::new(GzDecoder::new(compressed), Phase::Decompress))), None),
+        _ => {
+            let reader = prefetch::Prefetch::new(Timed::new(GzDecoder::new(compressed), Phase::Decompress));
+            let metrics = reader.metrics();
+            progress.set_style(style.with_key("queue", move |_: &ProgressState, w: &mut dyn std::fmt::Write| {
+                write!(w, "queue {:>2}/{}", metrics.depth(), prefetch::DEPTH).unwrap()
+            }).template("[{elapsed_precise}] {msg} [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta}, {queue})").unwrap());
+            let pipeline = Some(reader.metrics());
+            (Box::new(reader), pipeline)
+        }
+    };
+
+    (reader, progress, pipeline)
+}
 
-    Ok((reader, progress))
+/// Index the tables held by the backends storing only the link graph
+fn build_graph_index<B: Backend>(db: &mut B, backend: &str, mode: Option<Table>, input: &Input, classifier: &classify::Classifier, drop_self_links: bool) -> Result<Vec<TableReport>>
+where WikistraError: From<B::Error>
+{
+    match mode {
+        None | Some(Table::Page | Table::Redirect | Table::Link | Table::LinkTarget) => {},
+        Some(table) => return Err(eyre!("The {:?} table cannot be indexed into {}", table, backend)),
+    }
+    let mut tables = vec![];
+    if let Some(Table::Page) | None = mode { tables.push(build_page_index(db, input, classifier)?); }
+    if let Some(Table::Redirect) | None = mode { tables.push(build_redirect_index(db, input)?); }
+    if let Some(Table::Link | Table::LinkTarget) | None = mode { tables.extend(build_link_index(db, input, drop_self_links)?); }
+    Ok(tables)
 }
 
-fn build_page_index(db: &mut Db, wikiname: &str) -> Result<()> {
+fn build_page_index<B: Backend>(db: &mut B, input: &Input, classifier: &classify::Classifier) -> error::Result<TableReport>
+where WikistraError: From<B::Error>
+{
 
-    let path = format!("./{}-latest-page.sql.gz", wikiname);
+    let started = Instant::now();
+    let mut report = TableReport::new("page");
 
-    let (source, progress) = open_gz_with_progress(&path)?;
+    let (rows, progress, pipeline) = input.rows("page")?;
     progress.set_message("Building title index");
 
-    let (mut count, mut good) = (0,0);
-
-    for line in sql::Loader::load(source)? {
+    for line in rows {
         let mut line = line?.into_iter();
-        let mut field = || { line.next().ok_or(eyre!("invalid tuple"))};
-        count += 1;
+        let mut field = || { line.next().ok_or(WikistraError::MissingColumn)};
+        report.parsed += 1;
 
         let id = field()?.int()? as Id;
         let ns = field()?.int()?;
-        if ns != 0 { continue }
+        if ns == namespace::CATEGORY { db.add_category_page(id, &field()?.string()?)?; }
+        if ns != 0 { report.skipped_namespace += 1; continue }
         let title = field()?.string()?;
+        // Older dumps have a page_restrictions column before the redirect mark
+        let mut is_redirect = field()?;
+        if let sql::Value::String(_) | sql::Value::Bytes(_) = is_redirect { is_redirect = field()? }
+        let is_redirect = is_redirect.int()? != 0;
+        let is_new = field()?.int()? != 0;
 
-        db.add(id, title)?;
-        good += 1;
+        let mut flags = classifier.classify(&title);
+        if is_redirect { flags |= classify::REDIRECT }
+        if is_new { flags |= classify::NEW }
+        timings::time(Phase::Insert, || db.add(id, title, flags))?;
+        report.inserted += 1;
     }
 
-    progress.finish_with_message(format!("Processed {} titles, {} in main namespace.", count, good));
-    Ok(())
+    db.flush()?;
+    progress.finish_with_message(format!("Processed {} titles, {} in main namespace.", report.parsed, report.inserted));
+    report.finish(started.elapsed(), pipeline.as_deref());
+    Ok(report)
+}
+
+/// Index the namespace names, if their dump was downloaded. Titles can always use the
+/// canonical English prefixes.
+fn build_namespace_index(db: &mut Db, wikiname: &str) -> error::Result<Option<TableReport>> {
+
+    let path = source::namespaces_file(wikiname);
+    if !pack::exists(&path) {
+        eprintln!("{} not found, only the English namespace prefixes will be recognized", path);
+        return Ok(None)
+    }
+    let started = Instant::now();
+    let mut report = TableReport::new("siteinfo-namespaces");
+
+    let (source, progress, pipeline) = open_gz_with_progress(&path)?;
+    progress.set_message("Building namespace index");
+    let siteinfo = namespace::read_siteinfo(source)?;
+    report.parsed = siteinfo.names.len() as u64;
+    db.set_namespaces(siteinfo.names.iter().map(|n| (n.id, n.name.as_str(), n.local)))?;
+    if let Some(case) = siteinfo.case { db.set_case(case)?; }
+    report.inserted = report.parsed;
+
+    progress.finish_with_message(format!("Processed {} namespace names.", report.parsed));
+    report.finish(started.elapsed(), pipeline.as_deref());
+    Ok(Some(report))
+}
+
+/// Number of titles looked up at once while indexing links
+const LOOKUP_BATCH: usize = 10_000;
+
+/// What the ID of a link target refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    Article(Id),
+    /// A title of the main namespace missing from the page index
+    Missing,
+    OtherNamespace,
+}
+
+/// The articles of the link targets, by target ID
+fn build_link_targets<B: Backend>(db: &B, input: &Input) -> error::Result<(HashMap<u64, Target>, TableReport)> {
+
+    let started = Instant::now();
+    let mut report = TableReport::new("linktarget");
+    let mut targets = HashMap::new();
+
+    let (rows, progress, pipeline) = input.rows("linktarget")?;
+    progress.set_message("Resolving link targets");
+
+    let mut pending = vec![];
+    let resolve = |pending: &mut Vec<(u64, String)>, targets: &mut HashMap<u64, Target>, report: &mut TableReport| {
+        let titles: Vec<&str> = pending.iter().map(|(_, title)| title.as_str()).collect();
+        let ids = db.index_all(&titles);
+        for ((id, title), to) in pending.drain(..).zip(ids) {
+            let target = match to {
+                Some(to) => { report.inserted += 1; Target::Article(to) }
+                None => { report.not_found(&title); Target::Missing }
+            };
+            targets.insert(id, target);
+        }
+    };
+
+    for line in rows {
+        let mut line = line?.into_iter();
+        let mut field = || { line.next().ok_or(WikistraError::MissingColumn)};
+
+        report.parsed += 1;
+
+        let id = field()?.int()? as u64;
+        let namespace = field()?.int()?;
+        if namespace != 0 {
+            report.skipped_namespace += 1;
+            targets.insert(id, Target::OtherNamespace);
+            continue;
+        }
+        pending.push((id, field()?.string()?));
+        if pending.len() == LOOKUP_BATCH { resolve(&mut pending, &mut targets, &mut report) }
+    }
+    resolve(&mut pending, &mut targets, &mut report);
+
+    progress.finish_with_message(format!("Processed {} link targets ({} good, {} wrong namespace, {} missing from index)",
+        report.parsed, report.inserted, report.skipped_namespace, report.unresolved));
+    drop(progress);
+
+    report.finish(started.elapsed(), pipeline.as_deref());
+    Ok((targets, report))
 }
 
-fn build_link_index(db: &mut Db, wikiname: &str) -> Result<()> {
-    
-    let (mut count, mut good, mut skip, mut bad) = (0,0,0,0);
-    let path = format!("./{}-latest-pagelinks.sql.gz", wikiname);
+/// Index the links, along with their targets for recent dumps
+fn build_link_index<B: Backend>(db: &mut B, input: &Input, drop_self_links: bool) -> error::Result<Vec<TableReport>>
+where WikistraError: From<B::Error>
+{
 
-    let (source, progress) = open_gz_with_progress(&path)?;
+    let (rows, progress, pipeline) = input.rows("pagelinks")?;
+    let mut rows = rows.peekable();
+
+    // Recent dumps refer to the targets by their ID in the linktarget table,
+    // rather than by namespace and title
+    let mut reports = vec![];
+    let targets = match rows.peek() {
+        Some(Ok(row)) if row.len() == 3 => {
+            let (targets, report) = build_link_targets(db, input)?;
+            reports.push(report);
+            Some(targets)
+        }
+        _ => None,
+    };
+
+    let started = Instant::now();
+    let mut report = TableReport::new("pagelinks");
+    let mut runs = dedup::Runs::new(drop_self_links);
+    let mut pending = vec![];
     progress.set_message("Building link map");
 
-    for line in sql::Loader::load(source)? {
+    for line in rows {
         let mut line = line?.into_iter();
-        let mut field = || { line.next().ok_or(eyre!("invalid tuple"))};
+        let mut field = || { line.next().ok_or(WikistraError::MissingColumn)};
 
-        count += 1;
+        report.parsed += 1;
 
         let from = field()?.int()? as Id;
-        let namespace = field()?.int()?;
-        if namespace != 0 { skip += 1; continue; }
-        let title = field()?.string()?;
-        let from_ns = field()?.int()?;
-        if from_ns != 0 { skip += 1; continue; }
+        let to = match &targets {
+            Some(targets) => {
+                let from_ns = field()?.int()?;
+                if from_ns != 0 { report.skipped_namespace += 1; continue; }
+                match targets.get(&(field()?.int()? as u64)) {
+                    Some(Target::Article(to)) => *to,
+                    Some(Target::Missing) => { report.unresolved += 1; continue }
+                    Some(Target::OtherNamespace) => { report.skipped_namespace += 1; continue }
+                    None => { report.unknown_targets += 1; continue }
+                }
+            }
+            None => {
+                let namespace = field()?.int()?;
+                if namespace != 0 { report.skipped_namespace += 1; continue; }
+                let title = field()?.string()?;
+                let from_ns = field()?.int()?;
+                if from_ns != 0 { report.skipped_namespace += 1; continue; }
 
-        let Some(to) = db.index(&title) else {
-            bad += 1;
-            if bad < 1000 {
-                eprintln!("Warning: Title not found in index: {}", &title);
-            } else if bad == 1000 {
-                eprintln!("Too many bad articles, skipping report");
+                pending.push((from, title));
+                if pending.len() == LOOKUP_BATCH { add_titled_links(db, &mut pending, &mut report, &mut runs)? }
+                continue
             }
-            continue
         };
 
-        db.add_link((from, to))?;
-        good += 1;
+        runs.push((from, to), |link| { report.inserted += 1; timings::time(Phase::Insert, || db.add_link(link)) })?;
 
     }
 
-    progress.finish_with_message(format!("Processed {} links ({} good, {} wrong namespace, {} missing from index)", count, good, skip, bad));
+    add_titled_links(db, &mut pending, &mut report, &mut runs)?;
+    runs.finish(|link| { report.inserted += 1; timings::time(Phase::Insert, || db.add_link(link)) })?;
+    timings::time(Phase::Insert, || db.flush())?;
+    report.duplicates = runs.duplicates;
+    report.self_links = runs.self_links;
+    progress.finish_with_message(format!("Processed {} links ({} good, {} wrong namespace, {} missing from index, {} unknown targets, {} duplicates, {} self-links)",
+        report.parsed, report.inserted, report.skipped_namespace, report.unresolved, report.unknown_targets, report.duplicates, report.self_links));
     drop(progress);
 
+    report.finish(started.elapsed(), pipeline.as_deref());
+    reports.push(report);
+    Ok(reports)
+}
+
+/// Look up the targets of links given by title, and add the links
+fn add_titled_links<B: Backend>(db: &mut B, pending: &mut Vec<(Id, String)>, report: &mut TableReport, runs: &mut dedup::Runs) -> error::Result<()>
+where WikistraError: From<B::Error>
+{
+    let titles: Vec<&str> = pending.iter().map(|(_, title)| title.as_str()).collect();
+    let ids = db.index_all(&titles);
+    for ((from, title), to) in pending.drain(..).zip(ids) {
+        let Some(to) = to else { report.not_found(&title); continue };
+        runs.push((from, to), |link| { report.inserted += 1; timings::time(Phase::Insert, || db.add_link(link)) })?;
+    }
     Ok(())
 }
 
-fn build_redirect_index(db: &mut Db, wikiname: &str) -> Result<()> {
+fn build_redirect_index<B: Backend>(db: &mut B, input: &Input) -> error::Result<TableReport>
+where WikistraError: From<B::Error>
+{
 
-    let path = format!("./{}-latest-redirect.sql.gz", wikiname);
+    let started = Instant::now();
+    let mut report = TableReport::new("redirect");
 
-    let (source, progress) = open_gz_with_progress(&path)?;
+    let (rows, progress, pipeline) = input.rows("redirect")?;
     progress.set_message("Building redirect index");
 
-    let (mut count, mut good) = (0,0);
-
-    for line in sql::Loader::load(source)? {
+    for line in rows {
         let mut line = line?.into_iter();
-        let mut field = || { line.next().ok_or(eyre!("invalid tuple"))};
-        count += 1;
+        let mut field = || { line.next().ok_or(WikistraError::MissingColumn)};
+        report.parsed += 1;
 
         let id = field()?.int()? as Id;
         let ns = field()?.int()?;
-        if ns != 0 { continue }
+        if ns != 0 { report.skipped_namespace += 1; continue }
         let title = field()?.string()?;
+        let interwiki = field()?.opt_string()?;
+        if interwiki.is_some_and(|w| !w.is_empty()) { report.interwiki += 1; continue }
+
+        timings::time(Phase::Insert, || db.add_redirect(id, &title))?;
+        report.inserted += 1;
+    }
+
+    db.flush()?;
+    progress.finish_with_message(format!("Processed {} titles, {} in main namespace, {} to other wikis.", report.parsed, report.inserted, report.interwiki));
+    report.finish(started.elapsed(), pipeline.as_deref());
+    Ok(report)
+}
+
+/// Remove the redirects of pages that the page dump does not mark as redirects,
+/// and report them along with the marked pages missing from the redirect dump
+fn check_redirects(db: &mut Db, report: &mut TableReport) -> error::Result<()> {
+    let Some((stale, missing)) = db.clean_redirects()? else { return Ok(()) };
+    if stale + missing > 0 {
+        eprintln!("Removed {} redirects of pages not marked as redirects, {} pages marked as redirects have no target",
+            stale, missing);
+    }
+    report.inserted -= stale.min(report.inserted);
+    report.inconsistent = stale + missing;
+    Ok(())
+}
+
+fn build_category_index(db: &mut Db, wikiname: &str, charset: &Charsets) -> error::Result<TableReport> {
+
+    let path = source::file(wikiname, "categorylinks");
+    let started = Instant::now();
+    let mut report = TableReport::new("categorylinks");
+
+    let (source, progress, pipeline) = open_gz_with_progress(&path)?;
+    progress.set_message("Building category index");
+
+    for line in sql::Loader::load_with_charset(source, charset)? {
+        let mut line = line?.into_iter();
+        let mut field = || { line.next().ok_or(WikistraError::MissingColumn)};
+        report.parsed += 1;
+
+        let id = field()?.int()? as Id;
+        let category = field()?.string()?;
+        // Skip the sort key, timestamp, sort key prefix and collation
+        for _ in 0..4 { field()?; }
+        match field()?.string()?.as_str() {
+            "page" => db.add_category(id, &category)?,
+            "subcat" => db.add_subcategory(id, &category)?,
+            _ => { report.skipped_namespace += 1; continue }
+        }
+        report.inserted += 1;
+    }
+
+    progress.finish_with_message(format!("Processed {} category links, {} to articles and subcategories.", report.parsed, report.inserted));
+    report.finish(started.elapsed(), pipeline.as_deref());
+    Ok(report)
+}
+
+fn build_template_index(db: &mut Db, wikiname: &str, charset: &Charsets) -> error::Result<TableReport> {
+
+    let path = source::file(wikiname, "templatelinks");
+    let started = Instant::now();
+    let mut report = TableReport::new("templatelinks");
+
+    let (source, progress, pipeline) = open_gz_with_progress(&path)?;
+    progress.set_message("Building template index");
+
+    let rows = sql::Loader::load_with_charset(source, charset)?.map(|row| row.map_err(Into::into));
+    add_template_links(db, rows, &mut report, || template_targets(wikiname, charset))?;
+
+    progress.finish_with_message(format!("Processed {} template links, {} from articles.", report.parsed, report.inserted));
+    report.finish(started.elapsed(), pipeline.as_deref());
+    Ok(report)
+}
+
+/// Add the template links of the rows of templatelinks. Recent dumps refer to the
+/// templates by their ID in the linktarget table, read with `targets` when the
+/// first row shows it.
+fn add_template_links<I, T>(db: &mut Db, rows: I, report: &mut TableReport, targets: T) -> error::Result<()>
+where I: Iterator<Item = error::Result<Vec<sql::Value>>>, T: FnOnce() -> error::Result<HashMap<u64, String>>
+{
+    let mut rows = rows.peekable();
+    let targets = match rows.peek() {
+        Some(Ok(row)) if row.len() == 3 => Some(targets()?),
+        _ => None,
+    };
+
+    for line in rows {
+        let mut line = line?.into_iter();
+        let mut field = || { line.next().ok_or(WikistraError::MissingColumn)};
+        report.parsed += 1;
+
+        let from = field()?.int()? as Id;
+        let template = match &targets {
+            Some(targets) => {
+                let from_ns = field()?.int()?;
+                if from_ns != 0 { report.skipped_namespace += 1; continue }
+                // Only the targets in the template namespace are kept
+                match targets.get(&(field()?.int()? as u64)) {
+                    Some(template) => template.clone(),
+                    None => { report.skipped_namespace += 1; continue }
+                }
+            }
+            None => {
+                let namespace = field()?.int()?;
+                let template = field()?.string()?;
+                let from_ns = field()?.int()?;
+                if namespace != 10 || from_ns != 0 { report.skipped_namespace += 1; continue }
+                template
+            }
+        };
+
+        db.add_template_link(from, &template)?;
+        report.inserted += 1;
+    }
+    Ok(())
+}
+
+/// The titles of the templates of the linktarget table, by target ID
+fn template_targets(wikiname: &str, charset: &Charsets) -> error::Result<HashMap<u64, String>> {
+    let (source, progress, _) = open_gz_with_progress(&source::file(wikiname, "linktarget"))?;
+    progress.set_message("Reading template targets");
+
+    let mut targets = HashMap::new();
+    for line in sql::Loader::load_with_charset(source, charset)? {
+        let mut line = line?.into_iter();
+        let mut field = || { line.next().ok_or(WikistraError::MissingColumn)};
+        let id = field()?.int()? as u64;
+        if field()?.int()? != 10 { continue }
+        targets.insert(id, field()?.string()?);
+    }
+
+    progress.finish_with_message(format!("Read {} template targets.", targets.len()));
+    Ok(targets)
+}
+
+fn build_image_index(db: &mut Db, wikiname: &str, charset: &Charsets) -> error::Result<TableReport> {
+
+    let path = source::file(wikiname, "imagelinks");
+    let started = Instant::now();
+    let mut report = TableReport::new("imagelinks");
+
+    let (source, progress, pipeline) = open_gz_with_progress(&path)?;
+    progress.set_message("Building file index");
+
+    for line in sql::Loader::load_with_charset(source, charset)? {
+        let mut line = line?.into_iter();
+        let mut field = || { line.next().ok_or(WikistraError::MissingColumn)};
+        report.parsed += 1;
+
+        let from = field()?.int()? as Id;
+        let file = field()?.string()?;
+        let from_ns = field()?.int()?;
+        if from_ns != 0 { report.skipped_namespace += 1; continue }
+
+        db.add_image_link(from, &file)?;
+        report.inserted += 1;
+    }
+
+    progress.finish_with_message(format!("Processed {} file links, {} from articles.", report.parsed, report.inserted));
+    report.finish(started.elapsed(), pipeline.as_deref());
+    Ok(report)
+}
+
+fn build_external_index(db: &mut Db, wikiname: &str, charset: &Charsets) -> error::Result<TableReport> {
+
+    let path = source::file(wikiname, "externallinks");
+    let started = Instant::now();
+    let mut report = TableReport::new("externallinks");
+
+    let (source, progress, pipeline) = open_gz_with_progress(&path)?;
+    progress.set_message("Building external link index");
+
+    for line in sql::Loader::load_with_charset(source, charset)? {
+        let line = line?;
+        report.parsed += 1;
+
+        // Recent dumps store a reversed domain index and a path, older ones the full URL
+        let (from, url, domain) = match <[sql::Value; 4]>::try_from(line) {
+            Ok([_, from, index, path]) => {
+                let (index, path) = (index.string()?, path.string()?);
+                let Some((scheme, domain)) = web::unindex_domain(&index) else {
+                    report.unresolved += 1;
+                    continue
+                };
+                (from, format!("{}://{}{}", scheme, domain, path), Some(domain))
+            }
+            Err(line) => {
+                let mut line = line.into_iter();
+                let mut field = || { line.next().ok_or(WikistraError::MissingColumn)};
+                field()?;
+                let from = field()?;
+                let url = field()?.string()?;
+                let domain = web::domain_of(&url);
+                (from, url, domain)
+            }
+        };
 
-        db.add_redirect(id, &title)?;
-        good += 1;
+        db.add_external_link(from.int()? as Id, &url, domain.as_deref())?;
+        report.inserted += 1;
     }
 
-    progress.finish_with_message(format!("Processed {} titles, {} in main namespace.", count, good));
+    progress.finish_with_message(format!("Processed {} external links.", report.parsed));
+    report.finish(started.elapsed(), pipeline.as_deref());
+    Ok(report)
+}
+
+fn build_geo_index(db: &mut Db, wikiname: &str, charset: &Charsets) -> error::Result<TableReport> {
+
+    let path = source::file(wikiname, "geo_tags");
+    let started = Instant::now();
+    let mut report = TableReport::new("geo_tags");
+
+    let (source, progress, pipeline) = open_gz_with_progress(&path)?;
+    progress.set_message("Building coordinate index");
+
+    for line in sql::Loader::load_with_charset(source, charset)? {
+        let mut line = line?.into_iter();
+        let mut field = || { line.next().ok_or(WikistraError::MissingColumn)};
+        report.parsed += 1;
+
+        field()?;
+        let id = field()?.int()? as Id;
+        let globe = field()?.string()?;
+        let primary = field()?.int()?;
+        let lat = field()?.opt_float()?;
+        let lon = field()?.opt_float()?;
+
+        // Only keep the main location of the article, on Earth
+        if globe != "earth" || primary != 1 { report.skipped_namespace += 1; continue }
+        let (Some(lat), Some(lon)) = (lat, lon) else { report.unresolved += 1; continue };
+
+        db.add_coordinates(id, lat, lon)?;
+        report.inserted += 1;
+    }
+
+    progress.finish_with_message(format!("Processed {} coordinates, {} primary ones.", report.parsed, report.inserted));
+    report.finish(started.elapsed(), pipeline.as_deref());
+    Ok(report)
+}
+
+/// Index a table declared in an index spec
+fn build_spec_index(db: &mut Db, table: &spec::TableSpec, wikiname: &str, charset: &Charsets) -> error::Result<TableReport> {
+
+    let path = table.file(wikiname);
+    let started = Instant::now();
+    let mut report = TableReport::new(&table.name);
+    let filters = table.filters()?;
+
+    let (source, progress, pipeline) = open_gz_with_progress(&path)?;
+    progress.set_message(format!("Building {} index", table.name));
+
+    let columns: Vec<(&str, &str)> = table.columns.iter()
+        .map(|c| (c.name.as_str(), if c.resolve { "integer" } else { c.kind.sql() }))
+        .collect();
+    let key: Vec<&str> = table.key.iter().map(String::as_str).collect();
+    db.create_table(&table.name, &columns, &key)?;
+
+    for line in sql::Loader::load_with_charset(source, charset)? {
+        let line = line?;
+        report.parsed += 1;
+
+        if !filters.iter().all(|f| f.matches(&line)) { report.filtered += 1; continue }
+        let Some(row) = table.row(&line, |title| db.index_with(&title.replace(' ', "_"), true).map(i64::from)) else {
+            report.unresolved += 1;
+            continue
+        };
+
+        db.insert_row(&table.name, &row)?;
+        report.inserted += 1;
+    }
+
+    progress.finish_with_message(format!("Processed {} rows of {}, {} inserted.", report.parsed, table.dump, report.inserted));
+    report.finish(started.elapsed(), pipeline.as_deref());
+    Ok(report)
+}
+
+/// Import the article summaries of a Wikimedia abstract dump or of DBpedia short abstracts
+fn import_abstracts(db: &mut Db, path: &str) -> error::Result<()> {
+
+    let (source, progress, _) = open_gz_with_progress(path)?;
+    progress.set_message("Importing abstracts");
+
+    let abstracts: Box<dyn Iterator<Item = std::io::Result<abstracts::Abstract>>> = match path.contains(".ttl") {
+        true => Box::new(abstracts::read_turtle(source)),
+        false => Box::new(abstracts::read_xml(source)),
+    };
+
+    let (mut parsed, mut inserted) = (0, 0);
+    for summary in abstracts {
+        let summary = summary?;
+        parsed += 1;
+        let Some(id) = db.index(&summary.title) else { continue };
+        db.add_abstract(id, &summary.text)?;
+        inserted += 1;
+    }
+
+    progress.finish_with_message(format!("Processed {} abstracts, {} matching indexed articles.", parsed, inserted));
     Ok(())
 }
 
-fn parse_table(wikiname: &str, table: usize) -> Result<()> {
+fn parse_table(wikiname: &str, table: usize, charset: &Charsets, format: output::Format, out: Option<&str>) -> Result<()> {
 
     let filename = source::files(wikiname).nth(table)
         .ok_or(eyre!("No such table"))?;
 
-    for row in sql::Loader::load_gz_file(&filename)? {
-        let row = row?;
-        println!("{:?}", row);
+    let columns = schema::read_dump(&filename)?.map(|table| table.columns).unwrap_or_default();
+    let rows = sql::Loader::load_gz_file(&filename, charset)?;
+    match out {
+        Some(path) => write_rows(rows, &columns, format, std::io::BufWriter::new(File::create(path)?)),
+        None => write_rows(rows, &columns, format, std::io::stdout().lock()),
+    }
+}
+
+fn profile_table(wikiname: &str, table: usize, charset: &Charsets, format: output::Format) -> Result<()> {
+
+    let filename = source::files(wikiname).nth(table)
+        .ok_or(eyre!("No such table"))?;
+
+    let mut profile = profile::Profile::new();
+    for row in sql::Loader::load_gz_file(&filename, charset)? {
+        profile.add(&row?);
+    }
+    eprintln!("{} rows", profile.rows);
+
+    let mut out = Output::stdout(format, &["column", "type", "nulls:int", "distinct:int", "min", "max", "top"], |r| {
+        let top: Vec<String> = r[6].as_array().into_iter().flatten()
+            .map(|t| format!("{} ({})", display(&t["value"]), t["count"]))
+            .collect();
+        format!("{}: {}, {} nulls, ~{} distinct, min {}, max {}, top: {}",
+            display(&r[0]), display(&r[1]), r[2], r[3], display(&r[4]), display(&r[5]), top.join(", "))
+    });
+    for (i, column) in profile.columns.iter().enumerate() {
+        let kinds: Vec<&str> = column.kinds.iter().copied().collect();
+        let top: Vec<Value> = column.top(5).into_iter()
+            .map(|(value, count)| json!({"value": value, "count": count}))
+            .collect();
+        out.write(&[
+            json!(format!("c{}", i + 1)),
+            json!(kinds.join("|")),
+            json!(column.nulls),
+            json!(column.distinct()),
+            json!(column.min.as_ref().map(profile::text)),
+            json!(column.max.as_ref().map(profile::text)),
+            json!(top),
+        ])?;
+    }
+    out.finish()?;
+    Ok(())
+}
+
+/// Write the rows of a dump, as debug output in text format. In the other formats
+/// the columns are named `c1`, `c2`, ... and typed by the `columns` declared at
+/// the start of the dump.
+fn write_rows<W: Write>(rows: sql::DynLoader, columns: &[(String, String)], format: output::Format, mut out: W) -> Result<()> {
+
+    if format == output::Format::Text {
+        for row in rows {
+            writeln!(out, "{:?}", row?)?;
+        }
+        out.flush()?;
+        return Ok(())
     }
 
+    let mut rows = rows.peekable();
+    let width = match rows.peek() { Some(Ok(row)) => row.len(), _ => 0 };
+    let fields: Vec<String> = (1..=width).map(|i| match columns.get(i - 1) {
+        Some((_, sql_type)) => schema::field(&format!("c{}", i), sql_type),
+        None => format!("c{}", i),
+    }).collect();
+    let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+
+    let mut output = Output::new(format, out, &fields, |_| String::new());
+    for row in rows {
+        let row: Vec<Value> = row?.into_iter().map(|value| match value {
+            sql::Value::String(s) => json!(s),
+            sql::Value::Bytes(b) => json!(String::from_utf8_lossy(&b)),
+            sql::Value::Integer(i) => json!(i),
+            sql::Value::Float(f) => json!(f),
+            sql::Value::Null => Value::Null,
+        }).collect();
+        output.write(&row)?;
+    }
+    output.finish()?;
     Ok(())
 }
 
@@ -230,3 +2124,168 @@ fn test_parse() {
     parse_table(0).unwrap();
 }
 */
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn open_clean_db() -> Db {
+        let mut db = Db::new("file::memory:").unwrap();
+        db.initialize().unwrap();
+        db
+    }
+
+    fn classifier() -> classify::Classifier {
+        classify::Classifier::new(&"enwiki".parse().unwrap(), &Default::default()).unwrap()
+    }
+
+    #[test]
+    fn page_index_schemas() {
+        use sql::Value::{Integer, String};
+        let rows = vec![
+            // Older dumps have a page_restrictions column before the redirect mark
+            vec![Integer(1), Integer(0), String("Main_Page".into()), String("".into()), Integer(0), Integer(1)],
+            vec![Integer(2), Integer(0), String("USA".into()), Integer(1), Integer(0)],
+        ];
+        let input = Input::Rows(HashMap::from([("page", rows)]));
+        let mut db = open_clean_db();
+        let report = build_page_index(&mut db, &input, &classifier()).unwrap();
+        assert_eq!((report.parsed, report.inserted), (2, 2));
+        assert_ne!(db.title_flags(1) & classify::NEW, 0);
+        assert_ne!(db.title_flags(2) & classify::REDIRECT, 0);
+    }
+
+    #[test]
+    fn page_index_errors() {
+        use sql::Value::{Integer, String};
+        let mut db = open_clean_db();
+        let input = Input::Rows(HashMap::from([("page", vec![vec![Integer(1), Integer(0)]])]));
+        assert!(matches!(build_page_index(&mut db, &input, &classifier()), Err(WikistraError::MissingColumn)));
+        let input = Input::Rows(HashMap::from([("page", vec![vec![String("1".into()), Integer(0)]])]));
+        assert!(matches!(build_page_index(&mut db, &input, &classifier()), Err(WikistraError::Type(_))));
+    }
+
+    /// A database holding the articles A, B and C, numbered from 1
+    fn sample_pages() -> Db {
+        use sql::Value::{Integer, String};
+        let rows = ["A", "B", "C"].iter().zip(1..)
+            .map(|(title, id)| vec![Integer(id), Integer(0), String(title.to_string()), Integer(0), Integer(0)])
+            .collect();
+        let mut db = open_clean_db();
+        build_page_index(&mut db, &Input::Rows(HashMap::from([("page", rows)])), &classifier()).unwrap();
+        db
+    }
+
+    #[test]
+    fn redirect_index() {
+        use sql::Value::{Integer, Null, String};
+        // rd_from, rd_namespace, rd_title, rd_interwiki
+        let redirect = |from, ns, title: &str, interwiki| vec![Integer(from), Integer(ns), String(title.into()), interwiki];
+        let rows = vec![redirect(1, 0, "B", Null), redirect(2, 0, "C", String("".into())),
+            redirect(3, 0, "Paris", String("fr".into())), redirect(4, 2, "User", Null)];
+        let mut db = sample_pages();
+        let report = build_redirect_index(&mut db, &Input::Rows(HashMap::from([("redirect", rows)]))).unwrap();
+        assert_eq!((report.parsed, report.inserted, report.skipped_namespace, report.interwiki), (4, 2, 1, 1));
+    }
+
+    #[test]
+    fn sample_distances() {
+        let mut db = sample_pages();
+        for link in [(1, 2), (2, 3), (3, 2)] {
+            db.add_link(link).unwrap();
+        }
+        let mut found = vec![];
+        let (count, max) = distances(&db, 1, |title, d| { found.push((title, d)); Ok(()) }).unwrap();
+        assert_eq!(found, [("A".to_owned(), 0), ("B".to_owned(), 1), ("C".to_owned(), 2)]);
+        assert_eq!((count, max), (3, 2));
+        assert!(distances(&db, 2, |_, _| Err(eyre!("disk full"))).is_err());
+    }
+
+    #[test]
+    fn why_links() {
+        let mut db = sample_pages();
+        db.add_link((1, 2)).unwrap();
+        let explanations: Vec<String> = why(&db, "A", "B").unwrap().iter().map(|r| explain_link(r)).collect();
+        assert_eq!(explanations, ["A links to B", "B does not link to A"]);
+        assert_eq!(why(&db, "A", "Nowhere").unwrap_err().to_string(), "Unknown article: Nowhere");
+        assert_eq!(why(&db, "A", "Category:Physics").unwrap_err().to_string(),
+            "Category:Physics is in the Category namespace, but only articles are indexed");
+    }
+
+    #[test]
+    fn template_links() {
+        use sql::Value::{Integer, String};
+        let mut db = sample_pages();
+        // tl_from, tl_namespace, tl_title, tl_from_namespace
+        let rows = [vec![Integer(1), Integer(10), String("Infobox".into()), Integer(0)],
+            vec![Integer(2), Integer(0), String("C".into()), Integer(0)]];
+        let mut report = TableReport::new("templatelinks");
+        add_template_links(&mut db, rows.into_iter().map(Ok), &mut report, || panic!("old schema")).unwrap();
+        assert_eq!((report.parsed, report.inserted, report.skipped_namespace), (2, 1, 1));
+
+        // tl_from, tl_from_namespace, tl_target_id
+        let rows = [vec![Integer(2), Integer(0), Integer(20)], vec![Integer(3), Integer(0), Integer(21)], vec![Integer(3), Integer(2), Integer(20)]];
+        let mut report = TableReport::new("templatelinks");
+        add_template_links(&mut db, rows.into_iter().map(Ok), &mut report, || Ok(HashMap::from([(20, "Infobox".to_owned())]))).unwrap();
+        assert_eq!((report.parsed, report.inserted, report.skipped_namespace), (3, 1, 2));
+        assert_eq!(db.template_users("Infobox").unwrap(), [1, 2]);
+    }
+
+    #[test]
+    fn titled_links() {
+        use sql::Value::{Integer, String};
+        // pl_from, pl_namespace, pl_title, pl_from_namespace
+        let link = |from, ns, title: &str, from_ns| vec![Integer(from), Integer(ns), String(title.into()), Integer(from_ns)];
+        let rows = vec![link(1, 0, "B", 0), link(1, 0, "B", 0), link(1, 0, "C", 0), link(2, 2, "User", 0), link(2, 0, "A", 1), link(3, 0, "Nowhere", 0)];
+        let mut db = sample_pages();
+        let reports = build_link_index(&mut db, &Input::Rows(HashMap::from([("pagelinks", rows)])), false).unwrap();
+
+        assert_eq!(reports.len(), 1);
+        let report = &reports[0];
+        assert_eq!((report.parsed, report.inserted, report.skipped_namespace, report.unresolved, report.duplicates), (6, 2, 2, 1, 1));
+        assert_eq!(db.links_from(1), [2, 3]);
+    }
+
+    #[test]
+    fn link_targets() {
+        use sql::Value::{Integer, String};
+        // lt_id, lt_namespace, lt_title
+        let targets = [(10, 0, "B"), (11, 0, "C"), (12, 2, "User"), (13, 0, "Nowhere")]
+            .map(|(id, ns, title)| vec![Integer(id), Integer(ns), String(title.into())]).to_vec();
+        // pl_from, pl_from_namespace, pl_target_id
+        let links = [(1, 0, 10), (1, 0, 10), (1, 0, 11), (2, 0, 12), (2, 1, 10), (3, 0, 13), (3, 0, 99)]
+            .map(|(from, ns, target)| vec![Integer(from), Integer(ns), Integer(target)]).to_vec();
+        let mut db = sample_pages();
+        let input = Input::Rows(HashMap::from([("linktarget", targets), ("pagelinks", links)]));
+        let reports = build_link_index(&mut db, &input, false).unwrap();
+
+        let tables: Vec<_> = reports.iter().map(|r| r.table.as_str()).collect();
+        assert_eq!(tables, ["linktarget", "pagelinks"]);
+        let (targets, links) = (&reports[0], &reports[1]);
+        assert_eq!((targets.parsed, targets.inserted, targets.skipped_namespace, targets.unresolved), (4, 2, 1, 1));
+        assert_eq!((links.parsed, links.inserted, links.skipped_namespace, links.unresolved, links.unknown_targets, links.duplicates),
+            (7, 2, 2, 1, 1, 1));
+        assert_eq!(db.links_from(1), [2, 3]);
+    }
+
+    #[cfg(feature = "mysql")]
+    #[test]
+    fn page_index_from_mysql() {
+        use ::mysql::Value::{Bytes, Int};
+        // page_id, page_namespace, page_title, page_restrictions, page_is_redirect, page_is_new,
+        // as the binary protocol returns them
+        let rows = [
+            vec![Int(1), Int(0), Bytes(b"Main_Page".to_vec()), Bytes(vec![]), Int(0), Int(1)],
+            vec![Int(2), Int(0), Bytes(b"USA".to_vec()), Bytes(vec![]), Int(1), Int(0)],
+            vec![Int(3), Int(2), Bytes(b"Someone".to_vec()), Bytes(vec![]), Int(0), Int(0)],
+        ];
+        let rows = rows.into_iter().map(|row| row.into_iter().map(mysql::convert).collect()).collect();
+        let input = Input::Rows(HashMap::from([("page", rows)]));
+        let mut db = open_clean_db();
+        let report = build_page_index(&mut db, &input, &classifier()).unwrap();
+        assert_eq!((report.parsed, report.inserted, report.skipped_namespace), (3, 2, 1));
+        assert_eq!(db.index("USA"), Some(2));
+        assert_ne!(db.title_flags(2) & classify::REDIRECT, 0);
+        assert_ne!(db.title_flags(1) & classify::NEW, 0);
+    }
+}
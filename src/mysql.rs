@@ -0,0 +1,42 @@
+//! Reading the MediaWiki tables from a live MySQL or MariaDB database
+
+use std::{sync::mpsc, thread};
+
+use mysql::{Conn, Opts, prelude::Queryable};
+
+use crate::sql::Value;
+
+/// Convert a MySQL value read with the binary protocol to the type the dump parser
+/// would produce. The text protocol would return every column as bytes.
+pub fn convert(value: mysql::Value) -> Value {
+    match value {
+        mysql::Value::NULL => Value::Null,
+        mysql::Value::Bytes(bytes) => Value::Bytes(bytes),
+        mysql::Value::Int(n) => Value::Integer(n),
+        mysql::Value::UInt(n) => Value::Integer(n as i64),
+        mysql::Value::Float(x) => Value::Float(x as f64),
+        mysql::Value::Double(x) => Value::Float(x),
+        other => Value::String(other.as_sql(true)),
+    }
+}
+
+/// Stream all the rows of a table, with the columns in table order like in the dumps.
+/// The query runs on a separate thread, a bounded number of rows ahead of the consumer.
+/// It is a prepared statement, so that the numeric columns arrive as numbers.
+pub fn rows(uri: &str, table: &'static str) -> Result<impl Iterator<Item = Result<Vec<Value>, mysql::Error>>, mysql::Error> {
+    let mut conn = Conn::new(Opts::from_url(uri)?)?;
+    let (sender, receiver) = mpsc::sync_channel(4096);
+
+    thread::spawn(move || {
+        let rows = match conn.exec_iter(format!("SELECT * FROM `{}`", table), ()) {
+            Ok(rows) => rows,
+            Err(e) => { let _ = sender.send(Err(e)); return }
+        };
+        for row in rows {
+            let row = row.map(|row| row.unwrap().into_iter().map(convert).collect());
+            if sender.send(row).is_err() { return }
+        }
+    });
+
+    Ok(receiver.into_iter())
+}
@@ -0,0 +1,186 @@
+//! Namespace prefixes of titles, such as `Category:`, localized per wiki
+//!
+//! The local names and aliases come from the `siteinfo-namespaces` dump. The
+//! canonical English names are always accepted, as on the wikis themselves.
+
+use std::{borrow::Cow, io::Read};
+
+use serde_json::Value;
+
+use crate::sqlite::Db;
+
+pub const MAIN: i64 = 0;
+pub const FILE: i64 = 6;
+pub const TEMPLATE: i64 = 10;
+pub const CATEGORY: i64 = 14;
+
+/// Canonical names of the namespaces used by the optional tables
+const CANONICAL: [(i64, &str); 4] = [(FILE, "File"), (FILE, "Image"), (TEMPLATE, "Template"), (CATEGORY, "Category")];
+
+/// A name of a namespace, with underscores instead of spaces
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Name {
+    pub id: i64,
+    pub name: String,
+    /// Whether this is the name the wiki displays, rather than a canonical name or an alias
+    pub local: bool,
+}
+
+/// How a wiki normalizes the first letter of the titles of articles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Case {
+    /// The first letter is uppercased, as on most wikis
+    FirstLetter,
+    /// Titles are kept as typed, as on the Wiktionaries
+    Sensitive,
+}
+
+impl Case {
+    /// The name of the setting in the siteinfo dump
+    pub fn name(self) -> &'static str {
+        match self {
+            Case::FirstLetter => "first-letter",
+            Case::Sensitive => "case-sensitive",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        [Case::FirstLetter, Case::Sensitive].into_iter().find(|c| c.name() == name)
+    }
+
+    /// Normalize a title typed by a user
+    pub fn normalize(self, title: &str) -> Cow<'_, str> {
+        match self {
+            Case::FirstLetter => ucfirst(title),
+            Case::Sensitive => Cow::Borrowed(title),
+        }
+    }
+}
+
+/// A title with its first letter uppercased. Letters that do not uppercase to a
+/// single character, such as `ß`, are kept.
+pub fn ucfirst(title: &str) -> Cow<'_, str> {
+    let mut chars = title.chars();
+    let Some(first) = chars.next().filter(|c| c.is_lowercase()) else { return Cow::Borrowed(title) };
+    let mut upper = first.to_uppercase();
+    match (upper.next(), upper.next()) {
+        (Some(c), None) => Cow::Owned(format!("{}{}", c, chars.as_str())),
+        _ => Cow::Borrowed(title),
+    }
+}
+
+/// The contents of a `siteinfo-namespaces` dump
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Siteinfo {
+    pub names: Vec<Name>,
+    /// Case rule of the main namespace, if given
+    pub case: Option<Case>,
+}
+
+/// Read the namespace names and aliases of a `siteinfo-namespaces` JSON dump
+pub fn read_siteinfo<R: Read>(source: R) -> Result<Siteinfo, serde_json::Error> {
+    let siteinfo: Value = serde_json::from_reader(source)?;
+    let query = &siteinfo["query"];
+    let case = query["namespaces"]["0"]["case"].as_str().and_then(Case::parse);
+    let text = |v: &Value, keys: &[&str]| keys.iter()
+        .find_map(|k| v[k].as_str())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.replace(' ', "_"));
+
+    let mut names = vec![];
+    for ns in query["namespaces"].as_object().into_iter().flat_map(|o| o.values()) {
+        let Some(id) = ns["id"].as_i64() else { continue };
+        if let Some(name) = text(ns, &["name", "*"]) {
+            names.push(Name { id, name, local: true });
+        }
+        if let Some(name) = text(ns, &["canonical"]) {
+            names.push(Name { id, name, local: false });
+        }
+    }
+    for alias in query["namespacealiases"].as_array().into_iter().flatten() {
+        let Some(id) = alias["id"].as_i64() else { continue };
+        if let Some(name) = text(alias, &["alias", "*"]) {
+            names.push(Name { id, name, local: false });
+        }
+    }
+    Ok(Siteinfo { names, case })
+}
+
+/// The namespace of a prefix, such as `Kategorie` on dewiki. Canonical English names
+/// are recognized even if the namespaces were not indexed.
+pub fn id(db: &Db, prefix: &str) -> Option<i64> {
+    let prefix = prefix.trim().replace(' ', "_");
+    db.namespace_id(&prefix).or_else(|| CANONICAL.iter()
+        .find(|(_, name)| name.eq_ignore_ascii_case(&prefix))
+        .map(|(id, _)| *id))
+}
+
+/// Split a title into its namespace and its name within the namespace,
+/// if it starts with a known namespace prefix
+pub fn split<'t>(db: &Db, title: &'t str) -> Option<(i64, &'t str)> {
+    let (prefix, name) = title.split_once(':')?;
+    Some((id(db, prefix)?, name))
+}
+
+/// The namespace of a title, `MAIN` if it has no known prefix
+pub fn of(db: &Db, title: &str) -> i64 {
+    split(db, title).map_or(MAIN, |(id, _)| id)
+}
+
+/// The namespace named on the command line: `main`, a number or a prefix
+pub fn parse(db: &Db, name: &str) -> Option<i64> {
+    if name.eq_ignore_ascii_case("main") { return Some(MAIN) }
+    name.parse().ok().or_else(|| id(db, name))
+}
+
+/// Strip the prefix of a title if it is in the namespace `ns`, and use underscores
+pub fn strip(db: &Db, title: &str, ns: i64) -> String {
+    match split(db, title) {
+        Some((id, name)) if id == ns => name,
+        _ => title,
+    }.replace(' ', "_")
+}
+
+/// The prefix the wiki displays for a namespace
+pub fn prefix(db: &Db, ns: i64) -> String {
+    db.namespace_name(ns)
+        .or_else(|| CANONICAL.iter().find(|(id, _)| *id == ns).map(|(_, name)| name.to_string()))
+        .unwrap_or_else(|| ns.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sample_siteinfo() {
+        let dump = r#"{"batchcomplete": "", "query": {
+            "namespaces": {
+                "0": {"id": 0, "case": "first-letter", "content": "", "*": ""},
+                "6": {"id": 6, "case": "first-letter", "canonical": "File", "*": "Datei"},
+                "14": {"id": 14, "case": "first-letter", "canonical": "Category", "*": "Kategorie"}
+            },
+            "namespacealiases": [{"id": 6, "*": "Bild"}, {"id": 4, "*": "WP"}]
+        }}"#;
+        let siteinfo = read_siteinfo(dump.as_bytes()).unwrap();
+        assert_eq!(siteinfo.case, Some(Case::FirstLetter));
+        let name = |id, name: &str, local| Name { id, name: name.into(), local };
+        assert_eq!(siteinfo.names, [
+            name(6, "Datei", true), name(6, "File", false),
+            name(14, "Kategorie", true), name(14, "Category", false),
+            name(6, "Bild", false), name(4, "WP", false),
+        ]);
+    }
+
+    #[test]
+    fn first_letter() {
+        assert_eq!(ucfirst("paris"), "Paris");
+        assert_eq!(ucfirst("école"), "École");
+        assert_eq!(ucfirst("iPhone"), "IPhone");
+        assert_eq!(ucfirst("ßtraße"), "ßtraße");
+        assert_eq!(ucfirst("1984"), "1984");
+        assert_eq!(ucfirst(""), "");
+        assert_eq!(Case::Sensitive.normalize("dog"), "dog");
+        assert_eq!(Case::parse("case-sensitive"), Some(Case::Sensitive));
+    }
+}
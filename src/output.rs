@@ -0,0 +1,440 @@
+//! Formatting of query results
+//!
+//! Query commands describe their results as a stream of records sharing the same
+//! fields, and the chosen `Format` decides how they are written out. The text
+//! format is colored when `--color` allows it.
+//!
+//! Fields holding numbers or booleans declare it after their name, as in
+//! `id:int`, for the formats with a schema. The other fields hold text.
+
+use std::{collections::HashSet, fmt::Display, io::{self, IsTerminal, Write}, sync::atomic::{AtomicBool, Ordering}};
+
+use clap::ValueEnum;
+use owo_colors::OwoColorize;
+use serde_json::{Map, Value};
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// Tab-separated values, with a header line
+    Tsv,
+    /// A single JSON array of records
+    Json,
+    /// One JSON record per line
+    Ndjson,
+    /// An Arrow IPC stream, with the `arrow` feature
+    Arrow,
+    /// A JSON document of nodes and edges, for graph frontends such as D3 or
+    /// Cytoscape.js. Only `path` writes it, with the context of every hop.
+    GraphJson,
+}
+
+/// When to color the text output
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// When writing to a terminal, unless the `NO_COLOR` variable is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+static COLOR: AtomicBool = AtomicBool::new(false);
+
+/// Enable or disable the colors of the text output, which are off until then
+pub fn set_color(choice: ColorChoice) {
+    let enabled = match choice {
+        ColorChoice::Auto => io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+    };
+    COLOR.store(enabled, Ordering::Relaxed);
+}
+
+/// Styles of the text output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Style {
+    /// Table headers
+    Heading,
+    /// IDs, arrows and other secondary details
+    Muted,
+    /// A positive answer
+    Yes,
+    /// A negative answer or a problem
+    No,
+}
+
+/// `text` in the given style, if colors are enabled
+pub fn paint<T: Display>(text: T, style: Style) -> String {
+    if !COLOR.load(Ordering::Relaxed) { return text.to_string() }
+    match style {
+        Style::Heading => text.bold().to_string(),
+        Style::Muted => text.dimmed().to_string(),
+        Style::Yes => text.green().to_string(),
+        Style::No => text.red().to_string(),
+    }
+}
+
+/// Type of the values of a field
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Text,
+    Int,
+    Float,
+    Bool,
+}
+
+impl Kind {
+    /// The name and type of a declared field
+    pub fn parse(field: &str) -> (&str, Kind) {
+        match field.rsplit_once(':') {
+            Some((name, "int")) => (name, Kind::Int),
+            Some((name, "float")) => (name, Kind::Float),
+            Some((name, "bool")) => (name, Kind::Bool),
+            _ => (field, Kind::Text),
+        }
+    }
+}
+
+/// Number of rows the widths of table columns are measured on. Later rows are
+/// written as they come, and overflow their columns if they are wider.
+const PREVIEW: usize = 1000;
+
+/// Renders a record as a line of human-readable text
+pub type TextFn = Box<dyn Fn(&[Value]) -> String>;
+
+/// Writer for a stream of records, in a given format
+pub struct Output<W: Write> {
+    format: Format,
+    out: W,
+    fields: Vec<String>,
+    /// Without a text rendering, the text format is an aligned table
+    text: Option<TextFn>,
+    /// First rows of the table, buffered until the widths of the columns are known
+    cells: Vec<Vec<String>>,
+    /// The widths of the columns, once the first rows are written
+    widths: Option<Vec<usize>>,
+    /// Whether each column of the table only holds numbers, to align them right
+    numeric: Vec<bool>,
+    rows: usize,
+    #[cfg(feature = "arrow")]
+    arrow: Option<crate::arrow::Encoder>,
+}
+
+impl Output<io::StdoutLock<'static>> {
+    /// Write records to the standard output
+    pub fn stdout<F>(format: Format, fields: &[&str], text: F) -> Self
+    where F: Fn(&[Value]) -> String + 'static
+    {
+        Output::new(format, io::stdout().lock(), fields, text)
+    }
+
+    /// Write records to the standard output, as a table in the text format
+    pub fn stdout_table(format: Format, fields: &[&str]) -> Self {
+        Output::table(format, io::stdout().lock(), fields)
+    }
+}
+
+impl <W: Write> Output<W> {
+
+    /// Write records with the given fields. `text` is only used with `Format::Text`.
+    pub fn new<F>(format: Format, out: W, fields: &[&str], text: F) -> Self
+    where F: Fn(&[Value]) -> String + 'static
+    {
+        Output { text: Some(Box::new(text)), ..Output::table(format, out, fields) }
+    }
+
+    /// Write records with the given fields, as a table with one column per
+    /// field in the text format. The table is written out by `finish`.
+    pub fn table(format: Format, out: W, fields: &[&str]) -> Self {
+        Output {
+            format, out,
+            #[cfg(feature = "arrow")]
+            arrow: (format == Format::Arrow).then(|| crate::arrow::Encoder::new(fields)),
+            numeric: vec![true; fields.len()],
+            fields: fields.iter().map(|f| Kind::parse(f).0.to_owned()).collect(),
+            text: None,
+            cells: vec![],
+            widths: None,
+            rows: 0,
+        }
+    }
+
+    /// Write one record, with one value per field
+    pub fn write(&mut self, record: &[Value]) -> io::Result<()> {
+        debug_assert_eq!(record.len(), self.fields.len());
+
+        match self.format {
+            Format::Text => match &self.text {
+                Some(text) => writeln!(self.out, "{}", text(record))?,
+                None => {
+                    let cells: Vec<String> = record.iter().map(display).collect();
+                    if self.widths.is_some() {
+                        self.write_row(&cells)?;
+                    } else {
+                        for (numeric, value) in self.numeric.iter_mut().zip(record) {
+                            *numeric &= value.is_number() || value.is_null();
+                        }
+                        self.cells.push(cells);
+                        if self.cells.len() == PREVIEW { self.write_table()? }
+                    }
+                }
+            },
+            Format::Tsv => {
+                if self.rows == 0 { self.header()? }
+                let cells: Vec<String> = record.iter().map(tsv_cell).collect();
+                writeln!(self.out, "{}", cells.join("\t"))?;
+            }
+            Format::Json => {
+                let object = self.object(record);
+                self.out.write_all(if self.rows == 0 { b"[\n" } else { b",\n" })?;
+                serde_json::to_writer(&mut self.out, &object)?;
+            }
+            Format::Ndjson => {
+                let object = self.object(record);
+                serde_json::to_writer(&mut self.out, &object)?;
+                writeln!(self.out)?;
+            }
+            #[cfg(feature = "arrow")]
+            Format::Arrow => self.arrow.as_mut().expect("arrow encoder").write(&mut self.out, record)?,
+            #[cfg(not(feature = "arrow"))]
+            Format::Arrow => return Err(no_arrow()),
+            Format::GraphJson => return Err(io::Error::other("--format graph-json is only written by path, with a start and an end")),
+        }
+
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Terminate the output
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.format {
+            Format::Text if self.text.is_none() && self.widths.is_none() => self.write_table()?,
+            Format::Tsv if self.rows == 0 => self.header()?,
+            Format::Json if self.rows == 0 => writeln!(self.out, "[]")?,
+            Format::Json => writeln!(self.out, "\n]")?,
+            #[cfg(feature = "arrow")]
+            Format::Arrow => self.arrow.take().expect("arrow encoder").finish(&mut self.out)?,
+            #[cfg(not(feature = "arrow"))]
+            Format::Arrow => return Err(no_arrow()),
+            _ => (),
+        }
+        self.out.flush()
+    }
+
+    fn header(&mut self) -> io::Result<()> {
+        writeln!(self.out, "{}", self.fields.join("\t"))
+    }
+
+    /// Measure the columns on the buffered rows, and write them under a header
+    fn write_table(&mut self) -> io::Result<()> {
+        let mut widths: Vec<usize> = self.fields.iter().map(|f| f.chars().count()).collect();
+        for row in &self.cells {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        self.widths = Some(widths);
+
+        let header: Vec<String> = self.fields.iter().enumerate()
+            .map(|(i, field)| paint(self.pad(i, field), Style::Heading))
+            .collect();
+        writeln!(self.out, "{}", header.join("  "))?;
+        for row in std::mem::take(&mut self.cells) {
+            self.write_row(&row)?;
+        }
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[String]) -> io::Result<()> {
+        let line: Vec<String> = row.iter().enumerate()
+            .map(|(i, cell)| self.pad(i, cell))
+            .collect();
+        writeln!(self.out, "{}", line.join("  ").trim_end())
+    }
+
+    /// A cell padded to the width of its column. Numbers are aligned right, and
+    /// the last column is not padded.
+    fn pad(&self, i: usize, text: &str) -> String {
+        let width = self.widths.as_ref().map_or(0, |widths| widths[i]);
+        match (self.numeric[i], i + 1 == self.fields.len()) {
+            (true, _) => format!("{:>1$}", text, width),
+            (false, false) => format!("{:<1$}", text, width),
+            (false, true) => text.to_owned(),
+        }
+    }
+
+    fn object(&self, record: &[Value]) -> Map<String, Value> {
+        self.fields.iter()
+            .cloned()
+            .zip(record.iter().cloned())
+            .collect()
+    }
+
+}
+
+/// A graph of articles, written as a single JSON document of `nodes` and `edges`.
+/// Edges name their ends with `source` and `target`, as D3 expects.
+#[derive(Default)]
+pub struct Graph {
+    nodes: Vec<Value>,
+    edges: Vec<Value>,
+    ids: HashSet<u64>,
+    links: HashSet<(u64, u64)>,
+}
+
+impl Graph {
+
+    pub fn contains(&self, id: u64) -> bool {
+        self.ids.contains(&id)
+    }
+
+    /// Add a node, with its `id` and the fields of an object, unless it is already there
+    pub fn node(&mut self, id: u64, fields: Value) {
+        if !self.ids.insert(id) { return }
+        let mut node = Map::new();
+        node.insert("id".to_owned(), id.into());
+        if let Value::Object(fields) = fields { node.extend(fields) }
+        self.nodes.push(Value::Object(node));
+    }
+
+    /// Add an edge, with the fields of an object, unless it is already there
+    pub fn edge(&mut self, source: u64, target: u64, fields: Value) {
+        if !self.links.insert((source, target)) { return }
+        let mut edge = Map::new();
+        edge.insert("source".to_owned(), source.into());
+        edge.insert("target".to_owned(), target.into());
+        if let Value::Object(fields) = fields { edge.extend(fields) }
+        self.edges.push(Value::Object(edge));
+    }
+
+    pub fn write<W: Write>(self, mut out: W) -> io::Result<()> {
+        let mut document = Map::new();
+        document.insert("nodes".to_owned(), Value::Array(self.nodes));
+        document.insert("edges".to_owned(), Value::Array(self.edges));
+        serde_json::to_writer(&mut out, &document)?;
+        writeln!(out)?;
+        out.flush()
+    }
+}
+
+#[cfg(not(feature = "arrow"))]
+fn no_arrow() -> io::Error {
+    io::Error::other("--format arrow requires building wikistra with the `arrow` feature")
+}
+
+/// A value as plain text: strings without quotes, null as an empty string
+pub fn display(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// A value as TSV. Special characters in strings are escaped, and arrays are
+/// spread over the remaining columns.
+fn tsv_cell(value: &Value) -> String {
+    match value {
+        Value::Array(items) => items.iter().map(tsv_cell).collect::<Vec<_>>().join("\t"),
+        Value::String(s) => s.replace('\\', "\\\\")
+            .replace('\t', "\\t")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r"),
+        other => display(other),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn render(format: Format, records: &[[Value; 2]]) -> String {
+        let mut buffer = vec![];
+        let mut output = Output::new(format, &mut buffer, &["title", "distance:int"],
+            |r| format!("{} at {}", display(&r[0]), display(&r[1])));
+        for record in records {
+            output.write(record).unwrap();
+        }
+        output.finish().unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn sample_formats() {
+        let records = [[json!("Paris"), json!(0)], [json!("Tab\there"), Value::Null]];
+
+        assert_eq!(render(Format::Text, &records), "Paris at 0\nTab\there at \n");
+        assert_eq!(render(Format::Tsv, &records), "title\tdistance\nParis\t0\nTab\\there\t\n");
+        assert_eq!(render(Format::Ndjson, &records),
+            "{\"title\":\"Paris\",\"distance\":0}\n{\"title\":\"Tab\\there\",\"distance\":null}\n");
+        assert_eq!(render(Format::Json, &records),
+            "[\n{\"title\":\"Paris\",\"distance\":0},\n{\"title\":\"Tab\\there\",\"distance\":null}\n]\n");
+    }
+
+    #[test]
+    fn field_kinds() {
+        assert_eq!(Kind::parse("id:int"), ("id", Kind::Int));
+        assert_eq!(Kind::parse("score:float"), ("score", Kind::Float));
+        assert_eq!(Kind::parse("title"), ("title", Kind::Text));
+        assert_eq!(Kind::parse("to Category:France"), ("to Category:France", Kind::Text));
+    }
+
+    #[test]
+    fn sample_table() {
+        let mut buffer = vec![];
+        let mut output = Output::table(Format::Text, &mut buffer, &["domain", "articles", "note"]);
+        output.write(&[json!("example.org"), json!(12345), json!("a")]).unwrap();
+        output.write(&[json!("w.org"), json!(7), Value::Null]).unwrap();
+        output.finish().unwrap();
+
+        assert_eq!(String::from_utf8(buffer).unwrap(),
+            "domain       articles  note\nexample.org     12345  a\nw.org               7\n");
+    }
+
+    #[test]
+    fn long_table() {
+        let mut buffer = vec![];
+        let mut output = Output::table(Format::Text, &mut buffer, &["n", "title"]);
+        for n in 0..PREVIEW + 1 {
+            output.write(&[json!(n), json!("x")]).unwrap();
+        }
+        // The rows after the preview keep the widths measured on it
+        output.write(&[json!(123456), json!("y")]).unwrap();
+        output.finish().unwrap();
+
+        let text = String::from_utf8(buffer).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "  n  title");
+        assert_eq!(lines[1000], "999  x");
+        assert_eq!(lines[1001], "1000  x");
+        assert_eq!(lines[1002], "123456  y");
+        assert_eq!(lines.len(), PREVIEW + 3);
+    }
+
+    #[test]
+    fn sample_graph() {
+        let mut graph = Graph::default();
+        graph.node(1, json!({ "title": "Paris" }));
+        graph.node(2, json!({ "title": "Rome" }));
+        graph.node(1, json!({ "title": "Paris" }));
+        graph.edge(1, 2, Value::Null);
+        graph.edge(1, 2, Value::Null);
+        assert!(graph.contains(2) && !graph.contains(3));
+
+        let mut buffer = vec![];
+        graph.write(&mut buffer).unwrap();
+        assert_eq!(String::from_utf8(buffer).unwrap(),
+            "{\"nodes\":[{\"id\":1,\"title\":\"Paris\"},{\"id\":2,\"title\":\"Rome\"}],\"edges\":[{\"source\":1,\"target\":2}]}\n");
+    }
+
+    #[test]
+    fn empty_output() {
+        assert_eq!(render(Format::Json, &[]), "[]\n");
+        assert_eq!(render(Format::Tsv, &[]), "title\tdistance\n");
+        assert_eq!(render(Format::Ndjson, &[]), "");
+    }
+}
@@ -0,0 +1,186 @@
+//! Single-file archives of a database, to publish prebuilt indexes
+//!
+//! An archive is a gzipped tarball holding the database as `db.sq3`, followed by
+//! `metadata.json` describing it, with the SHA-256 of every file.
+
+use std::{fs::{self, File}, io::{self, BufReader, Read}, path::Path, time::{SystemTime, UNIX_EPOCH}};
+
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+/// Name of the database inside archives
+const DB_ENTRY: &str = "db.sq3";
+const METADATA_ENTRY: &str = "metadata.json";
+
+#[derive(Error, Debug)]
+pub enum PackError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("Invalid archive metadata: {0}")]
+    Metadata(#[from] serde_json::Error),
+    #[error("The archive has no {0}")]
+    Missing(&'static str),
+    #[error("Checksum mismatch for {0}, the archive is corrupted")]
+    Checksum(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FileEntry {
+    pub name: String,
+    pub size: u64,
+    /// Hex-encoded SHA-256 of the contents
+    pub sha256: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    pub wikiname: String,
+    /// Version of wikistra that built the archive
+    pub version: String,
+    /// Creation time, in seconds since the Unix epoch
+    pub created: u64,
+    pub files: Vec<FileEntry>,
+}
+
+/// Wraps a reader and hashes the bytes consumed from it
+struct Hashing<R> {
+    inner: R,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl <R: Read> Hashing<R> {
+    fn new(inner: R) -> Self {
+        Hashing { inner, hasher: Sha256::new(), size: 0 }
+    }
+
+    fn entry(self, name: &str) -> FileEntry {
+        let sha256 = self.hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+        FileEntry { name: name.to_owned(), size: self.size, sha256 }
+    }
+}
+
+impl <R: Read> Read for Hashing<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.size += n as u64;
+        Ok(n)
+    }
+}
+
+/// Write the database at `db_path` and its metadata to a new archive
+pub fn pack(db_path: &str, wikiname: &str, archive: &str) -> Result<Metadata, PackError> {
+    let mut builder = tar::Builder::new(GzEncoder::new(File::create(archive)?, Compression::default()));
+
+    let db = File::open(db_path)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(db.metadata()?.len());
+    header.set_mode(0o644);
+    let mut db = Hashing::new(BufReader::new(db));
+    builder.append_data(&mut header, DB_ENTRY, &mut db)?;
+
+    let metadata = Metadata {
+        wikiname: wikiname.to_owned(),
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        created: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()),
+        files: vec![db.entry(DB_ENTRY)],
+    };
+    let json = serde_json::to_vec_pretty(&metadata)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(json.len() as u64);
+    header.set_mode(0o644);
+    builder.append_data(&mut header, METADATA_ENTRY, &json[..])?;
+
+    builder.into_inner()?.finish()?;
+    Ok(metadata)
+}
+
+/// Extract the database of an archive to `db_path`, checking its integrity.
+/// The database is only moved in place once verified.
+pub fn unpack(archive: &str, db_path: &str) -> Result<Metadata, PackError> {
+    let partial = format!("{}.partial", db_path);
+    let result = extract(archive, &partial);
+    match &result {
+        Ok(_) => fs::rename(&partial, db_path)?,
+        Err(_) => { let _ = fs::remove_file(&partial); }
+    }
+    result
+}
+
+fn extract(archive: &str, db_path: &str) -> Result<Metadata, PackError> {
+    let mut archive = tar::Archive::new(GzDecoder::new(BufReader::new(File::open(archive)?)));
+    let (mut db, mut metadata) = (None, None);
+
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+        match name.as_str() {
+            DB_ENTRY => {
+                let mut reader = Hashing::new(entry);
+                io::copy(&mut reader, &mut File::create(db_path)?)?;
+                db = Some(reader.entry(DB_ENTRY));
+            }
+            METADATA_ENTRY => metadata = Some(serde_json::from_reader::<_, Metadata>(entry)?),
+            _ => continue,
+        }
+    }
+
+    let db = db.ok_or(PackError::Missing(DB_ENTRY))?;
+    let metadata = metadata.ok_or(PackError::Missing(METADATA_ENTRY))?;
+    if !metadata.files.contains(&db) {
+        return Err(PackError::Checksum(DB_ENTRY.to_owned()));
+    }
+    Ok(metadata)
+}
+
+/// Hex-encoded SHA-256 of a file
+pub fn sha256_file(path: &str) -> io::Result<String> {
+    let mut reader = Hashing::new(BufReader::new(File::open(path)?));
+    io::copy(&mut reader, &mut io::sink())?;
+    Ok(reader.entry(path).sha256)
+}
+
+/// Whether a file exists, to avoid overwriting a database by accident
+pub fn exists(path: &str) -> bool {
+    Path::new(path).exists()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let dir = std::env::temp_dir().join(format!("wikistra-pack-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+
+        fs::write(path("db.sq3"), b"not really a database").unwrap();
+        let packed = pack(&path("db.sq3"), "enwiki", &path("db.tar.gz")).unwrap();
+        assert_eq!(packed.files[0].size, 21);
+        assert_eq!(packed.files[0].sha256.len(), 64);
+        assert_eq!(sha256_file(&path("db.sq3")).unwrap(), packed.files[0].sha256);
+
+        let unpacked = unpack(&path("db.tar.gz"), &path("copy.sq3")).unwrap();
+        assert_eq!(unpacked, packed);
+        assert_eq!(fs::read(path("copy.sq3")).unwrap(), b"not really a database");
+
+        // An archive whose metadata does not match the database is rejected
+        let mut builder = tar::Builder::new(GzEncoder::new(File::create(path("bad.tar.gz")).unwrap(), Compression::default()));
+        builder.append_path_with_name(path("copy.sq3"), DB_ENTRY).unwrap();
+        let mut wrong = packed.clone();
+        wrong.files[0].sha256 = "0".repeat(64);
+        let json = serde_json::to_vec(&wrong).unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_size(json.len() as u64);
+        builder.append_data(&mut header, METADATA_ENTRY, &json[..]).unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+        assert!(matches!(unpack(&path("bad.tar.gz"), &path("bad.sq3")), Err(PackError::Checksum(_))));
+        assert!(!exists(&path("bad.sq3")) && !exists(&path("bad.sq3.partial")));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
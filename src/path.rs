@@ -2,9 +2,33 @@
 //!
 //! Using an unweighted graph greatly simplifies the stopping
 //! condition of the algorithm.
+//!
+//! The graph is never materialized: it is described by two closures returning
+//! the outgoing, respectively incoming links of a node. Nodes can be any ordered,
+//! cloneable type, borrowed or owned.
+//!
+//! ```
+//! use wikistra::path::bidi_dijkstra;
+//!
+//! let edges = [("Paris", "France"), ("France", "Europe"), ("Paris", "Seine"), ("Seine", "Europe")];
+//!
+//! let path = bidi_dijkstra("Paris", "Europe",
+//!     |n: &&str| edges.iter().filter(|(a, _)| a == n).map(|(_, b)| *b).collect::<Vec<_>>(),
+//!     |n: &&str| edges.iter().filter(|(_, b)| b == n).map(|(a, _)| *a).collect::<Vec<_>>(),
+//! );
+//!
+//! assert_eq!(path, Some(vec!["Paris", "France", "Europe"]));
+//! ```
+
 
+use std::collections::{BTreeMap, BTreeSet, btree_map::Entry};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use std::collections::BTreeMap;
+use memmap2::Mmap;
+
+use crate::job::{Cancelled, Control};
 
 
 /// Merge-intersection between two sorted arrays, returns the first element
@@ -14,7 +38,7 @@ use std::collections::BTreeMap;
 /// 
 /// Input lists must be sorted or this function may fail to find matches.
 fn merge<'a, T: Ord>(mut xs: &'a [T], mut ys: &[T]) -> Option<&'a T> {
-    while xs.len() > 0 && ys.len() > 0 {
+    while !xs.is_empty() && !ys.is_empty() {
         match xs[0].cmp(&ys[0]) {
             std::cmp::Ordering::Less => xs = &xs[1..],
             std::cmp::Ordering::Equal => return Some(&xs[0]),
@@ -24,83 +48,482 @@ fn merge<'a, T: Ord>(mut xs: &'a [T], mut ys: &[T]) -> Option<&'a T> {
     None
 }
 
-/// A partial Dijkstra map from an unweighted directed graph
+/// Counters describing the work done by a search
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    /// Number of nodes whose links were fetched
+    pub expanded: usize,
+    /// Number of nodes reached from the start
+    pub forward: usize,
+    /// Number of nodes reached from the goal
+    pub backward: usize,
+    /// If no path exists, the side of the search that ran out of nodes to expand
+    pub exhausted: Option<Side>,
+    /// Whether the path was taken from the path cache, without searching
+    pub cached: bool,
+    /// Whether the search was skipped, as the strongly connected components of
+    /// the endpoints rule out a path
+    pub pruned: bool,
+}
+
+/// One of the two directions of the bidirectional search
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    /// Following links from the start
+    Forward,
+    /// Following links backwards from the goal
+    Backward,
+}
+
+impl std::fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.cached {
+            return write!(f, "answered from the path cache, without searching")
+        }
+        if self.pruned {
+            return write!(f, "not searched, as no links lead from the component of the start to that of the goal")
+        }
+        write!(f, "expanded {} nodes, reached {} from the start and {} from the goal",
+            self.expanded, self.forward, self.backward)?;
+        match self.exhausted {
+            Some(Side::Forward) => write!(f, "; every node reachable from the start was visited"),
+            Some(Side::Backward) => write!(f, "; every node leading to the goal was visited"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The set of nodes reached by a search, along with the node through which
+/// each one was first discovered.
+pub trait Visited<T> {
+    /// The parent of a reached node
+    fn parent(&self, node: &T) -> Option<&T>;
+
+    /// Whether a node was reached
+    fn contains(&self, node: &T) -> bool {
+        self.parent(node).is_some()
+    }
+
+    /// Record a node as reached through `parent`, unless it already was.
+    /// Returns its existing parent in that case.
+    fn visit(&mut self, node: T, parent: &T) -> Option<&T>;
+
+    /// The number of reached nodes
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl <T: Ord + Clone> Visited<T> for BTreeMap<T, T> {
+    fn parent(&self, node: &T) -> Option<&T> {
+        self.get(node)
+    }
+
+    fn contains(&self, node: &T) -> bool {
+        self.contains_key(node)
+    }
+
+    fn visit(&mut self, node: T, parent: &T) -> Option<&T> {
+        match self.entry(node) {
+            Entry::Vacant(e) => { e.insert(parent.clone()); None }
+            Entry::Occupied(e) => Some(e.into_mut()),
+        }
+    }
+
+    fn len(&self) -> usize {
+        BTreeMap::len(self)
+    }
+}
+
+/// Compact visited set for graphs whose nodes are dense integers in `0..n`,
+/// using 4 bytes per node of the graph instead of a tree entry per reached node.
+/// 
+/// It pays off once a search reaches more than a few percent of the graph. Until
+/// the search reaches `n / 16` nodes, they are kept in a tree, so that short searches
+/// do not allocate and clear a vector as large as the graph.
+#[derive(Debug, Clone)]
+pub struct DenseMap {
+    n: u32,
+    /// The reached nodes, until the vector is allocated
+    sparse: BTreeMap<u32, u32>,
+    parents: Vec<u32>,
+    len: usize,
+}
+
+impl DenseMap {
+    const NONE: u32 = u32::MAX;
+
+    /// An empty set for nodes in `0..n`
+    pub fn new(n: u32) -> Self {
+        DenseMap { n, sparse: BTreeMap::new(), parents: vec![], len: 0 }
+    }
+
+    /// Whether the nodes moved from the tree to the vector
+    pub fn is_dense(&self) -> bool {
+        !self.parents.is_empty()
+    }
+
+    fn densify(&mut self) {
+        self.parents = vec![Self::NONE; self.n as usize];
+        for (node, parent) in std::mem::take(&mut self.sparse) {
+            self.parents[node as usize] = parent;
+        }
+    }
+}
+
+impl Visited<u32> for DenseMap {
+    fn parent(&self, node: &u32) -> Option<&u32> {
+        match self.is_dense() {
+            true => self.parents.get(*node as usize).filter(|&&p| p != Self::NONE),
+            false => self.sparse.get(node),
+        }
+    }
+
+    /// Panics if the node is out of range
+    fn visit(&mut self, node: u32, parent: &u32) -> Option<&u32> {
+        assert!(node < self.n, "node {} out of range", node);
+        if !self.is_dense() {
+            if self.sparse.contains_key(&node) { return self.sparse.get(&node) }
+            self.sparse.insert(node, *parent);
+            self.len += 1;
+            if self.sparse.len() > (self.n / 16) as usize { self.densify() }
+            return None
+        }
+        let p = &mut self.parents[node as usize];
+        if *p == Self::NONE {
+            *p = *parent;
+            self.len += 1;
+            None
+        } else {
+            Some(p)
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+/// Visited set for `u32` nodes keeping at most `threshold` reached nodes in a tree.
+/// 
+/// Beyond that, the parents of the nodes are moved to temporary files mapped in
+/// memory, and only the sorted node numbers stay on the heap, using 4 bytes per node.
+/// Spilled batches are merged so that each one is at least twice as large as the
+/// next, which keeps lookups logarithmic. If the files cannot be written, the nodes
+/// stay in memory.
+#[derive(Debug)]
+pub struct SpillMap {
+    recent: BTreeMap<u32, u32>,
+    threshold: usize,
+    runs: Vec<Run>,
+    len: usize,
+}
+
+/// The sorted nodes of a spilled batch, and their parents in a mapped file
 #[derive(Debug)]
-struct Front<T> {
+struct Run {
+    nodes: Vec<u32>,
+    parents: Mmap,
+}
+
+impl Run {
+    fn parents(&self) -> &[u32] {
+        // Safety: mappings are page-aligned, and any bit pattern is a valid u32
+        let (_, parents, _) = unsafe { self.parents.align_to::<u32>() };
+        parents
+    }
+
+    fn parent(&self, node: u32) -> Option<&u32> {
+        self.nodes.binary_search(&node).ok().map(|i| &self.parents()[i])
+    }
+
+    /// A single run holding the nodes of both
+    fn merge(&self, other: &Run) -> io::Result<Run> {
+        let mut a = self.nodes.iter().zip(self.parents()).peekable();
+        let mut b = other.nodes.iter().zip(other.parents()).peekable();
+        let mut nodes = Vec::with_capacity(self.nodes.len() + other.nodes.len());
+        let parents = spill(std::iter::from_fn(|| {
+            let (node, parent) = match (a.peek(), b.peek()) {
+                (Some(x), Some(y)) if y.0 < x.0 => b.next(),
+                (Some(_), _) => a.next(),
+                (None, _) => b.next(),
+            }?;
+            nodes.push(*node);
+            Some(*parent)
+        }))?;
+        Ok(Run { nodes, parents })
+    }
+}
+
+/// Write parents to a new temporary file, deleted as soon as it is unmapped
+fn spill(parents: impl Iterator<Item = u32>) -> io::Result<Mmap> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!("wikistra-spill-{}-{}",
+        std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed)));
+    let file = File::options().read(true).write(true).create_new(true).open(&path)?;
+    fs::remove_file(&path)?;
+    let mut out = BufWriter::new(file);
+    for parent in parents {
+        out.write_all(&parent.to_ne_bytes())?;
+    }
+    let file = out.into_inner().map_err(|e| e.into_error())?;
+    // Safety: the file is unlinked, nothing else can change it
+    unsafe { Mmap::map(&file) }
+}
+
+impl SpillMap {
+    /// An empty set spilling to disk beyond `threshold` nodes
+    pub fn new(threshold: usize) -> Self {
+        SpillMap { recent: BTreeMap::new(), threshold: threshold.max(1), runs: vec![], len: 0 }
+    }
+
+    /// Number of nodes whose parent is on disk
+    pub fn spilled(&self) -> usize {
+        self.len - self.recent.len()
+    }
+
+    fn spill(&mut self) -> io::Result<()> {
+        let parents = spill(self.recent.values().copied())?;
+        let mut run = Run { nodes: self.recent.keys().copied().collect(), parents };
+        self.recent.clear();
+        while let Some(last) = self.runs.last().filter(|last| last.nodes.len() < 2 * run.nodes.len()) {
+            match last.merge(&run) {
+                Ok(merged) => { self.runs.pop(); run = merged }
+                Err(e) => { self.runs.push(run); return Err(e) }
+            }
+        }
+        self.runs.push(run);
+        Ok(())
+    }
+}
+
+impl Visited<u32> for SpillMap {
+    fn parent(&self, node: &u32) -> Option<&u32> {
+        self.recent.get(node).or_else(|| self.runs.iter().find_map(|run| run.parent(*node)))
+    }
+
+    fn visit(&mut self, node: u32, parent: &u32) -> Option<&u32> {
+        if self.contains(&node) { return self.parent(&node) }
+        self.recent.insert(node, *parent);
+        self.len += 1;
+        if self.recent.len() >= self.threshold && self.spill().is_err() {
+            self.threshold = usize::MAX;
+        }
+        None
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+/// A partial Dijkstra map from an unweighted directed graph.
+/// 
+/// The front grows from a single root, one level of links at a time, and remembers
+/// through which node each reached node was first discovered.
+#[derive(Debug)]
+pub struct Front<T, M = BTreeMap<T, T>> {
     /// Set of nodes at the current edge
     edge: Vec<T>,
     /// Map to the parent node. The root node maps to itself.
-    map: BTreeMap<T, T>,
+    map: M,
+    /// When recording all parents, the parents other than the first one
+    /// found at the same level.
+    extra: Option<BTreeMap<T, Vec<T>>>,
 }
 
 /// Look for a collision in the edges of two Dijkstra maps. If one is found,
 /// returns the full path from the first root to the second.
 /// 
 /// The roots are included in the result.
-fn check_collision<T: Ord + Copy>(from: &mut Front<T>, to: &mut Front<T>) -> Option<Vec<T>> {
-    let &k = merge(&from.edge, &to.edge)?;
+fn check_collision<T: Ord + Clone, M: Visited<T>>(from: &mut Front<T, M>, to: &mut Front<T, M>) -> Option<Vec<T>> {
+    let k = merge(&from.edge, &to.edge)?;
 
-    let mut p = k;
-    let mut path = vec![k];
-    loop {
-        let p2 = *from.map.get(&p).expect("inconsistent Front state");
-        if p == p2 { break }
-        path.push(p2);
-        p = p2;
+    let mut path = from.path_to(k);
+    path.reverse();
+    path.extend(to.path_to(k).into_iter().skip(1));
+
+    Some(path)
+}
+
+impl <T: Ord + Clone> Front<T> {
+
+    /// Build a new partial map from a single root
+    pub fn new(root: T) -> Self {
+        Self::with_map(root, BTreeMap::new())
     }
 
-    path.reverse();
+    /// Build a new partial map that records every shortest-path parent of each
+    /// node, not just the first one found.
+    pub fn with_all_parents(root: T) -> Self {
+        Front { extra: Some(BTreeMap::new()), ..Self::new(root) }
+    }
 
-    let mut n = k;
-    loop {
-        let n2 = *to.map.get(&n).expect("inconsistent Front state");
-        if n == n2 { break }
-        path.push(n2);
-        n = n2;
+    /// Build a new partial map from several roots, all at distance 0.
+    /// The edge is empty if there are none.
+    pub fn with_roots<I: IntoIterator<Item = T>>(roots: I) -> Self {
+        let mut front = Front { edge: vec![], map: BTreeMap::new(), extra: None };
+        for root in roots {
+            if front.map.visit(root.clone(), &root).is_none() { front.edge.push(root) }
+        }
+        front.edge.sort();
+        front
     }
 
-    Some(path)
 }
 
-impl <T: Ord + Copy> Front<T> {
+impl <T: Ord + Clone, M: Visited<T>> Front<T, M> {
 
-    /// Build a new partial map from a single root
-    fn new(root: T) -> Self {
-        let edge = vec![root];
-        let mut map = BTreeMap::new();
-        map.insert(root, root);
-        Front { edge, map }
+    /// Build a new partial map from a single root, storing reached nodes in `map`,
+    /// which must be empty.
+    pub fn with_map(root: T, mut map: M) -> Self {
+        map.visit(root.clone(), &root);
+        Front { edge: vec![root], map, extra: None }
     }
 
     /// The size of the map
-    fn len(&self) -> usize {
+    pub fn len(&self) -> usize {
         self.map.len()
     }
 
+    /// Whether the map is empty. This is never the case, as the root is always present.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// The nodes reached by the last expansion, sorted
+    pub fn edge(&self) -> &[T] {
+        &self.edge
+    }
+
+    /// Whether a node has been reached
+    pub fn contains(&self, node: &T) -> bool {
+        self.map.contains(node)
+    }
+
+    /// The node through which `node` was reached. The root is its own parent.
+    pub fn parent(&self, node: &T) -> Option<&T> {
+        self.map.parent(node)
+    }
+
+    /// All the parents of a node at the previous level, if the front records them.
+    /// Otherwise, only the first parent. The root has none.
+    pub fn parents<'a>(&'a self, node: &T) -> impl Iterator<Item = &'a T> + 'a {
+        let first = self.map.parent(node)
+            .filter(|&p| p != node);
+        let extra = self.extra.as_ref()
+            .and_then(|extra| extra.get(node))
+            .into_iter()
+            .flatten();
+        first.into_iter().chain(extra)
+    }
+
+    /// The chain of parents from a reached node back to the root, both included.
+    /// 
+    /// Panics if the node was not reached.
+    pub fn path_to(&self, node: &T) -> Vec<T> {
+        let mut path = vec![node.clone()];
+        let mut p = node;
+        loop {
+            let p2 = self.map.parent(p).expect("inconsistent Front state");
+            if p == p2 { break }
+            path.push(p2.clone());
+            p = p2;
+        }
+        path
+    }
+
     /// Push the edge forward by one step, using the provided function for
     /// generating links.
     /// 
     /// `tmp` must be an empty vector. It is used for saving on allocations.
-    fn expand<F,L>(&mut self, mut links: F, tmp: &mut Vec<T>)
+    /// Returns the number of expanded nodes.
+    pub fn expand<F,L>(&mut self, mut links: F, tmp: &mut Vec<T>) -> usize
     where
         F: FnMut(&T) -> L,
         L: IntoIterator<Item = T>,
     {
+        let expanded = self.edge.len();
+        // Nodes discovered by this expansion, only tracked when recording all parents
+        let mut fresh = BTreeSet::new();
         for old in &self.edge {
             for new in links(old) {
-                self.map.entry(new).or_insert_with(|| {
-                    tmp.push(new);
-                    *old
-                });
+                match self.map.visit(new.clone(), old) {
+                    None => {
+                        if self.extra.is_some() { fresh.insert(new.clone()); }
+                        tmp.push(new);
+                    }
+                    Some(first) => if let Some(extra) = &mut self.extra {
+                        if first != old && fresh.contains(&new) {
+                            let parents = extra.entry(new).or_default();
+                            if !parents.contains(old) { parents.push(old.clone()) }
+                        }
+                    }
+                }
             }
         }
         tmp.sort();
         std::mem::swap(tmp, &mut self.edge);
         tmp.clear();
+        expanded
     }
 
 }
 
+/// Expand the smaller of two fronts until their edges intersect, or until one
+/// of them has no more nodes to expand.
+/// 
+/// Returns the number of expanded nodes, and the exhausted side if the fronts never met.
+fn meet<T,M,F1,F2,L1,L2>(from: &mut Front<T, M>, to: &mut Front<T, M>, links_from: F1, links_to: F2) -> (usize, Option<Side>)
+where
+    T: Ord + Clone,
+    M: Visited<T>,
+    F1: FnMut(&T) -> L1,
+    F2: FnMut(&T) -> L2,
+    L1: IntoIterator<Item=T>,
+    L2: IntoIterator<Item=T>,
+{
+    meet_with(from, to, links_from, links_to, |_| true).expect("never stopped")
+}
+
+/// Same as `meet`, calling `proceed` with the statistics so far after every expansion.
+/// Returns `None` as soon as it returns `false`.
+fn meet_with<T,M,F1,F2,L1,L2,G>(from: &mut Front<T, M>, to: &mut Front<T, M>, mut links_from: F1, mut links_to: F2, mut proceed: G) -> Option<(usize, Option<Side>)>
+where
+    T: Ord + Clone,
+    M: Visited<T>,
+    F1: FnMut(&T) -> L1,
+    F2: FnMut(&T) -> L2,
+    L1: IntoIterator<Item=T>,
+    L2: IntoIterator<Item=T>,
+    G: FnMut(&Stats) -> bool,
+{
+    let mut tmp_edge = vec![];
+    let mut expanded = 0;
+
+    while merge(&from.edge, &to.edge).is_none() {
+        if from.edge.is_empty() {
+            return Some((expanded, Some(Side::Forward)))
+        }
+        if to.edge.is_empty() {
+            return Some((expanded, Some(Side::Backward)))
+        }
+
+        if from.len() <= to.len() {
+            expanded += from.expand(&mut links_from, &mut tmp_edge);
+        } else {
+            expanded += to.expand(&mut links_to, &mut tmp_edge);
+        }
+
+        let stats = Stats { expanded, forward: from.len(), backward: to.len(), exhausted: None, cached: false, pruned: false };
+        if !proceed(&stats) { return None }
+    }
+
+    Some((expanded, None))
+}
+
 /// The Bidirectional Dijkstra algorithm.
 /// 
 /// Finds the shortest path through a directed, unweighted graph between
@@ -108,39 +531,439 @@ impl <T: Ord + Copy> Front<T> {
 /// 
 /// The two closures must return iterators to all the outgoing,
 /// respectively incoming links for a given node.
-pub fn bidi_dijkstra<T,F1,F2,L1,L2>(start: T, goal: T, mut links_from: F1, mut links_to: F2) -> Option<Vec<T>>
+pub fn bidi_dijkstra<T,F1,F2,L1,L2>(start: T, goal: T, links_from: F1, links_to: F2) -> Option<Vec<T>>
+where
+    T: Ord + Clone,
+    F1: FnMut(&T) -> L1,
+    F2: FnMut(&T) -> L2,
+    L1: IntoIterator<Item=T>,
+    L2: IntoIterator<Item=T>,
+{
+    bidi_dijkstra_with_stats(start, goal, links_from, links_to).0
+}
+
+/// Same as `bidi_dijkstra`, but also reports how much of the graph was explored.
+pub fn bidi_dijkstra_with_stats<T,F1,F2,L1,L2>(start: T, goal: T, mut links_from: F1, mut links_to: F2) -> (Option<Vec<T>>, Stats)
 where
-    T: Ord + Copy + std::fmt::Debug,
+    T: Ord + Clone,
     F1: FnMut(&T) -> L1,
     F2: FnMut(&T) -> L2,
     L1: IntoIterator<Item=T>,
     L2: IntoIterator<Item=T>,
 {
 
+    search(Front::new(start), Front::new(goal), &mut links_from, &mut links_to)
+}
+
+/// Same as `bidi_dijkstra_with_stats`, but stops early with `Err(Cancelled)` once
+/// `control` is cancelled. `progress` is called with the statistics so far after
+/// every level of one of the sides is explored.
+pub fn bidi_dijkstra_cancellable<T,F1,F2,L1,L2,P>(start: T, goal: T, mut links_from: F1, mut links_to: F2, control: &Control, mut progress: P) -> Result<(Option<Vec<T>>, Stats), Cancelled>
+where
+    T: Ord + Clone,
+    F1: FnMut(&T) -> L1,
+    F2: FnMut(&T) -> L2,
+    L1: IntoIterator<Item=T>,
+    L2: IntoIterator<Item=T>,
+    P: FnMut(&Stats),
+{
     let mut from = Front::new(start);
     let mut to = Front::new(goal);
+    // Levels can be huge: once cancelled, the remaining nodes are skipped without fetching their links
+    let links_from = |n: &T| (!control.is_cancelled()).then(|| links_from(n)).into_iter().flatten();
+    let links_to = |n: &T| (!control.is_cancelled()).then(|| links_to(n)).into_iter().flatten();
 
-    let mut tmp_edge = vec![];
+    let (expanded, exhausted) = meet_with(&mut from, &mut to, links_from, links_to, |stats| {
+        progress(stats);
+        !control.is_cancelled()
+    }).ok_or(Cancelled)?;
+    control.check()?;
+    let path = check_collision(&mut from, &mut to);
+
+    Ok((path, Stats { expanded, forward: from.len(), backward: to.len(), exhausted, cached: false, pruned: false }))
+}
+
+/// Same as `bidi_dijkstra_with_stats`, for a path to the nearest of several goals.
+/// The path ends at the goal that was reached.
+pub fn bidi_dijkstra_to_any<T,I,F1,F2,L1,L2>(start: T, goals: I, mut links_from: F1, mut links_to: F2) -> (Option<Vec<T>>, Stats)
+where
+    T: Ord + Clone,
+    I: IntoIterator<Item=T>,
+    F1: FnMut(&T) -> L1,
+    F2: FnMut(&T) -> L2,
+    L1: IntoIterator<Item=T>,
+    L2: IntoIterator<Item=T>,
+{
+    search(Front::new(start), Front::with_roots(goals), &mut links_from, &mut links_to)
+}
+
+/// Same as `bidi_dijkstra_with_stats`, for graphs whose nodes are numbered
+/// densely in `0..n`. Uses much less memory on searches reaching a large part
+/// of the graph.
+pub fn bidi_dijkstra_dense<F1,F2,L1,L2>(start: u32, goal: u32, n: u32, mut links_from: F1, mut links_to: F2) -> (Option<Vec<u32>>, Stats)
+where
+    F1: FnMut(&u32) -> L1,
+    F2: FnMut(&u32) -> L2,
+    L1: IntoIterator<Item=u32>,
+    L2: IntoIterator<Item=u32>,
+{
+    search(Front::with_map(start, DenseMap::new(n)), Front::with_map(goal, DenseMap::new(n)), &mut links_from, &mut links_to)
+}
+
+/// Same as `bidi_dijkstra_with_stats`, for `u32` nodes, keeping at most `threshold`
+/// reached nodes of each side in memory. See [`SpillMap`].
+pub fn bidi_dijkstra_spilling<F1,F2,L1,L2>(start: u32, goal: u32, threshold: usize, mut links_from: F1, mut links_to: F2) -> (Option<Vec<u32>>, Stats)
+where
+    F1: FnMut(&u32) -> L1,
+    F2: FnMut(&u32) -> L2,
+    L1: IntoIterator<Item=u32>,
+    L2: IntoIterator<Item=u32>,
+{
+    search(Front::with_map(start, SpillMap::new(threshold)), Front::with_map(goal, SpillMap::new(threshold)), &mut links_from, &mut links_to)
+}
+
+/// Unidirectional Dijkstra, for graphs where links can only be listed in one direction.
+/// 
+/// With `Side::Forward`, `links` must return the outgoing links of a node, and the
+/// search grows from the start. With `Side::Backward`, it must return the incoming
+/// links, and the search grows from the goal. This expands many more nodes than
+/// the bidirectional search.
+pub fn dijkstra_with_stats<T,F,L>(start: T, goal: T, side: Side, mut links: F) -> (Option<Vec<T>>, Stats)
+where
+    T: Ord + Clone,
+    F: FnMut(&T) -> L,
+    L: IntoIterator<Item=T>,
+{
+    let (root, target) = match side {
+        Side::Forward => (start, goal),
+        Side::Backward => (goal, start),
+    };
+    let mut front = Front::new(root);
+    let mut tmp = vec![];
+    let mut expanded = 0;
+    let mut exhausted = None;
+
+    while !front.contains(&target) {
+        if front.edge.is_empty() {
+            exhausted = Some(side);
+            break
+        }
+        expanded += front.expand(&mut links, &mut tmp);
+    }
+
+    let mut path = front.contains(&target).then(|| front.path_to(&target));
+    let mut stats = Stats { expanded, exhausted, ..Stats::default() };
+    match side {
+        Side::Forward => {
+            stats.forward = front.len();
+            if let Some(path) = &mut path { path.reverse() }
+        }
+        Side::Backward => stats.backward = front.len(),
+    }
+    (path, stats)
+}
+
+fn search<T,M,F1,F2,L1,L2>(mut from: Front<T, M>, mut to: Front<T, M>, links_from: F1, links_to: F2) -> (Option<Vec<T>>, Stats)
+where
+    T: Ord + Clone,
+    M: Visited<T>,
+    F1: FnMut(&T) -> L1,
+    F2: FnMut(&T) -> L2,
+    L1: IntoIterator<Item=T>,
+    L2: IntoIterator<Item=T>,
+{
+    let (expanded, exhausted) = meet(&mut from, &mut to, links_from, links_to);
+    let path = check_collision(&mut from, &mut to);
+
+    (path, Stats { expanded, forward: from.len(), backward: to.len(), exhausted, cached: false, pruned: false })
+}
+
+/// All the shortest paths between two nodes, as a predecessor DAG
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathDag<T> {
+    pub start: T,
+    pub goal: T,
+    /// Number of links on each of the shortest paths
+    pub length: usize,
+    /// For every node lying on a shortest path, its predecessors on shortest paths.
+    /// The start node has none.
+    pub preds: BTreeMap<T, Vec<T>>,
+}
+
+impl <T: Ord + Clone> PathDag<T> {
+
+    /// Number of distinct shortest paths
+    pub fn count(&self) -> u64 {
+        let mut counts: BTreeMap<&T, u64> = BTreeMap::new();
+        self.count_to(&self.goal, &mut counts)
+    }
+
+    fn count_to<'a>(&'a self, node: &'a T, counts: &mut BTreeMap<&'a T, u64>) -> u64 {
+        if let Some(&n) = counts.get(node) { return n }
+        let preds = &self.preds[node];
+        let n = if preds.is_empty() { 1 } else {
+            preds.iter().map(|p| self.count_to(p, counts)).sum()
+        };
+        counts.insert(node, n);
+        n
+    }
+
+    /// Enumerate every shortest path. The number of paths can be exponential
+    /// in their length; check `count()` first.
+    pub fn paths(&self) -> Vec<Vec<T>> {
+        let mut paths = vec![];
+        let mut suffix = vec![self.goal.clone()];
+        self.collect_paths(&mut suffix, &mut paths);
+        paths
+    }
+
+    fn collect_paths(&self, suffix: &mut Vec<T>, paths: &mut Vec<Vec<T>>) {
+        let node = suffix.last().unwrap().clone();
+        let preds = &self.preds[&node];
+        if preds.is_empty() {
+            paths.push(suffix.iter().rev().cloned().collect());
+        }
+        for p in preds {
+            suffix.push(p.clone());
+            self.collect_paths(suffix, paths);
+            suffix.pop();
+        }
+    }
+
+}
+
+/// Same as `bidi_dijkstra`, but returns every shortest path between `start` and
+/// `goal`, as a predecessor DAG.
+pub fn bidi_dijkstra_dag<T,F1,F2,L1,L2>(start: T, goal: T, mut links_from: F1, mut links_to: F2) -> Option<PathDag<T>>
+where
+    T: Ord + Clone,
+    F1: FnMut(&T) -> L1,
+    F2: FnMut(&T) -> L2,
+    L1: IntoIterator<Item=T>,
+    L2: IntoIterator<Item=T>,
+{
+    let mut from = Front::with_all_parents(start.clone());
+    let mut to = Front::with_all_parents(goal.clone());
+
+    if meet(&mut from, &mut to, &mut links_from, &mut links_to).1.is_some() {
+        return None
+    }
+
+    let meeting: Vec<T> = from.edge.iter()
+        .filter(|n| to.edge.binary_search(n).is_ok())
+        .cloned()
+        .collect();
+    let length = from.path_to(&meeting[0]).len() + to.path_to(&meeting[0]).len() - 2;
+
+    let mut preds: BTreeMap<T, Vec<T>> = BTreeMap::new();
+
+    // Start side: predecessors are the parents in the forward front
+    let mut stack = meeting.clone();
+    while let Some(n) = stack.pop() {
+        if preds.contains_key(&n) { continue }
+        let parents: Vec<T> = from.parents(&n).cloned().collect();
+        stack.extend(parents.iter().cloned());
+        preds.insert(n, parents);
+    }
+
+    // Goal side: parents in the backward front are successors
+    let mut stack = meeting;
+    let mut seen = BTreeSet::new();
+    while let Some(n) = stack.pop() {
+        if !seen.insert(n.clone()) { continue }
+        for succ in to.parents(&n) {
+            preds.entry(succ.clone()).or_default().push(n.clone());
+            stack.push(succ.clone());
+        }
+    }
+
+    Some(PathDag { start, goal, length, preds })
+}
+
+/// Breadth-first traversal from a root node.
+/// 
+/// `visit` is called on every reached node with its distance from the root, the
+/// root included at distance 0, in order of increasing distance. The traversal
+/// stops after `max_depth` levels if set, or as soon as `visit` returns `false`.
+pub fn bfs<T, F, L, V>(root: T, max_depth: Option<usize>, mut links: F, mut visit: V)
+where
+    T: Ord + Clone,
+    F: FnMut(&T) -> L,
+    L: IntoIterator<Item = T>,
+    V: FnMut(&T, usize) -> bool,
+{
+    let mut front = Front::new(root);
+    let mut tmp = vec![];
+    let mut depth = 0;
 
     loop {
+        for node in front.edge() {
+            if !visit(node, depth) { return }
+        }
+        if front.edge.is_empty() || max_depth.is_some_and(|d| depth >= d) { return }
 
-        if let Some(path) = check_collision(&mut from, &mut to) {
-            break Some(path);
+        front.expand(&mut links, &mut tmp);
+        depth += 1;
+    }
+}
+
+/// The way from a node to the nearest of a set of targets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hop<T> {
+    /// The next node on a shortest path to the target, or the target itself
+    pub next: T,
+    pub target: T,
+    pub distance: usize,
+}
+
+/// Next-hop table toward a set of targets, for every node from which one can be reached.
+/// 
+/// The reverse links are traversed breadth-first from all the targets at once, so every
+/// node gets the target it is closest to, ties going to the earliest one in `targets`.
+/// Nodes further than `max_depth` links from all targets are left out, if set.
+/// Following `next` from any node of the table leads to its target in `distance` hops.
+pub fn next_hops<T, F, L>(targets: &[T], max_depth: Option<usize>, mut links_to: F) -> BTreeMap<T, Hop<T>>
+where
+    T: Ord + Clone,
+    F: FnMut(&T) -> L,
+    L: IntoIterator<Item = T>,
+{
+    let mut hops = BTreeMap::new();
+    let mut edge = vec![];
+    for target in targets {
+        if let Entry::Vacant(e) = hops.entry(target.clone()) {
+            e.insert(Hop { next: target.clone(), target: target.clone(), distance: 0 });
+            edge.push(target.clone());
         }
+    }
 
-        if from.len() <= to.len() {
-            from.expand(&mut links_from, &mut tmp_edge);
-        } else {
-            to.expand(&mut links_to, &mut tmp_edge);
+    let mut distance = 0;
+    while !edge.is_empty() && max_depth.is_none_or(|d| distance < d) {
+        distance += 1;
+        let mut next_edge = vec![];
+        for node in edge {
+            let target = hops[&node].target.clone();
+            for pred in links_to(&node) {
+                if let Entry::Vacant(e) = hops.entry(pred.clone()) {
+                    e.insert(Hop { next: node.clone(), target: target.clone(), distance });
+                    next_edge.push(pred);
+                }
+            }
+        }
+        edge = next_edge;
+    }
+
+    hops
+}
+
+/// Connect a set of terminal nodes with a small sub-network, using a greedy
+/// Steiner tree heuristic.
+/// 
+/// Starting from the first terminal, the pending terminal closest to an already
+/// connected one is attached through the shortest path between them, in either
+/// direction, until all terminals are connected. `shortest` must return the
+/// shortest path between two nodes, if any.
+/// 
+/// Returns the paths making up the network, or `None` if some terminal cannot
+/// be connected to the others.
+pub fn connect<T, F>(terminals: &[T], mut shortest: F) -> Option<Vec<Vec<T>>>
+where
+    T: Ord + Clone,
+    F: FnMut(&T, &T) -> Option<Vec<T>>,
+{
+    let mut cache: BTreeMap<(usize, usize), Option<Vec<T>>> = BTreeMap::new();
+    let mut connected = vec![0];
+    let mut pending: Vec<usize> = (1..terminals.len()).collect();
+    let mut network = vec![];
+
+    while !pending.is_empty() {
+        let mut best: Option<(usize, Vec<T>)> = None;
+
+        for (k, &t) in pending.iter().enumerate() {
+            for &c in &connected {
+                for (a, b) in [(c, t), (t, c)] {
+                    let path = cache.entry((a, b))
+                        .or_insert_with(|| shortest(&terminals[a], &terminals[b]));
+                    if let Some(path) = path {
+                        if best.as_ref().is_none_or(|(_, p)| path.len() < p.len()) {
+                            best = Some((k, path.clone()));
+                        }
+                    }
+                }
+            }
         }
 
+        let (k, path) = best?;
+        connected.push(pending.remove(k));
+        network.push(path);
+    }
+
+    Some(network)
+}
+
+/// Strongly connected components of a graph over the nodes `0..n`, with Tarjan's algorithm.
+/// 
+/// Returns the component of every node. Components are numbered in reverse topological
+/// order: a link between two components always goes to a lower-numbered one, so there
+/// is no path from a node to a node of a higher-numbered component.
+pub fn strong_components<F, L>(n: u32, mut links_from: F) -> Vec<u32>
+where
+    F: FnMut(u32) -> L,
+    L: IntoIterator<Item = u32>,
+{
+    const UNSEEN: u32 = u32::MAX;
+    let mut index = vec![UNSEEN; n as usize];
+    let mut low = vec![0; n as usize];
+    let mut component = vec![UNSEEN; n as usize];
+    let mut next_index = 0;
+    let mut next_component = 0;
+    // Nodes of the components being explored, and the explicit recursion stack
+    let mut open = vec![];
+    let mut calls: Vec<(u32, L::IntoIter)> = vec![];
+
+    for root in 0..n {
+        if index[root as usize] != UNSEEN { continue }
+        let mut entering = Some(root);
+
+        loop {
+            if let Some(node) = entering.take() {
+                index[node as usize] = next_index;
+                low[node as usize] = next_index;
+                next_index += 1;
+                open.push(node);
+                calls.push((node, links_from(node).into_iter()));
+            }
+            let Some((node, links)) = calls.last_mut() else { break };
+            let node = *node;
+            match links.next() {
+                Some(succ) if index[succ as usize] == UNSEEN => entering = Some(succ),
+                Some(succ) => if component[succ as usize] == UNSEEN {
+                    low[node as usize] = low[node as usize].min(index[succ as usize]);
+                },
+                None => {
+                    calls.pop();
+                    if let Some((parent, _)) = calls.last() {
+                        low[*parent as usize] = low[*parent as usize].min(low[node as usize]);
+                    }
+                    if low[node as usize] == index[node as usize] {
+                        while let Some(member) = open.pop() {
+                            component[member as usize] = next_component;
+                            if member == node { break }
+                        }
+                        next_component += 1;
+                    }
+                }
+            }
+        }
     }
 
+    component
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::job::Job;
 
     #[test]
     fn sample_merge() {
@@ -166,4 +989,281 @@ mod test {
 
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn owned_nodes() {
+        let edges = [("a", "b"), ("b", "c"), ("a", "d"), ("d", "e"), ("e", "c")];
+        let links_from = |n: &String| edges.iter().filter(|(a,_)| a == n).map(|(_,b)| b.to_string()).collect::<Vec<_>>();
+        let links_to = |n: &String| edges.iter().filter(|(_,b)| b == n).map(|(a,_)| a.to_string()).collect::<Vec<_>>();
+
+        let path = bidi_dijkstra("a".to_owned(), "c".to_owned(), links_from, links_to);
+        assert_eq!(path, Some(vec!["a".to_owned(), "b".to_owned(), "c".to_owned()]));
+    }
+
+    #[test]
+    fn sample_dag() {
+        // Two routes from 1 to 4, three from 4 to 7, and a longer detour
+        let edges = [(1,2), (1,3), (2,4), (3,4), (4,5), (4,6), (4,8), (5,7), (6,7), (8,7), (1,9), (9,10), (10,11), (11,12), (12,7)];
+        let links_from = |f: &i32| { let f = *f; edges.iter().filter(move |&(a,_)| *a == f).map(|(_,b)| *b) };
+        let links_to = |t: &i32| { let t = *t; edges.iter().filter(move |&(_,b)| *b == t).map(|(a,_)| *a) };
+
+        let dag = bidi_dijkstra_dag(1, 7, links_from, links_to).unwrap();
+        assert_eq!(dag.length, 4);
+        assert_eq!(dag.count(), 6);
+
+        let mut paths = dag.paths();
+        paths.sort();
+        assert_eq!(paths.len(), 6);
+        assert_eq!(paths[0], vec![1,2,4,5,7]);
+        assert!(!dag.preds.contains_key(&9));
+    }
+
+    #[test]
+    fn cancellable_search() {
+        let edges = [(0,1), (0,2), (1,2), (2,3), (3,4), (4,0), (4,1), (5,0)];
+        let links_from = |f: &u32| { let f = *f; edges.iter().filter(move |&(a,_)| *a == f).map(|(_,b)| *b) };
+        let links_to = |t: &u32| { let t = *t; edges.iter().filter(move |&(_,b)| *b == t).map(|(a,_)| *a) };
+
+        let control = Control::new();
+        let mut updates = 0;
+        let result = bidi_dijkstra_cancellable(5, 3, links_from, links_to, &control, |_| updates += 1);
+        assert_eq!(result, Ok(bidi_dijkstra_with_stats(5, 3, links_from, links_to)));
+        assert!(updates > 0);
+
+        // Cancelled from the progress callback, as a GUI thread would from elsewhere
+        let result = bidi_dijkstra_cancellable(5, 3, links_from, links_to, &control, |_| control.cancel());
+        assert_eq!(result, Err(Cancelled));
+
+        // A job searching two chains that never meet runs until cancelled
+        let job = Job::spawn(|control, progress| {
+            bidi_dijkstra_cancellable(0u32, u32::MAX, |n: &u32| n.checked_add(2), |n: &u32| n.checked_sub(2), control, |s| progress(s.expanded))
+        });
+        while job.progress().next().is_none() { std::thread::yield_now() }
+        job.cancel();
+        assert_eq!(job.wait(), Err(Cancelled));
+    }
+
+    #[test]
+    fn search_to_any() {
+        let edges = [(0,1), (0,2), (1,2), (2,3), (3,4), (4,0), (4,1), (5,0)];
+        let links_from = |f: &u32| { let f = *f; edges.iter().filter(move |&(a,_)| *a == f).map(|(_,b)| *b) };
+        let links_to = |t: &u32| { let t = *t; edges.iter().filter(move |&(_,b)| *b == t).map(|(a,_)| *a) };
+
+        assert_eq!(bidi_dijkstra_to_any(5, [4, 2], links_from, links_to).0, Some(vec![5, 0, 2]));
+        assert_eq!(bidi_dijkstra_to_any(2, [4, 2], links_from, links_to).0, Some(vec![2]));
+        assert_eq!(bidi_dijkstra_to_any(0, [5], links_from, links_to).0, None);
+        assert_eq!(bidi_dijkstra_to_any(0, [], links_from, links_to).1.exhausted, Some(Side::Backward));
+    }
+
+    #[test]
+    fn sample_next_hops() {
+        let edges = [(1,2), (1,3), (2,3), (3,4), (4,5), (5,1), (5,2), (6,1)];
+        let links_to = |t: &i32| { let t = *t; edges.iter().filter(move |&(_,b)| *b == t).map(|(a,_)| *a) };
+
+        let hops = next_hops(&[3, 5], None, links_to);
+        let hop = |next, target, distance| Hop { next, target, distance };
+        assert_eq!(hops[&3], hop(3, 3, 0));
+        assert_eq!(hops[&4], hop(5, 5, 1));
+        assert_eq!(hops[&1], hop(3, 3, 1));
+        assert_eq!(hops[&6], hop(1, 3, 2));
+        assert_eq!(hops.len(), 6);
+
+        // Every table entry leads to its target in `distance` hops
+        for (&node, h) in &hops {
+            let mut n = node;
+            for _ in 0..h.distance { n = hops[&n].next }
+            assert_eq!(n, h.target);
+        }
+
+        assert_eq!(next_hops(&[3, 5], Some(1), links_to).len(), 5);
+    }
+
+    #[test]
+    fn sample_bfs() {
+        let edges = [(1,2), (1,3), (2,3), (3,4), (4,5), (5,1), (5,2), (6,1)];
+        let links = |f: &i32| { let f = *f; edges.iter().filter(move |&(a,_)| *a == f).map(|(_,b)| *b) };
+
+        let mut reached = vec![];
+        bfs(1, None, links, |&n, d| { reached.push((n, d)); true });
+        assert_eq!(reached, [(1,0), (2,1), (3,1), (4,2), (5,3)]);
+
+        let mut reached = vec![];
+        bfs(1, Some(1), links, |&n, _| { reached.push(n); true });
+        assert_eq!(reached, [1, 2, 3]);
+
+        let mut reached = vec![];
+        bfs(1, None, links, |&n, _| { reached.push(n); n != 3 });
+        assert_eq!(reached, [1, 2, 3]);
+    }
+
+    #[test]
+    fn sample_connect() {
+        let edges = [(1,2), (2,3), (3,4), (2,5), (5,6), (6,1), (7,6)];
+        let network = connect(&[1, 4, 6, 7], |&a, &b| try_path(&edges, a, b)).unwrap();
+        assert_eq!(network, vec![vec![6,1], vec![7,6], vec![1,2,3,4]]);
+
+        assert_eq!(connect(&[1, 8], |&a, &b| try_path(&edges, a, b)), None);
+        assert_eq!(connect(&[1], |&a, &b| try_path(&edges, a, b)), Some(vec![]));
+    }
+
+    #[test]
+    fn sample_stats() {
+        let edges = [(1,2), (1,3), (2,3), (3,4), (4,5), (5,1), (5,2)];
+        let links_from = |f: &i32| { let f = *f; edges.iter().filter(move |&(a,_)| *a == f).map(|(_,b)| *b) };
+        let links_to = |t: &i32| { let t = *t; edges.iter().filter(move |&(_,b)| *b == t).map(|(a,_)| *a) };
+
+        let (path, stats) = bidi_dijkstra_with_stats(1, 5, links_from, links_to);
+        assert_eq!(path, Some(vec![1,3,4,5]));
+        assert_eq!(stats, Stats { expanded: 3, forward: 3, backward: 3, exhausted: None, cached: false, pruned: false });
+    }
+
+    #[test]
+    fn dense_search() {
+        let edges = [(0,1), (0,2), (1,2), (2,3), (3,4), (4,0), (4,1), (5,0)];
+        let links_from = |f: &u32| { let f = *f; edges.iter().filter(move |&(a,_)| *a == f).map(|(_,b)| *b) };
+        let links_to = |t: &u32| { let t = *t; edges.iter().filter(move |&(_,b)| *b == t).map(|(a,_)| *a) };
+
+        for goal in 0..6 {
+            assert_eq!(bidi_dijkstra_dense(0, goal, 6, links_from, links_to),
+                bidi_dijkstra_with_stats(0, goal, links_from, links_to));
+        }
+
+        // The vector is only allocated once enough nodes are reached
+        let mut map = DenseMap::new(64);
+        for node in 0..4 { assert!(map.visit(node, &0).is_none()) }
+        assert!(!map.is_dense());
+        assert!(map.visit(40, &3).is_none());
+        assert!(map.is_dense());
+        assert_eq!(map.visit(2, &1), Some(&0));
+        assert_eq!((map.len(), map.parent(&40), map.parent(&2), map.parent(&63)), (5, Some(&3), Some(&0), None));
+    }
+
+    #[test]
+    fn spilling_search() {
+        let edges = [(0,1), (0,2), (1,2), (2,3), (3,4), (4,0), (4,1), (5,0)];
+        let links_from = |f: &u32| { let f = *f; edges.iter().filter(move |&(a,_)| *a == f).map(|(_,b)| *b) };
+        let links_to = |t: &u32| { let t = *t; edges.iter().filter(move |&(_,b)| *b == t).map(|(a,_)| *a) };
+
+        for goal in 0..6 {
+            assert_eq!(bidi_dijkstra_spilling(5, goal, 1, links_from, links_to),
+                bidi_dijkstra_with_stats(5, goal, links_from, links_to));
+        }
+
+        let mut map = SpillMap::new(2);
+        for (node, parent) in [(7, 7), (3, 7), (9, 3), (1, 9), (4, 9)] {
+            assert!(map.visit(node, &parent).is_none());
+        }
+        assert_eq!(map.visit(3, &1), Some(&7));
+        assert_eq!((map.len(), map.spilled(), map.runs.len()), (5, 4, 1));
+        assert_eq!([7, 3, 9, 1, 4, 2].map(|n| map.parent(&n)), [Some(&7), Some(&7), Some(&3), Some(&9), Some(&9), None]);
+
+        let mut map = SpillMap::new(3);
+        for node in 0..100 { map.visit(node, &(node / 2)); }
+        assert!(map.runs.windows(2).all(|w| w[0].nodes.len() >= 2 * w[1].nodes.len()));
+        assert!((0..100).all(|node| map.parent(&node) == Some(&(node / 2))));
+    }
+
+    #[test]
+    fn sample_components() {
+        // 0 -> {1, 2} form a cycle, which links to the cycle {3, 4}, and 5 is isolated
+        let edges = [(0,1), (1,2), (2,0), (2,3), (3,4), (4,3)];
+        let links = |f: u32| edges.iter().filter(move |&&(a,_)| a == f).map(|&(_,b)| b);
+        let components = strong_components(6, links);
+
+        assert_eq!(components[0], components[1]);
+        assert_eq!(components[0], components[2]);
+        assert_eq!(components[3], components[4]);
+        assert!(components[0] > components[3]);
+        assert_ne!(components[5], components[0]);
+        assert_ne!(components[5], components[3]);
+        for (a, b) in edges {
+            assert!(components[a as usize] >= components[b as usize]);
+        }
+    }
+
+    #[test]
+    fn one_sided_search() {
+        let edges = [(0,1), (0,2), (1,2), (2,3), (3,4), (4,0), (4,1), (5,0)];
+        let links_from = |f: &u32| { let f = *f; edges.iter().filter(move |&(a,_)| *a == f).map(|(_,b)| *b) };
+        let links_to = |t: &u32| { let t = *t; edges.iter().filter(move |&(_,b)| *b == t).map(|(a,_)| *a) };
+
+        for goal in 0..6 {
+            let expected = bidi_dijkstra(0, goal, links_from, links_to).map(|p| p.len());
+            let (forward, _) = dijkstra_with_stats(0, goal, Side::Forward, links_from);
+            let (backward, _) = dijkstra_with_stats(0, goal, Side::Backward, links_to);
+            assert_eq!(forward.as_ref().map(Vec::len), expected);
+            assert_eq!(backward.as_ref().map(Vec::len), expected);
+            for path in forward.iter().chain(&backward) {
+                assert_eq!((path[0], path[path.len() - 1]), (0, goal));
+            }
+        }
+
+        let (path, stats) = dijkstra_with_stats(1, 5, Side::Forward, links_from);
+        assert_eq!(path, None);
+        assert_eq!(stats.exhausted, Some(Side::Forward));
+        assert_eq!(stats.forward, 5);
+    }
+
+    #[test]
+    fn unreachable() {
+        let edges = [(1,2), (2,3), (4,3), (3,1)];
+        assert_eq!(try_path(&edges, 1, 4), None);
+
+        let links_from = |f: &i32| { let f = *f; edges.iter().filter(move |&(a,_)| *a == f).map(|(_,b)| *b) };
+        let links_to = |t: &i32| { let t = *t; edges.iter().filter(move |&(_,b)| *b == t).map(|(a,_)| *a) };
+        let (path, stats) = bidi_dijkstra_with_stats(1, 4, links_from, links_to);
+        assert_eq!(path, None);
+        assert_eq!(stats.exhausted, Some(Side::Backward));
+        assert_eq!(stats.backward, 1);
+    }
+
+    mod prop {
+        use super::try_path;
+        use proptest::prelude::*;
+
+        fn graph() -> impl Strategy<Value = (Vec<(i32, i32)>, i32, i32)> {
+            (prop::collection::vec((0..16, 0..16), 0..48), 0..16, 0..16)
+        }
+
+        proptest! {
+            /// Paths must be as short as the ones found by the `pathfinding` crate, and made of actual links.
+            #[test]
+            fn same_length_as_pathfinding((edges, start, goal) in graph()) {
+                let successors = |n: &i32| edges.iter().filter(|(a,_)| a == n).map(|&(_,b)| (b, 1)).collect::<Vec<_>>();
+                let Some((_, cost)) = pathfinding::directed::dijkstra::dijkstra(&start, successors, |n| *n == goal) else {
+                    return Ok(())
+                };
+
+                let path = try_path(&edges, start, goal).expect("no path found");
+                prop_assert_eq!(path.len() - 1, cost);
+                prop_assert_eq!(path.first(), Some(&start));
+                prop_assert_eq!(path.last(), Some(&goal));
+                for hop in path.windows(2) {
+                    prop_assert!(edges.contains(&(hop[0], hop[1])));
+                }
+            }
+
+            /// Every path of the DAG must be a shortest path
+            #[test]
+            fn dag_paths_are_shortest((edges, start, goal) in graph()) {
+                let successors = |n: &i32| edges.iter().filter(|(a,_)| a == n).map(|&(_,b)| b).collect::<Vec<_>>();
+                let Some(length) = pathfinding::directed::bfs::bfs(&start, successors, |n| *n == goal).map(|p| p.len() - 1) else {
+                    return Ok(())
+                };
+                let links_from = |n: &i32| edges.iter().filter(|(a,_)| a == n).map(|&(_,b)| b).collect::<Vec<_>>();
+                let links_to = |n: &i32| edges.iter().filter(|(_,b)| b == n).map(|&(a,_)| a).collect::<Vec<_>>();
+
+                let dag = super::super::bidi_dijkstra_dag(start, goal, links_from, links_to).unwrap();
+                prop_assert_eq!(dag.length, length);
+                let paths = dag.paths();
+                prop_assert_eq!(paths.len() as u64, dag.count());
+                for path in paths {
+                    prop_assert_eq!(path.len() - 1, length);
+                    for hop in path.windows(2) {
+                        prop_assert!(edges.contains(&(hop[0], hop[1])));
+                    }
+                }
+            }
+        }
+    }
+
+}
@@ -0,0 +1,121 @@
+//! PostgreSQL storage of the link graph, to query it alongside other data
+
+use std::{cell::RefCell, collections::HashMap};
+
+use postgres::{Client, NoTls};
+
+use crate::{Id, backend::Backend};
+
+/// Number of rows buffered before being inserted in one statement
+const BATCH: usize = 10_000;
+
+pub struct PgDb {
+    client: RefCell<Client>,
+    pages: (Vec<i64>, Vec<String>, Vec<i32>),
+    redirects: (Vec<i64>, Vec<String>),
+    links: (Vec<i64>, Vec<i64>),
+}
+
+impl PgDb {
+
+    /// Connect to a database, such as `postgresql://user@localhost/wikistra`,
+    /// and create the tables if needed
+    pub fn connect(uri: &str) -> Result<Self, postgres::Error> {
+        let mut client = Client::connect(uri, NoTls)?;
+        client.batch_execute("
+            CREATE TABLE IF NOT EXISTS page (id bigint primary key, title text unique not null, flags integer not null default 0);
+            CREATE TABLE IF NOT EXISTS redirect (id bigint primary key, title text not null);
+            CREATE TABLE IF NOT EXISTS link (\"from\" bigint not null, \"to\" bigint not null, primary key (\"to\", \"from\"));
+            CREATE INDEX IF NOT EXISTS link_reverse ON link(\"from\");
+        ")?;
+        Ok(PgDb { client: RefCell::new(client), pages: Default::default(), redirects: Default::default(), links: Default::default() })
+    }
+
+    fn query_id(&self, query: &str, param: &(dyn postgres::types::ToSql + Sync)) -> Option<Id> {
+        let row = self.client.borrow_mut().query_opt(query, &[param]).ok()??;
+        row.get::<_, i64>(0).try_into().ok()
+    }
+
+    fn query_ids(&self, query: &str, id: Id) -> Vec<Id> {
+        self.client.borrow_mut().query(query, &[&(id as i64)])
+            .map(|rows| rows.iter().filter_map(|row| row.get::<_, i64>(0).try_into().ok()).collect())
+            .unwrap_or_default()
+    }
+
+}
+
+impl Backend for PgDb {
+    type Error = postgres::Error;
+
+    fn add(&mut self, id: Id, title: String, flags: u32) -> Result<(), Self::Error> {
+        self.pages.0.push(id as i64);
+        self.pages.1.push(title);
+        self.pages.2.push(flags as i32);
+        if self.pages.0.len() >= BATCH { self.flush()? }
+        Ok(())
+    }
+
+    fn add_redirect(&mut self, from: Id, title: &str) -> Result<(), Self::Error> {
+        self.redirects.0.push(from as i64);
+        self.redirects.1.push(title.to_owned());
+        if self.redirects.0.len() >= BATCH { self.flush()? }
+        Ok(())
+    }
+
+    fn add_link(&mut self, (from, to): (Id, Id)) -> Result<(), Self::Error> {
+        self.links.0.push(from as i64);
+        self.links.1.push(to as i64);
+        if self.links.0.len() >= BATCH { self.flush()? }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        let client = self.client.get_mut();
+        let (ids, titles, flags) = std::mem::take(&mut self.pages);
+        if !ids.is_empty() {
+            client.execute("INSERT INTO page SELECT * FROM unnest($1::bigint[], $2::text[], $3::integer[]) ON CONFLICT DO NOTHING",
+                &[&ids, &titles, &flags])?;
+        }
+        let (ids, titles) = std::mem::take(&mut self.redirects);
+        if !ids.is_empty() {
+            client.execute("INSERT INTO redirect SELECT * FROM unnest($1::bigint[], $2::text[]) ON CONFLICT DO NOTHING",
+                &[&ids, &titles])?;
+        }
+        let (from, to) = std::mem::take(&mut self.links);
+        if !from.is_empty() {
+            client.execute("INSERT INTO link SELECT * FROM unnest($1::bigint[], $2::bigint[]) ON CONFLICT DO NOTHING",
+                &[&from, &to])?;
+        }
+        Ok(())
+    }
+
+    fn index(&self, title: &str) -> Option<Id> {
+        self.query_id("SELECT id FROM page WHERE title = $1", &title)
+    }
+
+    fn index_all(&self, titles: &[&str]) -> Vec<Option<Id>> {
+        let rows = self.client.borrow_mut().query("SELECT title, id FROM page WHERE title = ANY($1)", &[&titles]);
+        let ids: HashMap<String, i64> = rows.map(|rows| rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+            .unwrap_or_default();
+        titles.iter().map(|title| ids.get(*title).and_then(|&id| id.try_into().ok())).collect()
+    }
+
+    fn resolve(&self, title: &str) -> Option<Id> {
+        let id = self.index(title)?;
+        let target = self.query_id("SELECT page.id FROM redirect JOIN page ON page.title = redirect.title WHERE redirect.id = $1", &(id as i64));
+        Some(target.unwrap_or(id))
+    }
+
+    fn lookup(&self, id: Id) -> Option<String> {
+        let row = self.client.borrow_mut().query_opt("SELECT title FROM page WHERE id = $1", &[&(id as i64)]).ok()??;
+        Some(row.get(0))
+    }
+
+    fn links_from(&self, from: Id) -> Vec<Id> {
+        self.query_ids("SELECT \"to\" FROM link WHERE \"from\" = $1", from)
+    }
+
+    fn links_to(&self, to: Id) -> Vec<Id> {
+        self.query_ids("SELECT \"from\" FROM link WHERE \"to\" = $1", to)
+    }
+}
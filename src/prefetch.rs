@@ -0,0 +1,166 @@
+//! Reading ahead on a separate thread, so that dumps are decompressed while the
+//! previous chunks are parsed
+//!
+//! The fill of the queue between both threads tells which one is the bottleneck:
+//! decompression waits on a full queue when parsing and inserting are slower, and
+//! parsing waits on an empty queue when decompression is slower.
+
+use std::{io::{self, BufRead, Read}, sync::{Arc, atomic::{AtomicU64, Ordering}, mpsc::{self, Receiver, TryRecvError, TrySendError}}, thread, time::Instant};
+
+use serde::Serialize;
+
+/// Size of the chunks read ahead
+const CHUNK: usize = 256 * 1024;
+/// Number of chunks read ahead of the consumer
+pub const DEPTH: usize = 16;
+
+/// Counters of the queue between the reading thread and the consumer
+#[derive(Debug, Default)]
+pub struct Metrics {
+    sent: AtomicU64,
+    received: AtomicU64,
+    /// Sum of the queue depths seen by the consumer, for the mean
+    depth_sum: AtomicU64,
+    max_depth: AtomicU64,
+    producer_stall_ns: AtomicU64,
+    consumer_stall_ns: AtomicU64,
+}
+
+/// Summary of the metrics of a reader, for the index reports
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct PipelineReport {
+    pub chunks: u64,
+    pub queue_capacity: usize,
+    pub mean_queue_depth: f64,
+    pub max_queue_depth: u64,
+    /// Time the decompression waited on a full queue
+    pub decompress_stall_secs: f64,
+    /// Time the parsing waited on an empty queue
+    pub parse_stall_secs: f64,
+}
+
+impl Metrics {
+    /// Number of chunks waiting in the queue
+    pub fn depth(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed).saturating_sub(self.received.load(Ordering::Relaxed))
+    }
+
+    pub fn report(&self) -> PipelineReport {
+        let chunks = self.received.load(Ordering::Relaxed);
+        PipelineReport {
+            chunks,
+            queue_capacity: DEPTH,
+            mean_queue_depth: if chunks > 0 { self.depth_sum.load(Ordering::Relaxed) as f64 / chunks as f64 } else { 0.0 },
+            max_queue_depth: self.max_depth.load(Ordering::Relaxed),
+            decompress_stall_secs: self.producer_stall_ns.load(Ordering::Relaxed) as f64 / 1e9,
+            parse_stall_secs: self.consumer_stall_ns.load(Ordering::Relaxed) as f64 / 1e9,
+        }
+    }
+}
+
+pub struct Prefetch {
+    receiver: Receiver<io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    pos: usize,
+    metrics: Arc<Metrics>,
+}
+
+impl Prefetch {
+    pub fn new<R: Read + Send + 'static>(mut source: R) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(DEPTH);
+        let metrics = Arc::new(Metrics::default());
+        let producer = metrics.clone();
+        thread::spawn(move || loop {
+            let mut chunk = vec![0; CHUNK];
+            let result = match source.read(&mut chunk) {
+                Ok(0) => return,
+                Ok(n) => { chunk.truncate(n); Ok(chunk) }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => Err(e),
+            };
+            let failed = result.is_err();
+            let sent = match sender.try_send(result) {
+                Ok(()) => true,
+                Err(TrySendError::Full(result)) => {
+                    let stalled = Instant::now();
+                    let sent = sender.send(result).is_ok();
+                    producer.producer_stall_ns.fetch_add(stalled.elapsed().as_nanos() as u64, Ordering::Relaxed);
+                    sent
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            };
+            if !sent || failed { return }
+            producer.sent.fetch_add(1, Ordering::Relaxed);
+        });
+        Prefetch { receiver, chunk: vec![], pos: 0, metrics }
+    }
+
+    /// The metrics of the queue, updated as the reader is used
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    fn recv(&mut self) -> io::Result<Vec<u8>> {
+        let depth = self.metrics.depth();
+        self.metrics.depth_sum.fetch_add(depth, Ordering::Relaxed);
+        self.metrics.max_depth.fetch_max(depth, Ordering::Relaxed);
+        let received = match self.receiver.try_recv() {
+            Err(TryRecvError::Empty) => {
+                let stalled = Instant::now();
+                let received = self.receiver.recv();
+                self.metrics.consumer_stall_ns.fetch_add(stalled.elapsed().as_nanos() as u64, Ordering::Relaxed);
+                received.ok()
+            }
+            received => received.ok(),
+        };
+        // A closed channel is the end of the source
+        let Some(chunk) = received else { return Ok(vec![]) };
+        self.metrics.received.fetch_add(1, Ordering::Relaxed);
+        chunk
+    }
+}
+
+impl Read for Prefetch {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl BufRead for Prefetch {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.chunk.len() {
+            self.chunk = self.recv()?;
+            self.pos = 0;
+        }
+        Ok(&self.chunk[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.chunk.len());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn same_contents() {
+        let data: Vec<u8> = (0..CHUNK * 3 + 17).map(|i| (i % 251) as u8).collect();
+        let mut read = vec![];
+        let mut reader = Prefetch::new(io::Cursor::new(data.clone()));
+        reader.read_to_end(&mut read).unwrap();
+        assert_eq!(read, data);
+
+        let report = reader.metrics().report();
+        assert_eq!(report.chunks, 4);
+        assert!(report.max_queue_depth <= DEPTH as u64);
+
+        let lines: Vec<String> = Prefetch::new(&b"a\nb\n"[..]).lines().collect::<Result<_, _>>().unwrap();
+        assert_eq!(lines, ["a", "b"]);
+    }
+}
@@ -0,0 +1,212 @@
+//! Column statistics of dump tables, to get to know a table before indexing it
+//!
+//! Statistics are computed in one pass and bounded memory: the number of distinct
+//! values is a HyperLogLog estimate, and the most frequent values are tracked with
+//! the space-saving algorithm, so their counts are upper bounds.
+
+use std::{cmp::Ordering, collections::{BTreeSet, HashMap, hash_map::DefaultHasher}, hash::{Hash, Hasher}};
+
+use crate::sql::Value;
+
+/// Number of bits of the hash selecting a HyperLogLog register
+const PRECISION: u32 = 12;
+/// Number of candidate frequent values tracked per column
+const CANDIDATES: usize = 64;
+
+/// Statistics of every column of a table
+#[derive(Default)]
+pub struct Profile {
+    pub rows: u64,
+    pub columns: Vec<Column>,
+}
+
+pub struct Column {
+    pub nulls: u64,
+    /// Types of the non-null values
+    pub kinds: BTreeSet<&'static str>,
+    pub min: Option<Value>,
+    pub max: Option<Value>,
+    distinct: HyperLogLog,
+    top: HashMap<String, u64>,
+}
+
+impl Profile {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, row: &[Value]) {
+        while self.columns.len() < row.len() {
+            // Columns missing from the earlier rows were null there
+            self.columns.push(Column::new(self.rows));
+        }
+        for (column, value) in self.columns.iter_mut().zip(row) {
+            column.add(value);
+        }
+        self.rows += 1;
+    }
+}
+
+impl Column {
+
+    fn new(nulls: u64) -> Self {
+        Column { nulls, kinds: BTreeSet::new(), min: None, max: None, distinct: HyperLogLog::new(), top: HashMap::new() }
+    }
+
+    fn add(&mut self, value: &Value) {
+        let kind = match value {
+            Value::Null => { self.nulls += 1; return }
+            Value::String(_) => "string",
+            Value::Bytes(_) => "bytes",
+            Value::Integer(_) => "integer",
+            Value::Float(_) => "float",
+        };
+        self.kinds.insert(kind);
+        self.distinct.add(value);
+
+        if self.min.as_ref().is_none_or(|min| compare(value, min) == Ordering::Less) {
+            self.min = Some(value.clone());
+        }
+        if self.max.as_ref().is_none_or(|max| compare(value, max) == Ordering::Greater) {
+            self.max = Some(value.clone());
+        }
+
+        let key = text(value);
+        if let Some(count) = self.top.get_mut(&key) {
+            *count += 1;
+        } else if self.top.len() < CANDIDATES {
+            self.top.insert(key, 1);
+        } else {
+            // Space-saving: the new value replaces the least frequent candidate,
+            // inheriting its count
+            let (evicted, count) = self.top.iter()
+                .min_by_key(|(_, count)| **count)
+                .map(|(k, c)| (k.clone(), *c))
+                .expect("candidates");
+            self.top.remove(&evicted);
+            self.top.insert(key, count + 1);
+        }
+    }
+
+    /// Estimated number of distinct non-null values
+    pub fn distinct(&self) -> u64 {
+        self.distinct.estimate()
+    }
+
+    /// The `n` most frequent values, with their count
+    pub fn top(&self, n: usize) -> Vec<(String, u64)> {
+        let mut top: Vec<_> = self.top.iter().map(|(k, c)| (k.clone(), *c)).collect();
+        top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        top.truncate(n);
+        top
+    }
+}
+
+/// A value as text, for display
+pub fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Bytes(b) => String::from_utf8_lossy(b).into_owned(),
+        Value::Integer(i) => i.to_string(),
+        Value::Float(f) => f.to_string(),
+        Value::Null => "NULL".to_owned(),
+    }
+}
+
+/// Order of values: numbers, then strings, then bytes
+fn compare(a: &Value, b: &Value) -> Ordering {
+    use Value::*;
+    let rank = |v: &Value| match v { Null => 0, Integer(_) | Float(_) => 1, String(_) => 2, Bytes(_) => 3 };
+    match (a, b) {
+        (Integer(a), Integer(b)) => a.cmp(b),
+        (Integer(a), Float(b)) => (*a as f64).total_cmp(b),
+        (Float(a), Integer(b)) => a.total_cmp(&(*b as f64)),
+        (Float(a), Float(b)) => a.total_cmp(b),
+        (String(a), String(b)) => a.cmp(b),
+        (Bytes(a), Bytes(b)) => a.cmp(b),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+/// Estimator of the number of distinct values of a stream
+struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+
+    fn new() -> Self {
+        HyperLogLog { registers: vec![0; 1 << PRECISION] }
+    }
+
+    fn add(&mut self, value: &Value) {
+        let mut hasher = DefaultHasher::new();
+        match value {
+            Value::String(s) => (0u8, s).hash(&mut hasher),
+            Value::Bytes(b) => (1u8, b).hash(&mut hasher),
+            Value::Integer(i) => (2u8, i).hash(&mut hasher),
+            Value::Float(f) => (3u8, f.to_bits()).hash(&mut hasher),
+            Value::Null => return,
+        }
+        let hash = hasher.finish();
+        let register = (hash >> (64 - PRECISION)) as usize;
+        let rank = ((hash << PRECISION) | (1 << (PRECISION - 1))).leading_zeros() as u8 + 1;
+        self.registers[register] = self.registers[register].max(rank);
+    }
+
+    fn estimate(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let estimate = alpha * m * m / sum;
+
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            // Linear counting is more accurate for small cardinalities
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sample_profile() {
+        let mut profile = Profile::new();
+        profile.add(&[Value::Integer(3), Value::String("Paris".into())]);
+        profile.add(&[Value::Integer(1), Value::Null]);
+        profile.add(&[Value::Float(2.5), Value::String("Paris".into()), Value::Integer(7)]);
+
+        assert_eq!(profile.rows, 3);
+        let [id, title, extra] = &profile.columns[..] else { panic!("3 columns expected") };
+        assert_eq!(id.min, Some(Value::Integer(1)));
+        assert_eq!(id.max, Some(Value::Integer(3)));
+        assert_eq!(id.kinds.iter().copied().collect::<Vec<_>>(), ["float", "integer"]);
+        assert_eq!(id.distinct(), 3);
+        assert_eq!(title.nulls, 1);
+        assert_eq!(title.top(5), [("Paris".to_owned(), 2)]);
+        assert_eq!(extra.nulls, 2);
+    }
+
+    #[test]
+    fn distinct_estimate() {
+        let mut column = Column::new(0);
+        for i in 0..100_000 {
+            column.add(&Value::Integer(i % 50_000));
+        }
+        let error = (column.distinct() as f64 - 50_000.0).abs() / 50_000.0;
+        assert!(error < 0.05, "estimate {} is off by {:.1}%", column.distinct(), error * 100.0);
+
+        // The most frequent value is found among many rare ones
+        let mut column = Column::new(0);
+        for i in 0..10_000 {
+            column.add(&Value::Integer(if i % 3 == 0 { -1 } else { i }));
+        }
+        assert_eq!(column.top(1)[0].0, "-1");
+    }
+}
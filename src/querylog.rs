@@ -0,0 +1,109 @@
+//! Query logs, for replaying a workload against another database
+
+use std::{fs::{File, OpenOptions}, io::{self, BufRead, BufReader, Write}, time::{Duration, Instant}};
+
+use serde::{Deserialize, Serialize};
+
+use crate::sqlite::Db;
+
+/// A logged query
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Query {
+    Path { start: String, end: String },
+    Search { query: String },
+}
+
+impl std::fmt::Display for Query {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Query::Path { start, end } => write!(f, "path {} -> {}", start, end),
+            Query::Search { query } => write!(f, "search {}", query),
+        }
+    }
+}
+
+impl Query {
+    /// Run the query, and return the size of its result: the length of the path
+    /// found, or the number of articles matching the search.
+    pub fn run(&self, db: &mut Db) -> Option<usize> {
+        match self {
+            Query::Path { start, end } => {
+                let (from, to) = (db.resolve(start)?, db.resolve(end)?);
+                db.path_ids(from, to, &Default::default()).0.map(|p| p.len() - 1)
+            }
+            Query::Search { query } => Some(db.search(query).len()),
+        }
+    }
+
+    /// Run the query, and also return how long it took
+    pub fn run_timed(&self, db: &mut Db) -> (Option<usize>, Duration) {
+        let started = Instant::now();
+        let results = self.run(db);
+        (results, started.elapsed())
+    }
+}
+
+/// A line of the query log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    #[serde(flatten)]
+    pub query: Query,
+    pub ms: f64,
+    /// Size of the result, `None` if there was none
+    pub results: Option<usize>,
+}
+
+/// Appends queries to a newline-delimited JSON file, if enabled
+pub struct QueryLog {
+    file: Option<File>,
+}
+
+impl QueryLog {
+
+    /// Log to the given file, or nowhere
+    pub fn open(path: Option<&str>) -> io::Result<Self> {
+        let file = path
+            .map(|p| OpenOptions::new().create(true).append(true).open(p))
+            .transpose()?;
+        Ok(QueryLog { file })
+    }
+
+    pub fn record(&mut self, query: Query, elapsed: Duration, results: Option<usize>) -> io::Result<()> {
+        let Some(file) = &mut self.file else { return Ok(()) };
+        let entry = Entry { query, ms: elapsed.as_secs_f64() * 1000.0, results };
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+        file.write_all(line.as_bytes())
+    }
+
+}
+
+/// Read back a query log
+pub fn read(path: &str) -> io::Result<Vec<Entry>> {
+    BufReader::new(File::open(path)?).lines()
+        .filter(|line| !line.as_ref().is_ok_and(|l| l.trim().is_empty()))
+        .map(|line| Ok(serde_json::from_str(&line?)?))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn entry_format() {
+        let entry = Entry {
+            query: Query::Path { start: "Paris".into(), end: "Rome".into() },
+            ms: 1.5,
+            results: Some(2),
+        };
+        let line = serde_json::to_string(&entry).unwrap();
+        assert_eq!(line, r#"{"kind":"path","start":"Paris","end":"Rome","ms":1.5,"results":2}"#);
+        assert_eq!(serde_json::from_str::<Entry>(&line).unwrap(), entry);
+
+        let search: Entry = serde_json::from_str(r#"{"kind":"search","query":"Par%","ms":3.0,"results":null}"#).unwrap();
+        assert_eq!(search.query, Query::Search { query: "Par%".into() });
+        assert_eq!(search.results, None);
+    }
+}
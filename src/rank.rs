@@ -0,0 +1,67 @@
+//! PageRank of the articles, by power iteration
+//!
+//! Only the scores and the number of links of every article stay in memory: every
+//! iteration streams the links from the database again. The score of the articles
+//! without links is spread over all the articles, so that scores always sum to 1.
+
+pub struct PageRank {
+    /// Probability of following a link, rather than jumping to a random article
+    pub damping: f64,
+    /// Maximum number of iterations
+    pub iterations: usize,
+    /// Stop once the scores change by less than this, summed over all articles
+    pub tolerance: f64,
+}
+
+impl PageRank {
+
+    /// The scores of `n` articles numbered densely, and the number of iterations run.
+    /// `scan` passes every link to the function it is given, as dense indices.
+    pub fn run<E, S>(&self, n: usize, mut scan: S) -> Result<(Vec<f64>, usize), E>
+    where S: FnMut(&mut dyn FnMut(u32, u32)) -> Result<(), E>
+    {
+        if n == 0 { return Ok((vec![], 0)) }
+        let mut out_degree = vec![0u32; n];
+        scan(&mut |from, _| out_degree[from as usize] += 1)?;
+
+        let mut rank = vec![1.0 / n as f64; n];
+        let mut next = vec![0.0; n];
+        for iteration in 1..=self.iterations {
+            let dangling: f64 = rank.iter().zip(&out_degree)
+                .filter(|&(_, &degree)| degree == 0)
+                .map(|(score, _)| score)
+                .sum();
+            next.fill((1.0 - self.damping + self.damping * dangling) / n as f64);
+            scan(&mut |from, to| {
+                let from = from as usize;
+                next[to as usize] += self.damping * rank[from] / out_degree[from] as f64;
+            })?;
+            let change: f64 = rank.iter().zip(&next).map(|(a, b)| (a - b).abs()).sum();
+            std::mem::swap(&mut rank, &mut next);
+            if change < self.tolerance { return Ok((rank, iteration)) }
+        }
+        Ok((rank, self.iterations))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sample_ranks() {
+        // 0 -> 1 -> 2 -> 0, and 3 -> 2 without incoming links
+        let links = [(0, 1), (1, 2), (2, 0), (3, 2)];
+        let pagerank = PageRank { damping: 0.85, iterations: 200, tolerance: 1e-9 };
+        let (scores, iterations) = pagerank.run(4, |f| { links.iter().for_each(|&(from, to)| f(from, to)); Ok::<_, ()>(()) }).unwrap();
+        assert!(iterations < 200);
+        assert!((scores.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!((scores[3] - 0.15 / 4.0).abs() < 1e-6);
+        assert!(scores[2] > scores[0] && scores[0] > scores[1] && scores[1] > scores[3]);
+
+        // Dead ends spread their score
+        let (scores, _) = pagerank.run(2, |f| { f(0, 1); Ok::<_, ()>(()) }).unwrap();
+        assert!((scores.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!(scores[1] > scores[0]);
+    }
+}
@@ -0,0 +1,350 @@
+//! Read-only access to a database published over HTTP, with `--db-url`
+//!
+//! A path query reads a few hundred pages of the database, not the whole file. The
+//! database is opened through a SQLite VFS whose files fetch blocks of the remote
+//! file with HTTP range requests, and keep the latest ones in memory. Other files,
+//! such as temporary ones, go to the default VFS.
+
+use std::{collections::{HashMap, VecDeque}, ffi::CStr, io::{self, Read}, mem, os::raw::{c_char, c_int, c_void}, ptr, slice, sync::OnceLock};
+
+use rusqlite::ffi;
+
+use crate::{error::{Result, WikistraError}, timings::{self, Phase}};
+
+/// Name of the VFS, to open databases with
+pub const VFS: &str = "wikistra-http";
+const VFS_NAME: &CStr = c"wikistra-http";
+
+/// Bytes fetched per range request
+const BLOCK: u64 = 64 * 1024;
+
+/// Blocks kept in memory, for 64 MiB
+const CACHED_BLOCKS: usize = 1024;
+
+/// A remote file, read by blocks
+pub struct Remote {
+    agent: ureq::Agent,
+    url: String,
+    size: u64,
+    blocks: HashMap<u64, Vec<u8>>,
+    /// Cached blocks, oldest first
+    fetched: VecDeque<u64>,
+}
+
+impl Remote {
+
+    pub fn open(url: &str) -> Result<Self> {
+        let agent = ureq::AgentBuilder::new()
+            .build();
+        let response = agent.head(url).call()?;
+        if response.header("Accept-Ranges") == Some("none") {
+            return Err(WikistraError::NoRanges(url.to_owned()))
+        }
+        let size = response.header("Content-Length")
+            .ok_or_else(|| WikistraError::Header("Content-Length", "missing".to_owned()))?;
+        let size = size.parse()
+            .map_err(|_| WikistraError::Header("Content-Length", size.to_owned()))?;
+        Ok(Remote { agent, url: url.to_owned(), size, blocks: HashMap::new(), fetched: VecDeque::new() })
+    }
+
+    /// Read at an offset, returning the number of bytes read, which is short at the
+    /// end of the file
+    pub fn read_at(&mut self, mut offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let mut read = 0;
+        while read < buf.len() && offset < self.size {
+            let block = offset / BLOCK;
+            let start = (offset - block * BLOCK) as usize;
+            let data = self.block(block)?;
+            let n = (data.len() - start).min(buf.len() - read);
+            buf[read..read + n].copy_from_slice(&data[start..start + n]);
+            read += n;
+            offset += n as u64;
+        }
+        Ok(read)
+    }
+
+    fn block(&mut self, block: u64) -> Result<&[u8]> {
+        if !self.blocks.contains_key(&block) {
+            let start = block * BLOCK;
+            let end = (start + BLOCK).min(self.size);
+            let data = timings::time(Phase::Download, || -> Result<Vec<u8>> {
+                let response = self.agent.get(&self.url)
+                    .set("Range", &format!("bytes={}-{}", start, end - 1))
+                    .call()?;
+                // A server ignoring the range would send the whole file
+                if response.status() != 206 {
+                    return Err(WikistraError::NoRanges(self.url.clone()))
+                }
+                let mut data = Vec::with_capacity((end - start) as usize);
+                response.into_reader().take(end - start).read_to_end(&mut data)?;
+                Ok(data)
+            })?;
+            if data.len() as u64 != end - start {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into())
+            }
+            if self.fetched.len() >= CACHED_BLOCKS {
+                if let Some(oldest) = self.fetched.pop_front() { self.blocks.remove(&oldest); }
+            }
+            self.fetched.push_back(block);
+            self.blocks.insert(block, data);
+        }
+        Ok(&self.blocks[&block])
+    }
+}
+
+/// Register the VFS, once
+pub fn register() -> rusqlite::Result<()> {
+    static REGISTERED: OnceLock<c_int> = OnceLock::new();
+    let rc = *REGISTERED.get_or_init(|| unsafe {
+        let default = ffi::sqlite3_vfs_find(ptr::null());
+        if default.is_null() { return ffi::SQLITE_ERROR }
+        let vfs = Box::leak(Box::new(ffi::sqlite3_vfs {
+            iVersion: 1,
+            // Files of the default VFS are opened in the same space
+            szOsFile: (*default).szOsFile.max(mem::size_of::<File>() as c_int),
+            mxPathname: (*default).mxPathname,
+            pNext: ptr::null_mut(),
+            zName: VFS_NAME.as_ptr(),
+            pAppData: default.cast(),
+            xOpen: Some(open),
+            xDelete: Some(delete),
+            xAccess: Some(access),
+            xFullPathname: Some(full_pathname),
+            xDlOpen: None,
+            xDlError: None,
+            xDlSym: None,
+            xDlClose: None,
+            xRandomness: Some(randomness),
+            xSleep: Some(sleep),
+            xCurrentTime: Some(current_time),
+            xGetLastError: None,
+            xCurrentTimeInt64: None,
+            xSetSystemCall: None,
+            xGetSystemCall: None,
+            xNextSystemCall: None,
+        }));
+        ffi::sqlite3_vfs_register(vfs, 0)
+    });
+    match rc {
+        ffi::SQLITE_OK => Ok(()),
+        rc => Err(rusqlite::Error::SqliteFailure(ffi::Error::new(rc), None)),
+    }
+}
+
+/// An open remote file: the header of SQLite files, then the file
+#[repr(C)]
+struct File {
+    base: ffi::sqlite3_file,
+    remote: *mut Remote,
+}
+
+static METHODS: ffi::sqlite3_io_methods = ffi::sqlite3_io_methods {
+    iVersion: 1,
+    xClose: Some(close),
+    xRead: Some(read),
+    xWrite: Some(write),
+    xTruncate: Some(truncate),
+    xSync: Some(sync),
+    xFileSize: Some(file_size),
+    xLock: Some(lock),
+    xUnlock: Some(lock),
+    xCheckReservedLock: Some(check_reserved_lock),
+    xFileControl: Some(file_control),
+    xSectorSize: Some(sector_size),
+    xDeviceCharacteristics: Some(device_characteristics),
+    xShmMap: None,
+    xShmLock: None,
+    xShmBarrier: None,
+    xShmUnmap: None,
+    xFetch: None,
+    xUnfetch: None,
+};
+
+fn is_url(name: &[u8]) -> bool {
+    name.starts_with(b"http://") || name.starts_with(b"https://")
+}
+
+unsafe fn default_vfs(vfs: *mut ffi::sqlite3_vfs) -> *mut ffi::sqlite3_vfs {
+    (*vfs).pAppData.cast()
+}
+
+unsafe fn remote<'a>(file: *mut ffi::sqlite3_file) -> &'a mut Remote {
+    &mut *(*file.cast::<File>()).remote
+}
+
+unsafe extern "C" fn open(vfs: *mut ffi::sqlite3_vfs, name: *const c_char, file: *mut ffi::sqlite3_file,
+        flags: c_int, out_flags: *mut c_int) -> c_int {
+    let default = default_vfs(vfs);
+    if name.is_null() || flags & ffi::SQLITE_OPEN_MAIN_DB == 0 {
+        return (*default).xOpen.unwrap()(default, name, file, flags, out_flags)
+    }
+    (*file).pMethods = ptr::null();
+    let Ok(url) = CStr::from_ptr(name).to_str() else { return ffi::SQLITE_CANTOPEN };
+    match Remote::open(url) {
+        Ok(remote) => {
+            (*file.cast::<File>()).remote = Box::into_raw(Box::new(remote));
+            (*file).pMethods = &METHODS;
+            if !out_flags.is_null() { *out_flags = ffi::SQLITE_OPEN_READONLY }
+            ffi::SQLITE_OK
+        }
+        Err(e) => {
+            eprintln!("Cannot open {}: {}", url, e);
+            ffi::SQLITE_CANTOPEN
+        }
+    }
+}
+
+unsafe extern "C" fn delete(vfs: *mut ffi::sqlite3_vfs, name: *const c_char, sync_dir: c_int) -> c_int {
+    if is_url(CStr::from_ptr(name).to_bytes()) { return ffi::SQLITE_READONLY }
+    let default = default_vfs(vfs);
+    (*default).xDelete.unwrap()(default, name, sync_dir)
+}
+
+unsafe extern "C" fn access(vfs: *mut ffi::sqlite3_vfs, name: *const c_char, flags: c_int, out: *mut c_int) -> c_int {
+    // Remote databases have no journal
+    if is_url(CStr::from_ptr(name).to_bytes()) {
+        *out = 0;
+        return ffi::SQLITE_OK
+    }
+    let default = default_vfs(vfs);
+    (*default).xAccess.unwrap()(default, name, flags, out)
+}
+
+unsafe extern "C" fn full_pathname(vfs: *mut ffi::sqlite3_vfs, name: *const c_char, size: c_int, out: *mut c_char) -> c_int {
+    let url = CStr::from_ptr(name).to_bytes_with_nul();
+    if !is_url(url) {
+        let default = default_vfs(vfs);
+        return (*default).xFullPathname.unwrap()(default, name, size, out)
+    }
+    if url.len() > size as usize { return ffi::SQLITE_CANTOPEN }
+    ptr::copy_nonoverlapping(url.as_ptr().cast(), out, url.len());
+    ffi::SQLITE_OK
+}
+
+unsafe extern "C" fn randomness(vfs: *mut ffi::sqlite3_vfs, size: c_int, out: *mut c_char) -> c_int {
+    let default = default_vfs(vfs);
+    (*default).xRandomness.unwrap()(default, size, out)
+}
+
+unsafe extern "C" fn sleep(vfs: *mut ffi::sqlite3_vfs, micros: c_int) -> c_int {
+    let default = default_vfs(vfs);
+    (*default).xSleep.unwrap()(default, micros)
+}
+
+unsafe extern "C" fn current_time(vfs: *mut ffi::sqlite3_vfs, out: *mut f64) -> c_int {
+    let default = default_vfs(vfs);
+    (*default).xCurrentTime.unwrap()(default, out)
+}
+
+unsafe extern "C" fn close(file: *mut ffi::sqlite3_file) -> c_int {
+    drop(Box::from_raw((*file.cast::<File>()).remote));
+    ffi::SQLITE_OK
+}
+
+unsafe extern "C" fn read(file: *mut ffi::sqlite3_file, buf: *mut c_void, amount: c_int, offset: i64) -> c_int {
+    let buf = slice::from_raw_parts_mut(buf.cast::<u8>(), amount as usize);
+    match remote(file).read_at(offset as u64, buf) {
+        Ok(n) if n == buf.len() => ffi::SQLITE_OK,
+        Ok(n) => {
+            buf[n..].fill(0);
+            ffi::SQLITE_IOERR_SHORT_READ
+        }
+        Err(e) => {
+            eprintln!("Cannot read {}: {}", remote(file).url, e);
+            ffi::SQLITE_IOERR_READ
+        }
+    }
+}
+
+unsafe extern "C" fn write(_: *mut ffi::sqlite3_file, _: *const c_void, _: c_int, _: i64) -> c_int {
+    ffi::SQLITE_READONLY
+}
+
+unsafe extern "C" fn truncate(_: *mut ffi::sqlite3_file, _: i64) -> c_int {
+    ffi::SQLITE_READONLY
+}
+
+unsafe extern "C" fn sync(_: *mut ffi::sqlite3_file, _: c_int) -> c_int {
+    ffi::SQLITE_OK
+}
+
+unsafe extern "C" fn file_size(file: *mut ffi::sqlite3_file, out: *mut i64) -> c_int {
+    *out = remote(file).size as i64;
+    ffi::SQLITE_OK
+}
+
+/// Nobody writes to the remote file, so that locks always succeed
+unsafe extern "C" fn lock(_: *mut ffi::sqlite3_file, _: c_int) -> c_int {
+    ffi::SQLITE_OK
+}
+
+unsafe extern "C" fn check_reserved_lock(_: *mut ffi::sqlite3_file, out: *mut c_int) -> c_int {
+    *out = 0;
+    ffi::SQLITE_OK
+}
+
+unsafe extern "C" fn file_control(_: *mut ffi::sqlite3_file, _: c_int, _: *mut c_void) -> c_int {
+    ffi::SQLITE_NOTFOUND
+}
+
+unsafe extern "C" fn sector_size(_: *mut ffi::sqlite3_file) -> c_int {
+    512
+}
+
+unsafe extern "C" fn device_characteristics(_: *mut ffi::sqlite3_file) -> c_int {
+    ffi::SQLITE_IOCAP_IMMUTABLE
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{io::{BufRead, BufReader, Write}, net::TcpListener, thread};
+    use rusqlite::{Connection, OpenFlags};
+
+    /// Serve a file on a local port, answering range requests only
+    fn serve(data: Vec<u8>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/test.sq3", listener.local_addr().unwrap());
+        thread::spawn(move || for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut request = vec![];
+            for line in BufReader::new(&stream).lines() {
+                let line = line.unwrap();
+                if line.is_empty() { break }
+                request.push(line.to_ascii_lowercase());
+            }
+            let range = request.iter().find_map(|line| line.strip_prefix("range: bytes="))
+                .and_then(|range| range.split_once('-'))
+                .map(|(start, end)| start.parse::<usize>().unwrap() .. end.parse::<usize>().unwrap() + 1);
+            let head = match &range {
+                Some(range) => format!("HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\n", range.len()),
+                None => format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n", data.len()),
+            };
+            write!(stream, "{}Accept-Ranges: bytes\r\nConnection: close\r\n\r\n", head).unwrap();
+            if let Some(range) = range { stream.write_all(&data[range]).unwrap() }
+        });
+        url
+    }
+
+    #[test]
+    fn remote_database() {
+        let path = std::env::temp_dir().join(format!("wikistra-remote-{}.sq3", std::process::id()));
+        let local = Connection::open(&path).unwrap();
+        local.execute_batch("
+            CREATE TABLE page (id int primary key, title text);
+            WITH RECURSIVE n(id) AS (SELECT 1 UNION ALL SELECT id + 1 FROM n WHERE id < 20000)
+            INSERT INTO page SELECT id, printf('Article_%d', id) FROM n;
+        ").unwrap();
+        drop(local);
+        let url = serve(std::fs::read(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        register().unwrap();
+        let db = Connection::open_with_flags_and_vfs(&url, OpenFlags::SQLITE_OPEN_READ_ONLY, VFS).unwrap();
+        let title: String = db.query_row("SELECT title FROM page WHERE id = 12345", (), |r| r.get(0)).unwrap();
+        assert_eq!(title, "Article_12345");
+        let count: u64 = db.query_row("SELECT count(*) FROM page", (), |r| r.get(0)).unwrap();
+        assert_eq!(count, 20000);
+        assert!(db.execute("DELETE FROM page", ()).is_err());
+    }
+}
@@ -0,0 +1,110 @@
+//! The interactive search loop, reading one query per line
+//!
+//! Results are shown a page at a time. Besides queries, lines can be commands:
+//! `!more` shows the next page of the last query, and `!limit N` sets the number
+//! of results per page. Other lines are queries, even when they start with `!`,
+//! as some titles do.
+
+/// Results per page, unless set with `--limit` or `!limit`
+pub const DEFAULT_LIMIT: usize = 20;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Line<'a> {
+    Empty,
+    Query(&'a str),
+    More,
+    Limit(usize),
+}
+
+/// Parse an input line
+pub fn parse(line: &str) -> Result<Line<'_>, String> {
+    let line = line.trim_end_matches(['\r', '\n']);
+    match line.trim().split_once(' ').unwrap_or((line.trim(), "")) {
+        ("", _) => Ok(Line::Empty),
+        ("!more", "") => Ok(Line::More),
+        ("!limit", n) => match n.trim().parse::<usize>() {
+            Ok(0) => Err("invalid limit: 0".to_owned()),
+            Ok(n) => Ok(Line::Limit(n)),
+            Err(_) => Ok(Line::Query(line)),
+        },
+        _ => Ok(Line::Query(line)),
+    }
+}
+
+/// The results of the last query, shown a page at a time
+pub struct Pages<T> {
+    items: Vec<T>,
+    shown: usize,
+}
+
+impl <T> Default for Pages<T> {
+    fn default() -> Self {
+        Pages { items: vec![], shown: 0 }
+    }
+}
+
+impl <T> Pages<T> {
+
+    pub fn new(items: Vec<T>) -> Self {
+        Pages { items, shown: 0 }
+    }
+
+    /// The next `limit` results
+    pub fn next(&mut self, limit: usize) -> &[T] {
+        let start = self.shown;
+        self.shown = self.items.len().min(start + limit);
+        &self.items[start..self.shown]
+    }
+
+    /// Number of results not shown yet
+    pub fn remaining(&self) -> usize {
+        self.items.len() - self.shown
+    }
+}
+
+/// The line telling how many results were not shown
+pub fn footer(remaining: usize) -> String {
+    format!("…and {} more (refine your query, or enter !more)", grouped(remaining))
+}
+
+/// A number with its thousands separated by commas
+fn grouped(n: usize) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) { grouped.push(',') }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sample_session() {
+        assert_eq!(parse("Paris%"), Ok(Line::Query("Paris%")));
+        assert_eq!(parse("New York%\n"), Ok(Line::Query("New York%")));
+        assert_eq!(parse("  "), Ok(Line::Empty));
+        assert_eq!(parse("!more"), Ok(Line::More));
+        assert_eq!(parse("!limit 50"), Ok(Line::Limit(50)));
+        assert!(parse("!limit 0").is_err());
+        assert_eq!(parse("!less"), Ok(Line::Query("!less")));
+        assert_eq!(parse("!Kung people"), Ok(Line::Query("!Kung people")));
+        assert_eq!(parse("!more people"), Ok(Line::Query("!more people")));
+        assert_eq!(parse("!limit everything"), Ok(Line::Query("!limit everything")));
+
+        let mut pages = Pages::new((0..45).collect());
+        assert_eq!(pages.next(20), (0..20).collect::<Vec<_>>());
+        assert_eq!(pages.remaining(), 25);
+        assert_eq!(pages.next(20).len(), 20);
+        assert_eq!(pages.next(20), [40, 41, 42, 43, 44]);
+        assert_eq!(pages.remaining(), 0);
+        assert!(pages.next(20).is_empty());
+
+        assert_eq!(footer(3214), "…and 3,214 more (refine your query, or enter !more)");
+        assert_eq!(grouped(999), "999");
+        assert_eq!(grouped(1_000_000), "1,000,000");
+    }
+}
@@ -0,0 +1,107 @@
+//! Machine-readable reports for the indexers
+
+use std::{fs::File, io::{Write, stdout}, time::Duration};
+use serde::Serialize;
+
+use crate::prefetch::{Metrics, PipelineReport};
+
+/// Counters collected while indexing a single table
+#[derive(Debug, Default, Serialize)]
+pub struct TableReport {
+    pub table: String,
+    /// Rows read from the dump
+    pub parsed: u64,
+    /// Rows stored in the database
+    pub inserted: u64,
+    /// Rows dropped because they are outside the main namespace
+    pub skipped_namespace: u64,
+    /// Redirects to another wiki
+    pub interwiki: u64,
+    /// Rows referring to a title missing from the page index
+    pub unresolved: u64,
+    /// Links to a target missing from the linktarget table
+    pub unknown_targets: u64,
+    /// Rows dropped by the filters of an index spec
+    pub filtered: u64,
+    /// Links dropped because the same article links to the same target earlier in the dump
+    pub duplicates: u64,
+    /// Links from an article to itself, dropped with `--drop-self-links`
+    pub self_links: u64,
+    /// Rows contradicting another table, such as redirects of pages not marked as redirects
+    pub inconsistent: u64,
+    pub duration_secs: f64,
+    /// Parsed rows per second
+    pub throughput: f64,
+    /// Fill of the queue between decompression and parsing, when they ran on separate threads
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pipeline: Option<PipelineReport>,
+}
+
+impl TableReport {
+    pub fn new(table: &str) -> Self {
+        Self { table: table.to_owned(), ..Default::default() }
+    }
+
+    /// Count a title missing from the index, warning about the first ones
+    pub fn not_found(&mut self, title: &str) {
+        self.unresolved += 1;
+        if self.unresolved < 1000 {
+            eprintln!("Warning: Title not found in index: {}", title);
+        } else if self.unresolved == 1000 {
+            eprintln!("Too many bad articles, skipping report");
+        }
+    }
+
+    /// Record the time spent on the table, and derive the throughput from it,
+    /// along with the metrics of the dump reader if it read ahead on its own thread
+    pub fn finish(&mut self, elapsed: Duration, pipeline: Option<&Metrics>) {
+        self.pipeline = pipeline.map(Metrics::report);
+        self.duration_secs = elapsed.as_secs_f64();
+        self.throughput = if self.duration_secs > 0.0 {
+            self.parsed as f64 / self.duration_secs
+        } else {
+            0.0
+        };
+    }
+}
+
+/// Report for a whole `index` run
+#[derive(Debug, Serialize)]
+pub struct IndexReport {
+    pub wikiname: String,
+    pub tables: Vec<TableReport>,
+}
+
+impl IndexReport {
+    pub fn new(wikiname: &str) -> Self {
+        Self { wikiname: wikiname.to_owned(), tables: vec![] }
+    }
+
+    /// Write the report as JSON to the given path, or to stdout if the path is `-`
+    pub fn write(&self, path: &str) -> Result<(), std::io::Error> {
+        let mut out: Box<dyn Write> = if path == "-" {
+            Box::new(stdout().lock())
+        } else {
+            Box::new(File::create(path)?)
+        };
+        serde_json::to_writer_pretty(&mut out, self)?;
+        writeln!(out)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn throughput() {
+        let mut report = TableReport::new("page");
+        report.parsed = 500;
+        report.finish(Duration::from_secs(2), None);
+        assert_eq!(report.throughput, 250.0);
+
+        let mut empty = TableReport::new("link");
+        empty.finish(Duration::ZERO, None);
+        assert_eq!(empty.throughput, 0.0);
+    }
+}
@@ -0,0 +1,129 @@
+//! Seedable randomness, so that sampling runs can be reproduced
+
+use fastrand::Rng;
+
+use crate::{Id, sqlite::Db};
+
+/// A random generator seeded with `seed`. Without one, a fresh seed is
+/// drawn and reported on stderr, to be passed to `--seed` to reproduce the run.
+pub fn seeded(seed: Option<u64>) -> Rng {
+    let seed = seed.unwrap_or_else(|| {
+        let seed = fastrand::u64(..);
+        eprintln!("Random seed: {}", seed);
+        seed
+    });
+    Rng::with_seed(seed)
+}
+
+/// Draws uniformly random articles, redirects excepted. The article IDs are read
+/// once, so that a draw does not walk the title index.
+pub struct Articles<'a> {
+    db: &'a Db,
+    ids: Vec<Id>,
+}
+
+impl<'a> Articles<'a> {
+
+    pub fn new(db: &'a Db) -> Result<Self, rusqlite::Error> {
+        Ok(Articles { db, ids: db.article_ids()? })
+    }
+
+    /// A random article, or `None` if none is found in a reasonable number of draws
+    pub fn draw(&self, rng: &mut Rng) -> Option<Id> {
+        if self.ids.is_empty() { return None }
+        (0..100).map(|_| self.ids[rng.usize(..self.ids.len())])
+            .find(|&id| !self.db.is_redirect(id))
+    }
+}
+
+/// `count` links drawn uniformly, without replacement, and `negatives` random
+/// pairs of distinct articles without a link between them, shuffled together.
+/// Every pair comes with whether it is a link.
+pub fn edges(db: &Db, rng: &mut Rng, count: usize, negatives: usize) -> Result<Vec<(Id, Id, bool)>, rusqlite::Error> {
+    // Reservoir sampling, in a single pass over the links
+    let mut sample = Vec::with_capacity(count);
+    let mut seen = 0;
+    db.scan_links(|from, to| {
+        seen += 1;
+        if sample.len() < count {
+            sample.push((from, to, true));
+        } else {
+            let i = rng.usize(..seen);
+            if i < count { sample[i] = (from, to, true) }
+        }
+    })?;
+
+    let articles = Articles::new(db)?;
+    let mut draws = 0;
+    let target = sample.len() + negatives;
+    while sample.len() < target && draws < 100 * negatives {
+        draws += 1;
+        let (Some(from), Some(to)) = (articles.draw(rng), articles.draw(rng)) else { break };
+        if from != to && !db.has_link(from, to) {
+            sample.push((from, to, false));
+        }
+    }
+    rng.shuffle(&mut sample);
+    Ok(sample)
+}
+
+/// Parse a number of samples such as `500`, `20k` or `1M`
+pub fn parse_count(text: &str) -> Result<usize, String> {
+    let text = text.trim();
+    let (number, scale) = match text.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&text[..i], 1_000.0),
+        Some((i, 'M')) => (&text[..i], 1_000_000.0),
+        Some((i, 'G')) => (&text[..i], 1_000_000_000.0),
+        _ => (text, 1.0),
+    };
+    let value: f64 = number.trim().parse().map_err(|_| format!("invalid count: {}", text))?;
+    if value < 0.0 { return Err(format!("negative count: {}", text)) }
+    Ok((value * scale).round() as usize)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reproducible() {
+        let draw = |seed| { let mut rng = seeded(Some(seed)); [rng.u64(..), rng.u64(..), rng.u64(..)] };
+        assert_eq!(draw(42), draw(42));
+        assert_ne!(draw(42), draw(43));
+    }
+
+    #[test]
+    fn sample_edges() {
+        let path = std::env::temp_dir().join(format!("wikistra-edges-{}.sq3", std::process::id()));
+        let mut db = Db::new(path.to_str().unwrap()).unwrap();
+        for (id, title) in [(1, "a"), (2, "b"), (3, "c"), (4, "d")] {
+            db.add(id, title.into(), 0).unwrap();
+        }
+        for link in [(1,2), (2,3), (3,4), (4,1)] {
+            db.add_link(link).unwrap();
+        }
+
+        let edges = edges(&db, &mut seeded(Some(7)), 2, 3).unwrap();
+        assert_eq!(edges.iter().filter(|e| e.2).count(), 2);
+        assert_eq!(edges.iter().filter(|e| !e.2).count(), 3);
+        for (from, to, linked) in edges {
+            assert_eq!(db.has_link(from, to), linked);
+            assert_ne!(from, to);
+        }
+
+        // Redirects are never drawn
+        db.add(5, "e".into(), 0).unwrap();
+        db.add_redirect(5, "a").unwrap();
+        let articles = Articles::new(&db).unwrap();
+        let mut rng = seeded(Some(7));
+        let drawn: Vec<Id> = (0..50).filter_map(|_| articles.draw(&mut rng)).collect();
+        assert_eq!(drawn.len(), 50);
+        assert!(drawn.iter().all(|&id| (1..=4).contains(&id)));
+
+        assert_eq!(parse_count("1M"), Ok(1_000_000));
+        assert_eq!(parse_count("2.5k"), Ok(2500));
+        assert_eq!(parse_count("12"), Ok(12));
+        assert!(parse_count("many").is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,156 @@
+//! Query results saved as tables of a separate SQLite file, for later analysis
+//!
+//! Every saved table is described in the `results` table of the file: the command
+//! line that produced it, the wiki and database it was computed from, the version
+//! of wikistra and the time it was saved. Saving a table again replaces it.
+
+use std::{fs::File, io::{BufWriter, Write}};
+
+use rusqlite::{Connection, Error, types::Value};
+
+use crate::{error::Result, split, sql};
+
+/// Where results come from
+pub struct Provenance<'a> {
+    pub wikiname: &'a str,
+    /// Path of the database the results were computed from
+    pub db_path: &'a str,
+}
+
+/// A table of results being written
+pub struct Saved {
+    conn: Connection,
+    table: String,
+    insert: String,
+    rows: u64,
+}
+
+impl Saved {
+
+    /// Create the table `table` in the SQLite file at `path`, with the given
+    /// `(name, type)` columns, replacing any previous table of the same name
+    pub fn create(path: &str, table: &str, columns: &[(&str, &str)], provenance: &Provenance) -> Result<Self, Error> {
+        let conn = Connection::open(path)?;
+        let definitions: Vec<String> = columns.iter().map(|(name, kind)| format!("\"{}\" {}", name, kind)).collect();
+        let placeholders: Vec<String> = (1..=columns.len()).map(|i| format!("?{}", i)).collect();
+
+        // The previous table stays in place until the new one is complete
+        conn.execute_batch(&format!("
+            BEGIN;
+            CREATE TABLE IF NOT EXISTS results (name text primary key, command text not null, wikiname text not null,
+                db text not null, version text not null, created text not null, rows int not null);
+            DROP TABLE IF EXISTS \"{0}\";
+            CREATE TABLE \"{0}\" ({1});
+        ", table, definitions.join(", ")))?;
+
+        let command = std::env::args().collect::<Vec<_>>().join(" ");
+        conn.execute("INSERT OR REPLACE INTO results VALUES (?1, ?2, ?3, ?4, ?5, datetime('now'), 0)",
+            (table, command, provenance.wikiname, provenance.db_path, env!("CARGO_PKG_VERSION")))?;
+
+        let insert = format!("INSERT INTO \"{}\" VALUES ({})", table, placeholders.join(", "));
+        Ok(Saved { conn, table: table.to_owned(), insert, rows: 0 })
+    }
+
+    /// Append a row, with one value per column
+    pub fn write(&mut self, row: &[Value]) -> Result<(), Error> {
+        self.conn.prepare_cached(&self.insert)?
+            .execute(rusqlite::params_from_iter(row))?;
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Commit the table, and return its number of rows
+    pub fn finish(self) -> Result<u64, Error> {
+        self.conn.execute("UPDATE results SET rows = ?1 WHERE name = ?2", (self.rows, &self.table))?;
+        self.conn.execute_batch("COMMIT")?;
+        Ok(self.rows)
+    }
+}
+
+/// The rows of an analysis, written as CSV, saved as a table of results, or both
+pub struct Results {
+    csv: Option<BufWriter<File>>,
+    saved: Option<Saved>,
+}
+
+impl Results {
+
+    /// Write to the CSV file `out`, after a header row with the names of the columns,
+    /// and to the table `table` of the results file `save`, if given
+    pub fn open(out: Option<&str>, save: Option<&str>, table: &str, columns: &[(&str, &str)], provenance: &Provenance) -> Result<Self> {
+        let mut csv = out.map(|path| File::create(path).map(BufWriter::new)).transpose()?;
+        if let Some(csv) = &mut csv {
+            let header: Vec<sql::Value> = columns.iter().map(|(name, _)| sql::Value::String(name.to_string())).collect();
+            split::write_row(csv, &header)?;
+        }
+        let saved = save.map(|path| Saved::create(path, table, columns, provenance)).transpose()?;
+        Ok(Results { csv, saved })
+    }
+
+    pub fn write(&mut self, row: &[sql::Value]) -> Result<()> {
+        if let Some(csv) = &mut self.csv { split::write_row(csv, row)? }
+        if let Some(saved) = &mut self.saved {
+            let row: Vec<Value> = row.iter().map(|value| match value {
+                sql::Value::Null => Value::Null,
+                sql::Value::Integer(i) => Value::Integer(*i),
+                sql::Value::Float(f) => Value::Real(*f),
+                sql::Value::String(s) => Value::Text(s.clone()),
+                sql::Value::Bytes(b) => Value::Blob(b.clone()),
+            }).collect();
+            saved.write(&row)?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<()> {
+        if let Some(mut csv) = self.csv { csv.flush()? }
+        if let Some(saved) = self.saved { saved.finish()?; }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn save_twice() {
+        let path = std::env::temp_dir().join(format!("wikistra-save-{}.sq3", std::process::id()));
+        let path = path.to_str().unwrap();
+        let provenance = Provenance { wikiname: "enwiki", db_path: "./enwiki-db.sq3" };
+
+        for rows in [3, 2] {
+            let mut saved = Saved::create(path, "distances", &[("title", "text"), ("distance", "integer")], &provenance).unwrap();
+            for i in 0..rows {
+                saved.write(&[Value::Text(format!("T{}", i)), Value::Integer(i)]).unwrap();
+            }
+            assert_eq!(saved.finish().unwrap(), rows as u64);
+        }
+        // Interrupted before the end, it leaves the previous table
+        let mut saved = Saved::create(path, "distances", &[("title", "text")], &provenance).unwrap();
+        saved.write(&[Value::Text("T".into())]).unwrap();
+        drop(saved);
+
+        let conn = Connection::open(path).unwrap();
+        let count: i64 = conn.query_row("SELECT count(*) FROM distances", (), |r| r.get(0)).unwrap();
+        assert_eq!(count, 2);
+        let (wikiname, rows): (String, i64) = conn.query_row("SELECT wikiname, rows FROM results WHERE name = 'distances'", (), |r| Ok((r.get(0)?, r.get(1)?))).unwrap();
+        assert_eq!((wikiname.as_str(), rows), ("enwiki", 2));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn csv_header() {
+        let path = std::env::temp_dir().join(format!("wikistra-results-{}.csv", std::process::id()));
+        let path = path.to_str().unwrap();
+        let provenance = Provenance { wikiname: "enwiki", db_path: "./enwiki-db.sq3" };
+
+        let mut results = Results::open(Some(path), None, "distances", &[("title", "text"), ("distance", "integer")], &provenance).unwrap();
+        results.write(&[sql::Value::String("Paris".into()), sql::Value::Integer(1)]).unwrap();
+        results.finish().unwrap();
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "title,distance\nParis,1\n");
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
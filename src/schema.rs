@@ -0,0 +1,111 @@
+//! The tables of the database and of the dumps, for the `schema` command
+//!
+//! The dumps start with the `CREATE TABLE` statement of their table, in the MySQL
+//! dialect, which is read without going through the rows.
+
+use std::{fs::File, io::{self, BufRead, BufReader}};
+
+use flate2::bufread::GzDecoder;
+
+/// Lines read at most from the start of a dump to find its `CREATE TABLE`
+const HEADER_LINES: usize = 200;
+
+/// A table, with the definitions of its columns and indexes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Table {
+    pub name: String,
+    /// Names and types of the columns
+    pub columns: Vec<(String, String)>,
+    /// Names and definitions of the indexes
+    pub indexes: Vec<(String, String)>,
+    /// Number of rows, for the tables of the database
+    pub rows: Option<u64>,
+}
+
+/// The table declared by the `CREATE TABLE` statement at the start of a gzipped dump
+pub fn read_dump(path: &str) -> io::Result<Option<Table>> {
+    let source = BufReader::new(GzDecoder::new(BufReader::new(File::open(path)?)));
+    let mut statement = String::new();
+    for line in source.lines().take(HEADER_LINES) {
+        let line = line?;
+        if line.contains("DISABLE KEYS") { break }
+        if line.starts_with("CREATE TABLE") || !statement.is_empty() {
+            statement.push_str(&line);
+            statement.push('\n');
+            if line.starts_with(')') { break }
+        }
+    }
+    Ok(parse_create_table(&statement))
+}
+
+/// Parse a `CREATE TABLE` statement of a MySQL dump, with one column or key per line
+pub fn parse_create_table(statement: &str) -> Option<Table> {
+    let mut lines = statement.lines();
+    let name = unquote(lines.next()?.strip_prefix("CREATE TABLE ")?.trim_end_matches([' ', '(']));
+    let mut table = Table { name, columns: vec![], indexes: vec![], rows: None };
+    for line in lines {
+        let line = line.trim().trim_end_matches(',');
+        if line.starts_with(')') { break }
+        if let Some(rest) = line.strip_prefix('`') {
+            let (column, definition) = rest.split_once('`')?;
+            table.columns.push((column.to_owned(), definition.trim().to_owned()));
+        } else if let Some(columns) = line.strip_prefix("PRIMARY KEY") {
+            table.indexes.push(("PRIMARY".to_owned(), columns.trim().to_owned()));
+        } else if let Some((_, key)) = line.split_once("KEY `") {
+            let (index, columns) = key.split_once('`')?;
+            table.indexes.push((index.to_owned(), columns.trim().to_owned()));
+        }
+    }
+    Some(table)
+}
+
+/// A column as an output field, typed by its SQL type as in `id:int`
+pub fn field(name: &str, sql_type: &str) -> String {
+    let sql_type = sql_type.to_lowercase();
+    let base = sql_type.split(['(', ' ']).next().unwrap_or_default();
+    if ["tinyint", "smallint", "mediumint", "int", "integer", "bigint"].contains(&base) {
+        format!("{}:int", name)
+    } else if ["real", "float", "double", "decimal"].contains(&base) {
+        format!("{}:float", name)
+    } else {
+        name.to_owned()
+    }
+}
+
+fn unquote(name: &str) -> String {
+    name.trim().trim_matches('`').to_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sample_create_table() {
+        let statement = "CREATE TABLE `pagelinks` (
+  `pl_from` int(8) unsigned NOT NULL DEFAULT 0,
+  `pl_from_namespace` int(11) NOT NULL DEFAULT 0,
+  `pl_target_id` bigint(20) unsigned NOT NULL,
+  PRIMARY KEY (`pl_from`,`pl_target_id`),
+  KEY `pl_target_id` (`pl_target_id`,`pl_from`),
+  UNIQUE KEY `pl_backlinks_namespace_target_id` (`pl_from_namespace`,`pl_target_id`,`pl_from`)
+) ENGINE=InnoDB DEFAULT CHARSET=binary ROW_FORMAT=COMPRESSED;
+";
+        let table = parse_create_table(statement).unwrap();
+        assert_eq!(table.name, "pagelinks");
+        assert_eq!(table.columns.len(), 3);
+        assert_eq!(table.columns[2], ("pl_target_id".to_owned(), "bigint(20) unsigned NOT NULL".to_owned()));
+        assert_eq!(table.indexes.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>(),
+            ["PRIMARY", "pl_target_id", "pl_backlinks_namespace_target_id"]);
+        assert_eq!(table.indexes[1].1, "(`pl_target_id`,`pl_from`)");
+        assert!(parse_create_table("").is_none());
+    }
+
+    #[test]
+    fn typed_fields() {
+        assert_eq!(field("pl_from", "int(8) unsigned NOT NULL DEFAULT 0"), "pl_from:int");
+        assert_eq!(field("id", "INTEGER PRIMARY KEY"), "id:int");
+        assert_eq!(field("gt_lat", "decimal(11,8) DEFAULT NULL"), "gt_lat:float");
+        assert_eq!(field("page_title", "varbinary(255) NOT NULL DEFAULT ''"), "page_title");
+    }
+}
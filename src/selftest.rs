@@ -0,0 +1,55 @@
+//! Built-in correctness checks, comparing the custom algorithms to reference implementations
+
+use std::collections::HashSet;
+
+use color_eyre::{Result, eyre::eyre};
+use pathfinding::directed::dijkstra::dijkstra;
+use serde_json::{Value, json};
+
+use crate::{Id, output::{Format, Output, Style, display, paint}, rng, sqlite::Db};
+
+/// Run path queries between random articles through both the database search and the
+/// `pathfinding` crate's Dijkstra, and report the queries where the path lengths differ
+/// or the path found is not made of actual links. Fails if there is any mismatch.
+pub fn paths(db: &Db, samples: usize, seed: Option<u64>, format: Format) -> Result<()> {
+    let mut rng = rng::seeded(seed);
+    let mut out = Output::stdout(format, &["start", "end", "length:int", "expected:int", "problem"], |r| {
+        let length = |v: &Value| if v.is_null() { "-".to_owned() } else { v.to_string() };
+        format!("{} -> {}: {} (length {}, expected {})", display(&r[0]), display(&r[1]), paint(display(&r[4]), Style::No), length(&r[2]), length(&r[3]))
+    });
+    let mut mismatches = 0;
+    let articles = rng::Articles::new(db)?;
+
+    for i in 0..samples {
+        let mut article = || articles.draw(&mut rng).ok_or(eyre!("No article found"));
+        let (from, to) = (article()?, article()?);
+        eprintln!("[{}/{}] {} -> {}", i + 1, samples, from, to);
+
+        let (path, _) = db.path_ids(from, to, &HashSet::new());
+        let expected = dijkstra(&from, |n| db.links_from(*n).into_iter().map(|m| (m, 1u32)), |n| *n == to)
+            .map(|(path, _)| path);
+
+        let problem = if path.as_ref().map(Vec::len) != expected.as_ref().map(Vec::len) {
+            Some("different length")
+        } else if path.as_ref().is_some_and(|p| p.windows(2).any(|w| !db.links_from(w[0]).contains(&w[1]))) {
+            Some("missing link")
+        } else {
+            None
+        };
+
+        if let Some(problem) = problem {
+            mismatches += 1;
+            let title = |id| db.lookup(id).unwrap_or(id.to_string());
+            out.write(&[json!(title(from)), json!(title(to)), length(&path), length(&expected), json!(problem)])?;
+        }
+    }
+    out.finish()?;
+
+    eprintln!("{} queries, {} mismatches", samples, mismatches);
+    if mismatches > 0 { return Err(eyre!("{} of {} path queries disagree with the reference", mismatches, samples)) }
+    Ok(())
+}
+
+fn length(path: &Option<Vec<Id>>) -> Value {
+    path.as_ref().map_or(Value::Null, |p| json!(p.len() - 1))
+}
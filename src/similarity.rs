@@ -0,0 +1,64 @@
+//! Link prediction scores between two articles, from their neighbors: the
+//! articles linking to them or linked from them
+
+use std::collections::BTreeSet;
+
+use clap::ValueEnum;
+
+use crate::Id;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Metric {
+    /// Number of common neighbors
+    CommonNeighbors,
+    /// Common neighbors weighted by the inverse logarithm of their number of neighbors,
+    /// so that hubs count less
+    AdamicAdar,
+    /// Product of the numbers of neighbors
+    PreferentialAttachment,
+}
+
+impl Metric {
+    /// The score of a pair of articles, given the neighbors of any article
+    pub fn score<F: FnMut(Id) -> BTreeSet<Id>>(self, a: Id, b: Id, mut neighbors: F) -> f64 {
+        let (of_a, of_b) = (neighbors(a), neighbors(b));
+        match self {
+            Metric::CommonNeighbors => of_a.intersection(&of_b).count() as f64,
+            // Common neighbors of distinct articles have at least a and b as neighbors.
+            // Scoring an article with itself, those with a single neighbor are skipped,
+            // as the logarithm of 1 is zero.
+            Metric::AdamicAdar => of_a.intersection(&of_b)
+                .map(|&z| neighbors(z).len() as f64)
+                .filter(|&degree| degree > 1.0)
+                .map(|degree| 1.0 / degree.ln())
+                .sum(),
+            Metric::PreferentialAttachment => (of_a.len() * of_b.len()) as f64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sample_scores() {
+        // Undirected edges: 1-3, 1-4, 2-3, 2-4, 2-5, 4-5, 4-6
+        let edges = [(1, 3), (1, 4), (2, 3), (2, 4), (2, 5), (4, 5), (4, 6)];
+        let neighbors = |id: Id| -> BTreeSet<Id> {
+            edges.iter().filter_map(|&(x, y)| if x == id { Some(y) } else if y == id { Some(x) } else { None }).collect()
+        };
+
+        assert_eq!(Metric::CommonNeighbors.score(1, 2, neighbors), 2.0);
+        // Through 3 (2 neighbors) and 4 (4 neighbors)
+        let expected = 1.0 / 2f64.ln() + 1.0 / 4f64.ln();
+        assert!((Metric::AdamicAdar.score(1, 2, neighbors) - expected).abs() < 1e-12);
+        assert_eq!(Metric::PreferentialAttachment.score(1, 2, neighbors), 6.0);
+
+        assert_eq!(Metric::CommonNeighbors.score(3, 6, neighbors), 0.0);
+        assert_eq!(Metric::AdamicAdar.score(3, 6, neighbors), 0.0);
+        // Through 1, 2 and 5, but not 6, whose only neighbor is 4
+        let expected = 2.0 / 2f64.ln() + 1.0 / 3f64.ln();
+        assert!((Metric::AdamicAdar.score(4, 4, neighbors) - expected).abs() < 1e-12);
+    }
+}
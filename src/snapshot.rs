@@ -0,0 +1,367 @@
+//! Compact, self-checking files holding the whole link graph, written by `compile --snapshot`
+//!
+//! A snapshot starts with a header: the magic bytes `WIKISNAP`, the format version,
+//! the oldest version able to read the file, the number of articles and the number
+//! of sections, as little-endian `u16`, `u16`, `u32` and `u32`. Each section follows
+//! with a 4-byte tag, its length as a `u64` and the CRC-32 of its contents as a `u32`.
+//!
+//! Integers in sections are unsigned LEB128 varints. Articles are numbered densely
+//! in the order of their IDs:
+//! - `IDS ` holds the sorted article IDs, each as the difference with the previous one.
+//! - `TITL` holds the title of every article, as its length in bytes and its UTF-8 bytes.
+//! - `LINK` holds the outgoing links of every article: their count, then the sorted
+//!   dense numbers of their targets, each as the difference with the previous one.
+//!
+//! Readers skip the sections they do not know, so that later versions can add some
+//! without breaking them, and reject files requiring a newer reader.
+//!
+//! ```
+//! use wikistra::{path::bidi_dijkstra, snapshot::Snapshot};
+//!
+//! let snapshot = Snapshot::new(vec![10, 20, 30], vec!["A".into(), "B".into(), "C".into()], vec![0, 1, 2, 2], vec![1, 2]).unwrap();
+//! let mut file = vec![];
+//! snapshot.write(&mut file).unwrap();
+//!
+//! let snapshot = Snapshot::read(&file[..]).unwrap();
+//! let backward = snapshot.reverse();
+//! let path = bidi_dijkstra(0, 2, |&n| snapshot.links_from(n).to_vec(), |&n| backward.links_from(n).to_vec());
+//! assert_eq!(path, Some(vec![0, 1, 2]));
+//! assert_eq!(snapshot.title(2), "C");
+//! ```
+
+use std::io::{self, Read, Write};
+
+use thiserror::Error;
+
+const MAGIC: &[u8; 8] = b"WIKISNAP";
+/// Version of the format written by this code
+pub const VERSION: u16 = 1;
+/// Oldest version able to read what this code writes
+const COMPATIBLE: u16 = 1;
+
+const IDS: [u8; 4] = *b"IDS ";
+const TITLES: [u8; 4] = *b"TITL";
+const LINKS: [u8; 4] = *b"LINK";
+
+#[derive(Error, Debug)]
+pub enum SnapshotError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("Not a wikistra snapshot")]
+    NotASnapshot,
+    #[error("The snapshot needs version {0} of the format, this is version {VERSION}")]
+    UnsupportedVersion(u16),
+    #[error("The snapshot is corrupted: {0}")]
+    Corrupted(String),
+}
+
+fn corrupted<S: Into<String>>(reason: S) -> SnapshotError {
+    SnapshotError::Corrupted(reason.into())
+}
+
+/// The link graph of a wiki, with articles numbered densely in `0..len()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    ids: Vec<u32>,
+    titles: Vec<String>,
+    /// The links of article `i` are `targets[offsets[i]..offsets[i + 1]]`
+    offsets: Vec<usize>,
+    targets: Vec<u32>,
+}
+
+impl Snapshot {
+
+    /// A snapshot of the articles with the given sorted IDs and titles, and the links
+    /// of article `i` in `targets[offsets[i]..offsets[i + 1]]`, as dense numbers
+    pub fn new(ids: Vec<u32>, titles: Vec<String>, offsets: Vec<usize>, mut targets: Vec<u32>) -> Result<Self, SnapshotError> {
+        let n = ids.len();
+        if titles.len() != n || offsets.len() != n + 1 || offsets[n] != targets.len() {
+            return Err(corrupted("the sections have different numbers of articles"))
+        }
+        if ids.windows(2).any(|w| w[0] >= w[1]) {
+            return Err(corrupted("the article IDs are not sorted"))
+        }
+        if offsets.windows(2).any(|w| w[0] > w[1]) || targets.iter().any(|&t| t as usize >= n) {
+            return Err(corrupted("invalid links"))
+        }
+        for i in 0..n {
+            let links = &mut targets[offsets[i]..offsets[i + 1]];
+            links.sort_unstable();
+        }
+        Ok(Snapshot { ids, titles, offsets, targets })
+    }
+
+    /// Number of articles
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Number of links
+    pub fn link_count(&self) -> usize {
+        self.targets.len()
+    }
+
+    /// The article ID of a dense number
+    pub fn id(&self, node: u32) -> u32 {
+        self.ids[node as usize]
+    }
+
+    /// The dense number of an article ID
+    pub fn node(&self, id: u32) -> Option<u32> {
+        self.ids.binary_search(&id).ok().map(|i| i as u32)
+    }
+
+    pub fn title(&self, node: u32) -> &str {
+        &self.titles[node as usize]
+    }
+
+    /// The sorted targets of the links of an article
+    pub fn links_from(&self, node: u32) -> &[u32] {
+        &self.targets[self.offsets[node as usize]..self.offsets[node as usize + 1]]
+    }
+
+    /// The same articles with every link reversed, for backward searches
+    pub fn reverse(&self) -> Snapshot {
+        let n = self.len();
+        let mut offsets = vec![0; n + 1];
+        for &target in &self.targets { offsets[target as usize + 1] += 1 }
+        for i in 0..n { offsets[i + 1] += offsets[i] }
+        let mut ends = offsets.clone();
+        let mut targets = vec![0; self.targets.len()];
+        // Sources are visited in order, so every reversed list comes out sorted
+        for source in 0..n as u32 {
+            for &target in self.links_from(source) {
+                targets[ends[target as usize]] = source;
+                ends[target as usize] += 1;
+            }
+        }
+        Snapshot { ids: self.ids.clone(), titles: self.titles.clone(), offsets, targets }
+    }
+
+    pub fn write<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let mut ids = vec![];
+        let mut previous = 0;
+        for &id in &self.ids {
+            put_varint(&mut ids, (id - previous) as u64);
+            previous = id;
+        }
+
+        let mut titles = vec![];
+        for title in &self.titles {
+            put_varint(&mut titles, title.len() as u64);
+            titles.extend_from_slice(title.as_bytes());
+        }
+
+        let mut links = vec![];
+        for node in 0..self.len() as u32 {
+            let targets = self.links_from(node);
+            put_varint(&mut links, targets.len() as u64);
+            let mut previous = 0;
+            for &target in targets {
+                put_varint(&mut links, (target - previous) as u64);
+                previous = target;
+            }
+        }
+
+        let sections = [(IDS, ids), (TITLES, titles), (LINKS, links)];
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&COMPATIBLE.to_le_bytes())?;
+        out.write_all(&(self.len() as u32).to_le_bytes())?;
+        out.write_all(&(sections.len() as u32).to_le_bytes())?;
+        for (tag, contents) in sections {
+            out.write_all(&tag)?;
+            out.write_all(&(contents.len() as u64).to_le_bytes())?;
+            out.write_all(&crc32fast::hash(&contents).to_le_bytes())?;
+            out.write_all(&contents)?;
+        }
+        out.flush()
+    }
+
+    pub fn read<R: Read>(mut input: R) -> Result<Self, SnapshotError> {
+        let mut header = [0; 20];
+        input.read_exact(&mut header).map_err(|_| SnapshotError::NotASnapshot)?;
+        if &header[..8] != MAGIC { return Err(SnapshotError::NotASnapshot) }
+        let compatible = u16::from_le_bytes([header[10], header[11]]);
+        if compatible > VERSION { return Err(SnapshotError::UnsupportedVersion(compatible)) }
+        let n = u32::from_le_bytes(header[12..16].try_into().unwrap()) as usize;
+        let count = u32::from_le_bytes(header[16..20].try_into().unwrap());
+
+        let (mut ids, mut titles, mut links) = (None, None, None);
+        for _ in 0..count {
+            let mut section = [0; 16];
+            input.read_exact(&mut section)?;
+            let tag: [u8; 4] = section[..4].try_into().unwrap();
+            let length = u64::from_le_bytes(section[4..12].try_into().unwrap());
+            let checksum = u32::from_le_bytes(section[12..16].try_into().unwrap());
+
+            let mut contents = vec![];
+            input.by_ref().take(length).read_to_end(&mut contents)?;
+            if contents.len() as u64 != length {
+                return Err(corrupted(format!("section {} is truncated", String::from_utf8_lossy(&tag))))
+            }
+            if crc32fast::hash(&contents) != checksum {
+                return Err(corrupted(format!("checksum mismatch in section {}", String::from_utf8_lossy(&tag))))
+            }
+            match tag {
+                IDS => ids = Some(contents),
+                TITLES => titles = Some(contents),
+                LINKS => links = Some(contents),
+                _ => {},
+            }
+        }
+
+        let missing = |tag: [u8; 4]| corrupted(format!("no section {}", String::from_utf8_lossy(&tag)));
+        let ids = decode_ids(&ids.ok_or_else(|| missing(IDS))?, n)?;
+        let titles = decode_titles(&titles.ok_or_else(|| missing(TITLES))?, n)?;
+        let (offsets, targets) = decode_links(&links.ok_or_else(|| missing(LINKS))?, n)?;
+        Snapshot::new(ids, titles, offsets, targets)
+    }
+}
+
+fn put_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Reads the contents of a section
+struct Cursor<'a> {
+    bytes: &'a [u8],
+}
+
+impl Cursor<'_> {
+
+    fn varint(&mut self) -> Result<u64, SnapshotError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = self.bytes.split_first().ok_or_else(|| corrupted("truncated number"))?;
+            self.bytes = rest;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 { return Ok(value) }
+        }
+        Err(corrupted("number too long"))
+    }
+
+    /// A varint fitting in a `u32`
+    fn u32(&mut self) -> Result<u32, SnapshotError> {
+        self.varint()?.try_into().map_err(|_| corrupted("number too large"))
+    }
+
+    fn finish(&self) -> Result<(), SnapshotError> {
+        if self.bytes.is_empty() { Ok(()) } else { Err(corrupted("trailing bytes in a section")) }
+    }
+}
+
+// Every article takes at least a byte of each section, which bounds the memory
+// reserved for a corrupted article count
+
+fn decode_ids(bytes: &[u8], n: usize) -> Result<Vec<u32>, SnapshotError> {
+    let mut cursor = Cursor { bytes };
+    let mut ids = Vec::with_capacity(n.min(bytes.len()));
+    let mut previous: u32 = 0;
+    for _ in 0..n {
+        previous = previous.checked_add(cursor.u32()?).ok_or_else(|| corrupted("article ID too large"))?;
+        ids.push(previous);
+    }
+    cursor.finish()?;
+    Ok(ids)
+}
+
+fn decode_titles(bytes: &[u8], n: usize) -> Result<Vec<String>, SnapshotError> {
+    let mut cursor = Cursor { bytes };
+    let mut titles = Vec::with_capacity(n.min(bytes.len()));
+    for _ in 0..n {
+        let length = cursor.varint()? as usize;
+        if length > cursor.bytes.len() { return Err(corrupted("truncated title")) }
+        let (title, rest) = cursor.bytes.split_at(length);
+        titles.push(String::from_utf8(title.to_vec()).map_err(|_| corrupted("title is not UTF-8"))?);
+        cursor.bytes = rest;
+    }
+    cursor.finish()?;
+    Ok(titles)
+}
+
+fn decode_links(bytes: &[u8], n: usize) -> Result<(Vec<usize>, Vec<u32>), SnapshotError> {
+    let mut cursor = Cursor { bytes };
+    let mut offsets = Vec::with_capacity(n.min(bytes.len()) + 1);
+    let mut targets = vec![];
+    offsets.push(0);
+    for _ in 0..n {
+        let degree = cursor.varint()?;
+        let mut previous: u32 = 0;
+        for _ in 0..degree {
+            previous = previous.checked_add(cursor.u32()?).ok_or_else(|| corrupted("invalid link"))?;
+            targets.push(previous);
+        }
+        offsets.push(targets.len());
+    }
+    cursor.finish()?;
+    Ok((offsets, targets))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample() -> Snapshot {
+        let titles = ["Paris", "Zürich", "Empty"].map(String::from).to_vec();
+        Snapshot::new(vec![3, 300, 70_000], titles, vec![0, 2, 3, 3], vec![2, 1, 0]).unwrap()
+    }
+
+    #[test]
+    fn roundtrip() {
+        let snapshot = sample();
+        let mut file = vec![];
+        snapshot.write(&mut file).unwrap();
+
+        let read = Snapshot::read(&file[..]).unwrap();
+        assert_eq!(read, snapshot);
+        assert_eq!(read.links_from(0), [1, 2]);
+        assert_eq!(read.node(300), Some(1));
+        assert_eq!(read.title(1), "Zürich");
+        assert_eq!(read.reverse().links_from(0), [1]);
+
+        // Unknown sections from later versions are skipped
+        let mut extended = file.clone();
+        extended[16] += 1;
+        extended.extend_from_slice(b"NEW ");
+        extended.extend_from_slice(&2u64.to_le_bytes());
+        extended.extend_from_slice(&crc32fast::hash(b"xy").to_le_bytes());
+        extended.extend_from_slice(b"xy");
+        assert_eq!(Snapshot::read(&extended[..]).unwrap(), snapshot);
+    }
+
+    #[test]
+    fn invalid_snapshots() {
+        let mut file = vec![];
+        sample().write(&mut file).unwrap();
+
+        assert!(matches!(Snapshot::read(&b"WIKISNIP"[..]), Err(SnapshotError::NotASnapshot)));
+
+        let mut newer = file.clone();
+        newer[10] = 2;
+        assert!(matches!(Snapshot::read(&newer[..]), Err(SnapshotError::UnsupportedVersion(2))));
+
+        let mut flipped = file.clone();
+        *flipped.last_mut().unwrap() ^= 1;
+        assert!(matches!(Snapshot::read(&flipped[..]), Err(SnapshotError::Corrupted(_))));
+
+        assert!(matches!(Snapshot::read(&file[..file.len() - 1]), Err(SnapshotError::Corrupted(_))));
+
+        // An article count beyond what the sections hold fails without reserving memory for it
+        for byte in 12..16 {
+            let mut huge = file.clone();
+            huge[byte] = 0xff;
+            assert!(matches!(Snapshot::read(&huge[..]), Err(SnapshotError::Corrupted(_))));
+        }
+        assert!(Snapshot::new(vec![2, 1], vec![String::new(); 2], vec![0, 0, 0], vec![]).is_err());
+        assert!(Snapshot::new(vec![1], vec![String::new()], vec![0, 1], vec![5]).is_err());
+    }
+}
@@ -1,19 +1,159 @@
 //! Utilities for dowloading the mysql dumps
 
-use color_eyre::{Result, eyre::eyre};
-use indicatif::{ProgressBar, ProgressStyle, ProgressState};
-use std::{fs::File, io::Seek, ops::RangeInclusive, os::unix::prelude::MetadataExt};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle, ProgressState};
+use once_cell::sync::OnceCell;
+use sha1::{Digest, Sha1};
+use std::{fs::File, io::{self, BufReader, Read, Seek, Write}, ops::RangeInclusive, os::unix::prelude::MetadataExt, sync::Mutex, thread};
 use ureq::{self, Response};
 
-static NAMES: [&str; 3] = ["page", "redirect", "pagelinks"];
+use crate::{error::{Result, WikistraError}, timings::{Phase, Timed}};
+
+static NAMES: [&str; 9] = ["page", "redirect", "pagelinks", "linktarget", "categorylinks", "templatelinks", "imagelinks", "externallinks", "geo_tags"];
+
+/// The tables needed for path searches come first in `NAMES`, the others are optional
+pub const REQUIRED: usize = 4;
+
+static DATE: OnceCell<String> = OnceCell::new();
+
+/// Read and download the dumps of a date, as `YYYYMMDD`, rather than the latest ones.
+/// Only the first call has an effect.
+pub fn set_date(date: &str) {
+    let _ = DATE.set(date.to_owned());
+}
+
+/// The date of the dumps, `latest` unless set with `set_date`
+pub fn date() -> &'static str {
+    DATE.get().map_or("latest", String::as_str)
+}
+
+/// Check a dump date given on the command line: `latest`, or a date as `YYYYMMDD`
+pub fn parse_date(date: &str) -> std::result::Result<String, String> {
+    match date == "latest" || (date.len() == 8 && date.bytes().all(|b| b.is_ascii_digit())) {
+        true => Ok(date.to_owned()),
+        false => Err("expected `latest` or a date as YYYYMMDD".to_owned()),
+    }
+}
 
 pub fn files(wikiname: &str) -> impl Iterator<Item = String> + '_ {
-    NAMES.iter()
-        .map(move |n| format!("{}-latest-{}.sql.gz", wikiname, n))
+    NAMES.iter().map(move |n| file(wikiname, n))
 }
 
 pub fn urls(wikiname: &str) -> impl Iterator<Item = String> + '_ {
-    NAMES.iter().map(move |f| format!("https://dumps.wikimedia.org/{0}/latest/{0}-latest-{1}.sql.gz", wikiname, f))
+    NAMES.iter().map(move |n| url(wikiname, n))
+}
+
+/// The dump of a table, such as `page`, as in `enwiki-latest-page.sql.gz`
+pub fn file(wikiname: &str, table: &str) -> String {
+    format!("{}-{}-{}.sql.gz", wikiname, date(), table)
+}
+
+/// The URL of a file of the dumps on the mirror
+fn dump_url(wikiname: &str, file: &str) -> String {
+    format!("https://dumps.wikimedia.org/{}/{}/{}", wikiname, date(), file)
+}
+
+fn url(wikiname: &str, table: &str) -> String {
+    dump_url(wikiname, &file(wikiname, table))
+}
+
+/// Copies what is read from `source` into `copy`
+struct Tee<R, W> {
+    source: R,
+    copy: W,
+}
+
+impl <R: Read, W: Write> Read for Tee<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.source.read(buf)?;
+        self.copy.write_all(&buf[..n])?;
+        Ok(n)
+    }
+}
+
+/// Stream the dump of a table from the mirror, without storing it unless `keep`
+/// is set. Returns the compressed dump, and its length if known.
+pub fn stream(wikiname: &str, table: &str, keep: bool) -> Result<(Box<dyn Read + Send>, Option<u64>)> {
+    let response = ureq::get(&url(wikiname, table)).call()?;
+    let length = response.header("Content-Length").and_then(|l| l.parse().ok());
+    let source = Timed::new(response.into_reader(), Phase::Download);
+    let source: Box<dyn Read + Send> = match keep {
+        true => Box::new(Tee { source, copy: File::create(file(wikiname, table))? }),
+        false => Box::new(source),
+    };
+    Ok((source, length))
+}
+
+/// The dump of the namespace names and aliases of the wiki
+pub fn namespaces_file(wikiname: &str) -> String {
+    format!("{}-{}-siteinfo-namespaces.json.gz", wikiname, date())
+}
+
+fn namespaces_url(wikiname: &str) -> String {
+    dump_url(wikiname, &namespaces_file(wikiname))
+}
+
+/// The SHA-1 checksums of the dumps, one `<sha1>  <file>` line per file.
+/// The latest dumps are listed under their date rather than `latest`.
+fn checksums_file(wikiname: &str) -> String {
+    format!("{}-{}-sha1sums.txt", wikiname, date())
+}
+
+fn checksums_url(wikiname: &str) -> String {
+    dump_url(wikiname, &checksums_file(wikiname))
+}
+
+/// The date of the downloaded dumps, as `YYYYMMDD`. The latest dumps are dated by
+/// the names of the files in their checksums, saved by `download`.
+pub fn resolved_date(wikiname: &str) -> Option<String> {
+    if date() != "latest" { return Some(date().to_owned()) }
+    let sums = std::fs::read_to_string(checksums_file(wikiname)).ok()?;
+    date_in(&sums, wikiname)
+}
+
+/// The date in the file names of a checksums file
+fn date_in(sums: &str, wikiname: &str) -> Option<String> {
+    let file = sums.lines().next()?.split_whitespace().nth(1)?;
+    let date = file.strip_prefix(wikiname)?.strip_prefix('-')?.split('-').next()?;
+    parse_date(date).ok().filter(|date| date != "latest")
+}
+
+/// The checksum of a downloaded file, such as `enwiki-latest-page.sql.gz`, in the
+/// contents of a checksums file listing it as `enwiki-20240601-page.sql.gz`
+fn checksum_of<'s>(sums: &'s str, wikiname: &str, path: &str) -> Option<&'s str> {
+    // Files are compared without their date
+    fn undated<'f>(file: &'f str, wikiname: &str) -> Option<&'f str> {
+        Some(file.strip_prefix(wikiname)?.strip_prefix('-')?.split_once('-')?.1)
+    }
+    let file = std::path::Path::new(path).file_name()?.to_str()?;
+    let name = undated(file, wikiname)?;
+    sums.lines().find_map(|line| {
+        let (sum, file) = line.split_once(char::is_whitespace)?;
+        (undated(file.trim(), wikiname)? == name).then_some(sum)
+    })
+}
+
+/// Hex-encoded SHA-1 of a file
+fn sha1_file(path: &str) -> io::Result<String> {
+    let mut hasher = Sha1::new();
+    io::copy(&mut BufReader::new(File::open(path)?), &mut hasher)?;
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Check a downloaded file against its published checksum, if it is listed.
+/// A file that does not match is renamed with a `.bad` suffix, so that the next
+/// download starts over rather than resuming it.
+fn verify_file(sums: &str, wikiname: &str, path: &str) -> Result<()> {
+    let Some(expected) = checksum_of(sums, wikiname, path) else {
+        eprintln!("Warning: no published checksum for {}, not verified", path);
+        return Ok(())
+    };
+    let actual = sha1_file(path)?;
+    if actual != expected.to_lowercase() {
+        let bad = format!("{}.bad", path);
+        std::fs::rename(path, &bad)?;
+        return Err(WikistraError::Checksum { path: bad, expected: expected.to_lowercase(), actual });
+    }
+    Ok(())
 }
 
 /// A parsed HTTP Content-Range header
@@ -25,17 +165,18 @@ pub struct Resume<'s> {
 
 /// Parse an HTTP Content-Range header if present in the request
 fn should_resume(res: &Response) -> Result<Option<Resume<'_>>> {
-    let Some(range) = res.header("Content-Range") else { return Ok(None) };
-    eprintln!("Range is {}", range);
-    let (unit, range) = range.split_once(|c: char| c.is_whitespace())
-        .ok_or(eyre!("Could not parse Content-Range header: no space"))?;
+    let Some(header) = res.header("Content-Range") else { return Ok(None) };
+    eprintln!("Range is {}", header);
+    let invalid = || WikistraError::Header("Content-Range", header.to_owned());
+    let (unit, range) = header.split_once(|c: char| c.is_whitespace())
+        .ok_or_else(invalid)?;
     let (range, total) = range.split_once('/')
-        .ok_or(eyre!("Could not parse Content-Range header: no slash"))?;
-    let total = if total == "*" { None } else { Some(total.parse()?) };
+        .ok_or_else(invalid)?;
+    let total = if total == "*" { None } else { Some(total.parse().map_err(|_| invalid())?) };
     let range = if range == "*" { None } else {
         let (start, end) = range.split_once('-')
-            .ok_or(eyre!("Could not parse Content-Range header: no dash"))?;
-            Some(start.parse()? ..= end.parse()?)
+            .ok_or_else(invalid)?;
+            Some(start.parse().map_err(|_| invalid())? ..= end.parse().map_err(|_| invalid())?)
     };
 
     Ok(Some(Resume { unit, total, range }))
@@ -47,7 +188,7 @@ pub fn is_fresh(agent: &ureq::Agent, url: &str, path: &str) -> Option<()> {
     let local = file.metadata().ok()?
         .size();
 
-    let remote = agent.head(url)
+    let remote: u64 = agent.head(url)
         .call()
         .ok()?
         .header("Content-Length")?
@@ -57,8 +198,81 @@ pub fn is_fresh(agent: &ureq::Agent, url: &str, path: &str) -> Option<()> {
 
 }
 
-/// Download the source files. Resuming supported.
-pub fn download(wikiname: &str) -> Result<()> { 
+/// Download the source files of the given tables, by their index in `files`, and the
+/// namespace names, `--io-threads` at a time. Resuming supported. With `verify`, every
+/// file is checked against the published checksums once downloaded, so that a resumed
+/// download of a dump that changed in the meantime is not taken for a complete one.
+pub fn download(wikiname: &str, tables: &[usize], verify: bool) -> Result<()> { 
+
+    let agent = ureq::AgentBuilder::new()
+        .build();
+    // The checksums are kept, as they also tell the date of the latest dumps
+    let sums = match agent.get(&checksums_url(wikiname)).call().map_err(WikistraError::from).and_then(|r| Ok(r.into_string()?)) {
+        Ok(sums) => {
+            std::fs::write(checksums_file(wikiname), &sums)?;
+            Some(sums)
+        }
+        Err(e) => {
+            if verify { eprintln!("Warning: could not fetch the checksums of the dumps, not verifying them: {}", e) }
+            None
+        }
+    };
+    let sums = sums.filter(|_| verify);
+    let sources = urls(wikiname).zip(files(wikiname))
+        .enumerate()
+        .filter(|(i, _)| tables.contains(i))
+        .map(|(_, source)| source)
+        .chain([(namespaces_url(wikiname), namespaces_file(wikiname))]);
+
+    let queue = Mutex::new(sources);
+    let next = || queue.lock().unwrap().next();
+    let bars = MultiProgress::new();
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..crate::limits::io_threads())
+            .map(|_| scope.spawn(|| -> Result<()> {
+                while let Some((url, path)) = next() {
+                    fetch(&agent, &url, &path, &bars)?;
+                    if let Some(sums) = &sums { verify_file(sums, wikiname, &path)? }
+                }
+                Ok(())
+            }))
+            .collect();
+        workers.into_iter()
+            .try_for_each(|worker| worker.join().expect("download thread panicked"))
+    })
+}
+
+/// The URL of a prebuilt database archive, published under `base` as
+/// `<base>/<wikiname>/<date>/<wikiname>-db.tar.gz`
+pub fn prebuilt_url(base: &str, wikiname: &str, date: &str) -> String {
+    format!("{}/{1}/{2}/{1}-db.tar.gz", base.trim_end_matches('/'), wikiname, date)
+}
+
+/// Download a prebuilt database archive, and check it against the SHA-256 published
+/// next to it, in a `.sha256` file. Returns the path of the downloaded archive.
+pub fn download_prebuilt(base: &str, wikiname: &str, date: &str) -> Result<String> {
+    let agent = ureq::AgentBuilder::new()
+        .build();
+    let url = prebuilt_url(base, wikiname, date);
+    let path = format!("{}-db.tar.gz", wikiname);
+
+    let expected = agent.get(&format!("{}.sha256", url)).call()?.into_string()?;
+    let expected = expected.split_whitespace().next()
+        .ok_or_else(|| WikistraError::EmptyChecksum(url.clone()))?
+        .to_lowercase();
+
+    fetch(&agent, &url, &path, &MultiProgress::new())?;
+    let actual = crate::pack::sha256_file(&path)?;
+    if actual != expected {
+        let bad = format!("{}.bad", path);
+        std::fs::rename(&path, &bad)?;
+        return Err(WikistraError::Checksum { path: bad, expected, actual });
+    }
+    Ok(path)
+}
+
+/// Download a file, resuming a previous partial download, with a progress bar in `bars`
+fn fetch(agent: &ureq::Agent, url: &str, path: &str, bars: &MultiProgress) -> Result<()> {
 
     let style = ProgressStyle::with_template("[{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta})").unwrap()
         .with_key("eta", |state: &ProgressState, w: &mut dyn std::fmt::Write| { 
@@ -66,52 +280,88 @@ pub fn download(wikiname: &str) -> Result<()> {
         })
         .progress_chars("=> ");
 
-    let agent = ureq::AgentBuilder::new()
-        .build();
-    for (url, path) in urls(wikiname).zip(files(wikiname)) {
+    if is_fresh(agent, url, path).is_some() {
+        eprintln!("{} already present.", path);
+        return Ok(());
+    }
 
-        if is_fresh(&agent, &url, &path).is_some() {
-            eprintln!("{} already present.", path);
-            continue;
-        }
+    let mut file = File::options()
+        .create(true)
+        .append(true)
+        .open(path)?;
 
-        let mut file = File::options()
-            .create(true)
-            .append(true)
-            .open(&path)?;
+    file.seek(std::io::SeekFrom::End(0))?;
+    let resume = file.stream_position()?;
 
-        file.seek(std::io::SeekFrom::End(0))?;
-        let resume = file.stream_position()?;
+    eprintln!("{} is {} bytes long", &path, resume);
 
-        eprintln!("{} is {} bytes long", &path, resume);
+    let response = agent.get(url)
+        .set("Range", &format!("bytes={}-", resume))
+        .call()?;
 
-        let response = agent.get(&url)
-            .set("Range", &format!("bytes={}-", resume))
-            .call()?;
+    let pos = if let Some(Resume { range: Some(r), ..}) = should_resume(&response)? {
+        *r.start()
+    } else {
+        0
+    };
 
-        let pos = if let Some(Resume { range: Some(r), ..}) = should_resume(&response)? {
-            *r.start()
-        } else {
-            0
-        };
+    eprintln!("Starting download of {} at offset {}", &path, pos);
 
-        eprintln!("Starting download of {} at offset {}", &path, pos);
+    file.seek(std::io::SeekFrom::Start(pos))?;
 
-        file.seek(std::io::SeekFrom::Start(pos))?;
+    let length: Option<u64> = response.header("Content-Length")
+        .map(|l| l.parse().map_err(|_| WikistraError::Header("Content-Length", l.to_owned())))
+        .transpose()?;
 
-        let length: Option<u64> = response.header("Content-Length").map(str::parse).transpose()?;
+    let progress = bars.add(length
+        .map(ProgressBar::new)
+        .unwrap_or(ProgressBar::new_spinner())
+        .with_message(format!("Downloading {}", &path))
+        .with_style(style));
 
-        let progress = length
-            .map(|l| ProgressBar::new(l))
-            .unwrap_or(ProgressBar::new_spinner())
-            .with_message(format!("Downloading {}", &path))
-            .with_style(style.clone());
+    let mut source = progress.wrap_read(Timed::new(response.into_reader(), Phase::Download));
+    std::io::copy(&mut source, &mut file)?;
 
-        let mut source = progress.wrap_read(response.into_reader());
-        std::io::copy(&mut source, &mut file)?;
+    progress.finish_with_message("Done.");
+    Ok(())
+}
 
-        progress.finish_with_message("Done.")
+#[cfg(test)]
+mod test {
+    use super::*;
 
+    #[test]
+    fn sample_checksums() {
+        let sums = "c8a8e2d3b0a4f1e6a3f2d6b1e0c9a8b7c6d5e4f3  enwiki-20240601-page.sql.gz\n\
+            0123456789abcdef0123456789abcdef01234567  enwiki-20240601-pages-articles.xml.bz2\n\
+            89abcdef0123456789abcdef0123456789abcdef  enwiki-20240601-siteinfo-namespaces.json.gz\n";
+        assert_eq!(checksum_of(sums, "enwiki", "enwiki-latest-page.sql.gz"), Some("c8a8e2d3b0a4f1e6a3f2d6b1e0c9a8b7c6d5e4f3"));
+        assert_eq!(checksum_of(sums, "enwiki", &namespaces_file("enwiki")), Some("89abcdef0123456789abcdef0123456789abcdef"));
+        assert_eq!(checksum_of(sums, "enwiki", "enwiki-latest-redirect.sql.gz"), None);
+        assert_eq!(checksum_of(sums, "frwiki", "frwiki-latest-page.sql.gz"), None);
+        assert_eq!(checksum_of(sums, "enwiki", "enwiki-20240601-page.sql.gz"), Some("c8a8e2d3b0a4f1e6a3f2d6b1e0c9a8b7c6d5e4f3"));
+
+        let dir = std::env::temp_dir().join(format!("wikistra-sums-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("enwiki-latest-page.sql.gz");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, b"abc").unwrap();
+        // SHA-1 of "abc"
+        let good = "a9993e364706816aba3e25717850c26c9cd0d89d  enwiki-20240601-page.sql.gz\n";
+        assert!(verify_file(good, "enwiki", path).is_ok());
+        assert!(std::path::Path::new(path).exists());
+        let bad = "0000000000000000000000000000000000000000  enwiki-20240601-page.sql.gz\n";
+        assert!(matches!(verify_file(bad, "enwiki", path), Err(WikistraError::Checksum { .. })));
+        assert!(!std::path::Path::new(path).exists());
+        assert!(std::path::Path::new(&format!("{}.bad", path)).exists());
+        std::fs::remove_dir_all(dir).unwrap();
+
+        assert_eq!(date_in(sums, "enwiki").as_deref(), Some("20240601"));
+        assert_eq!(date_in(sums, "frwiki"), None);
+        assert_eq!(date_in("", "enwiki"), None);
+
+        assert_eq!(parse_date("20240501").as_deref(), Ok("20240501"));
+        assert!(parse_date("latest").is_ok());
+        assert!(parse_date("2024-05-01").is_err());
     }
-    Ok(())
 }
@@ -0,0 +1,340 @@
+//! Additional tables to index, declared in a TOML file
+//!
+//! Each `[[table]]` names a dump, the columns of its rows to store, and filters
+//! the rows must pass. For instance, to map articles to their Wikidata item:
+//!
+//! ```toml
+//! [[table]]
+//! name = "wikidata"
+//! dump = "page_props"     # read from ./<wikiname>-latest-page_props.sql.gz
+//! key = ["id"]
+//! columns = [
+//!     { name = "id", column = 1, type = "integer" },
+//!     { name = "item", column = 3 },
+//! ]
+//! filters = [{ column = 2, equals = "wikibase_item" }]
+//! ```
+//!
+//! Columns are numbered from 1, as in `parse --profile`. A column with
+//! `resolve = true` holds a title, stored as the ID of the article it leads to.
+
+use std::fs;
+
+use regex::Regex;
+use rusqlite::types::Value as SqlValue;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{error::WikistraError, sql::Value};
+
+#[derive(Error, Debug)]
+pub enum SpecError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Invalid index spec: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("Invalid index spec: {0}")]
+    Invalid(String),
+    #[error("Invalid regex in index spec: {0}")]
+    Regex(#[from] regex::Error),
+}
+
+impl From<SpecError> for WikistraError {
+    fn from(e: SpecError) -> Self {
+        WikistraError::Spec(e.to_string())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Spec {
+    #[serde(rename = "table", default)]
+    pub tables: Vec<TableSpec>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TableSpec {
+    /// Name of the SQLite table
+    pub name: String,
+    /// Name of the dump, as in `<wikiname>-<date>-<dump>.sql.gz`
+    pub dump: String,
+    /// Columns forming the primary key. Later rows replace earlier ones with the same key.
+    #[serde(default)]
+    pub key: Vec<String>,
+    pub columns: Vec<ColumnSpec>,
+    #[serde(default)]
+    pub filters: Vec<FilterSpec>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ColumnSpec {
+    pub name: String,
+    /// Position of the column in the rows of the dump, from 1
+    pub column: usize,
+    #[serde(rename = "type", default)]
+    pub kind: ColumnType,
+    /// Store the ID of the article that the title in this column leads to
+    #[serde(default)]
+    pub resolve: bool,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    Integer,
+    Real,
+    #[default]
+    Text,
+    Blob,
+}
+
+impl ColumnType {
+    pub fn sql(self) -> &'static str {
+        match self {
+            ColumnType::Integer => "integer",
+            ColumnType::Real => "real",
+            ColumnType::Text => "text",
+            ColumnType::Blob => "blob",
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct FilterSpec {
+    /// Position of the tested column in the rows of the dump, from 1
+    pub column: usize,
+    #[serde(flatten)]
+    pub test: Test,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Test {
+    /// The value is equal to this one
+    Equals(toml::Value),
+    /// The value is one of these
+    In(Vec<toml::Value>),
+    /// The value is a string matching this regex
+    Matches(String),
+}
+
+/// A filter ready to test the rows of a dump
+pub struct Filter {
+    column: usize,
+    test: Compiled,
+}
+
+enum Compiled {
+    Equals(Vec<toml::Value>),
+    Matches(Regex),
+}
+
+impl Spec {
+
+    /// Read and check a spec file
+    pub fn read(path: &str) -> Result<Self, SpecError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, SpecError> {
+        let spec: Spec = toml::from_str(text)?;
+        if spec.tables.is_empty() {
+            return Err(SpecError::Invalid("no [[table]] declared".into()));
+        }
+        for table in &spec.tables {
+            table.check()?;
+        }
+        Ok(spec)
+    }
+
+    /// Check that no table replaces one of the database that a spec did not create,
+    /// such as the tables of wikistra itself. `taken` lists these tables, as they
+    /// were before any spec ran.
+    pub fn check_tables(&self, taken: &[String]) -> Result<(), SpecError> {
+        match self.tables.iter().find(|t| taken.iter().any(|name| name.eq_ignore_ascii_case(&t.name))) {
+            Some(table) => Err(SpecError::Invalid(format!("table {}: this table is not from an index spec, and cannot be replaced", table.name))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl TableSpec {
+
+    fn check(&self) -> Result<(), SpecError> {
+        let invalid = |message: String| Err(SpecError::Invalid(format!("table {}: {}", self.name, message)));
+        let identifier = |name: &str| name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+        if !identifier(&self.name) {
+            return invalid("table names may only hold letters, digits and underscores".into());
+        }
+        if self.name.to_lowercase().starts_with("sqlite_") {
+            return invalid("this table name is reserved by SQLite".into());
+        }
+        if self.columns.is_empty() {
+            return invalid("no columns declared".into());
+        }
+        for (i, column) in self.columns.iter().enumerate() {
+            if !identifier(&column.name) {
+                return invalid(format!("invalid column name {:?}", column.name));
+            }
+            if self.columns[..i].iter().any(|c| c.name.eq_ignore_ascii_case(&column.name)) {
+                return invalid(format!("column {} declared twice", column.name));
+            }
+            if column.column == 0 {
+                return invalid(format!("column {}: positions start at 1", column.name));
+            }
+        }
+        if let Some(key) = self.key.iter().find(|k| !self.columns.iter().any(|c| &c.name == *k)) {
+            return invalid(format!("key column {} is not declared", key));
+        }
+        if self.filters.iter().any(|f| f.column == 0) {
+            return invalid("filter positions start at 1".into());
+        }
+        Ok(())
+    }
+
+    /// The dump file of the table
+    pub fn file(&self, wikiname: &str) -> String {
+        format!("./{}", crate::source::file(wikiname, &self.dump))
+    }
+
+    pub fn filters(&self) -> Result<Vec<Filter>, SpecError> {
+        self.filters.iter().map(|filter| {
+            let test = match &filter.test {
+                Test::Equals(value) => Compiled::Equals(vec![value.clone()]),
+                Test::In(values) => Compiled::Equals(values.clone()),
+                Test::Matches(regex) => Compiled::Matches(Regex::new(regex)?),
+            };
+            Ok(Filter { column: filter.column - 1, test })
+        }).collect()
+    }
+
+    /// Convert a row of the dump into the values to store, calling `resolve` on the
+    /// titles of `resolve` columns. `None` if a title could not be resolved.
+    pub fn row(&self, row: &[Value], resolve: impl Fn(&str) -> Option<i64>) -> Option<Vec<SqlValue>> {
+        self.columns.iter().map(|column| {
+            let value = row.get(column.column - 1).unwrap_or(&Value::Null);
+            if column.resolve {
+                return match value {
+                    Value::Null => Some(SqlValue::Null),
+                    other => resolve(&text(other)?).map(SqlValue::Integer),
+                };
+            }
+            Some(match (value, column.kind) {
+                (Value::Null, _) => SqlValue::Null,
+                (Value::Integer(i), _) => SqlValue::Integer(*i),
+                (Value::Float(f), _) => SqlValue::Real(*f),
+                (Value::Bytes(b), ColumnType::Blob) => SqlValue::Blob(b.clone()),
+                (Value::String(s), ColumnType::Blob) => SqlValue::Blob(s.as_bytes().to_vec()),
+                // Numbers stored as strings are converted by the type affinity of the column
+                (other, _) => SqlValue::Text(text(other)?),
+            })
+        }).collect()
+    }
+}
+
+impl Filter {
+    pub fn matches(&self, row: &[Value]) -> bool {
+        let value = row.get(self.column).unwrap_or(&Value::Null);
+        match &self.test {
+            Compiled::Equals(values) => values.iter().any(|expected| equals(value, expected)),
+            Compiled::Matches(regex) => text(value).is_some_and(|s| regex.is_match(&s)),
+        }
+    }
+}
+
+/// The text of a string value
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Bytes(b) => Some(String::from_utf8_lossy(b).into_owned()),
+        _ => None,
+    }
+}
+
+fn equals(value: &Value, expected: &toml::Value) -> bool {
+    match (value, expected) {
+        (Value::Integer(a), toml::Value::Integer(b)) => a == b,
+        (Value::Integer(a), toml::Value::Float(b)) => *a as f64 == *b,
+        (Value::Float(a), toml::Value::Float(b)) => a == b,
+        (Value::Float(a), toml::Value::Integer(b)) => *a == *b as f64,
+        (Value::String(_) | Value::Bytes(_), toml::Value::String(b)) => text(value).as_deref() == Some(b.as_str()),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SAMPLE: &str = r#"
+        [[table]]
+        name = "wikidata"
+        dump = "page_props"
+        key = ["id"]
+        columns = [
+            { name = "id", column = 1, type = "integer" },
+            { name = "item", column = 3 },
+        ]
+        filters = [{ column = 2, equals = "wikibase_item" }]
+
+        [[table]]
+        name = "langlinks"
+        dump = "langlinks"
+        columns = [
+            { name = "from", column = 1, type = "integer" },
+            { name = "lang", column = 2 },
+            { name = "to", column = 3, resolve = true },
+        ]
+        filters = [{ column = 2, in = ["de", "fr"] }, { column = 3, matches = "^[A-Z]" }]
+    "#;
+
+    fn row(values: &[&str]) -> Vec<Value> {
+        values.iter().map(|v| match v.parse() {
+            Ok(i) => Value::Integer(i),
+            Err(_) => Value::String(v.to_string()),
+        }).collect()
+    }
+
+    #[test]
+    fn sample_spec() {
+        let spec = Spec::parse(SAMPLE).unwrap();
+        let [wikidata, langlinks] = &spec.tables[..] else { panic!("2 tables expected") };
+        assert_eq!(wikidata.file("enwiki"), "./enwiki-latest-page_props.sql.gz");
+        assert_eq!(wikidata.columns[1].kind, ColumnType::Text);
+
+        let filters = wikidata.filters().unwrap();
+        let item = row(&["12", "wikibase_item", "Q90"]);
+        assert!(filters.iter().all(|f| f.matches(&item)));
+        assert!(!filters[0].matches(&row(&["12", "page_image", "Paris.jpg"])));
+        assert_eq!(wikidata.row(&item, |_| None), Some(vec![SqlValue::Integer(12), SqlValue::Text("Q90".into())]));
+
+        let filters = langlinks.filters().unwrap();
+        assert!(filters.iter().all(|f| f.matches(&row(&["12", "fr", "Paris"]))));
+        assert!(!filters.iter().all(|f| f.matches(&row(&["12", "it", "Parigi"]))));
+        assert!(!filters.iter().all(|f| f.matches(&row(&["12", "de", "paris"]))));
+
+        let resolve = |title: &str| (title == "Paris").then_some(7);
+        assert_eq!(langlinks.row(&row(&["12", "fr", "Paris"]), resolve).unwrap()[2], SqlValue::Integer(7));
+        assert_eq!(langlinks.row(&row(&["12", "fr", "Lyon"]), resolve), None);
+    }
+
+    #[test]
+    fn invalid_specs() {
+        let table = |body: &str| format!("[[table]]\nname = \"t\"\ndump = \"d\"\n{}", body);
+        assert!(matches!(Spec::parse(""), Err(SpecError::Invalid(_))));
+        assert!(matches!(Spec::parse(&table("columns = []")), Err(SpecError::Invalid(_))));
+        assert!(matches!(Spec::parse(&table("columns = [{ name = \"a\", column = 0 }]")), Err(SpecError::Invalid(_))));
+        assert!(matches!(Spec::parse(&table("columns = [{ name = \"a b\", column = 1 }]")), Err(SpecError::Invalid(_))));
+        assert!(matches!(Spec::parse(&table("key = [\"b\"]\ncolumns = [{ name = \"a\", column = 1 }]")), Err(SpecError::Invalid(_))));
+        assert!(matches!(Spec::parse(&table("columns = [{ name = \"a\", column = 1, kind = \"text\" }]")), Err(SpecError::Toml(_))));
+        assert!(matches!(Spec::parse("[[table]]\nname = \"sqlite_stat1\"\ndump = \"d\"\ncolumns = [{ name = \"a\", column = 1 }]"), Err(SpecError::Invalid(_))));
+        let spec = Spec::parse(&table("columns = [{ name = \"a\", column = 1 }]")).unwrap();
+        assert!(spec.check_tables(&["page".to_owned(), "rank".to_owned()]).is_ok());
+        assert!(matches!(spec.check_tables(&["page".to_owned(), "T".to_owned()]), Err(SpecError::Invalid(_))));
+    }
+}
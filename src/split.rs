@@ -0,0 +1,94 @@
+//! Splitting multi-table dumps into one CSV file per table
+
+use std::{collections::BTreeMap, io::{BufRead, Write}};
+
+use crate::{error::Result, sql::{Charset, Loader, Value}};
+
+/// Output of one table of the dump
+pub struct Table<W> {
+    pub writer: W,
+    pub rows: u64,
+}
+
+/// Write a value as a CSV field. NULL is written as an empty field.
+fn write_field<W: Write>(out: &mut W, value: &Value) -> Result<(), std::io::Error> {
+    let text = match value {
+        Value::Null => return Ok(()),
+        Value::Integer(n) => return write!(out, "{}", n),
+        Value::Float(x) => return write!(out, "{}", x),
+        Value::String(s) => s.as_str().into(),
+        Value::Bytes(b) => String::from_utf8_lossy(b),
+    };
+
+    if text.contains([',', '"', '\n', '\r']) {
+        write!(out, "\"{}\"", text.replace('"', "\"\""))
+    } else {
+        out.write_all(text.as_bytes())
+    }
+}
+
+/// Write a row as a line of CSV
+pub fn write_row<W: Write>(out: &mut W, row: &[Value]) -> Result<(), std::io::Error> {
+    for (i, value) in row.iter().enumerate() {
+        if i > 0 { out.write_all(b",")? }
+        write_field(out, value)?;
+    }
+    out.write_all(b"\n")
+}
+
+/// Route the rows of every `INSERT INTO` statement of a dump to a per-table output.
+/// 
+/// `open` is called once for each table name, the first time it is encountered.
+/// Statements other than inserts are skipped; mysqldump writes each insert on its own line.
+pub fn split<R, W, F>(mut source: R, charset: Charset, mut open: F) -> Result<BTreeMap<String, Table<W>>>
+where
+    R: BufRead,
+    W: Write,
+    F: FnMut(&str) -> Result<W, std::io::Error>,
+{
+    let mut tables: BTreeMap<String, Table<W>> = BTreeMap::new();
+    let mut line = vec![];
+
+    loop {
+        line.clear();
+        if source.read_until(b'\n', &mut line)? == 0 { break }
+        if !line.starts_with(b"INSERT INTO") { continue }
+
+        let mut loader = Loader::new(&line[..], charset);
+        while let Some(row) = loader.next() {
+            let row = row?;
+            let name = loader.table().unwrap_or_default();
+
+            if !tables.contains_key(name) {
+                tables.insert(name.to_owned(), Table { writer: open(name)?, rows: 0 });
+            }
+            let table = tables.get_mut(name).unwrap();
+
+            write_row(&mut table.writer, &row)?;
+            table.rows += 1;
+        }
+    }
+
+    Ok(tables)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn split_two_tables() {
+        let dump = b"-- MySQL dump\n\
+            CREATE TABLE `a` (`x` int);\n\
+            INSERT INTO `a` VALUES (1,'foo'),(2,'b,\"r');\n\
+            INSERT INTO `b` VALUES (3,NULL);\n\
+            INSERT INTO `a` VALUES (4,'baz');\n";
+
+        let tables = split(&dump[..], Charset::Utf8, |_| Ok(vec![])).unwrap();
+
+        assert_eq!(tables.keys().collect::<Vec<_>>(), ["a", "b"]);
+        assert_eq!(tables["a"].rows, 3);
+        assert_eq!(String::from_utf8_lossy(&tables["a"].writer), "1,foo\n2,\"b,\"\"r\"\n4,baz\n");
+        assert_eq!(String::from_utf8_lossy(&tables["b"].writer), "3,\n");
+    }
+}
@@ -1,21 +1,73 @@
 //! Streaming SQL tokenizer for loading Wikipedia mysql dumps
 
 use std::{fs::File, path::Path, io::{Error, BufReader, BufRead, Bytes, Read}, iter::{Peekable, Fuse}};
+use clap::ValueEnum;
 use flate2::bufread::GzDecoder;
 use smol_str::SmolStr;
 use thiserror::Error;
 use utf8_decode::UnsafeDecoder;
 
-//pub mod regex;
+use crate::job::{Cancelled, Control};
 
-pub struct Loader {
-    source: Peekable<Fuse<Tokenizer>>,
+pub mod regex;
+
+pub struct Loader<R: Read> {
+    source: Peekable<Fuse<Tokenizer<R>>>,
     expecting_tuple: bool,
+    table: Option<SmolStr>,
+    control: Option<Control>,
+    /// Charsets of the columns read as raw bytes, when some have their own
+    columns: Option<Charsets>,
+}
+
+/// Character set used to decode the string literals of a dump
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Charset {
+    #[default]
+    Utf8,
+    /// Every byte is mapped to the code point of the same value
+    Latin1,
+    /// Strings are kept as raw bytes
+    Binary,
+}
+
+/// Character sets of the string columns of a table: a default one, and the ones of
+/// the columns, by index from 0, that differ from it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Charsets {
+    pub default: Charset,
+    pub columns: Vec<(usize, Charset)>,
+}
+
+impl Charsets {
+    /// Character set of a column
+    pub fn of(&self, column: usize) -> Charset {
+        self.columns.iter().rev()
+            .find(|&&(c, _)| c == column)
+            .map_or(self.default, |&(_, charset)| charset)
+    }
 }
 
+impl From<Charset> for Charsets {
+    fn from(default: Charset) -> Self {
+        Self { default, columns: vec![] }
+    }
+}
+
+impl From<&Charsets> for Charsets {
+    fn from(charsets: &Charsets) -> Self {
+        charsets.clone()
+    }
+}
+
+/// A type-erased loader, for when the concrete reader type does not matter
+pub type DynLoader = Loader<Box<dyn BufRead>>;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     String(String),
+    /// String literal read with the `binary` charset
+    Bytes(Vec<u8>),
     Integer(i64),
     Float(f64),
     Null,
@@ -29,6 +81,28 @@ impl Value {
     pub fn string(self) -> Result<String, TypeError> {
         match self {
             Value::String(s) => Ok(s),
+            Value::Bytes(b) => String::from_utf8(b)
+                .map_err(|e| TypeError(Value::Bytes(e.into_bytes()))),
+            other => Err(TypeError(other)),
+        }
+    }
+
+    /// Decode a string literal read as raw bytes with a charset. Bytes that are not
+    /// valid UTF-8 stay as they are with the `utf8` charset, so nothing is lost.
+    pub fn decode(self, charset: Charset) -> Value {
+        match (self, charset) {
+            (Value::Bytes(b), Charset::Utf8) => String::from_utf8(b)
+                .map_or_else(|e| Value::Bytes(e.into_bytes()), Value::String),
+            (Value::Bytes(b), Charset::Latin1) => Value::String(b.into_iter().map(char::from).collect()),
+            (other, _) => other,
+        }
+    }
+
+    /// The raw bytes of a string literal
+    pub fn bytes(self) -> Result<Vec<u8>, TypeError> {
+        match self {
+            Value::String(s) => Ok(s.into_bytes()),
+            Value::Bytes(b) => Ok(b),
             other => Err(TypeError(other)),
         }
     }
@@ -40,6 +114,62 @@ impl Value {
         }
     }
 
+    pub fn float(self) -> Result<f64, TypeError> {
+        match self {
+            Value::Float(x) => Ok(x),
+            other => Err(TypeError(other)),
+        }
+    }
+
+    /// Like `string()`, but maps `NULL` to `None`
+    pub fn opt_string(self) -> Result<Option<String>, TypeError> {
+        match self {
+            Value::Null => Ok(None),
+            other => other.string().map(Some),
+        }
+    }
+
+    /// Like `int()`, but maps `NULL` to `None`
+    pub fn opt_int(self) -> Result<Option<i64>, TypeError> {
+        match self {
+            Value::Null => Ok(None),
+            other => other.int().map(Some),
+        }
+    }
+
+    /// Like `float()`, but maps `NULL` to `None`
+    pub fn opt_float(self) -> Result<Option<f64>, TypeError> {
+        match self {
+            Value::Null => Ok(None),
+            other => other.float().map(Some),
+        }
+    }
+
+}
+
+impl TryFrom<Value> for String {
+    type Error = TypeError;
+    fn try_from(value: Value) -> Result<Self, Self::Error> { value.string() }
+}
+
+impl TryFrom<Value> for i64 {
+    type Error = TypeError;
+    fn try_from(value: Value) -> Result<Self, Self::Error> { value.int() }
+}
+
+impl TryFrom<Value> for f64 {
+    type Error = TypeError;
+    fn try_from(value: Value) -> Result<Self, Self::Error> { value.float() }
+}
+
+impl TryFrom<Value> for u32 {
+    type Error = TypeError;
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Integer(n) => n.try_into().map_err(|_| TypeError(value)),
+            other => Err(TypeError(other)),
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -52,28 +182,61 @@ pub enum LoaderError {
     Syntax(Token, SmolStr),
     #[error("EOF")]
     EOF,
+    #[error(transparent)]
+    Cancelled(#[from] Cancelled),
 }
 
-impl Loader {
-    pub fn load_gz_file<P: AsRef<Path> + ?Sized>(path: &P) -> Result<Self, LoaderError> {
+impl DynLoader {
+    pub fn load_gz_file<P: AsRef<Path> + ?Sized>(path: &P, charset: impl Into<Charsets>) -> Result<Self, LoaderError> {
         let compressed = BufReader::new(File::open(path)?);
         let source = BufReader::new(GzDecoder::new(compressed));
-        Self::load(source)
+        Self::load_with_charset(Box::new(source), charset)
+    }
+}
+
+impl <R: BufRead> Loader<R> {
+
+    #[cfg(test)]
+    pub fn load(source: R) -> Result<Self, LoaderError> {
+        Self::load_with_charset(source, Charset::Utf8)
     }
 
-    pub fn load<R: BufRead + 'static>(mut source: R) -> Result<Self, LoaderError> {
+    pub fn load_with_charset(mut source: R, charset: impl Into<Charsets>) -> Result<Self, LoaderError> {
 
-        let mut linebuf = String::new();
+        let mut linebuf = vec![];
         let linebuf = &mut linebuf;
 
         loop {
-            source.read_line(linebuf)?;
-            if linebuf.contains("DISABLE KEYS") { break }
+            linebuf.clear();
+            if source.read_until(b'\n', linebuf)? == 0 { break }
+            if linebuf.windows(12).any(|w| w == b"DISABLE KEYS") { break }
         }
 
-        let source = tokenize(source).fuse().peekable();
-        Ok(Self { source, expecting_tuple: false })
+        Ok(Self::new(source, charset))
+
+    }
+
+    /// Create a loader reading statements from the start of the source, without
+    /// skipping the dump header. Columns with their own charset are read as raw
+    /// bytes, and decoded once their index in the row is known.
+    pub fn new(source: R, charset: impl Into<Charsets>) -> Self {
+        let charsets = charset.into();
+        let (charset, columns) = match charsets.columns.is_empty() {
+            true => (charsets.default, None),
+            false => (Charset::Binary, Some(charsets)),
+        };
+        let source = Tokenizer::with_charset(source, charset).fuse().peekable();
+        Self { source, expecting_tuple: false, table: None, control: None, columns }
+    }
+
+    /// Stop with [`LoaderError::Cancelled`] at the next row once `control` is cancelled
+    pub fn with_control(self, control: Control) -> Self {
+        Self { control: Some(control), ..self }
+    }
 
+    /// Name of the table targeted by the current `INSERT` statement
+    pub fn table(&self) -> Option<&str> {
+        self.table.as_deref()
     }
 
     fn peek(&mut self) -> Result<Option<&Token>, TokenizerError> {
@@ -103,7 +266,10 @@ impl Loader {
     fn expect_insert_into(&mut self) -> Result<(), LoaderError> {
         self.expect(sym("INSERT"))?;
         self.expect(sym("INTO"))?;
-        self.token()?;
+        match self.token()? {
+            Token::Symbol(table) => self.table = Some(table),
+            other => return Err(LoaderError::Syntax(other, "a table name".into())),
+        }
         self.expect(sym("VALUES"))
     }
 
@@ -114,7 +280,10 @@ impl Loader {
         loop {
             let v = self.token()?
                 .value().map_err(|t| LoaderError::Syntax(t, "a literal value".into()))?;
-            tuple.push(v);
+            match &self.columns {
+                Some(charsets) => tuple.push(v.decode(charsets.of(tuple.len()))),
+                None => tuple.push(v),
+            }
 
             match self.token()? {
                 Token::Symbol(s) if s == "," => continue,
@@ -135,6 +304,7 @@ impl Loader {
     }
 
     fn next_tuple(&mut self) -> Result<Option<Vec<Value>>, LoaderError> {
+        if let Some(control) = &self.control { control.check()? }
         if !self.expecting_tuple {
             if self.peek()? == Some(&sym("INSERT")) {
                 self.expect_insert_into()?;
@@ -151,7 +321,7 @@ impl Loader {
 }
 
 
-impl Iterator for Loader {
+impl <R: BufRead> Iterator for Loader<R> {
     type Item = Result<Vec<Value>, LoaderError>;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -192,6 +362,8 @@ pub const fn sym(s: &str) -> Token { Token::Symbol(SmolStr::new_inline(s)) }
 pub fn strt<S: Into<String>>(s: S) -> Token { Token::Value(Value::String(s.into()))}
 #[cfg(test)]
 pub const fn numt(n: i64) -> Token { Token::Value( Value::Integer(n) ) }
+#[cfg(test)]
+pub const fn floatt(x: f64) -> Token { Token::Value( Value::Float(x) ) }
 
 impl Token {
     fn value(self) -> Result<Value, Token> {
@@ -202,17 +374,46 @@ impl Token {
     }
 }
 
+/// Decodes a byte stream into characters
+enum Decoder<R: Read> {
+    Utf8(UnsafeDecoder<Bytes<BufReader<R>>>),
+    /// One character per byte, used for both latin1 and binary strings
+    Bytes(Bytes<BufReader<R>>),
+}
+
+impl <R: Read> Iterator for Decoder<R> {
+    type Item = Result<char, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Decoder::Utf8(chars) => chars.next(),
+            Decoder::Bytes(bytes) => bytes.next().map(|b| b.map(char::from)),
+        }
+    }
+}
+
 /// A streaming SQL tokenizer. Wraps a byte stream and provides iteration over tokens.
-pub struct Tokenizer {
-    source: Peekable<UnsafeDecoder<Bytes<Box<dyn Read>>>>,
+pub struct Tokenizer<R: Read> {
+    source: Peekable<Decoder<R>>,
     buffer: String,
+    charset: Charset,
 }
 
-impl Tokenizer {
+impl <R: Read> Tokenizer<R> {
 
     /// Create a tokenizer reading from a given source
-    pub fn new(source: Box<dyn Read>) -> Self {
-        Self { source: UnsafeDecoder::new(source.bytes()).peekable(), buffer: String::with_capacity(4096) }
+    #[cfg(test)]
+    pub fn new(source: R) -> Self {
+        Self::with_charset(source, Charset::Utf8)
+    }
+
+    /// Create a tokenizer decoding string literals with the given charset
+    pub fn with_charset(source: R, charset: Charset) -> Self {
+        let source = match charset {
+            Charset::Utf8 => Decoder::Utf8(UnsafeDecoder::new(BufReader::new(source).bytes())),
+            Charset::Latin1 | Charset::Binary => Decoder::Bytes(BufReader::new(source).bytes()),
+        };
+        Self { source: source.peekable(), buffer: String::with_capacity(4096), charset }
     }
 
     /// Consume white space at the start of the stream
@@ -241,7 +442,7 @@ impl Tokenizer {
                     self.source.next().unwrap()?;
                 },
                 Some(Ok(c)) if p(*c) => {
-                    self.buffer.push(*c as char);
+                    self.buffer.push(*c);
                     self.source.next();
                 },
                 _ => {
@@ -257,12 +458,27 @@ impl Tokenizer {
         self.collect_while(|c| c == '-')?;
         self.collect_while(|c| c.is_ascii_digit())?;
 
-        let v = if self.source.peek().and_then(|t| t.as_ref().ok()) == Some(&'.') {
+        let mut float = false;
 
-            self.buffer.push(self.source.next().unwrap().unwrap() as char);
+        if self.source.peek().and_then(|t| t.as_ref().ok()) == Some(&'.') {
+            self.buffer.push(self.source.next().unwrap().unwrap());
             self.collect_while(|c| c.is_ascii_digit())?;
-            Value::Float(self.buffer.parse()?)
+            float = true;
+        }
 
+        if let Some(Ok('e' | 'E')) = self.source.peek() {
+            self.buffer.push(self.source.next().unwrap().unwrap());
+            if let Some(Ok(sign@('-' | '+'))) = self.source.peek() {
+                let sign = *sign;
+                self.buffer.push(sign);
+                self.source.next();
+            }
+            self.collect_while(|c| c.is_ascii_digit())?;
+            float = true;
+        }
+
+        let v = if float {
+            Value::Float(self.buffer.parse()?)
         } else {
             Value::Integer(self.buffer.parse()?)
         };
@@ -298,6 +514,14 @@ impl Tokenizer {
                 match c {
                     '\\' => match self.source.next().ok_or(TokenizerError::IncompleteString)?? {
                         c@('\'' | '\\' | '"') => self.buffer.push(c),
+                        '0' => self.buffer.push('\0'),
+                        'n' => self.buffer.push('\n'),
+                        'r' => self.buffer.push('\r'),
+                        't' => self.buffer.push('\t'),
+                        'b' => self.buffer.push('\u{8}'),
+                        'Z' => self.buffer.push('\u{1a}'),
+                        // LIKE wildcards keep their backslash
+                        c@('%' | '_') => { self.buffer.push('\\'); self.buffer.push(c) },
                         other => return Err(TokenizerError::InvalidEscape(other))
                     },
                     '\'' => break,
@@ -309,7 +533,11 @@ impl Tokenizer {
             if let Some(Ok('\'')) = self.source.peek() { // Double quote escape
                 self.buffer.push('\'')
             } else { // actual end of quote
-                return Ok(Token::Value(Value::String(self.buffer.clone())))
+                let value = match self.charset {
+                    Charset::Binary => Value::Bytes(self.buffer.chars().map(|c| c as u8).collect()),
+                    _ => Value::String(self.buffer.clone()),
+                };
+                return Ok(Token::Value(value))
             }
         }
         
@@ -351,7 +579,7 @@ impl Tokenizer {
 
 }
 
-impl Iterator for Tokenizer{
+impl <R: Read> Iterator for Tokenizer<R> {
     type Item = Result<Token, TokenizerError>;
 
 
@@ -362,8 +590,9 @@ impl Iterator for Tokenizer{
 }
 
 /// Create a tokenizer over the given source
-pub fn tokenize<R: Read + 'static>(source: R) -> Tokenizer {
-    Tokenizer::new(Box::new(source))
+#[cfg(test)]
+pub fn tokenize<R: Read>(source: R) -> Tokenizer<R> {
+    Tokenizer::new(source)
 }
 
 #[test]
@@ -400,4 +629,90 @@ fn sample_tokenization() {
         ]
     )
 
-}
\ No newline at end of file
+}
+
+#[test]
+fn number_tokenization() {
+    let tokens: Result<Vec<_>,_> = tokenize(&b"(-3,2.5,1e-05,-0.5e3,7E+2)"[..]).collect();
+
+    assert_eq!(tokens.unwrap(),
+        [sym("("), numt(-3), sym(","), floatt(2.5), sym(","), floatt(1e-5), sym(","),
+         floatt(-500.0), sym(","), floatt(700.0), sym(")")]
+    );
+
+    assert_eq!(Value::Float(0.25).float().unwrap(), 0.25);
+    assert!(Value::Integer(1).float().is_err());
+}
+
+#[test]
+fn escape_tokenization() {
+    let tokens: Result<Vec<_>,_> = tokenize(&br"'a\nb\r\tc\0d\Ze\bf\%\_'"[..]).collect();
+    assert_eq!(tokens.unwrap(), [strt("a\nb\r\tc\0d\u{1a}e\u{8}f\\%\\_")]);
+
+    let invalid: Result<Vec<_>,_> = tokenize(&br"'\q'"[..]).collect();
+    assert!(matches!(invalid, Err(TokenizerError::InvalidEscape('q'))));
+}
+
+#[test]
+fn nullable_values() {
+    assert_eq!(Value::Null.opt_string().unwrap(), None);
+    assert_eq!(Value::String("x".into()).opt_string().unwrap().as_deref(), Some("x"));
+    assert_eq!(Value::Integer(3).opt_int().unwrap(), Some(3));
+    assert!(Value::Null.int().is_err());
+    assert!(Value::Float(1.0).opt_int().is_err());
+
+    assert_eq!(u32::try_from(Value::Integer(65537)).unwrap(), 65537);
+    assert!(u32::try_from(Value::Integer(-1)).is_err());
+    assert_eq!(String::try_from(Value::String("y".into())).unwrap(), "y");
+}
+
+#[test]
+fn charset_tokenization() {
+    let source = b"('caf\xe9','\xff\\0')";
+
+    let latin1: Result<Vec<_>,_> = Tokenizer::with_charset(&source[..], Charset::Latin1).collect();
+    assert_eq!(latin1.unwrap()[1], strt("caf\u{e9}"));
+
+    let binary: Result<Vec<_>,_> = Tokenizer::with_charset(&source[..], Charset::Binary).collect();
+    let binary = binary.unwrap();
+    assert_eq!(binary[1], Token::Value(Value::Bytes(b"caf\xe9".to_vec())));
+    assert_eq!(binary[3], Token::Value(Value::Bytes(vec![0xff, 0])));
+}
+
+#[test]
+fn column_charsets() {
+    let dump = b"INSERT INTO `page` VALUES (1,'caf\xe9','caf\xc3\xa9','\xff');\n";
+    let charsets = Charsets { default: Charset::Utf8, columns: vec![(1, Charset::Latin1), (3, Charset::Binary)] };
+    let rows: Result<Vec<_>,_> = Loader::new(&dump[..], charsets).collect();
+
+    assert_eq!(rows.unwrap(), vec![vec![
+        Value::Integer(1),
+        Value::String("caf\u{e9}".into()),
+        Value::String("caf\u{e9}".into()),
+        Value::Bytes(vec![0xff]),
+    ]]);
+    assert_eq!(Value::Bytes(vec![0xff]).decode(Charset::Utf8), Value::Bytes(vec![0xff]));
+}
+
+#[test]
+fn borrowed_loader() {
+    let dump = String::from("/*!40000 ALTER TABLE `page` DISABLE KEYS */;\nINSERT INTO `page` VALUES (1,'foo'),(2,NULL);\n");
+
+    let rows: Result<Vec<_>,_> = Loader::load(dump.as_bytes()).unwrap().collect();
+
+    assert_eq!(rows.unwrap(), vec![
+        vec![Value::Integer(1), Value::String("foo".into())],
+        vec![Value::Integer(2), Value::Null],
+    ]);
+}
+
+#[test]
+fn cancelled_loader() {
+    let control = Control::new();
+    let dump = "INSERT INTO `page` VALUES (1,'foo'),(2,NULL);\n";
+    let mut rows = Loader::new(dump.as_bytes(), Charset::Utf8).with_control(control.clone());
+
+    assert!(rows.next().unwrap().is_ok());
+    control.cancel();
+    assert!(matches!(rows.next(), Some(Err(LoaderError::Cancelled(_)))));
+}
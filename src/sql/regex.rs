@@ -1,96 +1,176 @@
-use std::{rc::Rc, ops::Deref};
+//! Regex-based alternative to the tokenizer.
+//! 
+//! Matches whole `INSERT` lines at once instead of streaming characters.
 
-use super::{Value, Token, sym};
+use std::{borrow::Cow, io::BufRead, vec::IntoIter};
 
-#[cfg(test)]
-use super::{strt, numt};
+use super::{Charset, Charsets, Value};
 
-use once_cell::sync::{OnceCell, Lazy};
-use regex::{Regex, Captures};
+use once_cell::sync::Lazy;
+use regex::Regex;
 
-const VALUE_RE: &str = r#"'(?:[\].|[^'])*'|\d+(?:[.]\d+)|NULL"#;
-const INSERT_RE: &str = "^INSERT INTO `[^`]*` VALUES (.*);$";
+const VALUE_RE: &str = r"'(?:[^'\\]|\\.|'')*'|-?\d+(?:\.\d+)?(?:[eE][-+]?\d+)?|NULL";
+const INSERT_RE: &str = "^INSERT INTO `([^`]*)` VALUES (.*);$";
 
 static INSERT_MATCHER: Lazy<Regex> = Lazy::new(|| Regex::new(INSERT_RE).unwrap());
 static TUPLE_MATCHER: Lazy<Regex> = Lazy::new(|| Regex::new(&tuple_re()).unwrap());
 static VALUE_MATCHER: Lazy<Regex> = Lazy::new(|| Regex::new(VALUE_RE).unwrap());
 
 fn tuple_re() -> String {
-    format!("[(]{0}(:?,{0})*[)],?", VALUE_RE)
+    format!("[(](?:{0})(?:,(?:{0}))*[)]", VALUE_RE)
 }
 
-#[derive(Clone,Copy)]
-struct Tuple<'s>(&'s str);
-
-impl <'s> IntoIterator for &Tuple<'s> {
-    type Item = &'s str;
-
-    type IntoIter = Box<dyn Iterator<Item=Self::Item> + 's>;
+/// Convert a matched SQL literal into a value. Strings of lines read with the
+/// `binary` charset hold one character per byte, which are turned back into bytes.
+fn parse_value(s: &str, charset: Charset) -> Value {
+    if s == "NULL" {
+        return Value::Null
+    }
 
-    fn into_iter(self) -> Self::IntoIter {
-        Box::new(VALUE_MATCHER.find_iter(self.0)
-        .map(|m| m.as_str()))
+    if let Some(quoted) = s.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')) {
+        let mut out = String::with_capacity(quoted.len());
+        let mut chars = quoted.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('0') => out.push('\0'),
+                    Some('n') => out.push('\n'),
+                    Some('r') => out.push('\r'),
+                    Some('t') => out.push('\t'),
+                    Some('b') => out.push('\u{8}'),
+                    Some('Z') => out.push('\u{1a}'),
+                    Some(c@('%' | '_')) => { out.push('\\'); out.push(c) },
+                    Some(c) => out.push(c),
+                    None => out.push('\\'),
+                },
+                '\'' => { chars.next(); out.push('\'') }, // doubled quote
+                c => out.push(c),
+            }
+        }
+        return match charset {
+            Charset::Binary => Value::Bytes(out.chars().map(|c| c as u8).collect()),
+            _ => Value::String(out),
+        }
     }
-}
 
-impl <'s> Tuple<'s> {
-    pub fn values(&self) -> Vec<&'s str> {
-        match_tuple(self)
+    match s.parse() {
+        Ok(n) => Value::Integer(n),
+        Err(_) => Value::Float(s.parse().unwrap_or(f64::NAN)),
     }
 }
 
-fn match_line(s: &str) -> Option<Vec<Tuple<'_>>> {
+/// Parse an `INSERT` line into rows. Returns `None` if the line is not an insert statement.
+fn match_line(s: &str, charset: Charset) -> Option<Vec<Vec<Value>>> {
 
     let blob = INSERT_MATCHER.captures(s)?
-                       .get(1).unwrap().as_str();
+                       .get(2).unwrap().as_str();
 
-    let slices = TUPLE_MATCHER.find_iter(blob)
-         .map(|tup| { Tuple(tup.as_str()) })
+    let rows = TUPLE_MATCHER.find_iter(blob)
+         .map(|tup| match_tuple(tup.as_str(), charset))
          .collect();
 
-    Some(slices)
+    Some(rows)
 
 }
 
-fn match_tuple<'s>(tuple: &Tuple<'s>) -> Vec<&'s str> {
-    VALUE_MATCHER.find_iter(&tuple.0)
-        .map(|m| m.as_str())
+fn match_tuple(tuple: &str, charset: Charset) -> Vec<Value> {
+    VALUE_MATCHER.find_iter(tuple)
+        .map(|m| parse_value(m.as_str(), charset))
         .collect()
 }
 
+/// Iterator over the rows of a dump, parsed one line at a time
+pub struct Loader<R> {
+    source: R,
+    line: Vec<u8>,
+    /// Charset the lines are decoded with
+    charset: Charset,
+    /// Charsets of the columns read as raw bytes, when some have their own
+    columns: Option<Charsets>,
+    rows: IntoIter<Vec<Value>>,
+}
+
+impl <R: BufRead> Loader<R> {
+    #[cfg(test)]
+    pub fn load(source: R) -> Self {
+        Self::load_with_charset(source, Charset::Utf8)
+    }
+
+    /// Create a loader decoding string literals with the given charsets, as
+    /// [`super::Loader`] does
+    pub fn load_with_charset(source: R, charset: impl Into<Charsets>) -> Self {
+        let charsets = charset.into();
+        let (charset, columns) = match charsets.columns.is_empty() {
+            true => (charsets.default, None),
+            false => (Charset::Binary, Some(charsets)),
+        };
+        Self { source, line: vec![], charset, columns, rows: vec![].into_iter() }
+    }
+}
+
+impl <R: BufRead> Iterator for Loader<R> {
+    type Item = Result<Vec<Value>, std::io::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(row) = self.rows.next() {
+                return Some(Ok(row))
+            }
+
+            self.line.clear();
+            match self.source.read_until(b'\n', &mut self.line) {
+                Ok(0) => return None,
+                Ok(_) => (),
+                Err(e) => return Some(Err(e)),
+            }
+
+            let line = match self.charset {
+                Charset::Utf8 => String::from_utf8_lossy(&self.line),
+                Charset::Latin1 | Charset::Binary => Cow::Owned(self.line.iter().copied().map(char::from).collect()),
+            };
+            if let Some(mut rows) = match_line(line.trim_end(), self.charset) {
+                if let Some(charsets) = &self.columns {
+                    for row in &mut rows {
+                        for (i, value) in row.iter_mut().enumerate() {
+                            *value = std::mem::replace(value, Value::Null).decode(charsets.of(i));
+                        }
+                    }
+                }
+                self.rows = rows.into_iter();
+            }
+        }
+    }
+}
 
 #[test]
-#[ignore]
-fn sample_tokenization() {
-
-    //let sym = |s| { Token::Symbol(SmolStr::new_inline(s)) };
-
-    let sample_statement = "INSERT INTO `my table` VALUES (1,'l o l',0),(2,'o\\''escape','es\\\"ca\\\' ped',0.5,NULL);";
-
-    let tokens: Vec<Token> = todo!();
-
-    assert_eq!(&tokens, 
-        &[sym("INSERT"),
-          sym("INTO"),
-          sym("my table"),
-          sym("VALUES"),
-          sym("("),
-          numt(1),
-          sym(","),
-          strt("l o l"),
-          sym(","),
-          numt(0),
-          sym(")"),
-          sym(","),
-          sym("("),
-          numt(2),
-          sym(","),
-          strt("o'escape"),
-          sym(","),
-          strt("es\"ca' ped"),
-          sym(")"),
+fn sample_regex_parsing() {
+
+    let sample_statement = "INSERT INTO `my table` VALUES (1,'l o l',0),(2,'o\\'''escape','es\\\"ca\\' ped',-0.5,NULL);\n";
+
+    let rows: Result<Vec<_>,_> = Loader::load(sample_statement.as_bytes()).collect();
 
+    assert_eq!(rows.unwrap(), 
+        [vec![Value::Integer(1), Value::String("l o l".into()), Value::Integer(0)],
+         vec![Value::Integer(2), Value::String("o''escape".into()), Value::String("es\"ca' ped".into()),
+              Value::Float(-0.5), Value::Null],
         ]
     )
 
-}
\ No newline at end of file
+}
+
+#[test]
+fn regex_charsets() {
+    let line = b"INSERT INTO `page` VALUES (1,'caf\xe9','caf\xc3\xa9','\xff');\n";
+
+    let rows: Result<Vec<_>,_> = Loader::load_with_charset(&line[..], Charset::Latin1).collect();
+    assert_eq!(rows.unwrap()[0][1], Value::String("caf\u{e9}".into()));
+
+    let charsets = Charsets { default: Charset::Utf8, columns: vec![(1, Charset::Latin1), (3, Charset::Binary)] };
+    let rows: Result<Vec<_>,_> = Loader::load_with_charset(&line[..], charsets).collect();
+    assert_eq!(rows.unwrap(), vec![vec![
+        Value::Integer(1),
+        Value::String("caf\u{e9}".into()),
+        Value::String("caf\u{e9}".into()),
+        Value::Bytes(vec![0xff]),
+    ]]);
+}
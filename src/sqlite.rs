@@ -1,14 +1,71 @@
 //! SQLite backend
-use rusqlite::{Connection, Error, Row, OpenFlags};
+use std::{borrow::Cow, collections::{BTreeSet, HashMap, HashSet}};
+
+use once_cell::unsync::OnceCell;
+use clap::ValueEnum;
+use rusqlite::{Connection, Error, OptionalExtension, Row, OpenFlags, Statement};
 use thiserror::Error;
 
 
-use crate::path::bidi_dijkstra;
+use wikistra::path::{bidi_dijkstra_dense, bidi_dijkstra_spilling, bidi_dijkstra_to_any, bidi_dijkstra_with_stats, dijkstra_with_stats, strong_components, Side, Stats};
 
-use super::Id;
+use super::{Id, backend::{Analytics, Backend}, classify, geo::{self, Point}, namespace::{Case, ucfirst}, remote, schema, stats::GraphStats, timings::{self, Phase}, translit, variant::Converter};
 
 pub struct Db {
     inner: Connection,
+    /// Dense ID remapping, loaded on first use if the DB was compiled
+    remap: OnceCell<Option<Remap>>,
+    /// Case rule of the titles, loaded on first use
+    case: OnceCell<Option<Case>>,
+    /// Conversion rules of the language variants, loaded on first use if they were indexed
+    variants: OnceCell<Option<Converter>>,
+    /// Number of reached articles per search side kept in memory
+    spill_after: Option<usize>,
+    /// Whether the paths found are stored, and reused by later queries
+    path_cache: bool,
+    cache: OnceCell<Connection>,
+}
+
+/// Mapping between article IDs and dense indices in `0..n`, built by `compile`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Remap {
+    /// Article IDs, sorted, indexed by their dense index
+    ids: Vec<Id>,
+}
+
+impl Remap {
+
+    /// Number of remapped articles
+    pub fn len(&self) -> u32 {
+        self.ids.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// The dense index of an article ID
+    pub fn dense(&self, id: Id) -> Option<u32> {
+        self.ids.binary_search(&id).ok().map(|i| i as u32)
+    }
+
+    /// The article IDs, sorted, indexed by their dense index
+    pub fn ids(&self) -> &[Id] {
+        &self.ids
+    }
+
+    /// The article ID for a dense index
+    pub fn id(&self, index: u32) -> Option<Id> {
+        self.ids.get(index as usize).copied()
+    }
+
+}
+
+/// A mapping of the given article IDs, which must be sorted
+impl From<Vec<Id>> for Remap {
+    fn from(ids: Vec<Id>) -> Self {
+        Remap { ids }
+    }
 }
 
 /*
@@ -19,17 +76,54 @@ impl Drop for Db {
 }
 */
 
+/// Directions in which the links can be listed efficiently
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum LinkDirection {
+    /// Outgoing links only
+    Forward,
+    /// Incoming links only
+    Reverse,
+    /// Both directions, as needed by the bidirectional search
+    #[default]
+    Both,
+}
+
+impl LinkDirection {
+    fn name(self) -> &'static str {
+        match self {
+            LinkDirection::Forward => "forward",
+            LinkDirection::Reverse => "reverse",
+            LinkDirection::Both => "both",
+        }
+    }
+}
+
 #[derive(Error,Debug)]
 pub enum PathError {
     #[error("Unknown article: {0}")]
     UnknownTitle(String),
-    #[error("No path found")]
-    NoPathFound
+    #[error("No path found: {0}")]
+    NoPathFound(Stats),
+}
+
+/// Page size of new databases, in bytes. Large pages suit the multi-GB,
+/// bulk-loaded and read-mostly link tables: see `wikistra bench storage`.
+pub const PAGE_SIZE: u32 = 16384;
+
+/// The file of the path cache of a database
+pub fn path_cache_file(db_path: &str) -> String {
+    format!("{}.cache", db_path)
 }
 
 impl Db {
 
     pub fn new(path: &str) -> Result<Self, Error> {
+        Self::open_with_page_size(path, PAGE_SIZE)
+    }
+
+    /// Open a database, creating it with pages of `page_size` bytes if it does not
+    /// exist yet. The page size of an existing database stays as it is.
+    pub fn open_with_page_size(path: &str, page_size: u32) -> Result<Self, Error> {
         let mut fresh = false;
         let mut flags = OpenFlags::default();
         flags.remove(OpenFlags::SQLITE_OPEN_CREATE);
@@ -46,41 +140,201 @@ impl Db {
             PRAGMA locking_mode = EXCLUSIVE;
             PRAGMA temp_store = MEMORY;
         ")?;
-        let mut new = Self { inner };
-        if fresh { new.initialize()? };
+        let mut new = Self { inner, remap: OnceCell::new(), case: OnceCell::new(), variants: OnceCell::new(), spill_after: None, path_cache: false, cache: OnceCell::new() };
+        if fresh { new.initialize_with_page_size(page_size)? };
+        new.upgrade()?;
         Ok(new)
     }
 
-    fn initialize(&mut self) -> Result<(), Error> {
-        self.inner.execute_batch("
-            CREATE TABLE page (id int(8) primary key, title text unique) without rowid;
+    /// Open an existing database read-only, without upgrading it, so that it can be
+    /// queried while another process uses it, or when its file cannot be written
+    pub fn open_read_only(path: &str) -> Result<Self, Error> {
+        let inner = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX | OpenFlags::SQLITE_OPEN_URI)?;
+        inner.execute_batch("
+            PRAGMA cache_size = 100000;
+            PRAGMA temp_store = MEMORY;
+        ")?;
+        Ok(Self { inner, remap: OnceCell::new(), case: OnceCell::new(), variants: OnceCell::new(), spill_after: None, path_cache: false, cache: OnceCell::new() })
+    }
+
+    /// Open a database published over HTTP, read-only, fetching the pages it needs
+    /// with range requests. It is not upgraded, and must have been built by this version.
+    pub fn open_remote(url: &str) -> Result<Self, Error> {
+        remote::register()?;
+        let inner = Connection::open_with_flags_and_vfs(url, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX, remote::VFS)?;
+        inner.execute_batch("
+            PRAGMA cache_size = 100000;
+            PRAGMA temp_store = MEMORY;
+        ")?;
+        Ok(Self { inner, remap: OnceCell::new(), case: OnceCell::new(), variants: OnceCell::new(), spill_after: None, path_cache: false, cache: OnceCell::new() })
+    }
+
+    /// Prepare a statement typed by the user. The connection is made read-only
+    /// first, so that it cannot modify the database.
+    pub fn prepare_read_only(&self, sql: &str) -> Result<Statement<'_>, Error> {
+        self.inner.execute_batch("PRAGMA query_only = ON;")?;
+        self.inner.prepare(sql)
+    }
+
+    /// Let SQLite memory-map up to `bytes` of the database file, instead of
+    /// copying pages through its own cache
+    pub fn set_mmap_size(&self, bytes: u64) -> Result<(), Error> {
+        self.inner.execute_batch(&format!("PRAGMA mmap_size = {};", bytes))
+    }
+
+    /// Spill the state of path searches to disk beyond `threshold` reached articles
+    /// per side. Compiled databases keep their compact in-memory state.
+    pub fn set_spill_after(&mut self, threshold: Option<usize>) {
+        self.spill_after = threshold;
+    }
+
+    /// Store the paths found between articles, and answer the same queries from the
+    /// store. Paths avoiding articles are not cached.
+    pub fn set_path_cache(&mut self, enabled: bool) {
+        self.path_cache = enabled;
+    }
+
+    #[cfg(test)]
+    pub(crate) fn initialize(&mut self) -> Result<(), Error> {
+        self.initialize_with_page_size(PAGE_SIZE)
+    }
+
+    fn initialize_with_page_size(&mut self, page_size: u32) -> Result<(), Error> {
+        // The page size can only be changed before the first table is created
+        self.inner.execute_batch(&format!("
+            PRAGMA page_size = {};
+            CREATE TABLE page (id int(8) primary key, title text unique, flags int not null default 0) without rowid;
+            CREATE INDEX page_flags ON page(flags) WHERE flags != 0;
             CREATE TABLE link(`to` int(8), `from` int(8), primary key (`to`, `from`)) without rowid;
             CREATE TABLE redirect (id int(8) primary key, title text) without rowid;
             CREATE TABLE redirect_link (`to` int(8), `from` int(8), primary key (`to`, `from`));
             CREATE INDEX link_reverse ON link(`from`);
             CREATE INDEX redirect_link_reverse ON redirect_link(`from`);
+        ", page_size))
+    }
+
+    /// Bring databases created by older versions up to date: add the title flags,
+    /// and the tables of the optional dumps
+    fn upgrade(&mut self) -> Result<(), Error> {
+        let page_columns: Vec<String> = self.inner.prepare("SELECT name FROM pragma_table_info('page')")?
+            .query_map((), |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        if !page_columns.is_empty() && !page_columns.iter().any(|c| c == "flags") {
+            self.inner.execute_batch("
+                ALTER TABLE page ADD COLUMN flags int not null default 0;
+                CREATE INDEX page_flags ON page(flags) WHERE flags != 0;
+            ")?;
+        }
+
+        self.inner.execute_batch("
+            CREATE TABLE IF NOT EXISTS category (id int(8), name text, primary key (id, name)) without rowid;
+            CREATE INDEX IF NOT EXISTS category_members ON category(name);
+            CREATE TABLE IF NOT EXISTS category_page (id int(8) primary key, title text not null);
+            CREATE TABLE IF NOT EXISTS subcategory (parent text, id int(8), primary key (parent, id)) without rowid;
+            CREATE TABLE IF NOT EXISTS template_link (`from` int(8), template text, primary key (`from`, template)) without rowid;
+            CREATE INDEX IF NOT EXISTS template_link_reverse ON template_link(template);
+            CREATE TABLE IF NOT EXISTS image_link (`from` int(8), file text, primary key (`from`, file)) without rowid;
+            CREATE INDEX IF NOT EXISTS image_link_reverse ON image_link(file);
+            CREATE VIRTUAL TABLE IF NOT EXISTS geo USING rtree(id, min_lat, max_lat, min_lon, max_lon);
+            CREATE TABLE IF NOT EXISTS external_link (`from` int(8), url text, domain text, primary key (`from`, url)) without rowid;
+            CREATE TABLE IF NOT EXISTS abstract (id int(8) primary key, text text not null);
+            CREATE TABLE IF NOT EXISTS meta (key text primary key, value text not null) without rowid;
+            CREATE TABLE IF NOT EXISTS namespace (name text primary key collate nocase, id int not null, local int not null) without rowid;
+            CREATE TABLE IF NOT EXISTS alias (name text primary key collate nocase, title text not null) without rowid;
+            CREATE TABLE IF NOT EXISTS translit (key text, id int(8), primary key (key, id)) without rowid;
+            CREATE TABLE IF NOT EXISTS variant (key text, id int(8), primary key (key, id)) without rowid;
+            CREATE TABLE IF NOT EXISTS variant_rule (source text primary key, target text not null) without rowid;
+            DROP TABLE IF EXISTS path_cache;
         ")
     }
 
     pub fn search(&mut self, regex: &str) -> Vec<(Id, String, Option<String>)> {
+        let mut results = vec![];
+        self.search_each(regex, |id, title, redirect| { results.push((id, title, redirect)); Ok::<_, Error>(()) })
+            .unwrap();
+        results
+    }
 
-        self.inner.prepare_cached("SELECT page.id, page.title, redirect.title FROM page LEFT JOIN redirect ON page.id = redirect.id WHERE page.title LIKE ?1")
+    /// Pass the `(id, title, redirect target)` of every article matching a search
+    /// to `f` as it is read, and return their number. Results are not kept in memory.
+    pub fn search_each<E, F>(&self, regex: &str, mut f: F) -> Result<usize, E>
+    where E: From<Error>, F: FnMut(Id, String, Option<String>) -> Result<(), E>
+    {
+        let _timer = timings::start(Phase::Query);
+
+        // LIKE ignores the case of ASCII letters only
+        let regex = match self.case() {
+            Some(case) => case.normalize(regex),
+            None => Cow::Borrowed(regex),
+        };
+        let mut stmt = self.inner.prepare_cached("SELECT page.id, page.title, redirect.title FROM page LEFT JOIN redirect ON page.id = redirect.id
+                WHERE page.title LIKE ?1 OR page.id IN (SELECT id FROM translit WHERE key LIKE ?2)")?;
+        let mut rows = stmt.query((&regex, translit::key(&regex)))?;
+        let mut count = 0;
+        while let Some(row) = rows.next()? {
+            f(row.get(0)?, row.get(1)?, row.get(2)?)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Search the titles of the category pages, without their prefix
+    pub fn search_category_pages(&mut self, regex: &str) -> Vec<(Id, String)> {
+        let regex = match self.case() {
+            Some(case) => case.normalize(regex),
+            None => Cow::Borrowed(regex),
+        };
+        self.inner.prepare_cached("SELECT id, title FROM category_page WHERE title LIKE ?1")
             .unwrap()
-            .query((regex,))
+            .query((&regex,))
             .unwrap()
-            .mapped(|r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))
+            .mapped(|r| Ok((r.get(0)?, r.get(1)?)))
             .map(|r| r.unwrap())
             .collect()
     }
 
-    /// Insert an article in the DB. This updates both the forward and the reverse map.
-    pub fn add(&mut self, id: Id, name: String) -> Result<(), Error>{
-        self.inner.prepare_cached("INSERT INTO page VALUES (?1,?2)")
+    /// Insert an article in the DB, along with its title flags from `classify`.
+    /// This updates both the forward and the reverse map.
+    pub fn add(&mut self, id: Id, name: String, flags: u32) -> Result<(), Error>{
+        self.inner.prepare_cached("INSERT INTO page VALUES (?1,?2,?3)")
             .unwrap()
-            .execute((id, name))?;
+            .execute((id, name, flags))?;
         Ok(())
     }
 
+    /// The title flags of an article, 0 if unknown
+    pub fn title_flags(&self, id: Id) -> u32 {
+        self.inner.query_row("SELECT flags FROM page WHERE id = ?1", (id,), |row| row.get(0))
+        .unwrap_or(0)
+    }
+
+    /// Whether title flags were computed when indexing the articles. Databases
+    /// indexed before it was recorded by `save_title_flags` are scanned instead.
+    pub fn has_title_flags(&self) -> bool {
+        match self.meta("title_flags") {
+            Some(flags) => flags == "1",
+            None => self.scan_title_flags(),
+        }
+    }
+
+    fn scan_title_flags(&self) -> bool {
+        self.inner.query_row("SELECT 1 FROM page WHERE flags != 0 LIMIT 1", (), |_| Ok(()))
+        .is_ok()
+    }
+
+    /// Record whether title flags were computed, once the articles are indexed
+    pub fn save_title_flags(&mut self) -> Result<(), Error> {
+        let flagged = self.scan_title_flags();
+        self.set_meta("title_flags", if flagged { "1" } else { "0" })
+    }
+
+    /// The articles having any of the given flags
+    pub fn ids_with_flags(&self, mask: u32) -> Result<Vec<Id>, Error> {
+        self.inner.prepare_cached("SELECT id FROM page WHERE flags != 0 AND flags & ?1 != 0")?
+            .query_map((mask,), |row| row.get(0))?
+            .collect()
+    }
+
     pub fn links_to(&self, to: Id) -> Vec<Id> {
         let query = "SELECT `from` FROM link WHERE `to` = ?1 UNION SELECT `from` FROM redirect_link WHERE `to` = ?1";
         self.links_query(query, to)
@@ -91,6 +345,28 @@ impl Db {
         self.links_query(query, from)
     }
 
+    /// At most `limit` of the articles linking to this one
+    pub fn links_to_limit(&self, to: Id, limit: usize) -> Vec<Id> {
+        let query = "SELECT `from` FROM link WHERE `to` = ?1 UNION SELECT `from` FROM redirect_link WHERE `to` = ?1 LIMIT ?2";
+        self.links_query_limit(query, to, limit)
+    }
+
+    /// At most `limit` of the articles this one links to
+    pub fn links_from_limit(&self, from: Id, limit: usize) -> Vec<Id> {
+        let query = "SELECT `to` FROM link WHERE `from` = ?1 UNION SELECT `to` FROM redirect_link WHERE `from` = ?1 LIMIT ?2";
+        self.links_query_limit(query, from, limit)
+    }
+
+    fn links_query_limit(&self, query: &'static str, id: Id, limit: usize) -> Vec<Id> {
+        self.inner.prepare_cached(query)
+            .unwrap()
+            .query((id, limit as i64))
+            .unwrap()
+            .mapped(|row: &Row| -> Result<Id, Error> { row.get(0) })
+            .map(Result::unwrap)
+            .collect()
+    }
+
     /// Gives a list of all articles linking to this one
     pub fn links_query(&self, query: &'static str, to: Id) -> Vec<Id> {
         self.inner.prepare_cached(query)
@@ -102,6 +378,68 @@ impl Db {
             .collect()
     }
 
+    /// A value of the database metadata
+    pub fn meta(&self, key: &str) -> Option<String> {
+        self.inner.query_row("SELECT value FROM meta WHERE key = ?1", (key,), |row| row.get(0))
+        .ok()
+    }
+
+    pub fn set_meta(&mut self, key: &str, value: &str) -> Result<(), Error> {
+        self.inner.prepare_cached("INSERT OR REPLACE INTO meta VALUES (?1, ?2)")?
+            .execute((key, value))?;
+        Ok(())
+    }
+
+    /// How the wiki normalizes the first letter of titles, if known
+    pub fn case(&self) -> Option<Case> {
+        *self.case.get_or_init(|| self.meta("case").and_then(|c| Case::parse(&c)))
+    }
+
+    pub fn set_case(&mut self, case: Case) -> Result<(), Error> {
+        self.set_meta("case", case.name())?;
+        self.case = OnceCell::from(Some(case));
+        Ok(())
+    }
+
+    /// The date of the dumps the database was indexed from, if known
+    pub fn dump_date(&self) -> Option<String> {
+        self.meta("dump_date")
+    }
+
+    pub fn set_dump_date(&mut self, date: Option<&str>) -> Result<(), Error> {
+        match date {
+            Some(date) => self.set_meta("dump_date", date),
+            None => self.inner.execute("DELETE FROM meta WHERE key = 'dump_date'", ()).map(drop),
+        }
+    }
+
+    /// The directions in which the links were indexed
+    pub fn link_direction(&self) -> LinkDirection {
+        self.meta("links")
+            .and_then(|name| LinkDirection::from_str(&name, false).ok())
+            .unwrap_or_default()
+    }
+
+    /// Lay out the link table for the given directions. Changing the layout
+    /// empties the table, which must then be indexed again.
+    pub fn set_link_direction(&mut self, direction: LinkDirection) -> Result<(), Error> {
+        if direction == self.link_direction() { return Ok(()) }
+
+        // The primary key serves one direction, the `link_reverse` index the other
+        let key = match direction {
+            LinkDirection::Forward => "`from`, `to`",
+            LinkDirection::Reverse | LinkDirection::Both => "`to`, `from`",
+        };
+        self.inner.execute_batch(&format!("
+            DROP TABLE IF EXISTS link;
+            CREATE TABLE link(`to` int(8), `from` int(8), primary key ({})) without rowid;
+        ", key))?;
+        if direction == LinkDirection::Both {
+            self.inner.execute_batch("CREATE INDEX link_reverse ON link(`from`);")?;
+        }
+        self.set_meta("links", direction.name())
+    }
+
     /// Adds a link from one article to another
     pub fn add_link(&mut self, link: (Id, Id)) -> Result<(), Error> {
         self.inner.prepare_cached("INSERT OR IGNORE INTO link(`from`, `to`) VALUES (?1,?2)")?
@@ -115,11 +453,321 @@ impl Db {
         Ok(())
     }
 
+    /// Record that an article belongs to a category
+    pub fn add_category(&mut self, id: Id, name: &str) -> Result<(), Error> {
+        self.inner.prepare_cached("INSERT OR IGNORE INTO category VALUES (?1, ?2)")?
+            .execute((id, name))?;
+        Ok(())
+    }
+
+    /// Record the title of a category page, without the `Category:` prefix
+    pub fn add_category_page(&mut self, id: Id, title: &str) -> Result<(), Error> {
+        self.inner.prepare_cached("INSERT OR REPLACE INTO category_page VALUES (?1, ?2)")?
+            .execute((id, title))?;
+        Ok(())
+    }
+
+    /// Record that the category page `id` is a subcategory of `parent`
+    pub fn add_subcategory(&mut self, id: Id, parent: &str) -> Result<(), Error> {
+        self.inner.prepare_cached("INSERT OR IGNORE INTO subcategory VALUES (?1, ?2)")?
+            .execute((parent, id))?;
+        Ok(())
+    }
+
+    /// The articles of a category and of its subcategories down to `depth` levels,
+    /// given without the `Category:` prefix
+    pub fn category_members(&self, name: &str, depth: usize) -> Result<BTreeSet<Id>, Error> {
+        let mut members = BTreeSet::new();
+        let mut seen = HashSet::from([name.to_owned()]);
+        let mut level = vec![name.to_owned()];
+
+        for d in 0..=depth {
+            let mut next = vec![];
+            for category in &level {
+                let mut stmt = self.inner.prepare_cached("SELECT id FROM category WHERE name = ?1")?;
+                for id in stmt.query_map((category,), |row| row.get(0))? {
+                    members.insert(id?);
+                }
+                if d == depth { continue }
+                let mut stmt = self.inner.prepare_cached("SELECT title FROM subcategory JOIN category_page USING (id) WHERE parent = ?1")?;
+                for sub in stmt.query_map((category,), |row| row.get::<_, String>(0))? {
+                    let sub = sub?;
+                    if seen.insert(sub.clone()) { next.push(sub) }
+                }
+            }
+            level = next;
+        }
+
+        Ok(members)
+    }
+
+    /// The categories of an article, without the `Category:` prefix
+    pub fn categories_of(&self, id: Id) -> Result<Vec<String>, Error> {
+        self.inner.prepare_cached("SELECT name FROM category WHERE id = ?1 ORDER BY name")?
+            .query_map((id,), |row| row.get(0))?
+            .collect()
+    }
+
+    /// Whether an article belongs to a category, given without the `Category:` prefix
+    pub fn in_category(&self, id: Id, name: &str) -> bool {
+        self.inner.query_row("SELECT 1 FROM category WHERE id = ?1 AND name = ?2", (id, name), |_| Ok(()))
+        .is_ok()
+    }
+
+    /// The tables of the database, with their columns, indexes and number of rows
+    pub fn schema(&self) -> Result<Vec<schema::Table>, Error> {
+        let names: Vec<String> = self.inner.prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")?
+            .query_map((), |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        let mut tables = vec![];
+        for name in names {
+            let columns = self.inner.prepare("SELECT name, type FROM pragma_table_info(?1)")?
+                .query_map((&name,), |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<Result<_, _>>()?;
+            let indexes = self.inner.prepare("SELECT list.name, '(' || group_concat(info.name, ', ') || ')'
+                    FROM pragma_index_list(?1) list, pragma_index_info(list.name) info GROUP BY list.name ORDER BY list.name")?
+                .query_map((&name,), |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<Result<_, _>>()?;
+            let rows = self.inner.query_row(&format!("SELECT count(*) FROM \"{}\"", name), (), |row| row.get(0))?;
+            tables.push(schema::Table { name, columns, indexes, rows: Some(rows) });
+        }
+        Ok(tables)
+    }
+
+    /// Replace the namespace names with the given `(id, name, local)` ones
+    pub fn set_namespaces<'n, I: IntoIterator<Item = (i64, &'n str, bool)>>(&mut self, names: I) -> Result<(), Error> {
+        self.inner.execute("DELETE FROM namespace", ())?;
+        let mut insert = self.inner.prepare_cached("INSERT OR IGNORE INTO namespace VALUES (?1, ?2, ?3)")?;
+        for (id, name, local) in names {
+            insert.execute((name, id, local))?;
+        }
+        Ok(())
+    }
+
+    /// The namespace of a local name, canonical name or alias, ignoring case
+    pub fn namespace_id(&self, name: &str) -> Option<i64> {
+        self.inner.query_row("SELECT id FROM namespace WHERE name = ?1", (name,), |row| row.get(0))
+        .ok()
+    }
+
+    /// The local name of a namespace
+    pub fn namespace_name(&self, id: i64) -> Option<String> {
+        self.inner.query_row("SELECT name FROM namespace WHERE id = ?1 AND local", (id,), |row| row.get(0))
+        .ok()
+    }
+
+    /// Replace the user aliases with the given `(name, title)` ones
+    pub fn set_aliases<'a, I: IntoIterator<Item = (&'a str, &'a str)>>(&mut self, aliases: I) -> Result<(), Error> {
+        self.inner.execute("DELETE FROM alias", ())?;
+        let mut insert = self.inner.prepare_cached("INSERT OR REPLACE INTO alias VALUES (?1, ?2)")?;
+        for (name, title) in aliases {
+            insert.execute((name.replace(' ', "_"), title.replace(' ', "_")))?;
+        }
+        Ok(())
+    }
+
+    /// The title a user alias stands for, ignoring case
+    pub fn alias(&self, name: &str) -> Option<String> {
+        self.inner.prepare_cached("SELECT title FROM alias WHERE name = ?1").ok()?
+            .query_row((name.replace(' ', "_"),), |row| row.get(0))
+            .ok()
+    }
+
+    /// All the user aliases, as `(name, title)`
+    pub fn aliases(&self) -> Result<Vec<(String, String)>, Error> {
+        self.inner.prepare("SELECT name, title FROM alias ORDER BY name")?
+            .query_map((), |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect()
+    }
+
+    /// Record that an article uses a template, given without the `Template:` prefix
+    pub fn add_template_link(&mut self, from: Id, template: &str) -> Result<(), Error> {
+        self.inner.prepare_cached("INSERT OR IGNORE INTO template_link VALUES (?1, ?2)")?
+            .execute((from, template))?;
+        Ok(())
+    }
+
+    /// Record that an article uses a file, given without the `File:` prefix
+    pub fn add_image_link(&mut self, from: Id, file: &str) -> Result<(), Error> {
+        self.inner.prepare_cached("INSERT OR IGNORE INTO image_link VALUES (?1, ?2)")?
+            .execute((from, file))?;
+        Ok(())
+    }
+
+    /// The templates used by an article
+    pub fn templates_of(&self, id: Id) -> Result<Vec<String>, Error> {
+        self.inner.prepare_cached("SELECT template FROM template_link WHERE `from` = ?1 ORDER BY template")?
+            .query_map((id,), |row| row.get(0))?
+            .collect()
+    }
+
+    /// The articles using a template
+    pub fn template_users(&self, template: &str) -> Result<Vec<Id>, Error> {
+        self.inner.prepare_cached("SELECT `from` FROM template_link WHERE template = ?1")?
+            .query_map((template,), |row| row.get(0))?
+            .collect()
+    }
+
+    /// The articles using a file
+    pub fn image_users(&self, file: &str) -> Result<Vec<Id>, Error> {
+        self.inner.prepare_cached("SELECT `from` FROM image_link WHERE file = ?1")?
+            .query_map((file,), |row| row.get(0))?
+            .collect()
+    }
+
+    /// Record a link from an article to an external URL
+    pub fn add_external_link(&mut self, from: Id, url: &str, domain: Option<&str>) -> Result<(), Error> {
+        self.inner.prepare_cached("INSERT OR IGNORE INTO external_link VALUES (?1, ?2, ?3)")?
+            .execute((from, url, domain))?;
+        Ok(())
+    }
+
+    /// The external links of an article
+    pub fn external_links(&self, id: Id) -> Result<Vec<String>, Error> {
+        self.inner.prepare_cached("SELECT url FROM external_link WHERE `from` = ?1 ORDER BY url")?
+            .query_map((id,), |row| row.get(0))?
+            .collect()
+    }
+
+    /// The external domains linked from the most articles, with their number of linking articles
+    pub fn top_domains(&self, limit: usize) -> Result<Vec<(String, u64)>, Error> {
+        self.inner.prepare("SELECT domain, count(DISTINCT `from`) AS n FROM external_link
+            WHERE domain IS NOT NULL GROUP BY domain ORDER BY n DESC, domain LIMIT ?1")?
+            .query_map((limit as i64,), |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect()
+    }
+
+    /// Record the one-line summary of an article
+    pub fn add_abstract(&mut self, id: Id, text: &str) -> Result<(), Error> {
+        self.inner.prepare_cached("INSERT OR REPLACE INTO abstract VALUES (?1, ?2)")?
+            .execute((id, text))?;
+        Ok(())
+    }
+
+    /// The summary of an article, if one was imported
+    pub fn abstract_of(&self, id: Id) -> Result<Option<String>, Error> {
+        self.inner.query_row("SELECT text FROM abstract WHERE id = ?1", (id,), |row| row.get(0))
+        .optional()
+    }
+
+    /// The tables of the database
+    pub fn tables(&self) -> Result<Vec<String>, Error> {
+        self.inner.prepare("SELECT name FROM sqlite_master WHERE type IN ('table', 'view')")?
+            .query_map((), |row| row.get(0))?
+            .collect()
+    }
+
+    /// The tables created by `create_table`, for index specs
+    pub fn spec_tables(&self) -> Vec<String> {
+        self.meta("spec_tables").map(|names| names.split_whitespace().map(str::to_owned).collect()).unwrap_or_default()
+    }
+
+    /// Create a table declared in an index spec, with `(name, SQL type)` columns,
+    /// replacing any previous version of it
+    pub fn create_table(&mut self, name: &str, columns: &[(&str, &str)], key: &[&str]) -> Result<(), Error> {
+        let mut definitions: Vec<String> = columns.iter()
+            .map(|(column, kind)| format!("\"{}\" {}", column, kind))
+            .collect();
+        if !key.is_empty() {
+            let key: Vec<String> = key.iter().map(|k| format!("\"{}\"", k)).collect();
+            definitions.push(format!("primary key ({})", key.join(", ")));
+        }
+        self.inner.execute_batch(&format!("DROP TABLE IF EXISTS \"{0}\"; CREATE TABLE \"{0}\" ({1});",
+            name, definitions.join(", ")))?;
+        let mut specs = self.spec_tables();
+        if !specs.iter().any(|spec| spec.eq_ignore_ascii_case(name)) {
+            specs.push(name.to_owned());
+            self.set_meta("spec_tables", &specs.join(" "))?;
+        }
+        Ok(())
+    }
+
+    /// Insert a row into a table created by `create_table`, replacing the row with the same key
+    pub fn insert_row(&mut self, table: &str, row: &[rusqlite::types::Value]) -> Result<(), Error> {
+        let placeholders = vec!["?"; row.len()].join(", ");
+        self.inner.prepare_cached(&format!("INSERT OR REPLACE INTO \"{}\" VALUES ({})", table, placeholders))?
+            .execute(rusqlite::params_from_iter(row))?;
+        Ok(())
+    }
+
+    /// Record the coordinates of an article, in degrees
+    pub fn add_coordinates(&mut self, id: Id, lat: f64, lon: f64) -> Result<(), Error> {
+        self.inner.prepare_cached("INSERT OR REPLACE INTO geo VALUES (?1, ?2, ?2, ?3, ?3)")?
+            .execute((id, lat, lon))?;
+        Ok(())
+    }
+
+    /// The coordinates of an article, if known
+    pub fn coordinates(&self, id: Id) -> Option<Point> {
+        self.inner.query_row("SELECT min_lat, min_lon FROM geo WHERE id = ?1", (id,),
+        |row| Ok((row.get(0)?, row.get(1)?)))
+        .ok()
+    }
+
+    /// The articles within `radius_km` of a point, with their coordinates and
+    /// distance in kilometers, nearest first
+    pub fn near(&self, center: Point, radius_km: f64) -> Result<Vec<(Id, Point, f64)>, Error> {
+        let (min_lat, max_lat, min_lon, max_lon) = geo::bounding_box(center, radius_km);
+        let mut found: Vec<(Id, Point, f64)> = self.inner
+            .prepare_cached("SELECT id, min_lat, min_lon FROM geo
+                WHERE max_lat >= ?1 AND min_lat <= ?2 AND max_lon >= ?3 AND min_lon <= ?4")?
+            .query_map((min_lat, max_lat, min_lon, max_lon), |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?))))?
+            .filter_map(|r| r.map(|(id, point)| {
+                let distance = geo::distance_km(center, point);
+                (distance <= radius_km).then_some((id, point, distance))
+            }).transpose())
+            .collect::<Result<_, _>>()?;
+
+        found.sort_by(|a, b| a.2.total_cmp(&b.2));
+        Ok(found)
+    }
+
     /// Retrieves the article ID for a given title
     pub fn index(&self, name: &str) -> Option<Id> {
-        self.inner.query_row("SELECT id FROM page WHERE title = ?1", (name,),
-        |row| row.get(0))
-        .ok().flatten()
+        self.index_with(name, false)
+    }
+
+    /// Retrieves the article ID for a given title. If `follow_redirects` is set and
+    /// the title is a redirect, retrieves the ID of the redirect target instead.
+    /// The title is normalized according to the case rule of the wiki. If it is
+    /// unknown, the title is tried as is, then with its first letter uppercased.
+    pub fn index_with(&self, name: &str, follow_redirects: bool) -> Option<Id> {
+        let page_id = |name: &str| self.inner.prepare_cached("SELECT id FROM page WHERE title = ?1").ok()?
+            .query_row((name,), |row| row.get(0))
+            .ok();
+        let id = match self.case() {
+            Some(case) => page_id(&case.normalize(name)),
+            None => page_id(name).or_else(|| match ucfirst(name) {
+                Cow::Owned(upper) => page_id(&upper),
+                _ => None,
+            }),
+        }?;
+
+        if !follow_redirects { return Some(id) }
+        Some(self.redirect_target(id).unwrap_or(id))
+    }
+
+    /// Resolve a title given by the user to an article ID. User aliases take
+    /// precedence over the titles of the wiki.
+    /// Titles typed without their diacritics are found if they match a single
+    /// transliterated title.
+    pub fn resolve(&self, name: &str) -> Option<Id> {
+        match self.alias(name) {
+            Some(title) => self.index_with(&title, true),
+            None => self.index_with(name, true)
+                .or_else(|| self.variant(name))
+                .or_else(|| match self.transliterated(name)[..] {
+                    [id] => Some(self.redirect_target(id).unwrap_or(id)),
+                    _ => None,
+                }),
+        }
+    }
+
+    /// The ID of the article a redirect points to, if it is indexed
+    pub fn redirect_target(&self, id: Id) -> Option<Id> {
+        self.inner.query_row("SELECT page.id FROM redirect JOIN page ON page.title = redirect.title WHERE redirect.id = ?1",
+            (id,), |row| row.get(0))
+        .ok()
     }
 
     /// Lookup the article title given its ID
@@ -129,25 +777,495 @@ impl Db {
         .ok()
     }
 
-    pub fn path(&self, from: &str, to: &str) -> Result<Vec<String>, PathError> {
-        let from = self.index(from)
+    /// Number of links pointing to, respectively from, an article
+    pub fn degree(&self, id: Id) -> (usize, usize) {
+        (self.links_to(id).len(), self.links_from(id).len())
+    }
+
+    /// Percentage of the articles with fewer links pointing to, respectively from them
+    /// than the given numbers, if the degree distribution was computed by `compile`
+    pub fn degree_percentiles(&self, (links_in, links_out): (usize, usize)) -> Option<(f64, f64)> {
+        let percentile = |direction, degree| -> Option<f64> {
+            let (lower, total): (Option<f64>, Option<f64>) = self.inner.prepare_cached("
+                SELECT sum(CASE WHEN degree < ?2 THEN articles ELSE 0 END), sum(articles) FROM degree_count WHERE direction = ?1").ok()?
+                .query_row((direction, degree), |row| Ok((row.get(0)?, row.get(1)?))).ok()?;
+            Some(100.0 * lower? / total.filter(|&t| t > 0.0)?)
+        };
+        Some((percentile("in", links_in)?, percentile("out", links_out)?))
+    }
+
+    /// Whether there is a direct link from one article to another
+    pub fn has_link(&self, from: Id, to: Id) -> bool {
+        self.links_from(from).contains(&to)
+    }
+
+    /// Check the redirects against the pages marked as redirects in the page dump, if
+    /// the pages were indexed with their marks. Redirects of pages that are not marked,
+    /// left over from redirects turned back into articles, are removed. Returns their
+    /// number, and the number of marked pages without a redirect.
+    pub fn clean_redirects(&mut self) -> Result<Option<(u64, u64)>, Error> {
+        let marked: bool = self.inner.query_row("SELECT EXISTS (SELECT 1 FROM page WHERE flags & ?1)", (classify::REDIRECT,), |row| row.get(0))?;
+        if !marked { return Ok(None) }
+        let stale = self.inner.execute("DELETE FROM redirect WHERE id IN (SELECT id FROM page WHERE flags & ?1 = 0)", (classify::REDIRECT,))?;
+        let missing = self.inner.query_row("SELECT count(*) FROM page WHERE flags & ?1 AND id NOT IN (SELECT id FROM redirect)",
+            (classify::REDIRECT,), |row| row.get(0))?;
+        Ok(Some((stale as u64, missing)))
+    }
+
+    /// Whether the article is a redirect to another one
+    pub fn is_redirect(&self, id: Id) -> bool {
+        self.inner.query_row("SELECT 1 FROM redirect WHERE id = ?1", (id,), |_| Ok(()))
+        .is_ok()
+    }
+
+    /// The title a redirect points to, even if it is not indexed
+    pub fn redirect_title(&self, id: Id) -> Option<String> {
+        self.inner.query_row("SELECT title FROM redirect WHERE id = ?1", (id,), |row| row.get(0))
+        .ok()
+    }
+
+    /// The IDs of the redirects, sorted
+    pub fn redirect_ids(&self) -> Result<Vec<Id>, Error> {
+        self.inner.prepare("SELECT id FROM redirect ORDER BY id")?
+            .query_map((), |row| row.get(0))?
+            .collect()
+    }
+
+    /// Size of the database, in bytes
+    pub fn file_size(&self) -> Result<u64, Error> {
+        self.inner.query_row("SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()", (), |row| row.get(0))
+    }
+
+    /// Number of indexed articles, including redirects
+    pub fn article_count(&self) -> Result<u32, Error> {
+        match self.remap() {
+            Some(remap) if !remap.is_empty() => Ok(remap.len()),
+            _ => self.inner.query_row("SELECT count(*) FROM page", (), |row| row.get(0)),
+        }
+    }
+
+    /// The IDs of all the articles, in ID order
+    pub fn article_ids(&self) -> Result<Vec<Id>, Error> {
+        match self.remap() {
+            Some(remap) if !remap.is_empty() => Ok(remap.ids().to_vec()),
+            _ => self.inner.prepare("SELECT id FROM page ORDER BY id")?
+                .query_map((), |row| row.get(0))?
+                .collect(),
+        }
+    }
+
+    /// Call a function on the ID and title of every article
+    pub fn scan_titles<F: FnMut(Id, &str)>(&self, mut f: F) -> Result<(), Error> {
+        let mut stmt = self.inner.prepare("SELECT id, title FROM page")?;
+        let mut rows = stmt.query(())?;
+        while let Some(row) = rows.next()? {
+            f(row.get(0)?, row.get_ref(1)?.as_str()?);
+        }
+        Ok(())
+    }
+
+    /// Call a function on every article with its title flags, and whether it is a redirect
+    pub fn scan_articles<F: FnMut(Id, &str, u32, bool)>(&self, mut f: F) -> Result<(), Error> {
+        let mut stmt = self.inner.prepare("
+            SELECT page.id, page.title, page.flags, redirect.id IS NOT NULL FROM page LEFT JOIN redirect ON redirect.id = page.id")?;
+        let mut rows = stmt.query(())?;
+        while let Some(row) = rows.next()? {
+            f(row.get(0)?, row.get_ref(1)?.as_str()?, row.get(2)?, row.get(3)?);
+        }
+        Ok(())
+    }
+
+    /// The number of links pointing to, respectively from every article having any
+    pub fn degrees(&self) -> Result<HashMap<Id, (usize, usize)>, Error> {
+        let mut degrees: HashMap<Id, (usize, usize)> = HashMap::new();
+        self.scan_links(|from, to| {
+            degrees.entry(to).or_default().0 += 1;
+            degrees.entry(from).or_default().1 += 1;
+        })?;
+        Ok(degrees)
+    }
+
+    /// Call a function on every link, including the links of redirects
+    pub fn scan_links<F: FnMut(Id, Id)>(&self, mut f: F) -> Result<(), Error> {
+        let mut stmt = self.inner.prepare("SELECT `from`, `to` FROM link UNION ALL SELECT `from`, `to` FROM redirect_link")?;
+        let mut rows = stmt.query(())?;
+        while let Some(row) = rows.next()? {
+            f(row.get(0)?, row.get(1)?);
+        }
+        Ok(())
+    }
+
+    /// Index the ASCII transliterations of the titles that are not plain ASCII,
+    /// replacing any previous ones. Returns the number of transliterated titles.
+    pub fn transliterate(&mut self) -> Result<usize, Error> {
+        let mut keys = vec![];
+        self.scan_titles(|id, title| if !title.is_ascii() { keys.push((translit::key(title), id)) })?;
+        self.inner.execute("DELETE FROM translit", ())?;
+        let mut insert = self.inner.prepare_cached("INSERT OR IGNORE INTO translit VALUES (?1, ?2)")?;
+        for (key, id) in &keys {
+            insert.execute((key, id))?;
+        }
+        Ok(keys.len())
+    }
+
+    /// The articles whose title transliterates to the same as `title`
+    pub fn transliterated(&self, title: &str) -> Vec<Id> {
+        self.inner.prepare_cached("SELECT id FROM translit WHERE key = ?1")
+            .and_then(|mut stmt| stmt.query_map((translit::key(title),), |row| row.get(0))?.collect())
+            .unwrap_or_default()
+    }
+
+    /// Index the titles that the language variant rules of `converter` change, and
+    /// the rules, replacing any previous ones. Returns the number of converted titles.
+    pub fn index_variants(&mut self, converter: &Converter) -> Result<usize, Error> {
+        let mut keys = vec![];
+        self.scan_titles(|id, title| {
+            let key = converter.convert(title);
+            if key != title { keys.push((key, id)) }
+        })?;
+        self.inner.execute_batch("DELETE FROM variant; DELETE FROM variant_rule;")?;
+        let mut insert = self.inner.prepare_cached("INSERT OR IGNORE INTO variant VALUES (?1, ?2)")?;
+        for (key, id) in &keys {
+            insert.execute((key, id))?;
+        }
+        let mut insert = self.inner.prepare_cached("INSERT OR REPLACE INTO variant_rule VALUES (?1, ?2)")?;
+        for rule in converter.rules() {
+            insert.execute(rule)?;
+        }
+        self.variants = OnceCell::new();
+        Ok(keys.len())
+    }
+
+    /// The article whose title is `name` once both are converted to a single
+    /// language variant, if the variants were indexed
+    pub fn variant(&self, name: &str) -> Option<Id> {
+        let converter = self.variants.get_or_init(|| {
+            let mut converter = Converter::new();
+            let mut stmt = self.inner.prepare("SELECT source, target FROM variant_rule").ok()?;
+            let rules = stmt.query_map((), |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))).ok()?;
+            for (from, to) in rules.flatten() { converter.add(&from, &to) }
+            Some(converter).filter(|c| !c.is_empty())
+        }).as_ref()?;
+
+        let key = converter.convert(&name.replace(' ', "_"));
+        let key = match self.case() {
+            Some(case) => case.normalize(&key).into_owned(),
+            None => key,
+        };
+        let ids: Vec<Id> = self.inner.prepare_cached("SELECT id FROM variant WHERE key = ?1").ok()?
+            .query_map((&key,), |row| row.get(0)).ok()?
+            .collect::<Result<_, _>>().ok()?;
+        match ids[..] {
+            [id] => Some(self.redirect_target(id).unwrap_or(id)),
+            [] => self.index_with(&key, true),
+            _ => None,
+        }
+    }
+
+    /// Build the dense ID remapping of all articles, replacing any previous one.
+    /// Returns the number of remapped articles.
+    pub fn compile(&mut self) -> Result<usize, Error> {
+        self.clear_compiled()?;
+        self.inner.execute_batch("
+            CREATE TABLE dense (idx int(8) primary key, id int(8) unique) without rowid;
+            INSERT INTO dense SELECT row_number() OVER (ORDER BY id) - 1, id FROM page;
+            CREATE TABLE degree_count (direction text, degree int, articles int, primary key (direction, degree)) without rowid;
+            INSERT INTO degree_count SELECT 'in', degree, count(*) FROM (SELECT (SELECT count(*) FROM
+                (SELECT `from` FROM link WHERE `to` = page.id UNION SELECT `from` FROM redirect_link WHERE `to` = page.id)) AS degree FROM page)
+                GROUP BY degree;
+            INSERT INTO degree_count SELECT 'out', degree, count(*) FROM (SELECT (SELECT count(*) FROM
+                (SELECT `to` FROM link WHERE `from` = page.id UNION SELECT `to` FROM redirect_link WHERE `from` = page.id)) AS degree FROM page)
+                GROUP BY degree;
+        ")?;
+        self.inner.query_row("SELECT count(*) FROM dense", (), |row| row.get(0))
+    }
+
+    /// Compute the strongly connected components of the link graph, so that paths
+    /// between some components can be ruled out without searching. Needs the dense
+    /// remapping, and holds all the links in memory. Returns the number of components.
+    pub fn compile_components(&mut self) -> Result<u32, Error> {
+        let remap = self.load_remap()?;
+        let (offsets, targets) = self.link_lists(&remap)?;
+
+        let components = strong_components(remap.len(), |node| targets[offsets[node as usize]..offsets[node as usize + 1]].iter().copied());
+
+        self.inner.execute_batch("
+            DROP TABLE IF EXISTS component;
+            CREATE TABLE component (id int(8) primary key, scc int(8)) without rowid;
+        ")?;
+        let mut insert = self.inner.prepare("INSERT INTO component VALUES (?1, ?2)")?;
+        for (id, scc) in remap.ids.iter().zip(&components) {
+            insert.execute((id, scc))?;
+        }
+        Ok(components.iter().max().map_or(0, |c| c + 1))
+    }
+
+    /// The dense remapping built by `compile`, or else a remapping of the articles
+    /// in ID order, held by the caller
+    pub fn article_remap(&self) -> Result<Remap, Error> {
+        if let Some(remap) = self.remap() { return Ok(remap.clone()) }
+        let ids = self.inner.prepare("SELECT id FROM page ORDER BY id")?
+            .query_map((), |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        Ok(Remap { ids })
+    }
+
+    /// Store the PageRank of the articles, replacing any previous one
+    pub fn save_ranks(&mut self, scores: impl Iterator<Item = (Id, f64)>) -> Result<(), Error> {
+        // The previous scores stay in place until the new ones are complete
+        let tx = self.inner.transaction()?;
+        tx.execute_batch("
+            DROP TABLE IF EXISTS rank;
+            CREATE TABLE rank (id int(8) primary key, score real not null) without rowid;
+        ")?;
+        {
+            let mut insert = tx.prepare("INSERT INTO rank VALUES (?1, ?2)")?;
+            for score in scores {
+                insert.execute(score)?;
+            }
+        }
+        tx.execute_batch("CREATE INDEX rank_score ON rank(score);")?;
+        tx.commit()
+    }
+
+    /// The articles with the highest PageRank, with their title and score
+    pub fn top_ranks(&self, n: usize) -> Result<Vec<(Id, String, f64)>, Error> {
+        self.inner.prepare("SELECT id, title, score FROM rank JOIN page USING (id) ORDER BY score DESC LIMIT ?1")?
+            .query_map((n as i64,), |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect()
+    }
+
+    /// Whether `rank` stored the PageRank of the articles
+    pub fn ranked(&self) -> Result<bool, Error> {
+        Ok(self.tables()?.iter().any(|table| table == "rank"))
+    }
+
+    /// The stored PageRank of an article, if it was ranked
+    pub fn rank(&self, id: Id) -> Result<Option<f64>, Error> {
+        self.inner.query_row("SELECT score FROM rank WHERE id = ?1", (id,), |row| row.get(0))
+        .optional()
+    }
+
+    /// All the links between remapped articles, as dense indices. The links of the
+    /// article `i` are `targets[offsets[i]..offsets[i + 1]]`.
+    pub fn link_lists(&self, remap: &Remap) -> Result<(Vec<usize>, Vec<u32>), Error> {
+        let n = remap.len() as usize;
+        let dense = |(from, to)| Some((remap.dense(from)? as usize, remap.dense(to)?));
+
+        let mut offsets = vec![0; n + 1];
+        self.scan_links(|from, to| if let Some((from, _)) = dense((from, to)) { offsets[from + 1] += 1 })?;
+        for i in 0..n { offsets[i + 1] += offsets[i] }
+        let mut targets = vec![0; offsets[n]];
+        let mut ends = offsets.clone();
+        self.scan_links(|from, to| if let Some((from, to)) = dense((from, to)) {
+            targets[ends[from]] = to;
+            ends[from] += 1;
+        })?;
+        Ok((offsets, targets))
+    }
+
+    /// The titles of the remapped articles, by dense index
+    pub fn dense_titles(&self, remap: &Remap) -> Result<Vec<String>, Error> {
+        let mut titles = vec![String::new(); remap.len() as usize];
+        self.scan_titles(|id, title| if let Some(i) = remap.dense(id) { titles[i as usize] = title.to_owned() })?;
+        Ok(titles)
+    }
+
+    /// The strongly connected component of an article, if they were computed.
+    /// There is no path from an article to one of a higher-numbered component.
+    pub fn component(&self, id: Id) -> Option<u32> {
+        self.inner.prepare_cached("SELECT scc FROM component WHERE id = ?1").ok()?
+            .query_row((id,), |row| row.get(0)).ok()
+    }
+
+    /// Drop the compiled data, which becomes stale when the articles change
+    pub fn clear_compiled(&mut self) -> Result<(), Error> {
+        self.remap = OnceCell::new();
+        self.clear_path_cache()?;
+        self.inner.execute_batch("DROP TABLE IF EXISTS dense; DROP TABLE IF EXISTS component; DROP TABLE IF EXISTS degree_count;")
+    }
+
+    /// The file of the path cache, next to the database. Databases in memory or
+    /// published over HTTP keep their cache in memory.
+    fn path_cache_file(&self) -> Option<String> {
+        self.inner.path()
+            .filter(|path| std::path::Path::new(path).is_file())
+            .map(path_cache_file)
+    }
+
+    /// The path cache is written on every query, so it is kept in its own database
+    /// with the default journaling and locking, which concurrent queries can share
+    fn path_cache_db(&self) -> Result<&Connection, Error> {
+        self.cache.get_or_try_init(|| {
+            let conn = match self.path_cache_file() {
+                Some(file) => Connection::open(file)?,
+                None => Connection::open_in_memory()?,
+            };
+            conn.busy_timeout(std::time::Duration::from_secs(5))?;
+            conn.execute_batch("CREATE TABLE IF NOT EXISTS path_cache (`from` int(8), `to` int(8), path text not null, hits int not null default 0, primary key (`from`, `to`)) without rowid;")?;
+            Ok(conn)
+        })
+    }
+
+    /// The cached path between two articles, counting the hit
+    fn cached_path(&self, from: Id, to: Id) -> Result<Option<Vec<Id>>, Error> {
+        let path: Option<String> = self.path_cache_db()?.prepare_cached("UPDATE path_cache SET hits = hits + 1 WHERE `from` = ?1 AND `to` = ?2 RETURNING path")?
+            .query_row((from, to), |row| row.get(0))
+            .optional()?;
+        Ok(path.map(|path| path.split(' ').filter_map(|id| id.parse().ok()).collect()))
+    }
+
+    fn cache_path(&self, path: &[Id]) -> Result<(), Error> {
+        let (Some(from), Some(to)) = (path.first(), path.last()) else { return Ok(()) };
+        let ids: Vec<String> = path.iter().map(Id::to_string).collect();
+        self.path_cache_db()?.prepare_cached("INSERT OR REPLACE INTO path_cache (`from`, `to`, path) VALUES (?1, ?2, ?3)")?
+            .execute((from, to, ids.join(" ")))?;
+        Ok(())
+    }
+
+    /// Number of cached paths, and of queries answered from the cache
+    pub fn path_cache_stats(&self) -> Result<(u64, u64), Error> {
+        self.path_cache_db()?.query_row("SELECT count(*), coalesce(sum(hits), 0) FROM path_cache", (), |row| Ok((row.get(0)?, row.get(1)?)))
+    }
+
+    pub fn clear_path_cache(&self) -> Result<(), Error> {
+        // Do not create the cache only to empty it
+        let missing = self.path_cache_file().is_some_and(|file| !std::path::Path::new(&file).exists());
+        if self.cache.get().is_none() && missing { return Ok(()) }
+        self.path_cache_db()?.execute_batch("DELETE FROM path_cache;")
+    }
+
+    /// The dense ID remapping, if the DB was compiled
+    pub fn remap(&self) -> Option<&Remap> {
+        self.remap.get_or_init(|| self.load_remap().ok()).as_ref()
+    }
+
+    fn load_remap(&self) -> Result<Remap, Error> {
+        let ids = self.inner.prepare("SELECT id FROM dense ORDER BY idx")?
+            .query_map((), |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        Ok(Remap { ids })
+    }
+
+    pub fn path(&self, from: &str, to: &str, avoid: &HashSet<Id>) -> Result<Vec<String>, PathError> {
+        let path = self.path_hops(from, to, avoid)?;
+        Ok(path.iter().map(|&i| self.lookup(i).unwrap_or("???".to_owned())).collect::<Vec<_>>())
+    }
+
+    /// Find a shortest path between two titles, as a list of article IDs.
+    /// The path does not go through the articles of `avoid`, except for its endpoints.
+    pub fn path_hops(&self, from: &str, to: &str, avoid: &HashSet<Id>) -> Result<Vec<Id>, PathError> {
+        let from = self.resolve(from)
             .ok_or_else(|| PathError::UnknownTitle(from.to_owned()))?;
-        let to = self.index(to)
+        let to = self.resolve(to)
             .ok_or_else(|| PathError::UnknownTitle(to.to_owned()))?;  
     
-        let links_from = |from: &u32| self.links_from(*from);
-        let links_to = |to: &u32| self.links_to(*to);
-    
-        let path = bidi_dijkstra(from, to, links_from, links_to)
-            .ok_or(PathError::NoPathFound)?;
-    
-        Ok(path.iter().map(|&i| self.lookup(i).unwrap_or("???".to_owned())).collect::<Vec<_>>())
-    
+        let (path, stats) = self.path_ids(from, to, avoid);
+        path.ok_or(PathError::NoPathFound(stats))
+    }
+
+    /// Find a shortest path from an article to the nearest of `goals`, avoiding the
+    /// articles of `avoid` except for the endpoints.
+    pub fn path_to_any(&self, from: Id, goals: &BTreeSet<Id>, avoid: &HashSet<Id>) -> (Option<Vec<Id>>, Stats) {
+        let keep = |id: &Id| *id == from || goals.contains(id) || !avoid.contains(id);
+        bidi_dijkstra_to_any(from, goals.iter().copied(),
+            |n: &Id| self.links_from(*n).into_iter().filter(keep),
+            |n: &Id| self.links_to(*n).into_iter().filter(keep))
+    }
+
+    /// Search for a path between two article IDs avoiding a set of articles,
+    /// reporting the search statistics
+    pub fn path_ids(&self, from: Id, to: Id, avoid: &HashSet<Id>) -> (Option<Vec<Id>>, Stats) {
+        // Endpoints are resolved to article IDs, so that all the titles and redirects
+        // of the same articles share their cached path
+        let cached = self.path_cache && avoid.is_empty();
+        if cached {
+            if let Ok(Some(path)) = self.cached_path(from, to) { return (Some(path), Stats { cached: true, ..Stats::default() }) }
+        }
+        let (path, stats) = timings::time(Phase::Query, || self.search_path_ids(from, to, avoid));
+        if let (true, Some(path)) = (cached, &path) {
+            // Failing to cache a path only makes the next query slower
+            let _ = self.cache_path(path);
+        }
+        (path, stats)
+    }
+
+    fn search_path_ids(&self, from: Id, to: Id, avoid: &HashSet<Id>) -> (Option<Vec<Id>>, Stats) {
+        let keep = |id: &Id| *id == from || *id == to || !avoid.contains(id);
+
+        // Links never lead to a higher-numbered component
+        if let (Some(a), Some(b)) = (self.component(from), self.component(to)) {
+            if a < b { return (None, Stats { pruned: true, ..Stats::default() }) }
+        }
+
+        // Searching from one side only is much slower, but avoids scanning the links
+        match self.link_direction() {
+            LinkDirection::Forward => return dijkstra_with_stats(from, to, Side::Forward,
+                |n: &u32| self.links_from(*n).into_iter().filter(keep)),
+            LinkDirection::Reverse => return dijkstra_with_stats(from, to, Side::Backward,
+                |n: &u32| self.links_to(*n).into_iter().filter(keep)),
+            LinkDirection::Both => {},
+        }
+
+        if let Some(remap) = self.remap().filter(|r| !r.is_empty()) {
+            if let (Some(dense_from), Some(dense_to)) = (remap.dense(from), remap.dense(to)) {
+                let links_from = |n: &u32| self.links_from(remap.ids[*n as usize]).into_iter()
+                    .filter(keep).filter_map(|id| remap.dense(id));
+                let links_to = |n: &u32| self.links_to(remap.ids[*n as usize]).into_iter()
+                    .filter(keep).filter_map(|id| remap.dense(id));
+
+                // The dense map starts as the default tree, and only allocates its vector
+                // for searches reaching a large part of the graph (see `bench visited`)
+                let (path, stats) = bidi_dijkstra_dense(dense_from, dense_to, remap.len(), links_from, links_to);
+                return (path.map(|p| p.into_iter().filter_map(|i| remap.id(i)).collect()), stats)
+            }
+        }
+
+        let links_from = |n: &u32| self.links_from(*n).into_iter().filter(keep);
+        let links_to = |n: &u32| self.links_to(*n).into_iter().filter(keep);
+
+        match self.spill_after {
+            Some(threshold) => bidi_dijkstra_spilling(from, to, threshold, links_from, links_to),
+            None => bidi_dijkstra_with_stats(from, to, links_from, links_to),
+        }
     }
 
 }
 
 
+impl Backend for Db {
+    type Error = Error;
+
+    fn add(&mut self, id: Id, title: String, flags: u32) -> Result<(), Error> { Db::add(self, id, title, flags) }
+    fn add_redirect(&mut self, from: Id, title: &str) -> Result<(), Error> { Db::add_redirect(self, from, title) }
+    fn add_link(&mut self, link: (Id, Id)) -> Result<(), Error> { Db::add_link(self, link) }
+    fn add_category_page(&mut self, id: Id, title: &str) -> Result<(), Error> { Db::add_category_page(self, id, title) }
+    fn index(&self, title: &str) -> Option<Id> { Db::index(self, title) }
+    fn resolve(&self, title: &str) -> Option<Id> { Db::resolve(self, title) }
+    fn lookup(&self, id: Id) -> Option<String> { Db::lookup(self, id) }
+    fn links_from(&self, from: Id) -> Vec<Id> { Db::links_from(self, from) }
+    fn links_to(&self, to: Id) -> Vec<Id> { Db::links_to(self, to) }
+
+    fn path_ids(&self, from: Id, to: Id) -> (Option<Vec<Id>>, Stats) {
+        Db::path_ids(self, from, to, &HashSet::new())
+    }
+}
+
+impl Analytics for Db {
+    type Error = Error;
+
+    fn graph_stats(&self) -> Result<GraphStats, Error> { GraphStats::compute(self) }
+    fn dump_date(&self) -> Option<String> { Db::dump_date(self) }
+    fn stage_links(&self) -> Result<Remap, Error> { self.article_remap() }
+
+    fn scan_dense_links<F: FnMut(u32, u32)>(&self, remap: &Remap, mut f: F) -> Result<(), Error> {
+        self.scan_links(|from, to| if let (Some(from), Some(to)) = (remap.dense(from), remap.dense(to)) { f(from, to) })
+    }
+
+    fn save_ranks(&mut self, scores: impl Iterator<Item = (Id, f64)>) -> Result<(), Error> { Db::save_ranks(self, scores) }
+    fn top_ranks(&self, n: usize) -> Result<Vec<(Id, String, f64)>, Error> { Db::top_ranks(self, n) }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -162,9 +1280,9 @@ mod test {
     #[test]
     fn sample_titles_data() {
         let mut db = open_clean_db();  
-        db.add(0, "foo".into()).unwrap();
-        db.add(1, "bar".into()).unwrap();
-        db.add(65537, "baz".into()).unwrap();
+        db.add(0, "foo".into(), 0).unwrap();
+        db.add(1, "bar".into(), 0).unwrap();
+        db.add(65537, "baz".into(), 0).unwrap();
 
         assert_eq!(db.index("baz"), Some(65537));
         assert_eq!(db.index("foo"), Some(0));
@@ -176,6 +1294,164 @@ mod test {
 
     }
 
+    #[test]
+    fn sample_redirect_index() {
+        let mut db = open_clean_db();
+        db.add(1, "United_States".into(), 0).unwrap();
+        db.add(2, "USA".into(), 0).unwrap();
+        db.add(3, "Dangling".into(), 0).unwrap();
+        db.add_redirect(2, "United_States").unwrap();
+        db.add_redirect(3, "Nowhere").unwrap();
+
+        assert_eq!(db.index("USA"), Some(2));
+        assert_eq!(db.resolve("USA"), Some(1));
+        assert_eq!(db.resolve("United_States"), Some(1));
+        assert_eq!(db.resolve("Dangling"), Some(3));
+        assert_eq!(db.resolve("Nope"), None);
+    }
+
+    #[test]
+    fn sample_categories() {
+        let mut db = open_clean_db();
+        db.add_category(1, "Capitals_in_Europe").unwrap();
+        db.add_category(1, "Cities_in_France").unwrap();
+        db.add_category(2, "Cities_in_France").unwrap();
+
+        assert_eq!(db.categories_of(1).unwrap(), ["Capitals_in_Europe", "Cities_in_France"]);
+        assert!(db.categories_of(3).unwrap().is_empty());
+        assert!(db.in_category(2, "Cities_in_France"));
+        assert!(!db.in_category(2, "Capitals_in_Europe"));
+    }
+
+    #[test]
+    fn sample_category_tree() {
+        let mut db = open_clean_db();
+        db.add_category(1, "Physics").unwrap();
+        db.add_category(2, "Mechanics").unwrap();
+        db.add_category(3, "Fluid_dynamics").unwrap();
+        for (id, title) in [(10, "Mechanics"), (11, "Fluid_dynamics"), (12, "Physics")] {
+            db.add_category_page(id, title).unwrap();
+        }
+        // Category loops are common on the wikis
+        for (id, parent) in [(10, "Physics"), (11, "Mechanics"), (12, "Fluid_dynamics")] {
+            db.add_subcategory(id, parent).unwrap();
+        }
+
+        assert_eq!(db.category_members("Physics", 0).unwrap(), BTreeSet::from([1]));
+        assert_eq!(db.category_members("Physics", 1).unwrap(), BTreeSet::from([1, 2]));
+        assert_eq!(db.category_members("Physics", 5).unwrap(), BTreeSet::from([1, 2, 3]));
+        assert!(db.category_members("Chemistry", 5).unwrap().is_empty());
+
+        for link in [(5,6), (6,2), (5,7), (7,8), (8,1)] {
+            db.add_link(link).unwrap();
+        }
+        let members = db.category_members("Physics", 1).unwrap();
+        assert_eq!(db.path_to_any(5, &members, &HashSet::new()).0, Some(vec![5, 6, 2]));
+        assert_eq!(db.path_to_any(5, &members, &HashSet::from([6])).0, Some(vec![5, 7, 8, 1]));
+    }
+
+    #[test]
+    fn sample_usage_links() {
+        let mut db = open_clean_db();
+        db.add_template_link(1, "Infobox_country").unwrap();
+        db.add_template_link(1, "Cite_web").unwrap();
+        db.add_template_link(2, "Cite_web").unwrap();
+        db.add_image_link(2, "Flag.svg").unwrap();
+
+        assert_eq!(db.templates_of(1).unwrap(), ["Cite_web", "Infobox_country"]);
+        assert_eq!(db.template_users("Cite_web").unwrap(), [1, 2]);
+        assert_eq!(db.image_users("Flag.svg").unwrap(), [2]);
+        assert!(db.image_users("Cite_web").unwrap().is_empty());
+    }
+
+    #[test]
+    fn sample_external_links() {
+        let mut db = open_clean_db();
+        db.add_external_link(1, "https://example.com/a", Some("example.com")).unwrap();
+        db.add_external_link(1, "https://example.com/b", Some("example.com")).unwrap();
+        db.add_external_link(2, "https://example.com/a", Some("example.com")).unwrap();
+        db.add_external_link(2, "https://archive.org/", Some("archive.org")).unwrap();
+        db.add_external_link(3, "mailto:someone", None).unwrap();
+
+        assert_eq!(db.external_links(1).unwrap(), ["https://example.com/a", "https://example.com/b"]);
+        assert_eq!(db.top_domains(5).unwrap(), [("example.com".to_owned(), 2), ("archive.org".to_owned(), 1)]);
+        assert_eq!(db.top_domains(1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn sample_coordinates() {
+        let mut db = open_clean_db();
+        db.add_coordinates(1, 48.8566, 2.3522).unwrap();
+        db.add_coordinates(2, 48.8584, 2.2945).unwrap();
+        db.add_coordinates(3, 51.5074, -0.1278).unwrap();
+
+        let (lat, lon) = db.coordinates(3).unwrap();
+        assert!((lat - 51.5074).abs() < 1e-4 && (lon + 0.1278).abs() < 1e-4);
+        assert_eq!(db.coordinates(4), None);
+
+        let near: Vec<Id> = db.near((48.8566, 2.3522), 10.0).unwrap().into_iter().map(|(id, _, _)| id).collect();
+        assert_eq!(near, [1, 2]);
+        assert_eq!(db.near((48.8566, 2.3522), 500.0).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn sample_abstracts() {
+        let mut db = open_clean_db();
+        db.add_abstract(1, "Capital of France").unwrap();
+        db.add_abstract(1, "Capital and largest city of France").unwrap();
+        assert_eq!(db.abstract_of(1).unwrap().as_deref(), Some("Capital and largest city of France"));
+        assert_eq!(db.abstract_of(2).unwrap(), None);
+    }
+
+    #[test]
+    fn sample_ranks() {
+        let mut db = open_clean_db();
+        assert!(!db.ranked().unwrap());
+        for (id, title) in [(1, "Paris"), (2, "France"), (3, "Europe")] {
+            db.add(id, title.into(), 0).unwrap();
+        }
+        db.save_ranks([(1, 0.2), (2, 0.5)].into_iter()).unwrap();
+        db.save_ranks([(1, 0.3), (2, 0.1), (3, 0.6)].into_iter()).unwrap();
+        assert_eq!(db.top_ranks(2).unwrap(), [(3, "Europe".to_owned(), 0.6), (1, "Paris".to_owned(), 0.3)]);
+        assert!(db.ranked().unwrap());
+        assert_eq!(db.rank(2).unwrap(), Some(0.1));
+        assert_eq!(db.rank(4).unwrap(), None);
+    }
+
+    #[test]
+    fn sample_flags() {
+        let mut db = open_clean_db();
+        db.add(1, "Paris".into(), 0).unwrap();
+        assert!(!db.has_title_flags());
+        db.save_title_flags().unwrap();
+
+        db.add(2, "List_of_cities".into(), 1).unwrap();
+        db.add(3, "1999".into(), 2).unwrap();
+        assert!(!db.has_title_flags());
+        db.save_title_flags().unwrap();
+        assert!(db.has_title_flags());
+        assert_eq!(db.ids_with_flags(1).unwrap(), [2]);
+        assert_eq!(db.ids_with_flags(3).unwrap(), [2, 3]);
+        assert_eq!(db.title_flags(3), 2);
+        assert_eq!(db.title_flags(4), 0);
+    }
+
+    #[test]
+    fn sample_redirect_marks() {
+        let mut db = open_clean_db();
+        db.add(1, "Paris".into(), 0).unwrap();
+        db.add(2, "Paname".into(), 0).unwrap();
+        assert_eq!(db.clean_redirects().unwrap(), None);
+
+        db.add(3, "Lutèce".into(), classify::REDIRECT).unwrap();
+        db.add(4, "Ville_Lumière".into(), classify::REDIRECT).unwrap();
+        db.add_redirect(2, "Paris").unwrap();
+        db.add_redirect(3, "Paris").unwrap();
+        assert_eq!(db.clean_redirects().unwrap(), Some((1, 1)));
+        assert!(!db.is_redirect(2));
+        assert!(db.is_redirect(3));
+    }
+
     #[test]
     fn sample_link_data() {
         let mut db = open_clean_db();
@@ -192,6 +1468,248 @@ mod test {
 
     }
 
+    #[test]
+    fn sample_degree() {
+        let mut db = open_clean_db();
+        db.add_link((1,2)).unwrap();
+        db.add_link((3,2)).unwrap();
+        db.add_link((2,4)).unwrap();
+        db.add_redirect(3, "two").unwrap();
+
+        assert_eq!(db.degree(2), (2, 1));
+        assert_eq!(db.degree(5), (0, 0));
+        assert!(db.is_redirect(3));
+        assert!(!db.is_redirect(2));
+        assert!(db.has_link(3, 2));
+        assert!(!db.has_link(2, 3));
+    }
+
+    #[test]
+    fn sample_avoided_path() {
+        let mut db = open_clean_db();
+        for link in [(1,2), (2,4), (1,3), (3,5), (5,4)] {
+            db.add_link(link).unwrap();
+        }
+
+        assert_eq!(db.path_ids(1, 4, &HashSet::new()).0, Some(vec![1,2,4]));
+        assert_eq!(db.path_ids(1, 4, &HashSet::from([2])).0, Some(vec![1,3,5,4]));
+        assert_eq!(db.path_ids(1, 4, &HashSet::from([1,4])).0, Some(vec![1,2,4]));
+        assert_eq!(db.path_ids(1, 4, &HashSet::from([2,3])).0, None);
+    }
+
+    #[test]
+    fn sample_path_cache() {
+        let mut db = open_clean_db();
+        for (id, title) in [(1, "Paris"), (2, "France"), (3, "Europe")] {
+            db.add(id, title.into(), 0).unwrap();
+        }
+        db.add(4, "Ville_Lumière".into(), 0).unwrap();
+        db.add_redirect(4, "Paris").unwrap();
+        for link in [(1,2), (2,3)] {
+            db.add_link(link).unwrap();
+        }
+        db.set_path_cache(true);
+
+        assert_eq!(db.path_hops("Paris", "Europe", &HashSet::new()).unwrap(), [1, 2, 3]);
+        assert_eq!(db.path_cache_stats().unwrap(), (1, 0));
+        // Found again from the cache, through a redirect
+        db.add_link((1,3)).unwrap();
+        assert_eq!(db.path_hops("Ville_Lumière", "Europe", &HashSet::new()).unwrap(), [1, 2, 3]);
+        assert_eq!(db.path_cache_stats().unwrap(), (1, 1));
+        assert!(db.path_ids(1, 3, &HashSet::new()).1.cached);
+        // Paths avoiding articles are searched
+        assert_eq!(db.path_ids(1, 3, &HashSet::from([2])).0, Some(vec![1, 3]));
+
+        db.clear_path_cache().unwrap();
+        assert_eq!(db.path_ids(1, 3, &HashSet::new()).0, Some(vec![1, 3]));
+    }
+
+    #[test]
+    fn sample_link_directions() {
+        for direction in [LinkDirection::Forward, LinkDirection::Reverse, LinkDirection::Both] {
+            let mut db = open_clean_db();
+            db.set_link_direction(direction).unwrap();
+            assert_eq!(db.link_direction(), direction);
+
+            for link in [(1,2), (2,3), (3,4), (1,5), (5,4)] {
+                db.add_link(link).unwrap();
+            }
+            assert_eq!(db.links_to(4), [3, 5]);
+            assert_eq!(db.path_ids(1, 4, &HashSet::new()).0, Some(vec![1, 5, 4]), "{:?}", direction);
+            assert_eq!(db.path_ids(1, 4, &HashSet::from([5])).0, Some(vec![1, 2, 3, 4]), "{:?}", direction);
+            assert_eq!(db.path_ids(4, 1, &HashSet::new()).0, None);
+
+            // Changing the layout drops the links
+            let other = if direction == LinkDirection::Both { LinkDirection::Forward } else { LinkDirection::Both };
+            db.set_link_direction(other).unwrap();
+            assert_eq!(db.links_from(1), Vec::<Id>::new());
+        }
+    }
+
+    #[test]
+    fn sample_namespaces() {
+        let mut db = open_clean_db();
+        db.set_namespaces([(14, "Kategorie", true), (14, "Category", false), (6, "Bild", false)]).unwrap();
+        assert_eq!(db.namespace_id("kategorie"), Some(14));
+        assert_eq!(db.namespace_id("Bild"), Some(6));
+        assert_eq!(db.namespace_id("Vorlage"), None);
+        assert_eq!(db.namespace_name(14).as_deref(), Some("Kategorie"));
+        assert_eq!(db.namespace_name(6), None);
+
+        db.set_namespaces([(10, "Vorlage", true)]).unwrap();
+        assert_eq!(db.namespace_id("Kategorie"), None);
+        assert_eq!(db.namespace_id("Vorlage"), Some(10));
+
+        use crate::namespace;
+        assert_eq!(namespace::parse(&db, "main"), Some(namespace::MAIN));
+        assert_eq!(namespace::parse(&db, "vorlage"), Some(10));
+        assert_eq!(namespace::parse(&db, "Category"), Some(namespace::CATEGORY));
+        assert_eq!(namespace::parse(&db, "Portal"), None);
+        assert_eq!(namespace::of(&db, "Category:Physics"), namespace::CATEGORY);
+        assert_eq!(namespace::of(&db, "Star Wars: Andor"), namespace::MAIN);
+
+        db.add_category_page(1, "Physics").unwrap();
+        db.add_category_page(2, "Physicists").unwrap();
+        db.add_category_page(3, "Chemistry").unwrap();
+        assert_eq!(db.search_category_pages("physic%").len(), 2);
+    }
+
+    #[test]
+    fn sample_case() {
+        let mut db = open_clean_db();
+        for (id, title) in [(1, "Paris"), (2, "dog"), (3, "École")] {
+            db.add(id, title.into(), 0).unwrap();
+        }
+        assert_eq!(db.case(), None);
+        assert_eq!(db.index("paris"), Some(1));
+        assert_eq!(db.index("dog"), Some(2));
+
+        db.set_case(Case::FirstLetter).unwrap();
+        assert_eq!(db.index("paris"), Some(1));
+        assert_eq!(db.index("dog"), None);
+        assert_eq!(db.search("éc%").len(), 1);
+
+        db.set_case(Case::Sensitive).unwrap();
+        assert_eq!(db.index("paris"), None);
+        assert_eq!(db.index("dog"), Some(2));
+        assert_eq!(db.search("éc%").len(), 0);
+    }
+
+    #[test]
+    fn sample_aliases() {
+        let mut db = open_clean_db();
+        for (id, title) in [(1, "United_States"), (2, "New_York_City"), (3, "NYC")] {
+            db.add(id, title.into(), 0).unwrap();
+        }
+        db.set_aliases([("USA", "United States"), ("NYC", "New York City")]).unwrap();
+        assert_eq!(db.resolve("usa"), Some(1));
+        assert_eq!(db.resolve("NYC"), Some(2));
+        assert_eq!(db.index("NYC"), Some(3));
+        assert_eq!(db.aliases().unwrap()[0], ("NYC".to_owned(), "New_York_City".to_owned()));
+
+        db.set_aliases([]).unwrap();
+        assert_eq!(db.resolve("NYC"), Some(3));
+    }
+
+    #[test]
+    fn sample_transliterations() {
+        let mut db = open_clean_db();
+        for (id, title) in [(1, "Zürich"), (2, "Kurt_Gödel"), (3, "Gödel"), (4, "Godel's"), (5, "Gödël")] {
+            db.add(id, title.into(), 0).unwrap();
+        }
+        assert_eq!(db.resolve("Zurich"), None);
+        assert_eq!(db.transliterate().unwrap(), 4);
+        assert_eq!(db.resolve("Zurich"), Some(1));
+        assert_eq!(db.resolve("kurt_godel"), Some(2));
+        // Ambiguous transliterations are not resolved
+        assert_eq!(db.resolve("Godel"), None);
+        assert_eq!(db.resolve("Gödel"), Some(3));
+
+        let found: Vec<Id> = db.search("Kurt_God%").iter().map(|r| r.0).collect();
+        assert_eq!(found, [2]);
+    }
+
+    #[test]
+    fn sample_variants() {
+        let mut db = open_clean_db();
+        for (id, title) in [(1, "Београд"), (2, "Novi_Sad"), (3, "Ниш")] {
+            db.add(id, title.into(), 0).unwrap();
+        }
+        assert_eq!(db.resolve("Beograd"), None);
+        assert_eq!(db.index_variants(&Converter::builtin("srwiki").unwrap()).unwrap(), 2);
+        assert_eq!(db.resolve("Beograd"), Some(1));
+        assert_eq!(db.resolve("Нови Сад"), Some(2));
+        assert_eq!(db.resolve("Niš"), Some(3));
+        assert_eq!(db.resolve("Ниш"), Some(3));
+    }
+
+    #[test]
+    fn sample_custom_table() {
+        use rusqlite::types::Value;
+        let mut db = open_clean_db();
+        db.create_table("props", &[("page", "integer"), ("name", "text")], &["page"]).unwrap();
+        db.insert_row("props", &[Value::Integer(3), Value::Text("a".into())]).unwrap();
+        db.insert_row("props", &[Value::Integer(3), Value::Text("b".into())]).unwrap();
+        let name: String = db.inner.query_row("SELECT name FROM props WHERE page = 3", (), |row| row.get(0)).unwrap();
+        assert_eq!(name, "b");
+
+        // Declaring the table again starts over
+        db.create_table("props", &[("page", "integer"), ("name", "text")], &[]).unwrap();
+        let count: i64 = db.inner.query_row("SELECT count(*) FROM props", (), |row| row.get(0)).unwrap();
+        assert_eq!(count, 0);
+        assert_eq!(db.spec_tables(), ["props"]);
+        assert!(db.tables().unwrap().iter().any(|t| t == "page"));
+    }
+
+    #[test]
+    fn sample_remap() {
+        let mut db = open_clean_db();
+        for (id, title) in [(70, "c"), (3, "a"), (5, "b")] {
+            db.add(id, title.into(), 0).unwrap();
+        }
+        for link in [(3,5), (5,70), (3,70), (70,3)] {
+            db.add_link(link).unwrap();
+        }
+        assert_eq!(db.remap(), None);
+        assert_eq!(db.path_ids(5, 3, &HashSet::new()).0, Some(vec![5,70,3]));
+        assert_eq!(db.article_count().unwrap(), 3);
+        assert_eq!(db.article_ids().unwrap(), [3, 5, 70]);
+
+        assert_eq!(db.compile().unwrap(), 3);
+        let remap = db.remap().unwrap();
+        assert_eq!(remap.dense(70), Some(2));
+        assert_eq!(remap.dense(4), None);
+        assert_eq!(remap.id(0), Some(3));
+        assert_eq!(db.article_ids().unwrap(), [3, 5, 70]);
+        assert_eq!(db.path_ids(5, 3, &HashSet::new()).0, Some(vec![5,70,3]));
+        assert_eq!(db.path_ids(5, 3, &HashSet::from([70])).0, None);
+    }
+
+    #[test]
+    fn sample_components() {
+        let mut db = open_clean_db();
+        for (id, title) in [(1, "a"), (2, "b"), (3, "c"), (4, "d")] {
+            db.add(id, title.into(), 0).unwrap();
+        }
+        for link in [(1,2), (2,1), (2,3), (3,4)] {
+            db.add_link(link).unwrap();
+        }
+        db.compile().unwrap();
+        assert_eq!(db.compile_components().unwrap(), 3);
+        assert_eq!(db.degree_percentiles(db.degree(2)), Some((0.0, 75.0)));
+        assert_eq!(db.component(1), db.component(2));
+        assert!(db.component(3) > db.component(4));
+
+        // Answered from the components, without expanding any article
+        let (path, stats) = db.path_ids(4, 1, &HashSet::new());
+        assert_eq!((path, stats.expanded, stats.pruned), (None, 0, true));
+        assert_eq!(db.path_ids(1, 4, &HashSet::new()).0, Some(vec![1,2,3,4]));
+
+        db.clear_compiled().unwrap();
+        assert_eq!(db.component(1), None);
+        assert_eq!(db.degree_percentiles((1, 1)), None);
+    }
+
     #[test]
     fn sample_reverse_link_data() {
         let mut db = open_clean_db();
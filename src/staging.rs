@@ -0,0 +1,137 @@
+//! Building a new database next to the one serving queries, and moving it in place
+//!
+//! With `--staging`, commands work on `<db>.staging.sq3` instead of the database.
+//! `promote` then renames the staging database over the database, which is atomic:
+//! processes opening the database get either the old or the new one, and those
+//! that already have it open keep reading the old one until they reopen it.
+//! The paths cached on the old database are dropped with it.
+
+use std::{fs, io, path::Path};
+
+use thiserror::Error;
+
+use crate::sqlite;
+
+#[derive(Error, Debug)]
+pub enum StagingError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("There is no staging database at {0}, build one with --staging")]
+    Missing(String),
+}
+
+/// Path of the staging database of a database
+pub fn path(db_path: &str) -> String {
+    match db_path.strip_suffix(".sq3") {
+        Some(base) => format!("{}.staging.sq3", base),
+        None => format!("{}.staging", db_path),
+    }
+}
+
+/// Path where `promote` keeps the replaced database
+pub fn previous_path(db_path: &str) -> String {
+    match db_path.strip_suffix(".sq3") {
+        Some(base) => format!("{}.previous.sq3", base),
+        None => format!("{}.previous", db_path),
+    }
+}
+
+/// Replace the database with its staging database. With `keep_previous`, the
+/// replaced database is kept at `previous_path` with its path cache, replacing
+/// any earlier one.
+pub fn promote(db_path: &str, keep_previous: bool) -> Result<(), StagingError> {
+    let staging = path(db_path);
+    if !Path::new(&staging).is_file() {
+        return Err(StagingError::Missing(staging));
+    }
+    let previous = (keep_previous && Path::new(db_path).exists()).then(|| previous_path(db_path));
+    if let Some(previous) = &previous {
+        // A second link to the database, so that it is never missing
+        remove(previous)?;
+        fs::hard_link(db_path, previous)?;
+    }
+    fs::rename(&staging, db_path)?;
+
+    // The paths cached on the replaced database may not exist in the new one
+    let cache = sqlite::path_cache_file(db_path);
+    if let Some(previous) = &previous {
+        let previous_cache = sqlite::path_cache_file(previous);
+        remove(&previous_cache)?;
+        if Path::new(&cache).exists() { fs::rename(&cache, &previous_cache)? }
+    }
+    remove(&cache)?;
+    remove(&sqlite::path_cache_file(&staging))?;
+    Ok(())
+}
+
+fn remove(path: &str) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
+    use crate::Id;
+
+    #[test]
+    fn promote_twice() {
+        let dir = std::env::temp_dir().join(format!("wikistra-staging-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("enwiki-db.sq3");
+        let db = db.to_str().unwrap();
+        assert_eq!(path(db), dir.join("enwiki-db.staging.sq3").to_str().unwrap());
+
+        assert!(matches!(promote(db, true), Err(StagingError::Missing(_))));
+        for version in ["v1", "v2"] {
+            fs::write(path(db), version).unwrap();
+            promote(db, true).unwrap();
+            assert_eq!(fs::read_to_string(db).unwrap(), version);
+            assert!(!Path::new(&path(db)).exists());
+        }
+        assert_eq!(fs::read_to_string(previous_path(db)).unwrap(), "v1");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn promote_cache() {
+        let dir = std::env::temp_dir().join(format!("wikistra-staging-cache-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("enwiki-db.sq3");
+        let db_path = db_path.to_str().unwrap();
+        let build = |path: &str, links: &[(Id, Id)]| {
+            let mut db = sqlite::Db::new(path).unwrap();
+            for (id, title) in [(1, "Paris"), (2, "France"), (3, "Europe")] {
+                db.add(id, title.into(), 0).unwrap();
+            }
+            for &link in links {
+                db.add_link(link).unwrap();
+            }
+            db.set_path_cache(true);
+            db
+        };
+
+        let live = build(db_path, &[(1, 2), (2, 3)]);
+        assert_eq!(live.path_ids(1, 3, &HashSet::new()).0, Some(vec![1, 2, 3]));
+        drop(live);
+        drop(build(&path(db_path), &[(1, 3)]));
+        promote(db_path, true).unwrap();
+
+        let open = |path: &str| {
+            let mut db = sqlite::Db::new(path).unwrap();
+            db.set_path_cache(true);
+            db
+        };
+        let (found, stats) = open(db_path).path_ids(1, 3, &HashSet::new());
+        assert_eq!(found, Some(vec![1, 3]));
+        assert!(!stats.cached);
+        assert_eq!(open(&previous_path(db_path)).path_cache_stats().unwrap(), (1, 0));
+        assert!(!Path::new(&sqlite::path_cache_file(&path(db_path))).exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,109 @@
+//! Summary of a built database, for the `stats` command
+//!
+//! The degrees of all the articles are counted in memory, in one pass over the links,
+//! rather than with a query per article.
+
+use serde_json::{Value, json};
+
+use crate::sqlite::Db;
+
+/// Distribution of the number of links of the articles
+#[derive(Debug, Clone, PartialEq)]
+pub struct Distribution {
+    pub mean: f64,
+    pub median: u32,
+    pub p90: u32,
+    pub p99: u32,
+    pub max: u32,
+}
+
+impl Distribution {
+
+    /// The distribution of some degrees, which are sorted in place
+    pub fn new(degrees: &mut [u32]) -> Self {
+        degrees.sort_unstable();
+        let quantile = |q: f64| match degrees.len() {
+            0 => 0,
+            n => degrees[((n - 1) as f64 * q).round() as usize],
+        };
+        let total: u64 = degrees.iter().map(|&d| d as u64).sum();
+        Distribution {
+            mean: total as f64 / degrees.len().max(1) as f64,
+            median: quantile(0.5),
+            p90: quantile(0.9),
+            p99: quantile(0.99),
+            max: degrees.last().copied().unwrap_or(0),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({ "mean": self.mean, "median": self.median, "p90": self.p90, "p99": self.p99, "max": self.max })
+    }
+}
+
+pub struct GraphStats {
+    /// Indexed pages, redirects included
+    pub pages: u64,
+    pub redirects: u64,
+    /// Links between indexed pages, through redirects included
+    pub links: u64,
+    /// Articles without incoming links
+    pub orphans: u64,
+    /// Articles without outgoing links
+    pub dead_ends: u64,
+    /// Incoming links per article, redirects excluded
+    pub links_in: Distribution,
+    /// Outgoing links per article, redirects excluded
+    pub links_out: Distribution,
+    /// Size of the database, in bytes
+    pub size: u64,
+}
+
+impl GraphStats {
+
+    pub fn compute(db: &Db) -> Result<Self, rusqlite::Error> {
+        let remap = db.article_remap()?;
+        let n = remap.len() as usize;
+        let mut links_in = vec![0u32; n];
+        let mut links_out = vec![0u32; n];
+        let mut links = 0;
+        db.scan_links(|from, to| if let (Some(from), Some(to)) = (remap.dense(from), remap.dense(to)) {
+            links_out[from as usize] += 1;
+            links_in[to as usize] += 1;
+            links += 1;
+        })?;
+
+        let mut redirect = vec![false; n];
+        for id in db.redirect_ids()? {
+            if let Some(i) = remap.dense(id) { redirect[i as usize] = true }
+        }
+        let articles = |degrees: Vec<u32>| -> Vec<u32> {
+            degrees.into_iter().zip(&redirect).filter(|&(_, &r)| !r).map(|(d, _)| d).collect()
+        };
+        let (mut links_in, mut links_out) = (articles(links_in), articles(links_out));
+
+        Ok(GraphStats {
+            pages: n as u64,
+            redirects: redirect.iter().filter(|&&r| r).count() as u64,
+            links,
+            orphans: links_in.iter().filter(|&&d| d == 0).count() as u64,
+            dead_ends: links_out.iter().filter(|&&d| d == 0).count() as u64,
+            links_in: Distribution::new(&mut links_in),
+            links_out: Distribution::new(&mut links_out),
+            size: db.file_size()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sample_distribution() {
+        let mut degrees: Vec<u32> = (0..=100).rev().collect();
+        let distribution = Distribution::new(&mut degrees);
+        assert_eq!(distribution, Distribution { mean: 50.0, median: 50, p90: 90, p99: 99, max: 100 });
+        assert_eq!(Distribution::new(&mut []).max, 0);
+    }
+}
@@ -0,0 +1,133 @@
+//! Time spent in each phase of a command, printed at exit with `--timings`
+//!
+//! Phases are timed where they happen, on any thread, into global counters. When
+//! timings are disabled, timers are not even started. Phases nest: parsing includes
+//! the decompression with `--threads 1`, and the waits for it otherwise, and
+//! decompressing a streamed dump includes downloading it.
+
+use std::{io::{self, Read}, sync::atomic::{AtomicBool, AtomicU64, Ordering}, time::{Duration, Instant}};
+
+use crate::output::{Style, paint};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Download,
+    Decompress,
+    Parse,
+    Insert,
+    Query,
+}
+
+const PHASES: [Phase; 5] = [Phase::Download, Phase::Decompress, Phase::Parse, Phase::Insert, Phase::Query];
+
+impl Phase {
+    fn name(self) -> &'static str {
+        match self {
+            Phase::Download => "download",
+            Phase::Decompress => "decompress",
+            Phase::Parse => "parse",
+            Phase::Insert => "insert",
+            Phase::Query => "query",
+        }
+    }
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static NANOS: [AtomicU64; 5] = [const { AtomicU64::new(0) }; 5];
+static CALLS: [AtomicU64; 5] = [const { AtomicU64::new(0) }; 5];
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Adds the time since its start to a phase when dropped
+pub struct Timer {
+    phase: Phase,
+    started: Instant,
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        let i = self.phase as usize;
+        NANOS[i].fetch_add(self.started.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        CALLS[i].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Start timing a phase, until the timer is dropped
+pub fn start(phase: Phase) -> Option<Timer> {
+    ENABLED.load(Ordering::Relaxed).then(|| Timer { phase, started: Instant::now() })
+}
+
+/// Time a phase around a call
+pub fn time<T>(phase: Phase, f: impl FnOnce() -> T) -> T {
+    let _timer = start(phase);
+    f()
+}
+
+/// A reader or iterator whose reads are timed as a phase
+pub struct Timed<T> {
+    inner: T,
+    phase: Phase,
+}
+
+impl <T> Timed<T> {
+    pub fn new(inner: T, phase: Phase) -> Self {
+        Timed { inner, phase }
+    }
+}
+
+impl <R: Read> Read for Timed<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        time(self.phase, || self.inner.read(buf))
+    }
+}
+
+impl <I: Iterator> Iterator for Timed<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        time(self.phase, || self.inner.next())
+    }
+}
+
+/// The time spent in each phase that ran, and the number of timed calls
+pub fn summary() -> Vec<(&'static str, Duration, u64)> {
+    PHASES.iter()
+        .map(|&phase| (phase.name(), NANOS[phase as usize].load(Ordering::Relaxed), CALLS[phase as usize].load(Ordering::Relaxed)))
+        .filter(|&(_, _, calls)| calls > 0)
+        .map(|(name, nanos, calls)| (name, Duration::from_nanos(nanos), calls))
+        .collect()
+}
+
+/// Prints the summary to the standard error when dropped, if timings are enabled,
+/// so that it is printed even if the command fails
+pub struct Report;
+
+impl Drop for Report {
+    fn drop(&mut self) {
+        if !ENABLED.load(Ordering::Relaxed) { return }
+        eprintln!("{}", paint("Timings:", Style::Heading));
+        for (name, elapsed, calls) in summary() {
+            eprintln!("  {:<12}{:>10.3}s  {}", name, elapsed.as_secs_f64(), paint(format!("({} calls)", calls), Style::Muted));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn timed_reads() {
+        enable();
+        let mut read = vec![];
+        Timed::new(&b"some dump"[..], Phase::Download).read_to_end(&mut read).unwrap();
+        assert_eq!(read, b"some dump");
+        assert_eq!(Timed::new(1..=3, Phase::Query).sum::<i32>(), 6);
+        // Other tests may time queries at the same time
+        let calls = |phase| summary().into_iter().find(|&(name, _, _)| name == phase).map_or(0, |(_, _, calls)| calls);
+        assert!(calls("download") >= 2);
+        assert!(calls("query") >= 4);
+    }
+}
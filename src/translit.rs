@@ -0,0 +1,22 @@
+//! ASCII transliterations of titles, so that `Zurich` finds `Zürich`
+//!
+//! Transliterations are stored lowercased, for the titles that are not plain ASCII.
+
+/// The lookup key of a title or query: its ASCII transliteration, lowercased
+pub fn key(title: &str) -> String {
+    deunicode::deunicode(title).to_lowercase().replace(' ', "_")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sample_keys() {
+        assert_eq!(key("Zürich"), "zurich");
+        assert_eq!(key("Kurt_Gödel"), "kurt_godel");
+        assert_eq!(key("Łódź"), "lodz");
+        assert_eq!(key("Zurich"), "zurich");
+        assert_eq!(key("Gö%"), "go%");
+    }
+}
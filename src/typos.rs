@@ -0,0 +1,83 @@
+//! Suggesting known titles close to a mistyped one
+
+use std::collections::BinaryHeap;
+
+use crate::sqlite::Db;
+
+/// Edit distance between two strings, counted in characters.
+/// Gives up and returns `None` once the distance exceeds `max`.
+pub fn levenshtein(a: &str, b: &str, max: usize) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > max { return None }
+
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + (ca != cb) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+        if row.iter().min().is_some_and(|&d| d > max) { return None }
+    }
+
+    Some(row[b.len()]).filter(|&d| d <= max)
+}
+
+/// The largest edit distance at which a title is still considered a typo
+fn tolerance(title: &str) -> usize {
+    (title.chars().count() / 4).clamp(1, 4)
+}
+
+/// Up to `limit` indexed titles closest to `title`, best first.
+///
+/// Titles starting with `title` are also suggested, after the close ones.
+/// This scans the whole title index, keeping only the best `limit` titles.
+pub fn suggest(db: &Db, title: &str, limit: usize) -> Result<Vec<String>, rusqlite::Error> {
+    let max = tolerance(title);
+    let lower = title.to_lowercase();
+    // The worst of the best titles so far is on top, to be replaced by better ones
+    let mut best: BinaryHeap<(usize, String)> = BinaryHeap::with_capacity(limit + 1);
+
+    db.scan_titles(|_, candidate| {
+        let distance = levenshtein(&lower, &candidate.to_lowercase(), max)
+            .or_else(|| candidate.starts_with(title).then_some(max + 1));
+        let Some(distance) = distance else { return };
+        if best.len() == limit && best.peek().is_none_or(|(d, t)| (distance, candidate) >= (*d, t.as_str())) { return }
+        best.push((distance, candidate.to_owned()));
+        if best.len() > limit { best.pop(); }
+    })?;
+
+    Ok(best.into_sorted_vec().into_iter().map(|(_, t)| t).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sample_distances() {
+        assert_eq!(levenshtein("Paris", "Paris", 2), Some(0));
+        assert_eq!(levenshtein("Pari", "Paris", 2), Some(1));
+        assert_eq!(levenshtein("Piras", "Paris", 2), Some(2));
+        assert_eq!(levenshtein("kitten", "sitting", 3), Some(3));
+        assert_eq!(levenshtein("kitten", "sitting", 2), None);
+        assert_eq!(levenshtein("Zürich", "Zurich", 1), Some(1));
+        assert_eq!(levenshtein("", "abc", 5), Some(3));
+        assert_eq!(levenshtein("a", "abcdef", 2), None);
+    }
+
+    #[test]
+    fn best_suggestions() {
+        let mut db = Db::new("file::memory:").unwrap();
+        db.initialize().unwrap();
+        for (id, title) in [(1, "Paris"), (2, "Parish"), (3, "Pari"), (4, "Paris_Hilton"), (5, "Lyon"), (6, "Parma")] {
+            db.add(id, title.into(), 0).unwrap();
+        }
+        assert_eq!(suggest(&db, "Paris", 3).unwrap(), ["Paris", "Pari", "Parish"]);
+        assert_eq!(suggest(&db, "Paris", 10).unwrap(), ["Paris", "Pari", "Parish", "Paris_Hilton"]);
+        assert!(suggest(&db, "Paris", 0).unwrap().is_empty());
+    }
+}
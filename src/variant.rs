@@ -0,0 +1,124 @@
+//! Language variants of titles, so that titles typed in either script or variant
+//! of a language find the article
+//!
+//! Titles and queries are converted to a single variant with replacement rules,
+//! applied longest first. The Cyrillic to Latin rules of Serbian are built in.
+//! Chinese needs the conversion tables of OpenCC, such as `TSCharacters.txt`
+//! and `TSPhrases.txt`, to convert Traditional to Simplified Chinese.
+
+use std::{collections::HashMap, fs, io};
+
+use crate::wikiname;
+
+/// Serbian Cyrillic letters, with their Latin equivalent
+const SERBIAN: [(char, &str); 30] = [
+    ('А', "A"), ('Б', "B"), ('В', "V"), ('Г', "G"), ('Д', "D"), ('Ђ', "Đ"), ('Е', "E"), ('Ж', "Ž"),
+    ('З', "Z"), ('И', "I"), ('Ј', "J"), ('К', "K"), ('Л', "L"), ('Љ', "Lj"), ('М', "M"), ('Н', "N"),
+    ('Њ', "Nj"), ('О', "O"), ('П', "P"), ('Р', "R"), ('С', "S"), ('Т', "T"), ('Ћ', "Ć"), ('У', "U"),
+    ('Ф', "F"), ('Х', "H"), ('Ц', "C"), ('Ч', "Č"), ('Џ', "Dž"), ('Ш', "Š"),
+];
+
+/// Replacement rules converting text to a single variant
+#[derive(Debug, Default, Clone)]
+pub struct Converter {
+    rules: HashMap<String, String>,
+    /// Length of the longest rule, in characters
+    longest: usize,
+}
+
+impl Converter {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The built-in rules for the variants of a wiki, if it has some
+    pub fn builtin(wikiname: &str) -> Option<Self> {
+        match wikiname::language(wikiname)?.as_str() {
+            "sr" | "sh" => {
+                let mut converter = Converter::new();
+                for (cyrillic, latin) in SERBIAN {
+                    converter.add(&cyrillic.to_string(), latin);
+                    converter.add(&cyrillic.to_lowercase().to_string(), &latin.to_lowercase());
+                }
+                Some(converter)
+            }
+            _ => None,
+        }
+    }
+
+    /// Replace `from` with `to`
+    pub fn add(&mut self, from: &str, to: &str) {
+        self.longest = self.longest.max(from.chars().count());
+        self.rules.insert(from.to_owned(), to.to_owned());
+    }
+
+    /// Add the rules of an OpenCC dictionary, where every line holds a word, a tab,
+    /// and its conversions separated by spaces, of which the first is used.
+    /// Returns the number of rules read.
+    pub fn read_opencc(&mut self, path: &str) -> io::Result<usize> {
+        let mut count = 0;
+        for line in fs::read_to_string(path)?.lines() {
+            let Some((from, to)) = line.split_once('\t') else { continue };
+            let Some(to) = to.split_whitespace().next() else { continue };
+            self.add(from, to);
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn rules(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.rules.iter().map(|(from, to)| (from.as_str(), to.as_str()))
+    }
+
+    /// Convert a text, replacing the longest matching rule at every position
+    pub fn convert(&self, text: &str) -> String {
+        // Byte offsets of the characters, and of the end of the text, so that
+        // candidate words are slices of the text
+        let bounds: Vec<usize> = text.char_indices().map(|(i, _)| i).chain([text.len()]).collect();
+        let chars = bounds.len() - 1;
+        let mut converted = String::with_capacity(text.len());
+        let mut i = 0;
+        'outer: while i < chars {
+            for len in (1..=self.longest.min(chars - i)).rev() {
+                if let Some(to) = self.rules.get(&text[bounds[i]..bounds[i + len]]) {
+                    converted.push_str(to);
+                    i += len;
+                    continue 'outer;
+                }
+            }
+            converted.push_str(&text[bounds[i]..bounds[i + 1]]);
+            i += 1;
+        }
+        converted
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sample_conversions() {
+        let serbian = Converter::builtin("srwiki").unwrap();
+        assert_eq!(serbian.convert("Београд"), "Beograd");
+        assert_eq!(serbian.convert("Ђорђе_Џексон"), "Đorđe_Džekson");
+        assert_eq!(serbian.convert("Beograd"), "Beograd");
+        assert!(Converter::builtin("enwiki").is_none());
+
+        let mut chinese = Converter::new();
+        chinese.add("臺", "台");
+        chinese.add("灣", "湾");
+        chinese.add("臺灣", "台湾");
+        chinese.add("乾", "干");
+        chinese.add("乾隆", "乾隆");
+        assert_eq!(chinese.convert("臺灣"), "台湾");
+        // Longer rules take precedence
+        assert_eq!(chinese.convert("乾隆帝"), "乾隆帝");
+        assert_eq!(chinese.convert("乾燥"), "干燥");
+    }
+}
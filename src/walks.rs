@@ -0,0 +1,138 @@
+//! Random walks over the link graph, as sentences of article IDs for word2vec-style
+//! training of article embeddings (DeepWalk, node2vec)
+//!
+//! Walks are biased as in node2vec: after a step from `prev` to `current`, the next
+//! article is weighted `1/p` if it is `prev`, 1 if `prev` links to it, and `1/q`
+//! otherwise. Steps are drawn by rejection sampling, without precomputing the
+//! transition probabilities of every pair of links.
+
+use std::io::{self, Write};
+
+use fastrand::Rng;
+
+use crate::Id;
+
+pub struct Walks {
+    /// Maximum number of articles of a walk
+    pub length: usize,
+    /// Number of walks starting from each article
+    pub per_node: usize,
+    /// Return parameter
+    pub p: f64,
+    /// In-out parameter
+    pub q: f64,
+}
+
+/// Parse a node2vec parameter, which weighs steps by its inverse and must be
+/// a positive number
+pub fn parse_bias(text: &str) -> Result<f64, String> {
+    let value: f64 = text.trim().parse().map_err(|_| format!("invalid number: {}", text))?;
+    if !value.is_finite() || value <= 0.0 { return Err(format!("must be a positive number: {}", text)) }
+    Ok(value)
+}
+
+/// A graph as adjacency lists: the links of article `i` are
+/// `targets[offsets[i]..offsets[i + 1]]`, sorted
+pub struct Graph<'a> {
+    pub offsets: &'a [usize],
+    pub targets: &'a [u32],
+}
+
+impl Graph<'_> {
+    fn links(&self, node: u32) -> &[u32] {
+        &self.targets[self.offsets[node as usize]..self.offsets[node as usize + 1]]
+    }
+}
+
+impl Walks {
+
+    /// Write the walks, one per line, as the IDs of the articles separated by spaces.
+    /// Articles are walked from in a new random order in every round, and walks stop
+    /// early at articles without links. Returns the number of walks written.
+    pub fn write<W: Write>(&self, graph: &Graph, ids: &[Id], rng: &mut Rng, mut out: W) -> io::Result<usize> {
+        let n = graph.offsets.len() - 1;
+        let mut starts: Vec<u32> = (0..n as u32).filter(|&node| !graph.links(node).is_empty()).collect();
+        let mut walk = Vec::with_capacity(self.length);
+        let mut written = 0;
+        for _ in 0..self.per_node {
+            rng.shuffle(&mut starts);
+            for &start in &starts {
+                self.walk(graph, start, rng, &mut walk);
+                let mut line = String::new();
+                for (i, node) in walk.iter().enumerate() {
+                    if i > 0 { line.push(' ') }
+                    line.push_str(&ids[*node as usize].to_string());
+                }
+                line.push('\n');
+                out.write_all(line.as_bytes())?;
+                written += 1;
+            }
+        }
+        out.flush()?;
+        Ok(written)
+    }
+
+    fn walk(&self, graph: &Graph, start: u32, rng: &mut Rng, walk: &mut Vec<u32>) {
+        let (back, away) = (1.0 / self.p, 1.0 / self.q);
+        let max = back.max(1.0).max(away);
+        walk.clear();
+        walk.push(start);
+        while walk.len() < self.length {
+            let current = walk[walk.len() - 1];
+            let links = graph.links(current);
+            if links.is_empty() { break }
+            let next = match walk.len() {
+                1 => links[rng.usize(..links.len())],
+                len => {
+                    let prev = walk[len - 2];
+                    loop {
+                        let candidate = links[rng.usize(..links.len())];
+                        let weight = if candidate == prev { back }
+                            else if graph.links(prev).binary_search(&candidate).is_ok() { 1.0 }
+                            else { away };
+                        if rng.f64() * max < weight { break candidate }
+                    }
+                }
+            };
+            walk.push(next);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn biases() {
+        assert_eq!(parse_bias("0.5"), Ok(0.5));
+        assert_eq!(parse_bias("2"), Ok(2.0));
+        for text in ["0", "-1", "inf", "NaN", "often"] {
+            assert!(parse_bias(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn sample_walks() {
+        // 0 <-> 1 -> 2 -> 3, and 3 is a dead end
+        let graph = Graph { offsets: &[0, 1, 3, 4, 4], targets: &[1, 0, 2, 3] };
+        let ids = [10, 20, 30, 40];
+        let walks = Walks { length: 5, per_node: 2, p: 1.0, q: 0.5 };
+        let mut out = vec![];
+        let written = walks.write(&graph, &ids, &mut Rng::with_seed(1), &mut out).unwrap();
+        assert_eq!(written, 6);
+
+        let lines: Vec<Vec<Id>> = String::from_utf8(out).unwrap().lines()
+            .map(|line| line.split(' ').map(|id| id.parse().unwrap()).collect())
+            .collect();
+        assert_eq!(lines.len(), 6);
+        for walk in lines {
+            assert!(walk.len() <= 5);
+            assert!(walk.len() == 5 || walk.last() == Some(&40));
+            for step in walk.windows(2) {
+                let (from, to) = (ids.iter().position(|&i| i == step[0]).unwrap(), ids.iter().position(|&i| i == step[1]).unwrap());
+                assert!(graph.links(from as u32).contains(&(to as u32)));
+            }
+        }
+    }
+}
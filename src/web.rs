@@ -0,0 +1,147 @@
+//! Links to the live wiki and to external sites, and browsing results
+
+use std::{fs, io, path::PathBuf};
+
+use crate::wikiname::host;
+
+/// The URL of an article
+pub fn article_url(wikiname: &str, title: &str) -> String {
+    let mut url = format!("https://{}/wiki/", host(wikiname));
+    for b in title.bytes() {
+        if b.is_ascii_alphanumeric() || b"-_.~()!*',:;@/$".contains(&b) {
+            url.push(b as char);
+        } else {
+            url.push_str(&format!("%{:02X}", b));
+        }
+    }
+    url
+}
+
+/// The page a link to the wiki points to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    Title(String),
+    Id(u32),
+}
+
+/// The page a URL of the wiki points to, as in `https://en.wikipedia.org/wiki/AC/DC`,
+/// `/w/index.php?title=AC/DC` or `/w/index.php?curid=1234`. The host is not checked.
+pub fn url_target(url: &str) -> Option<Target> {
+    let rest = url.split_once("://").map(|(_, rest)| rest)
+        .or_else(|| url.strip_prefix("//"))?;
+    let rest = rest.split('#').next()?;
+    let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let path = &path[path.find('/')?..];
+    if let Some(title) = path.strip_prefix("/wiki/").filter(|t| !t.is_empty()) {
+        return Some(Target::Title(percent_decode(title).replace(' ', "_")))
+    }
+    let param = |name: &str| query.split('&')
+        .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+        .map(|value| percent_decode(&value.replace('+', " ")));
+    param("curid").and_then(|id| id.parse().ok()).map(Target::Id)
+        .or_else(|| param("title").filter(|t| !t.is_empty()).map(|t| Target::Title(t.replace(' ', "_"))))
+}
+
+/// Decode the `%XX` escapes of a URL, keeping invalid ones as they are
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%').then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(b) => { decoded.push(b); i += 3 }
+            None => { decoded.push(bytes[i]); i += 1 }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// The lowercase host name of an external link, if it has one
+pub fn domain_of(url: &str) -> Option<String> {
+    let rest = url.split_once("://").map(|(_, rest)| rest)
+        .or_else(|| url.strip_prefix("//"))?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?;
+    let host = match host.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next()?,
+        None => host.split(':').next()?,
+    };
+    (!host.is_empty()).then(|| host.to_lowercase())
+}
+
+/// Decode the reversed domain index of recent `externallinks` dumps, such as
+/// `https://org.wikipedia.en.`, into a scheme and a domain (`en.wikipedia.org`)
+pub fn unindex_domain(index: &str) -> Option<(&str, String)> {
+    let (scheme, reversed) = index.split_once("://")?;
+    let mut labels: Vec<&str> = reversed.trim_end_matches('.').split('.').collect();
+    labels.reverse();
+    Some((scheme, labels.join(".").to_lowercase()))
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A standalone HTML page listing a path, with every hop linking to the live article
+pub fn path_report(wikiname: &str, path: &[String]) -> String {
+    let title = match (path.first(), path.last()) {
+        (Some(start), Some(end)) => format!("{} to {}", start, end),
+        _ => "Empty path".to_owned(),
+    };
+
+    let mut html = format!("<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{}</title></head>\n<body>\n<h1>{}</h1>\n<ol>\n",
+        escape_html(&title), escape_html(&title));
+    for hop in path {
+        html.push_str(&format!("<li><a href=\"{}\">{}</a></li>\n",
+            escape_html(&article_url(wikiname, hop)), escape_html(&hop.replace('_', " "))));
+    }
+    html.push_str("</ol>\n</body>\n</html>\n");
+    html
+}
+
+/// Write the HTML report of a path to a temporary file, and open it in the default browser
+pub fn open_path(wikiname: &str, path: &[String]) -> io::Result<PathBuf> {
+    let file = std::env::temp_dir().join(format!("wikistra-{}.html", std::process::id()));
+    fs::write(&file, path_report(wikiname, path))?;
+    webbrowser::open(&file.to_string_lossy())?;
+    Ok(file)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sample_urls() {
+        assert_eq!(host("enwiki"), "en.wikipedia.org");
+        assert_eq!(host("zh_min_nanwiki"), "zh-min-nan.wikipedia.org");
+        assert_eq!(host("frwiktionary"), "fr.wiktionary.org");
+        assert_eq!(article_url("enwiki", "AC/DC"), "https://en.wikipedia.org/wiki/AC/DC");
+        assert_eq!(article_url("enwiki", "Who_Framed_Roger_Rabbit?"), "https://en.wikipedia.org/wiki/Who_Framed_Roger_Rabbit%3F");
+        assert_eq!(article_url("dewiki", "Zürich"), "https://de.wikipedia.org/wiki/Z%C3%BCrich");
+
+        let title = |t: &str| Some(Target::Title(t.to_owned()));
+        assert_eq!(url_target("https://de.wikipedia.org/wiki/Z%C3%BCrich#Geschichte"), title("Zürich"));
+        assert_eq!(url_target("https://en.m.wikipedia.org/wiki/AC/DC?oldid=1"), title("AC/DC"));
+        assert_eq!(url_target("https://en.wikipedia.org/w/index.php?title=Roger+Rabbit&action=edit"), title("Roger_Rabbit"));
+        assert_eq!(url_target("https://en.wikipedia.org/w/index.php?curid=1234"), Some(Target::Id(1234)));
+        assert_eq!(url_target("https://en.wikipedia.org/wiki/100%"), title("100%"));
+        assert_eq!(url_target("https://en.wikipedia.org/"), None);
+        assert_eq!(url_target("AC/DC"), None);
+    }
+
+    #[test]
+    fn sample_domains() {
+        assert_eq!(domain_of("https://www.Example.com/a?b").as_deref(), Some("www.example.com"));
+        assert_eq!(domain_of("http://user@host.org:8080").as_deref(), Some("host.org"));
+        assert_eq!(domain_of("//archive.org/web").as_deref(), Some("archive.org"));
+        assert_eq!(domain_of("mailto:someone@example.com"), None);
+        assert_eq!(unindex_domain("https://org.wikipedia.en."), Some(("https", "en.wikipedia.org".to_owned())));
+    }
+}
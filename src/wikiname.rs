@@ -0,0 +1,137 @@
+//! Names of the wikis in the Wikimedia dumps
+//!
+//! A wiki is named after its language and project: `enwiki` is the English Wikipedia,
+//! and `frwiktionary` the French Wiktionary. Underscores stand for the dashes of
+//! language codes, as in `zh_min_nanwiki`. A few wikis, such as `commonswiki`, are
+//! named after a site instead of a language.
+
+use std::{fmt, ops::Deref, str::FromStr};
+
+use thiserror::Error;
+
+/// Projects, by the suffix of their wikis. `wiki` stands for Wikipedia, and must
+/// come last as the others end with it.
+const PROJECTS: [&str; 9] = ["wiktionary", "wikibooks", "wikinews", "wikiquote", "wikisource",
+    "wikiversity", "wikivoyage", "wikimedia", "wiki"];
+
+/// Wikis without a language, and their hosts
+const SITES: [(&str, &str); 8] = [
+    ("commonswiki", "commons.wikimedia.org"),
+    ("metawiki", "meta.wikimedia.org"),
+    ("specieswiki", "species.wikimedia.org"),
+    ("incubatorwiki", "incubator.wikimedia.org"),
+    ("wikidatawiki", "www.wikidata.org"),
+    ("mediawikiwiki", "www.mediawiki.org"),
+    ("testwiki", "test.wikipedia.org"),
+    ("test2wiki", "test2.wikipedia.org"),
+];
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum WikiNameError {
+    #[error("{0} is not a wiki name, which only holds lowercase letters, digits and underscores")]
+    Characters(String),
+    #[error("{0} is not a wiki name, did you mean {1}?")]
+    Typo(String, String),
+    #[error("{0} is not a wiki name, which ends with a project such as `wiki` or `wiktionary`")]
+    Project(String),
+}
+
+/// The name of a wiki, such as `enwiki`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WikiName(String);
+
+impl FromStr for WikiName {
+    type Err = WikiNameError;
+
+    fn from_str(name: &str) -> Result<Self, WikiNameError> {
+        if !name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_') {
+            return Err(WikiNameError::Characters(name.to_owned()))
+        }
+        let lowercase = name.to_ascii_lowercase();
+        if lowercase != name {
+            return Err(WikiNameError::Typo(name.to_owned(), lowercase))
+        }
+        if let Some(lang) = name.strip_suffix("wikipedia") {
+            return Err(WikiNameError::Typo(name.to_owned(), format!("{}wiki", lang)))
+        }
+        match split(name) {
+            Some((lang, _)) if lang.starts_with(|c: char| c.is_ascii_lowercase()) => Ok(WikiName(name.to_owned())),
+            _ if (2..=3).contains(&name.len()) => Err(WikiNameError::Typo(name.to_owned(), format!("{}wiki", name))),
+            _ => Err(WikiNameError::Project(name.to_owned())),
+        }
+    }
+}
+
+impl WikiName {
+
+    /// The language code of the wiki, such as `zh-min-nan`, unless it is named after a site
+    pub fn language(&self) -> Option<String> {
+        language(&self.0)
+    }
+}
+
+impl Deref for WikiName {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for WikiName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// The language code and project of a wiki name
+fn split(name: &str) -> Option<(&str, &str)> {
+    PROJECTS.iter().find_map(|&project| Some((name.strip_suffix(project)?, project)))
+}
+
+/// The language code of a wiki, such as `en` for `enwiki` or `zh-yue` for
+/// `zh_yuewiki`, unless it is named after a site
+pub fn language(wikiname: &str) -> Option<String> {
+    if SITES.iter().any(|&(site, _)| site == wikiname) { return None }
+    let (lang, _) = split(wikiname)?;
+    (!lang.is_empty()).then(|| lang.replace('_', "-"))
+}
+
+/// The host serving a wiki: `enwiki` is served from `en.wikipedia.org`, and
+/// `frwiktionary` from `fr.wiktionary.org`
+pub fn host(wikiname: &str) -> String {
+    if let Some(&(_, host)) = SITES.iter().find(|&&(site, _)| site == wikiname) {
+        return host.to_owned()
+    }
+    match split(wikiname) {
+        Some((lang, "wiki")) => format!("{}.wikipedia.org", lang.replace('_', "-")),
+        Some((lang, project)) => format!("{}.{}.org", lang.replace('_', "-"), project),
+        None => format!("{}.org", wikiname),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sample_names() {
+        let name: WikiName = "zh_min_nanwiki".parse().unwrap();
+        assert_eq!(name.language().as_deref(), Some("zh-min-nan"));
+        assert_eq!(language("frwiktionary").as_deref(), Some("fr"));
+        assert_eq!(language("commonswiki"), None);
+        assert_eq!(host("commonswiki"), "commons.wikimedia.org");
+        assert_eq!(host("dewikivoyage"), "de.wikivoyage.org");
+
+        let typo = |name: &str| match name.parse::<WikiName>() {
+            Err(WikiNameError::Typo(_, fixed)) => Some(fixed),
+            _ => None,
+        };
+        assert_eq!(typo("en").as_deref(), Some("enwiki"));
+        assert_eq!(typo("enwikipedia").as_deref(), Some("enwiki"));
+        assert_eq!(typo("ENWIKI").as_deref(), Some("enwiki"));
+        assert_eq!("en.wikipedia.org".parse::<WikiName>(), Err(WikiNameError::Characters("en.wikipedia.org".to_owned())));
+        assert_eq!("english".parse::<WikiName>(), Err(WikiNameError::Project("english".to_owned())));
+        assert!("wiki".parse::<WikiName>().is_err());
+    }
+}